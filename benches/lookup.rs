@@ -0,0 +1,59 @@
+use std::hint::black_box;
+use std::time::Instant;
+
+use xdg_mime::{LookupContext, SharedMimeInfo};
+
+// Runs @f @iterations times, and prints the average time it took
+fn bench<F: FnMut()>(name: &str, iterations: u32, mut f: F) {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+
+    println!("{:<32} {:>10.1?} per scan", name, start.elapsed() / iterations);
+}
+
+fn main() {
+    // A copy of the shared-mime-info database
+    let mime_db = SharedMimeInfo::new_for_directory("test_files");
+
+    // The file names of a typical directory
+    let extensions = ["jpg", "png", "txt", "rs", "tar.gz", "pdf", "html", "mp3", "c", "unknown"];
+    let names: Vec<String> = (0..1000)
+        .map(|i| format!("file-{}.{}", i, extensions[i % extensions.len()]))
+        .collect();
+
+    bench("file names", 20, || {
+        for name in &names {
+            black_box(mime_db.get_mime_types_from_file_name(black_box(name)));
+        }
+    });
+
+    let mut ctx = LookupContext::new();
+    let mut types = Vec::new();
+    bench("file names, reused buffers", 20, || {
+        for name in &names {
+            mime_db.get_mime_types_from_file_name_into(black_box(name), &mut ctx, &mut types);
+            black_box(&types);
+        }
+    });
+
+    let mime_types: Vec<String> = names
+        .iter()
+        .map(|name| mime_db.get_mime_types_from_file_name(name).remove(0))
+        .collect();
+
+    bench("ancestors", 20, || {
+        for mime_type in &mime_types {
+            black_box(mime_db.ancestors(black_box(mime_type)));
+        }
+    });
+
+    let mut ancestors = Vec::new();
+    bench("ancestors, reused buffer", 20, || {
+        for mime_type in &mime_types {
+            mime_db.ancestors_into(black_box(mime_type), &mut ancestors);
+            black_box(&ancestors);
+        }
+    });
+}
@@ -0,0 +1,34 @@
+use std::hint::black_box;
+use std::time::Instant;
+
+use xdg_mime::{ChunkedData, SharedMimeInfo};
+
+// Runs @f @iterations times, and prints the average time it took
+fn bench<F: FnMut()>(name: &str, iterations: u32, mut f: F) {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+
+    println!("{:<24} {:>10.1?} per lookup", name, start.elapsed() / iterations);
+}
+
+fn main() {
+    // A copy of the shared-mime-info database
+    let mime_db = SharedMimeInfo::new_for_directory("test_files");
+
+    // Data matching no entry, so that all the rules are evaluated
+    let unknown: Vec<u8> = (0..16 * 1024).map(|i| (i * 7 % 251) as u8).collect();
+    let png = include_bytes!("../test_files/files/rust-logo.png");
+
+    bench("unknown data", 200, || {
+        black_box(mime_db.get_mime_type_for_data(black_box(&unknown)));
+    });
+    bench("unknown data, chunked", 200, || {
+        let chunks = ChunkedData::new(unknown.chunks(1500));
+        black_box(mime_db.get_mime_type_for_input(black_box(&chunks)));
+    });
+    bench("png data", 2000, || {
+        black_box(mime_db.get_mime_type_for_data(black_box(&png[..])));
+    });
+}
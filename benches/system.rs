@@ -0,0 +1,60 @@
+use std::hint::black_box;
+use std::time::Instant;
+
+use xdg_mime::{bench, LookupContext, MappedSnapshot, SharedMimeInfo};
+
+// Runs @f @iterations times, and prints the average time it took
+fn bench<F: FnMut()>(name: &str, iterations: u32, mut f: F) {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+
+    println!("{:<24} {:>10.1?} per run", name, start.elapsed() / iterations);
+}
+
+fn main() {
+    // The database merged from the XDG base directories of this system
+    bench("load", 10, || {
+        black_box(bench::load(SharedMimeInfo::builder()));
+    });
+    bench("load, no cache", 10, || {
+        black_box(bench::load(SharedMimeInfo::builder().use_cache(false)));
+    });
+
+    let mime_db = bench::load(SharedMimeInfo::builder());
+
+    let snapshot = std::env::temp_dir().join(format!("xdg-mime-bench-{}", std::process::id()));
+    mime_db.save_snapshot(&snapshot).unwrap();
+    bench("load snapshot", 10, || {
+        black_box(SharedMimeInfo::load_snapshot(&snapshot).unwrap());
+    });
+    bench("map snapshot", 10, || {
+        // The snapshot is not modified while it is mapped
+        black_box(unsafe { MappedSnapshot::open(&snapshot) }.unwrap());
+    });
+    std::fs::remove_file(&snapshot).unwrap();
+
+    let extensions = ["jpg", "png", "txt", "rs", "tar.gz", "pdf", "html", "mp3", "c", "unknown"];
+    let names: Vec<String> = (0..1000)
+        .map(|i| format!("file-{}.{}", i, extensions[i % extensions.len()]))
+        .collect();
+
+    let mut ctx = LookupContext::new();
+    bench("glob lookup, 1000 names", 20, || {
+        for name in &names {
+            black_box(bench::glob_lookup(&mime_db, black_box(name), &mut ctx));
+        }
+    });
+
+    // Data matching no entry, so that all the rules are evaluated
+    let unknown: Vec<u8> = (0..16 * 1024).map(|i| (i * 7 % 251) as u8).collect();
+    let png = include_bytes!("../test_files/files/rust-logo.png");
+
+    bench("magic, unknown data", 200, || {
+        black_box(bench::magic_match(&mime_db, black_box(&unknown[..])));
+    });
+    bench("magic, png data", 2000, || {
+        black_box(bench::magic_match(&mime_db, black_box(&png[..])));
+    });
+}
@@ -0,0 +1,48 @@
+#!/bin/sh
+# Regenerates data/mime, the database bundled by the embedded-db feature,
+# from the freedesktop.org package of the shared-mime-info release named
+# in data/VERSION.
+#
+# Usage: scripts/update-embedded-db.sh [PACKAGE]
+#
+# Without PACKAGE, the package is taken from the release archive, which
+# needs network access; PACKAGE can instead be the freedesktop.org.xml
+# file installed by the same release, like
+# /usr/share/mime/packages/freedesktop.org.xml. The files are generated
+# by update-mime-database, which should come from the same release.
+#
+# To move to another release, change data/VERSION, run this script, and
+# check the tests of the embedded-db feature.
+
+set -eu
+
+root=$(cd "$(dirname "$0")/.." && pwd)
+version=$(sed -n 's/^shared-mime-info //p' "$root/data/VERSION")
+files="aliases generic-icons globs2 icons magic subclasses treemagic types"
+
+tmp=$(mktemp -d)
+trap 'rm -rf "$tmp"' EXIT
+mkdir -p "$tmp/mime/packages"
+
+if [ $# -gt 0 ]; then
+    cp "$1" "$tmp/mime/packages/freedesktop.org.xml"
+else
+    name="shared-mime-info-$version"
+    url="https://gitlab.freedesktop.org/xdg/shared-mime-info/-/archive/$version/$name.tar.gz"
+    curl -fsSL "$url" | tar -xzf - -C "$tmp" "$name/data/freedesktop.org.xml.in"
+    # The release build only merges the translations of the comments
+    # into the package, which the generated files do not hold
+    cp "$tmp/$name/data/freedesktop.org.xml.in" "$tmp/mime/packages/freedesktop.org.xml"
+fi
+
+installed=$(update-mime-database -v 2>&1 | sed -n 's/^update-mime-database (shared-mime-info) //p')
+if [ "$installed" != "$version" ]; then
+    echo "warning: update-mime-database $installed, expected $version" >&2
+fi
+
+PKGSYSTEM_ENABLE_FSYNC=0 XDG_DATA_DIRS="$tmp" update-mime-database "$tmp/mime"
+for file in $files; do
+    cp "$tmp/mime/$file" "$root/data/mime/$file"
+done
+
+echo "data/mime updated from shared-mime-info $version"
@@ -0,0 +1,157 @@
+// The quality of the media ranges without a `q` parameter, in thousandths
+const DEFAULT_QUALITY: u16 = 1000;
+
+/// How a MIME type fits an HTTP `Accept` header, as returned by
+/// [`SharedMimeInfo::negotiate`][crate::SharedMimeInfo::negotiate].
+///
+/// The quality values are in thousandths, from 1 to 1000 for `q=1`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Negotiation {
+    /// The type itself is acceptable.
+    Acceptable { quality: u16 },
+    /// The type is not acceptable, but @mime_type, its closest acceptable
+    /// ancestor, is; e.g. a source file can be served as `text/plain`.
+    Ancestor { mime_type: String, quality: u16 },
+    /// Neither the type nor any of its ancestors is acceptable.
+    NotAcceptable,
+}
+
+// A media range of an Accept header
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct MediaRange {
+    // The lowercase range, like `text/html`, `text/*` or `*/*`
+    pub(crate) range: String,
+    pub(crate) quality: u16,
+}
+
+// Parses a quality value, like `0.5`, into thousandths
+fn parse_quality(value: &str) -> Option<u16> {
+    let (int, frac) = match value.find('.') {
+        Some(idx) => (&value[..idx], &value[idx + 1..]),
+        None => (value, ""),
+    };
+
+    if frac.len() > 3 || !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let frac: u16 = format!("{:0<3}", frac).parse().ok()?;
+    match int {
+        "0" => Some(frac),
+        "1" if frac == 0 => Some(1000),
+        _ => None,
+    }
+}
+
+// Parses the media ranges of an Accept @header, skipping the malformed
+// ones; the parameters other than the quality are ignored
+pub(crate) fn parse_accept(header: &str) -> Vec<MediaRange> {
+    let mut res = Vec::new();
+
+    for item in header.split(',') {
+        let mut params = item.split(';');
+        let range = params.next().unwrap_or("").trim().to_ascii_lowercase();
+
+        let valid = match range.find('/') {
+            Some(idx) => idx > 0 && idx < range.len() - 1 && (range.as_str() == "*/*" || !range.starts_with('*')),
+            None => false,
+        };
+        if !valid {
+            continue;
+        }
+
+        let mut quality = Some(DEFAULT_QUALITY);
+        for param in params {
+            let mut kv = param.splitn(2, '=');
+            if kv.next().map(|k| k.trim().eq_ignore_ascii_case("q")).unwrap_or(false) {
+                quality = kv.next().and_then(|v| parse_quality(v.trim()));
+            }
+        }
+
+        if let Some(quality) = quality {
+            res.push(MediaRange { range, quality });
+        }
+    }
+
+    res
+}
+
+// Returns the quality of the lowercase @mime_type given by the most
+// specific of @ranges matching it, if any
+pub(crate) fn quality_of(ranges: &[MediaRange], mime_type: &str) -> Option<u16> {
+    let media = mime_type.split('/').next().unwrap_or("");
+
+    ranges
+        .iter()
+        .filter_map(|r| {
+            let specificity = if r.range == mime_type {
+                2
+            } else if r.range.strip_suffix("/*") == Some(media) {
+                1
+            } else if r.range == "*/*" {
+                0
+            } else {
+                return None;
+            };
+
+            Some((specificity, r.quality))
+        })
+        .max_by_key(|v| v.0)
+        .map(|v| v.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SharedMimeInfo;
+    use std::env;
+
+    #[test]
+    fn accept_header() {
+        let ranges = parse_accept("text/html;level=1, TEXT/*;q=0.5, */*;q=0.05, image/png;q=2, bogus, */png");
+        assert_eq!(
+            ranges,
+            vec![
+                MediaRange { range: "text/html".to_string(), quality: 1000 },
+                MediaRange { range: "text/*".to_string(), quality: 500 },
+                MediaRange { range: "*/*".to_string(), quality: 50 },
+            ]
+        );
+
+        assert_eq!(quality_of(&ranges, "text/html"), Some(1000));
+        assert_eq!(quality_of(&ranges, "text/plain"), Some(500));
+        assert_eq!(quality_of(&ranges, "image/png"), Some(50));
+        assert_eq!(quality_of(&[], "image/png"), None);
+        assert_eq!(parse_quality("0.125"), Some(125));
+        assert_eq!(parse_quality("1.000"), Some(1000));
+        assert_eq!(parse_quality("1.5"), None);
+        assert_eq!(parse_quality("0.1234"), None);
+    }
+
+    #[test]
+    fn negotiate() {
+        let mime_db = SharedMimeInfo::new_for_directory(env::current_dir().unwrap().join("test_files"));
+
+        assert_eq!(
+            mime_db.negotiate("text/x-csrc", "text/html, text/plain;q=0.8"),
+            Negotiation::Ancestor {
+                mime_type: "text/plain".to_string(),
+                quality: 800
+            }
+        );
+        assert_eq!(
+            mime_db.negotiate("application/x-compressed-tar", "application/x-gzip"),
+            Negotiation::Ancestor {
+                mime_type: "application/gzip".to_string(),
+                quality: 1000
+            }
+        );
+        assert_eq!(
+            mime_db.negotiate("text/x-csrc", "text/*;q=0.3, text/plain;q=0"),
+            Negotiation::Acceptable { quality: 300 }
+        );
+        assert_eq!(mime_db.negotiate("image/png", "text/*"), Negotiation::NotAcceptable);
+        assert_eq!(mime_db.negotiate("image/png", "image/png;q=0"), Negotiation::NotAcceptable);
+        assert_eq!(mime_db.negotiate("image/png", ""), Negotiation::Acceptable { quality: 1000 });
+    }
+}
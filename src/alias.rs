@@ -1,10 +1,14 @@
 use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::fmt;
 use std::fs::File;
+use std::io;
 use std::io::BufRead;
 use std::io::BufReader;
 use std::path::Path;
 
+use crate::lines;
+
 #[derive(Clone, Eq)]
 pub struct Alias {
     pub alias: String,
@@ -13,7 +17,14 @@ pub struct Alias {
 
 impl fmt::Debug for Alias {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Alias {} {}\n", self.alias, self.mime_type)
+        writeln!(f, "Alias {} {}", self.alias, self.mime_type)
+    }
+}
+
+// Formats the alias as a line of an aliases file
+impl fmt::Display for Alias {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.alias, self.mime_type)
     }
 }
 
@@ -31,7 +42,7 @@ impl Ord for Alias {
 
 impl PartialOrd for Alias {
     fn partial_cmp(&self, other: &Alias) -> Option<Ordering> {
-        Some(self.alias.cmp(&other.alias))
+        Some(self.cmp(other))
     }
 }
 
@@ -57,69 +68,112 @@ impl Alias {
         };
 
         Some(Alias {
-            alias: alias,
-            mime_type: mime_type,
+            alias,
+            mime_type,
         })
     }
 }
 
 pub struct AliasesList {
     aliases: Vec<Alias>,
+    // The MIME type of each alias, and the aliases of each MIME type,
+    // rebuilt whenever aliases are added
+    canonical: HashMap<String, String>,
+    reverse: HashMap<String, Vec<String>>,
 }
 
 impl AliasesList {
     pub fn new() -> AliasesList {
         AliasesList {
             aliases: Vec::new(),
+            canonical: HashMap::new(),
+            reverse: HashMap::new(),
         }
     }
 
     pub fn add_alias(&mut self, alias: Alias) {
         self.aliases.push(alias);
+        self.aliases_changed();
     }
 
     pub fn add_aliases(&mut self, aliases: Vec<Alias>) {
         self.aliases.extend(aliases);
+        self.aliases_changed();
+    }
+
+    /// Adds the aliases from @other that are not already defined in this
+    /// list.
+    pub fn add_missing_aliases(&mut self, other: AliasesList) {
+        for alias in other.aliases {
+            if !self.aliases.contains(&alias) {
+                self.aliases.push(alias);
+            }
+        }
+
+        self.aliases_changed();
+    }
+
+    fn aliases_changed(&mut self) {
+        self.canonical.clear();
+        self.reverse.clear();
+
+        // The first definition of an alias wins
+        for alias in &self.aliases {
+            if !self.canonical.contains_key(&alias.alias) {
+                self.canonical.insert(alias.alias.clone(), alias.mime_type.clone());
+                self.reverse
+                    .entry(alias.mime_type.clone())
+                    .or_default()
+                    .push(alias.alias.clone());
+            }
+        }
+
+        for aliases in self.reverse.values_mut() {
+            aliases.sort_unstable();
+        }
     }
 
     pub fn sort(&mut self) {
         self.aliases.sort_unstable();
     }
 
-    pub fn unalias_mime_type(&self, mime_type: &str) -> Option<String> {
-        for a in self.aliases.iter() {
-            if a.alias == *mime_type {
-                return Some(a.mime_type.to_string());
-            }
+    pub fn iter(&self) -> std::slice::Iter<'_, Alias> {
+        self.aliases.iter()
+    }
+
+    /// Returns the aliases of @mime_type, sorted by name.
+    pub fn aliases_for(&self, mime_type: &str) -> &[String] {
+        match self.reverse.get(mime_type) {
+            Some(v) => v,
+            None => &[],
         }
+    }
 
-        None
+    pub fn unalias_mime_type(&self, mime_type: &str) -> Option<String> {
+        self.canonical.get(mime_type).cloned()
+    }
+
+    /// Like unalias_mime_type(), without copying the MIME type.
+    pub fn unalias_mime_type_ref(&self, mime_type: &str) -> Option<&str> {
+        self.canonical.get(mime_type).map(|v| v.as_str())
     }
 }
 
-pub fn read_aliases_from_file<P: AsRef<Path>>(file_name: P) -> Vec<Alias> {
-    let mut res = Vec::new();
+/// Reads the aliases from @reader, in the format of an `aliases` file.
+///
+/// Comments and empty lines are skipped; the lines that cannot be parsed
+/// are returned as errors of kind `InvalidData`.
+pub fn read_aliases<R: BufRead>(reader: R) -> impl Iterator<Item = io::Result<Alias>> {
+    lines::parse_lines(reader, Alias::from_string).map(|(_, res)| res)
+}
 
+pub fn read_aliases_from_file<P: AsRef<Path>>(file_name: P) -> Vec<Alias> {
     let f = match File::open(file_name) {
         Ok(v) => v,
-        Err(_) => return res,
+        Err(_) => return Vec::new(),
     };
 
-    let file = BufReader::new(&f);
-    for line in file.lines() {
-        let line = line.unwrap();
-
-        if line.is_empty() || line.starts_with('#') {
-            continue;
-        }
-
-        match Alias::from_string(line) {
-            Some(v) => res.push(v),
-            None => continue,
-        }
-    }
-
-    res
+    lines::collect_valid(read_aliases(BufReader::new(f)))
 }
 
 #[cfg(test)]
@@ -141,4 +195,28 @@ mod tests {
             Alias::new("application/x-foo", "application/foo")
         );
     }
+
+    #[test]
+    fn reverse_map() {
+        let mut aliases = AliasesList::new();
+        aliases.add_aliases(vec![
+            Alias::new("application/x-foo", "application/foo"),
+            Alias::new("text/x-foo", "application/foo"),
+            Alias::new("application/x-foo", "application/bar"),
+        ]);
+
+        assert_eq!(aliases.aliases_for("application/foo"), &["application/x-foo", "text/x-foo"]);
+        assert!(aliases.aliases_for("application/bar").is_empty());
+        assert_eq!(aliases.unalias_mime_type("application/x-foo"), Some("application/foo".to_string()));
+    }
+
+    #[test]
+    fn read_from_memory() {
+        let data = b"# aliases\napplication/x-foo application/foo\ninvalid\n";
+        let res: Vec<io::Result<Alias>> = read_aliases(&data[..]).collect();
+
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[0].as_ref().unwrap(), &Alias::new("application/x-foo", "application/foo"));
+        assert_eq!(res[1].as_ref().unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
 }
@@ -0,0 +1,142 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::document::GlobsDocument;
+use crate::glob::Glob;
+
+// The weight of the associations of an application, so that they take
+// precedence over the globs of the desktop-wide database
+const APP_WEIGHT: i32 = 100;
+
+/// The associations between file names and MIME types private to an
+/// application, like an IDE recognizing its own script files, persisted
+/// in the data directory of the application instead of the database of
+/// the user.
+///
+/// The associations are stored as a `mime/globs2` file of the data
+/// directory, and take precedence over the other globs of the databases
+/// they are loaded in with
+/// [`SharedMimeInfoBuilder::app_associations`][crate::SharedMimeInfoBuilder::app_associations].
+pub struct AppAssociations {
+    data_dir: PathBuf,
+    document: GlobsDocument,
+}
+
+impl AppAssociations {
+    /// Loads the associations of the application @app_id, from its data
+    /// directory `$XDG_DATA_HOME/<app_id>`.
+    pub fn new(app_id: &str) -> io::Result<AppAssociations> {
+        match dirs::data_dir() {
+            Some(dir) => AppAssociations::with_data_dir(dir.join(app_id)),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "no data directory")),
+        }
+    }
+
+    /// Loads the associations of the application whose data directory is
+    /// @data_dir; there are none if the directory does not exist yet.
+    pub fn with_data_dir<P: AsRef<Path>>(data_dir: P) -> io::Result<AppAssociations> {
+        let data_dir = data_dir.as_ref().to_path_buf();
+
+        let document = match GlobsDocument::from_file(data_dir.join("mime/globs2")) {
+            Ok(v) => v,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => GlobsDocument::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(AppAssociations { data_dir, document })
+    }
+
+    /// The data directory of the application.
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    /// Associates the file names matching @pattern, like `*.foo`, with
+    /// @mime_type, replacing any previous association of @pattern.
+    pub fn associate(&mut self, pattern: &str, mime_type: &str) {
+        self.dissociate(pattern);
+        self.document.push(Glob::with_weight(mime_type, pattern, APP_WEIGHT));
+    }
+
+    /// Removes the association of @pattern, returning whether there was
+    /// one.
+    pub fn dissociate(&mut self, pattern: &str) -> bool {
+        let mut found = false;
+        self.document.retain(|g| {
+            let matches = g.pattern() == pattern;
+            found |= matches;
+            !matches
+        });
+
+        found
+    }
+
+    /// Returns the MIME type @pattern is associated with, if any.
+    pub fn mime_type_for(&self, pattern: &str) -> Option<&str> {
+        self.document
+            .entries()
+            .find(|g| g.pattern() == pattern)
+            .map(|g| g.mime_type())
+    }
+
+    /// Iterates over the associations, as globs.
+    pub fn globs(&self) -> impl Iterator<Item = &Glob> {
+        self.document.entries()
+    }
+
+    /// Writes the associations to the data directory of the application,
+    /// creating it if needed.
+    pub fn save(&self) -> io::Result<()> {
+        let mime_dir = self.data_dir.join("mime");
+        fs::create_dir_all(&mime_dir)?;
+
+        // Write to a temporary file first, so that the applications
+        // loading the associations never see a partially written file
+        let tmp_path = mime_dir.join(".globs2.new");
+        self.document.save(&tmp_path)?;
+        fs::rename(&tmp_path, mime_dir.join("globs2"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::{GlobMatch, SharedMimeInfo};
+
+    #[test]
+    fn persist_associations() {
+        let dir = TempDir::new("app");
+
+        let mut assoc = AppAssociations::with_data_dir(dir.path()).unwrap();
+        assert_eq!(assoc.globs().count(), 0);
+        assoc.associate("*.foo", "text/x-foo");
+        assoc.associate("*.txt", "text/x-ide-notes");
+        assoc.associate("*.foo", "text/x-foo-script");
+        assoc.save().unwrap();
+
+        let mut assoc = AppAssociations::with_data_dir(dir.path()).unwrap();
+        assert_eq!(assoc.globs().count(), 2);
+        assert_eq!(assoc.mime_type_for("*.foo"), Some("text/x-foo-script"));
+
+        let mime_db = SharedMimeInfo::builder()
+            .system_directories(false)
+            .directory("test_files")
+            .app_associations(&assoc)
+            .build();
+        assert_eq!(mime_db.lookup_file_name("a.txt"), GlobMatch::Unique("text/x-ide-notes".to_string()));
+        assert_eq!(mime_db.lookup_file_name("a.foo"), GlobMatch::Unique("text/x-foo-script".to_string()));
+        assert!(mime_db.load_warnings().is_empty());
+
+        assert!(assoc.dissociate("*.txt"));
+        assert!(!assoc.dissociate("*.txt"));
+        assoc.save().unwrap();
+        let mime_db = SharedMimeInfo::builder()
+            .system_directories(false)
+            .directory("test_files")
+            .app_associations(&assoc)
+            .build();
+        assert_eq!(mime_db.lookup_file_name("a.txt"), GlobMatch::Unique("text/plain".to_string()));
+    }
+}
@@ -0,0 +1,220 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use crate::SharedMimeInfo;
+
+/// The associations between MIME types and applications, following the
+/// [MIME applications associations][mime-apps] specification.
+///
+/// The applications are identified by the ID of their desktop file, like
+/// `org.gnome.Nautilus.desktop`.
+///
+/// [mime-apps]: https://specifications.freedesktop.org/mime-apps-spec/latest/
+#[derive(Clone, Debug, Default)]
+pub struct MimeApps {
+    defaults: HashMap<String, Vec<String>>,
+    added: HashMap<String, Vec<String>>,
+    removed: HashSet<(String, String)>,
+}
+
+// The contents of a single mimeapps.list or mimeinfo.cache file
+#[derive(Default)]
+struct AppsList {
+    defaults: Vec<(String, Vec<String>)>,
+    added: Vec<(String, Vec<String>)>,
+    removed: Vec<(String, Vec<String>)>,
+}
+
+fn read_apps_list<P: AsRef<Path>>(file_name: P) -> Option<AppsList> {
+    let f = File::open(file_name).ok()?;
+
+    let mut res = AppsList::default();
+    let mut section = String::new();
+    for line in BufReader::new(f).lines() {
+        let line = match line {
+            Ok(v) => v,
+            Err(_) => break,
+        };
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            section = line[1..line.len() - 1].to_string();
+            continue;
+        }
+
+        let (mime_type, apps) = match line.find('=') {
+            Some(v) => (line[..v].trim(), &line[v + 1..]),
+            None => continue,
+        };
+        let apps: Vec<String> = apps
+            .split(';')
+            .map(|a| a.trim())
+            .filter(|a| !a.is_empty())
+            .map(|a| a.to_string())
+            .collect();
+
+        let entries = match section.as_str() {
+            "Default Applications" => &mut res.defaults,
+            // The section of the mimeinfo.cache files
+            "Added Associations" | "MIME Cache" => &mut res.added,
+            "Removed Associations" => &mut res.removed,
+            _ => continue,
+        };
+        entries.push((mime_type.to_string(), apps));
+    }
+
+    Some(res)
+}
+
+impl MimeApps {
+    /// Loads the associations from the standard locations: the
+    /// `mimeapps.list` files of the XDG configuration directories, then
+    /// the ones of the `applications` sub-directory of the XDG data
+    /// directories, along with their legacy `defaults.list` and their
+    /// `mimeinfo.cache` files.
+    pub fn new() -> MimeApps {
+        let mut config_dirs = Vec::new();
+        if let Some(config_home) = dirs::config_dir() {
+            config_dirs.push(config_home);
+        }
+        match env::var_os("XDG_CONFIG_DIRS") {
+            Some(v) => config_dirs.extend(env::split_paths(&v)),
+            None => config_dirs.push(PathBuf::from("/etc/xdg")),
+        }
+
+        let desktops: Vec<String> = match env::var("XDG_CURRENT_DESKTOP") {
+            Ok(v) => v.split(':').map(|d| d.to_lowercase()).collect(),
+            Err(_) => Vec::new(),
+        };
+
+        MimeApps::load(&config_dirs, &SharedMimeInfo::xdg_data_dirs(), &desktops)
+    }
+
+    /// Loads the associations from @config_dirs and @data_dirs, by
+    /// decreasing order of importance, taking into account the desktop
+    /// specific files of each of @desktops.
+    pub fn load<P: AsRef<Path>>(config_dirs: &[P], data_dirs: &[P], desktops: &[String]) -> MimeApps {
+        let mut files = Vec::new();
+        let dirs = config_dirs
+            .iter()
+            .map(|d| d.as_ref().to_path_buf())
+            .chain(data_dirs.iter().map(|d| d.as_ref().join("applications")));
+        let mut caches = Vec::new();
+        for (i, dir) in dirs.enumerate() {
+            for desktop in desktops {
+                files.push(dir.join(format!("{}-mimeapps.list", desktop)));
+            }
+            files.push(dir.join("mimeapps.list"));
+
+            if i >= config_dirs.len() {
+                // The deprecated defaults.list files come after the
+                // mimeapps.list file of their directory
+                files.push(dir.join("defaults.list"));
+                caches.push(dir.join("mimeinfo.cache"));
+            }
+        }
+
+        let mut res = MimeApps::default();
+        for list in files.iter().chain(caches.iter()).filter_map(read_apps_list) {
+            res.add_list(list);
+        }
+
+        res
+    }
+
+    // Adds the associations of a less important file than the ones
+    // already added
+    fn add_list(&mut self, list: AppsList) {
+        for (mime_type, apps) in list.defaults {
+            self.defaults.entry(mime_type).or_default().extend(apps);
+        }
+
+        for (mime_type, apps) in list.added {
+            for app in apps {
+                if self.removed.contains(&(mime_type.clone(), app.clone())) {
+                    continue;
+                }
+
+                let v = self.added.entry(mime_type.clone()).or_default();
+                if !v.contains(&app) {
+                    v.push(app);
+                }
+            }
+        }
+
+        // The removals only apply to the less important files
+        for (mime_type, apps) in list.removed {
+            for app in apps {
+                self.removed.insert((mime_type.clone(), app));
+            }
+        }
+    }
+
+    /// Returns the default application for @mime_type, if any.
+    ///
+    /// The first default that is also registered for the type wins, and
+    /// any default when none is.
+    pub fn default_application(&self, mime_type: &str) -> Option<&str> {
+        let defaults = self.defaults.get(mime_type)?;
+        let registered = self.registered_applications(mime_type);
+
+        defaults
+            .iter()
+            .find(|a| registered.contains(a))
+            .or_else(|| defaults.first())
+            .map(|a| a.as_str())
+    }
+
+    /// Returns the applications registered for @mime_type, by decreasing
+    /// order of preference.
+    pub fn registered_applications(&self, mime_type: &str) -> &[String] {
+        match self.added.get(mime_type) {
+            Some(v) => v,
+            None => &[],
+        }
+    }
+}
+
+/// The applications that can handle one of the content types of a volume.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VolumeHandlers {
+    /// The `x-content/*` type detected on the volume.
+    pub content_type: String,
+    /// The default application for the type, if any.
+    pub default_application: Option<String>,
+    /// All the applications registered for the type.
+    pub applications: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_associations() {
+        let dir = env::current_dir().unwrap().join("test_files/apps");
+        let config_dir = dir.join("config");
+        let data_dir = dir.join("data");
+
+        let apps = MimeApps::load(&[&config_dir], &[&data_dir], &["gnome".to_string()]);
+        assert_eq!(apps.default_application("image/png"), Some("viewer.desktop"));
+        assert_eq!(apps.default_application("text/plain"), Some("gedit.desktop"));
+        assert_eq!(apps.default_application("image/gif"), Some("gimp.desktop"));
+        assert_eq!(apps.default_application("image/jpeg"), None);
+        assert_eq!(
+            apps.registered_applications("image/png"),
+            &["editor.desktop".to_string(), "viewer.desktop".to_string()]
+        );
+
+        let apps = MimeApps::load(&[&config_dir], &[&data_dir], &[]);
+        assert_eq!(apps.default_application("text/plain"), Some("kate.desktop"));
+    }
+}
@@ -0,0 +1,184 @@
+use crate::glob::GlobType;
+use crate::{GlobMatch, Guess, Hierarchy, SharedMimeInfo, UNKNOWN_TYPE};
+
+// The containers of several files
+pub(crate) const ARCHIVE_TYPES: &[&str] = &[
+    "application/vnd.rar",
+    "application/x-7z-compressed",
+    "application/x-archive",
+    "application/x-bcpio",
+    "application/x-cpio",
+    "application/x-sv4cpio",
+    "application/x-tar",
+    "application/zip",
+];
+
+// The compressors of a single stream, whose types wrap another type
+pub(crate) const COMPRESSED_TYPES: &[&str] = &[
+    "application/gzip",
+    "application/x-bzip",
+    "application/x-compress",
+    "application/x-lrzip",
+    "application/x-lz4",
+    "application/x-lzip",
+    "application/x-lzma",
+    "application/x-lzop",
+    "application/x-xz",
+    "application/zstd",
+];
+
+// The structured syntax suffixes of the compressed types, from RFC 6839
+// and RFC 8478, like the `+gzip` of `application/x-tar+gzip`
+const COMPRESSION_SUFFIXES: &[&str] = &["+gzip", "+zstd"];
+
+// Returns the suffixes of the simple globs of @mime_type, like `.tar.gz`
+fn glob_suffixes<'a>(db: &'a SharedMimeInfo, mime_type: &'a str) -> impl Iterator<Item = &'a str> {
+    db.globs
+        .iter()
+        .filter(move |g| g.mime_type() == mime_type)
+        .filter_map(|g| match g.glob_type() {
+            GlobType::Simple(s) => Some(s.as_str()),
+            _ => None,
+        })
+}
+
+// Returns the type of the single stream inside a compressed container,
+// like the tar of `application/x-compressed-tar`, from the globs: its
+// `*.tar.gz` glob without the `.gz` of its parent `application/gzip` is
+// the glob of `application/x-tar`
+pub(crate) fn decompressed_type(db: &SharedMimeInfo, archive_mime: &str) -> Option<String> {
+    let archive_mime = db.canonical_ref(archive_mime);
+
+    for parent in db.parents_in(archive_mime, Hierarchy::Explicit) {
+        for parent_suffix in glob_suffixes(db, &parent) {
+            for suffix in glob_suffixes(db, archive_mime) {
+                let inner = match suffix.strip_suffix(parent_suffix) {
+                    Some(v) if !v.is_empty() => v,
+                    _ => continue,
+                };
+
+                if let GlobMatch::Unique(mime_type) = db.globs.lookup_best_match(&format!("x{}", inner), db.profile) {
+                    return Some(mime_type);
+                }
+            }
+        }
+    }
+
+    None
+}
+
+// Whether @mime_type is an archive or a compressed stream, or a format
+// stored in one of them
+pub(crate) fn is_archive(db: &SharedMimeInfo, mime_type: &str) -> bool {
+    let mime_type = db.canonical_ref(mime_type);
+    if mime_type.ends_with("+zip") || COMPRESSION_SUFFIXES.iter().any(|s| mime_type.ends_with(s)) {
+        return true;
+    }
+
+    ARCHIVE_TYPES
+        .iter()
+        .chain(COMPRESSED_TYPES)
+        .any(|t| db.is_subclass_of(mime_type, t))
+}
+
+// Returns the type of the stream compressed in @mime_type, from its
+// structured syntax suffix or from the globs of its compressor
+pub(crate) fn inner_type_of_compressed(db: &SharedMimeInfo, mime_type: &str) -> Option<String> {
+    let mime_type = db.canonical_ref(mime_type);
+    for suffix in COMPRESSION_SUFFIXES {
+        if let Some(inner) = mime_type.strip_suffix(suffix) {
+            return Some(db.canonical_ref(inner).to_string());
+        }
+    }
+
+    if !COMPRESSED_TYPES.iter().any(|t| db.is_subclass_of(mime_type, t)) {
+        return None;
+    }
+
+    decompressed_type(db, mime_type)
+}
+
+// Guesses the type of the @member_name of an archive of @archive_mime,
+// from its name and the @prefix of its contents
+pub(crate) fn guess_member(db: &SharedMimeInfo, archive_mime: &str, member_name: &str, prefix: &[u8]) -> Guess {
+    if member_name.ends_with('/') {
+        return Guess::new("inode/directory", false);
+    }
+
+    let guess = match member_name.rsplit('/').next().filter(|n| !n.is_empty()) {
+        Some(name) => db.guess().file_name(name).data(prefix).guess(),
+        None => db.guess().data(prefix).guess(),
+    };
+
+    // The stream of a compressed container is often nameless, but its type
+    // follows from the one of the container
+    if guess.uncertain() || guess.mime_type() == UNKNOWN_TYPE {
+        if let Some(mime_type) = decompressed_type(db, archive_mime) {
+            return Guess::new(mime_type, false);
+        }
+    }
+
+    guess
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn archive_members() {
+        let mime_db = SharedMimeInfo::new_for_directory(env::current_dir().unwrap().join("test_files"));
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+
+        assert_eq!(
+            decompressed_type(&mime_db, "application/x-compressed-tar"),
+            Some("application/x-tar".to_string())
+        );
+        assert_eq!(
+            decompressed_type(&mime_db, "application/x-bzip-compressed-tar"),
+            Some("application/x-tar".to_string())
+        );
+        assert_eq!(decompressed_type(&mime_db, "application/gzip"), None);
+        assert_eq!(decompressed_type(&mime_db, "application/zip"), None);
+
+        let guess = guess_member(&mime_db, "application/zip", "docs/images/logo.png", png_data);
+        assert_eq!(guess, Guess::new("image/png", false));
+        let guess = guess_member(&mime_db, "application/zip", "docs/images/", b"");
+        assert_eq!(guess, Guess::new("inode/directory", false));
+        // The contents decide when the name says nothing
+        let guess = guess_member(&mime_db, "application/x-tar", "LOGO", png_data);
+        assert_eq!(guess.mime_type(), "image/png");
+
+        let guess = guess_member(&mime_db, "application/x-compressed-tar", "", b"\x00\x01\x02");
+        assert_eq!(guess, Guess::new("application/x-tar", false));
+        let guess = guess_member(&mime_db, "application/x-tar", "", b"\x00\x01\x02");
+        assert_eq!(guess.mime_type(), UNKNOWN_TYPE);
+    }
+
+    #[test]
+    fn archive_types() {
+        let mime_db = SharedMimeInfo::new_for_directory(env::current_dir().unwrap().join("test_files"));
+
+        let archives = [
+            "application/zip",
+            "application/x-gzip",
+            "application/x-compressed-tar",
+            "application/epub+zip",
+        ];
+        for mime_type in &archives {
+            assert!(is_archive(&mime_db, mime_type), "{}", mime_type);
+        }
+        assert!(is_archive(&mime_db, "application/x-foo+gzip"));
+        assert!(!is_archive(&mime_db, "text/plain"));
+        assert!(!is_archive(&mime_db, "image/png"));
+
+        let inner = |mime_type| inner_type_of_compressed(&mime_db, mime_type);
+        assert_eq!(inner("application/x-compressed-tar").as_deref(), Some("application/x-tar"));
+        assert_eq!(inner("application/x-cpio-compressed").as_deref(), Some("application/x-cpio"));
+        assert_eq!(inner("application/x-tar+gzip").as_deref(), Some("application/x-tar"));
+        assert_eq!(inner("application/gzip"), None);
+        // An archive is not a compressed stream
+        assert_eq!(inner("application/vnd.oasis.opendocument.text"), None);
+    }
+}
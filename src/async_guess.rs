@@ -0,0 +1,251 @@
+use std::io;
+use std::path::Path;
+
+use tokio::fs::{self, File};
+use tokio::io::AsyncReadExt;
+
+use crate::guess::{inode_type, Head};
+use crate::{GlobMatch, Guess, GuessPolicy, IoBudget, NoContent, SharedMimeInfo};
+
+/// The content of an [`AsyncGuessBuilder`] reading the beginning of a
+/// file.
+pub struct AsyncFromPath<'a> {
+    path: &'a Path,
+    budget: IoBudget,
+}
+
+/// The content of an [`AsyncGuessBuilder`] reading the beginning of an
+/// open file.
+pub struct AsyncFromFile<'a>(&'a mut File);
+
+/// Guesses the MIME type of a file like a [`GuessBuilder`][crate::GuessBuilder],
+/// reading its contents asynchronously, created by
+/// [`SharedMimeInfo::guess_mime_type_async`].
+///
+/// The contents are read either from a path or from an open file; the
+/// futures must run on a tokio runtime.
+pub struct AsyncGuessBuilder<'a, C = NoContent> {
+    db: &'a SharedMimeInfo,
+    file_name: Option<&'a str>,
+    policy: GuessPolicy,
+    content: C,
+}
+
+async fn read_head(path: &Path, max_read: usize) -> io::Result<Head> {
+    // Reading a fifo could block until the other end writes to it
+    if let Some(mime_type) = inode_type(&fs::metadata(path).await?) {
+        return Ok(Head::Inode(mime_type));
+    }
+
+    let mut res = Vec::new();
+    File::open(path).await?.take(max_read as u64).read_to_end(&mut res).await?;
+
+    Ok(Head::Data(res))
+}
+
+impl<'a, C> AsyncGuessBuilder<'a, C> {
+    /// Sets the name of the file.
+    pub fn file_name(mut self, file_name: &'a str) -> AsyncGuessBuilder<'a, C> {
+        self.file_name = Some(file_name);
+        self
+    }
+
+    /// Sets how the file name and the contents are weighed.
+    pub fn policy(mut self, policy: GuessPolicy) -> AsyncGuessBuilder<'a, C> {
+        self.policy = policy;
+        self
+    }
+
+    fn with_content<D>(self, content: D) -> AsyncGuessBuilder<'a, D> {
+        AsyncGuessBuilder {
+            db: self.db,
+            file_name: self.file_name,
+            policy: self.policy,
+            content,
+        }
+    }
+
+    // Returns the guess from the file name, when it is enough to skip
+    // reading the contents
+    fn name_guess(&self) -> Option<Guess> {
+        match (self.policy, self.file_name) {
+            (GuessPolicy::NameFirst, Some(name)) => match self.db.lookup_file_name(name) {
+                GlobMatch::Unique(mime_type) => Some(Guess::new(mime_type, false)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn resolve(&self, data: Option<&[u8]>, uncertain: bool) -> Guess {
+        let mut builder = self.db.guess().policy(self.policy);
+        if let Some(name) = self.file_name {
+            builder = builder.file_name(name);
+        }
+
+        builder.resolve(data, uncertain)
+    }
+}
+
+impl<'a> AsyncGuessBuilder<'a, NoContent> {
+    pub(crate) fn new(db: &'a SharedMimeInfo) -> AsyncGuessBuilder<'a, NoContent> {
+        AsyncGuessBuilder {
+            db,
+            file_name: None,
+            policy: GuessPolicy::default(),
+            content: NoContent,
+        }
+    }
+
+    /// Sets the @path of the file to read the contents from, when the
+    /// file name is not enough; the file name defaults to the one of
+    /// @path.
+    pub fn path(self, path: &'a Path) -> AsyncGuessBuilder<'a, AsyncFromPath<'a>> {
+        self.with_content(AsyncFromPath {
+            path,
+            budget: IoBudget::default(),
+        })
+    }
+
+    /// Sets the open @file to read the contents from, when the file name is
+    /// not enough, like an upload being received.
+    ///
+    /// Only the beginning of the file is read from its current position, up
+    /// to the largest extent of the magic entries of the database.
+    pub fn file(self, file: &'a mut File) -> AsyncGuessBuilder<'a, AsyncFromFile<'a>> {
+        self.with_content(AsyncFromFile(file))
+    }
+}
+
+impl<'a> AsyncGuessBuilder<'a, AsyncFromPath<'a>> {
+    /// Sets the limits on the I/O done when reading the file; the timeout
+    /// needs the time driver of the tokio runtime.
+    pub fn limits(mut self, budget: IoBudget) -> AsyncGuessBuilder<'a, AsyncFromPath<'a>> {
+        self.content.budget = budget;
+        self
+    }
+
+    /// Guesses the MIME type, reading the file if needed.
+    ///
+    /// If the contents cannot be read within the limits, the guess only
+    /// uses the file name, and is marked as uncertain.
+    pub async fn guess(mut self) -> Guess {
+        if self.file_name.is_none() {
+            self.file_name = self.content.path.file_name().and_then(|n| n.to_str());
+        }
+
+        // The file is not read when the name is enough
+        if let Some(guess) = self.name_guess() {
+            return guess;
+        }
+
+        if self.content.budget.metadata_only {
+            match fs::metadata(self.content.path).await {
+                Ok(metadata) => {
+                    if let Some(mime_type) = inode_type(&metadata) {
+                        return Guess::new(mime_type, false);
+                    }
+                    if metadata.len() == 0 {
+                        return self.resolve(Some(&[]), false);
+                    }
+                }
+                Err(_) => return self.resolve(None, true),
+            }
+
+            let unique = matches!(self.file_name.map(|n| self.db.lookup_file_name(n)), Some(GlobMatch::Unique(_)));
+            return self.resolve(None, !unique);
+        }
+
+        let head = read_head(self.content.path, self.content.budget.max_read);
+        let head = match self.content.budget.timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, head).await {
+                Ok(res) => res,
+                Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "reading the file timed out")),
+            },
+            None => head.await,
+        };
+
+        match head {
+            Ok(Head::Data(data)) => {
+                #[cfg(feature = "metrics")]
+                self.db.count_sniffed_bytes(data.len());
+                self.resolve(Some(&data), false)
+            }
+            Ok(Head::Inode(mime_type)) => Guess::new(mime_type, false),
+            Err(_) => self.resolve(None, true),
+        }
+    }
+}
+
+impl<'a> AsyncGuessBuilder<'a, AsyncFromFile<'a>> {
+    /// Guesses the MIME type, reading the beginning of the file if needed.
+    ///
+    /// If reading the file fails, the guess only uses the file name, and is
+    /// marked as uncertain.
+    pub async fn guess(self) -> Guess {
+        // The file is not read when the name is enough
+        if let Some(guess) = self.name_guess() {
+            return guess;
+        }
+
+        let mut data = Vec::new();
+        let max_read = self.db.max_magic_extent();
+        #[cfg(feature = "infer")]
+        let max_read = max_read.max(crate::guess::INFER_EXTENT);
+        match (&mut *self.content.0).take(max_read as u64).read_to_end(&mut data).await {
+            Ok(_) => {
+                #[cfg(feature = "metrics")]
+                self.db.count_sniffed_bytes(data.len());
+                self.resolve(Some(&data), false)
+            }
+            Err(_) => self.resolve(None, true),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::time::Duration;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn guess_async() {
+        let mime_db = SharedMimeInfo::new_for_directory(env::current_dir().unwrap().join("test_files"));
+        let files = env::current_dir().unwrap().join("test_files/files");
+        let png_path = files.join("rust-logo.png");
+
+        block_on(async {
+            let guess = mime_db.guess_mime_type_async().path(&png_path).guess().await;
+            assert_eq!(guess, Guess::new("image/png", false));
+
+            // The contents decide when the name says nothing
+            let guess = mime_db
+                .guess_mime_type_async()
+                .file_name("upload")
+                .path(&png_path)
+                .limits(IoBudget::new().timeout(Duration::from_secs(10)))
+                .guess()
+                .await;
+            assert_eq!(guess.mime_type(), "image/png");
+
+            let mut file = File::open(&png_path).await.unwrap();
+            let guess = mime_db.guess_mime_type_async().file(&mut file).guess().await;
+            assert_eq!(guess, Guess::new("image/png", false));
+
+            let guess = mime_db.guess_mime_type_async().path(&files).guess().await;
+            assert_eq!(guess.mime_type(), "inode/directory");
+
+            let guess = mime_db.guess_mime_type_async().path(&files.join("missing")).guess().await;
+            assert!(guess.uncertain());
+        });
+    }
+}
@@ -0,0 +1,53 @@
+//! Entry points running a single stage of the MIME type detection, for
+//! benchmarking and regression tracking, e.g. with Criterion:
+//!
+//! ```ignore
+//! let db = xdg_mime::bench::load(xdg_mime::SharedMimeInfo::builder());
+//! let mut ctx = xdg_mime::LookupContext::new();
+//! c.bench_function("glob lookup", |b| {
+//!     b.iter(|| xdg_mime::bench::glob_lookup(&db, black_box("photo.jpg"), &mut ctx))
+//! });
+//! ```
+//!
+//! Unlike the methods of [`SharedMimeInfo`], they never record the lookups
+//! nor allocate their results, so that only the stage itself is measured;
+//! their signatures are kept stable across releases.
+
+use crate::{magic, LookupContext, MagicInput, SharedMimeInfo, SharedMimeInfoBuilder};
+
+/// Loads the database described by @builder, merging all its data
+/// directories; [`SharedMimeInfo::builder`] describes the database of the
+/// XDG base directories.
+pub fn load(builder: SharedMimeInfoBuilder) -> SharedMimeInfo {
+    builder.build()
+}
+
+/// Looks up the MIME type of the best glob matching @file_name, without
+/// looking at any data, using the buffers of @ctx.
+pub fn glob_lookup<'a>(db: &'a SharedMimeInfo, file_name: &str, ctx: &mut LookupContext) -> Option<&'a str> {
+    db.globs.best_match_with(file_name, db.profile, ctx).map(|v| v.0)
+}
+
+/// Looks up the MIME type of the first magic entry matching @data, without
+/// looking at any file name.
+pub fn magic_match<'a, D: MagicInput + ?Sized>(db: &'a SharedMimeInfo, data: &D) -> Option<&'a str> {
+    magic::lookup_data_type(&db.magic, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_stages() {
+        let db = load(SharedMimeInfo::builder().system_directories(false).directory("test_files"));
+        let mut ctx = LookupContext::new();
+
+        assert_eq!(glob_lookup(&db, "file.tar.gz", &mut ctx), Some("application/x-compressed-tar"));
+        assert_eq!(glob_lookup(&db, "file.unknown", &mut ctx), None);
+
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+        assert_eq!(magic_match(&db, &png_data[..]), Some("image/png"));
+        assert_eq!(magic_match(&db, &b"\x00\x01\x02"[..]), None);
+    }
+}
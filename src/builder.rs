@@ -0,0 +1,519 @@
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "remote")]
+use crate::remote::RemoteDatabase;
+#[cfg(feature = "remote")]
+use crate::{LoadWarning, LoadWarningKind};
+use crate::app_associations::AppAssociations;
+use crate::{locale, DotfileHandling, FilesystemProfile, LoadError, Loader, MemoryDirectory, SharedMimeInfo};
+
+/// A builder for [`SharedMimeInfo`], controlling which data directories
+/// are loaded.
+///
+/// By default, the builder loads the MIME information from the XDG base
+/// directories, like [`SharedMimeInfo::new`] does.
+#[derive(Clone)]
+pub struct SharedMimeInfoBuilder {
+    system: bool,
+    directories: Vec<PathBuf>,
+    memory_directories: Vec<MemoryDirectory>,
+    fallback_directories: Vec<PathBuf>,
+    app_directories: Vec<PathBuf>,
+    profile: FilesystemProfile,
+    dotfiles: DotfileHandling,
+    locales: Vec<String>,
+    strict: bool,
+    cache: bool,
+    interpreters: Vec<(String, String)>,
+    executable_types: Vec<(String, bool)>,
+    extension_types: bool,
+    max_glob_complexity: Option<usize>,
+    #[cfg(feature = "remote")]
+    remotes: Vec<RemoteDatabase>,
+}
+
+impl SharedMimeInfoBuilder {
+    /// Creates a new builder, loading from the XDG base directories.
+    pub fn new() -> SharedMimeInfoBuilder {
+        SharedMimeInfoBuilder {
+            system: true,
+            directories: Vec::new(),
+            memory_directories: Vec::new(),
+            fallback_directories: Vec::new(),
+            app_directories: Vec::new(),
+            profile: FilesystemProfile::default(),
+            dotfiles: DotfileHandling::default(),
+            locales: Vec::new(),
+            strict: false,
+            cache: true,
+            interpreters: Vec::new(),
+            executable_types: Vec::new(),
+            extension_types: false,
+            max_glob_complexity: None,
+            #[cfg(feature = "remote")]
+            remotes: Vec::new(),
+        }
+    }
+
+    /// Sets whether the XDG base directories should be loaded.
+    ///
+    /// With the `embedded-db` feature, when none of them has a `mime`
+    /// sub-directory, the database bundled in the program is loaded
+    /// instead.
+    pub fn system_directories(mut self, system: bool) -> SharedMimeInfoBuilder {
+        self.system = system;
+        self
+    }
+
+    /// Adds a data directory to load, after the XDG base directories.
+    ///
+    /// The MIME information is expected to be inside the `mime`
+    /// sub-directory of @directory.
+    pub fn directory<P: AsRef<Path>>(mut self, directory: P) -> SharedMimeInfoBuilder {
+        self.directories.push(directory.as_ref().to_path_buf());
+        self
+    }
+
+    /// Adds a data directory whose files are held in memory, like a
+    /// database embedded in the program, loaded after the directories on
+    /// disk; with no system directories, no MIME database is needed on
+    /// disk at all.
+    pub fn memory_directory(mut self, directory: MemoryDirectory) -> SharedMimeInfoBuilder {
+        self.memory_directories.push(directory);
+        self
+    }
+
+    /// Adds a fallback data directory, like a database bundled with the
+    /// application.
+    ///
+    /// Unlike [`directory`](#method.directory), the contents of a fallback
+    /// directory never override the other directories. Each kind of
+    /// information is merged on its own: a MIME type gets the globs of the
+    /// fallback directory only if it has no globs anywhere else, its magic
+    /// only if it has no magic anywhere else, and likewise for the tree
+    /// magic, aliases, parents and icons. This allows using newer formats
+    /// on systems with an older database, without overriding any local
+    /// customization.
+    pub fn fallback_directory<P: AsRef<Path>>(mut self, directory: P) -> SharedMimeInfoBuilder {
+        self.fallback_directories.push(directory.as_ref().to_path_buf());
+        self
+    }
+
+    /// Loads the associations private to an application, after all the
+    /// other directories; they take precedence over the globs of the
+    /// desktop-wide database, which they never modify.
+    pub fn app_associations(mut self, associations: &AppAssociations) -> SharedMimeInfoBuilder {
+        self.app_directories.push(associations.data_dir().to_path_buf());
+        self
+    }
+
+    /// Sets the profile of the file system the looked up file names come
+    /// from; by default, the globs are matched case-insensitively on macOS.
+    pub fn filesystem_profile(mut self, profile: FilesystemProfile) -> SharedMimeInfoBuilder {
+        self.profile = profile;
+        self
+    }
+
+    /// Sets how the leading dot of hidden files is treated when matching
+    /// the suffix globs; by default, like the reference implementation,
+    /// it is an ordinary character.
+    pub fn dotfile_handling(mut self, dotfiles: DotfileHandling) -> SharedMimeInfoBuilder {
+        self.dotfiles = dotfiles;
+        self
+    }
+
+    /// Sets the chain of locales the descriptions are looked up with, from
+    /// the most to the least preferred; by default, the descriptions are
+    /// not translated.
+    pub fn locales(mut self, locales: Vec<String>) -> SharedMimeInfoBuilder {
+        self.locales = locales;
+        self
+    }
+
+    /// Sets the chain of locales the descriptions are looked up with from
+    /// the `LANGUAGE`, `LC_ALL`, `LC_MESSAGES` and `LANG` environment
+    /// variables, like gettext does.
+    pub fn locales_from_env(mut self) -> SharedMimeInfoBuilder {
+        self.locales = locale::locale_chain_from_env();
+        self
+    }
+
+    /// Sets @mime_type as the type of the scripts run by the interpreter
+    /// @name, like `python3`, given without its path in their `#!` line.
+    ///
+    /// The interpreters set here take precedence over the built-in ones;
+    /// the magic rules of the database still come first.
+    pub fn interpreter(mut self, name: &str, mime_type: &str) -> SharedMimeInfoBuilder {
+        self.interpreters.retain(|v| v.0 != name);
+        self.interpreters.push((name.to_string(), mime_type.to_string()));
+        self
+    }
+
+    /// Sets whether the files of @mime_type and its sub-classes can run
+    /// code when opened, for
+    /// [`SharedMimeInfo::is_potentially_executable`], instead of the
+    /// built-in lists; e.g. to trust the JavaScript files, or to distrust
+    /// the types of a custom package.
+    ///
+    /// When several overrides apply, the one of the closest ancestor of
+    /// the type wins.
+    pub fn executable_type(mut self, mime_type: &str, executable: bool) -> SharedMimeInfoBuilder {
+        self.executable_types.retain(|v| v.0 != mime_type);
+        self.executable_types.push((mime_type.to_string(), executable));
+        self
+    }
+
+    /// Sets whether the file names with an extension that matches no glob
+    /// get the `application/x-extension-<ext>` type, like some desktops
+    /// do, instead of `application/octet-stream`, so that the applications
+    /// opening them can be remembered per extension.
+    ///
+    /// The data still decides first. Each extension type returned is kept
+    /// by the database until it is dropped, so that the lookups filling a
+    /// [`LookupContext`][crate::LookupContext] can borrow it.
+    pub fn extension_types(mut self, extension_types: bool) -> SharedMimeInfoBuilder {
+        self.extension_types = extension_types;
+        self
+    }
+
+    /// Sets whether the database is loaded in strict mode, where the
+    /// malformed lines, the unreadable files and the files missing from the
+    /// data directories make [`try_build`](#method.try_build) fail, instead
+    /// of being skipped.
+    ///
+    /// The XDG base directories without a `mime` sub-directory are still
+    /// skipped.
+    pub fn strict(mut self, strict: bool) -> SharedMimeInfoBuilder {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets whether the binary `mime.cache` files of the data directories
+    /// are used when present, instead of parsing the text files they are
+    /// generated from; they are by default.
+    pub fn use_cache(mut self, cache: bool) -> SharedMimeInfoBuilder {
+        self.cache = cache;
+        self
+    }
+
+    /// Sets the largest [complexity](crate::Glob::complexity) of the globs
+    /// loaded, e.g. to protect the lookup latency from the pathological
+    /// patterns of untrusted custom packages, like huge character classes
+    /// or dozens of wildcards; by default, all the globs are loaded.
+    ///
+    /// The globs above it are skipped, and reported in
+    /// [`SharedMimeInfo::load_warnings`], which fails in strict mode.
+    pub fn max_glob_complexity(mut self, max: usize) -> SharedMimeInfoBuilder {
+        self.max_glob_complexity = Some(max);
+        self
+    }
+
+    /// Adds a remote database, fetched over HTTPS into its cache directory
+    /// and loaded after all the local directories.
+    ///
+    /// If the remote database cannot be fetched, and no valid copy exists
+    /// in its cache directory, it is skipped, and reported in
+    /// [`SharedMimeInfo::load_warnings`], which fails in strict mode.
+    #[cfg(feature = "remote")]
+    pub fn remote(mut self, remote: RemoteDatabase) -> SharedMimeInfoBuilder {
+        self.remotes.push(remote);
+        self
+    }
+
+    // Returns the data directories on disk the database may be loaded
+    // from, including the ones without a `mime` sub-directory yet
+    pub(crate) fn candidate_data_dirs(&self) -> Vec<PathBuf> {
+        let mut res = Vec::new();
+        if self.system {
+            res.extend(SharedMimeInfo::xdg_data_dirs());
+        }
+        res.extend(self.directories.iter().cloned());
+        #[cfg(feature = "remote")]
+        res.extend(self.remotes.iter().map(|v| v.cache_dir().to_path_buf()));
+        res.extend(self.app_directories.iter().cloned());
+        res.extend(self.fallback_directories.iter().cloned());
+
+        res
+    }
+
+    /// Creates the SharedMimeInfo database.
+    ///
+    /// The problems found while loading the database are available from
+    /// [`SharedMimeInfo::load_warnings`].
+    pub fn build(self) -> SharedMimeInfo {
+        let mut db = SharedMimeInfo::create();
+        let mut loader = Loader::new()
+            .report_missing_files(self.strict)
+            .use_cache(self.cache);
+        // The data directory of an application only has globs, which must
+        // not be reported as missing files in strict mode
+        let mut app_loader = Loader::new().use_cache(false);
+        if let Some(max) = self.max_glob_complexity {
+            loader = loader.max_glob_complexity(max);
+            app_loader = app_loader.max_glob_complexity(max);
+        }
+
+        if self.system {
+            for dir in SharedMimeInfo::xdg_data_dirs() {
+                if dir.join("mime").is_dir() {
+                    db.load_directory(dir, &loader);
+                }
+            }
+        }
+
+        // The bundled database stands for the missing system one
+        #[cfg(feature = "embedded-db")]
+        if self.system && db.data_dirs.is_empty() {
+            db.load_memory_directory(&crate::embedded_directory(), &loader);
+        }
+
+        for dir in &self.directories {
+            db.load_directory(dir, &loader);
+        }
+
+        for dir in &self.memory_directories {
+            db.load_memory_directory(dir, &loader);
+        }
+
+        #[cfg(feature = "remote")]
+        for remote in &self.remotes {
+            match remote.fetch() {
+                Ok(dir) => db.load_directory(dir, &loader),
+                Err(e) => db.load_warnings.push(LoadWarning {
+                    file: remote.cache_dir().to_path_buf(),
+                    line: None,
+                    kind: LoadWarningKind::Remote,
+                    reason: e.to_string(),
+                }),
+            }
+        }
+
+        for dir in &self.app_directories {
+            db.load_directory(dir, &app_loader);
+        }
+
+        for dir in &self.fallback_directories {
+            let mut fallback = SharedMimeInfo::create();
+            fallback.load_directory(dir, &loader);
+            db.add_missing(fallback);
+        }
+
+        db.canonicalize_types();
+        db.break_parent_cycles();
+        db.profile = self.profile;
+        db.globs.set_dotfile_handling(self.dotfiles);
+        db.locales = self.locales.clone();
+        db.interpreters = self.interpreters.clone();
+        db.executable_types = self.executable_types.clone();
+        db.extension_types = self.extension_types;
+        db.source = self;
+
+        db
+    }
+
+    /// Creates the SharedMimeInfo database, failing in strict mode if any
+    /// problem was found while loading it.
+    pub fn try_build(self) -> Result<SharedMimeInfo, LoadError> {
+        let strict = self.strict;
+        let db = self.build();
+
+        if strict && !db.load_warnings().is_empty() {
+            return Err(LoadError::new(db.load_warnings().to_vec()));
+        }
+
+        Ok(db)
+    }
+}
+
+impl Default for SharedMimeInfoBuilder {
+    fn default() -> SharedMimeInfoBuilder {
+        SharedMimeInfoBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use crate::GlobMatch;
+
+    #[test]
+    fn build_for_directory() {
+        let cwd = env::current_dir().unwrap().to_string_lossy().into_owned();
+        let dir = PathBuf::from(&format!("{}/test_files", cwd));
+
+        let mime_db = SharedMimeInfoBuilder::new()
+            .system_directories(false)
+            .directory(dir)
+            .build();
+
+        assert_eq!(
+            mime_db.get_mime_types_from_file_name("foo.txt"),
+            vec!["text/plain".to_string()]
+        );
+    }
+
+    #[test]
+    fn build_with_fallback() {
+        let cwd = env::current_dir().unwrap().to_string_lossy().into_owned();
+        let dir = PathBuf::from(&format!("{}/test_files", cwd));
+
+        let local_dir = dir.join("fallback");
+
+        let mime_db = SharedMimeInfoBuilder::new()
+            .system_directories(false)
+            .directory(&local_dir)
+            .fallback_directory(dir)
+            .build();
+
+        // text/plain is defined locally, so its fallback globs are ignored
+        assert_eq!(
+            mime_db.get_mime_types_from_file_name("foo.text"),
+            vec!["text/plain".to_string()]
+        );
+        assert_eq!(
+            mime_db.get_mime_types_from_file_name("foo.txt"),
+            vec![crate::UNKNOWN_TYPE.to_string()]
+        );
+
+        // image/gif is only defined in the fallback directory
+        assert_eq!(
+            mime_db.get_mime_types_from_file_name("bar.gif"),
+            vec!["image/gif".to_string()]
+        );
+
+        // image/png only has local globs, so its fallback magic is used
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+        assert_eq!(mime_db.get_mime_types_from_file_name("logo.png"), vec![crate::UNKNOWN_TYPE.to_string()]);
+        assert_eq!(mime_db.get_mime_type_for_data(png_data), Some("image/png".to_string()));
+    }
+
+    #[test]
+    fn build_from_memory() {
+        let directory = MemoryDirectory::new("embedded")
+            .globs2(include_bytes!("../test_files/mime/globs2"))
+            .magic(include_bytes!("../test_files/mime/magic"))
+            .aliases(include_bytes!("../test_files/mime/aliases"))
+            .subclasses(include_bytes!("../test_files/mime/subclasses"));
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+
+        let mime_db = SharedMimeInfoBuilder::new()
+            .system_directories(false)
+            .strict(true)
+            .memory_directory(directory)
+            .try_build()
+            .unwrap();
+
+        assert_eq!(mime_db.lookup_file_name("foo.txt"), GlobMatch::Unique("text/plain".to_string()));
+        assert_eq!(mime_db.get_mime_type_for_data(png_data), Some("image/png".to_string()));
+        assert_eq!(mime_db.unalias_mime_type("application/x-gzip"), Some("application/gzip".to_string()));
+        assert!(mime_db.is_subclass_of("application/x-compressed-tar", "application/gzip"));
+        assert!(mime_db.loaded_files.is_empty() && mime_db.data_dirs.is_empty());
+    }
+
+    #[test]
+    fn build_strict() {
+        let mime_db = SharedMimeInfoBuilder::new()
+            .system_directories(false)
+            .directory("test_files")
+            .strict(true)
+            .try_build();
+        assert!(mime_db.is_ok());
+
+        let dir = env::current_dir().unwrap().join("test_files/strict");
+
+        let builder = SharedMimeInfoBuilder::new().system_directories(false).directory(&dir);
+        assert!(builder.clone().try_build().is_ok());
+
+        let err = builder.strict(true).try_build().err().unwrap();
+        assert_eq!(err.warnings()[0].file, dir.join("mime/globs2"));
+        assert_eq!(err.warnings()[0].line, Some(2));
+        assert!(err.warnings().iter().any(|w| w.file == dir.join("mime/aliases")));
+    }
+
+    #[test]
+    fn build_with_glob_complexity() {
+        let dir = env::current_dir().unwrap().join("test_files/complexity");
+
+        let builder = SharedMimeInfoBuilder::new().system_directories(false).directory(&dir);
+        let mime_db = builder.clone().build();
+        assert_eq!(mime_db.lookup_file_name("a1.bar"), GlobMatch::Unique("text/x-bar".to_string()));
+        assert!(mime_db.load_warnings().is_empty());
+
+        let mime_db = builder.clone().max_glob_complexity(5).build();
+        assert_eq!(mime_db.lookup_file_name("a1.bar"), GlobMatch::NoMatch);
+        assert_eq!(mime_db.lookup_file_name("x.baz1"), GlobMatch::Unique("text/x-baz".to_string()));
+        assert_eq!(mime_db.lookup_file_name("x.foo"), GlobMatch::Unique("text/x-foo".to_string()));
+        assert_eq!(mime_db.load_warnings().len(), 1);
+        assert_eq!(mime_db.load_warnings()[0].file, dir.join("mime/globs2"));
+        assert!(mime_db.load_warnings()[0].reason.contains("*[a-z]*[0-9]*.bar"));
+
+        assert!(builder.max_glob_complexity(5).strict(true).try_build().is_err());
+    }
+
+    #[test]
+    fn build_with_cache() {
+        let cached = SharedMimeInfo::new_for_directory("test_files");
+        let uncached = SharedMimeInfoBuilder::new()
+            .system_directories(false)
+            .directory("test_files")
+            .use_cache(false)
+            .build();
+
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+        for name in &["foo.png", "foo.c", "core", "foo.tar.gz", "Makefile"] {
+            assert_eq!(cached.lookup_file_name(name), uncached.lookup_file_name(name), "{}", name);
+        }
+        // The cache has no case-insensitive duplicate of the `*.C` glob
+        assert_eq!(cached.lookup_file_name("foo.C"), GlobMatch::Unique("text/x-c++src".to_string()));
+        assert_eq!(cached.get_mime_type_for_data(png_data), Some("image/png".to_string()));
+        assert_eq!(cached.unalias_mime_type("application/x-gzip"), Some("application/gzip".to_string()));
+        assert_eq!(cached.ancestors("image/svg+xml"), uncached.ancestors("image/svg+xml"));
+        assert_eq!(cached.lookup_icon_names("application/json"), uncached.lookup_icon_names("application/json"));
+    }
+
+    #[test]
+    fn build_with_subclass_cycle() {
+        let dir = env::current_dir().unwrap().join("test_files/cycle");
+
+        let mime_db = SharedMimeInfoBuilder::new()
+            .system_directories(false)
+            .directory(&dir)
+            .build();
+
+        let warnings: Vec<_> = mime_db
+            .load_warnings()
+            .iter()
+            .filter(|w| w.reason.contains("cycle"))
+            .collect();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].file, dir.join("mime/subclasses"));
+        assert_eq!(
+            mime_db.get_parents("text/x-c"),
+            Some(vec!["text/x-c".to_string()])
+        );
+        assert!(mime_db.is_subclass_of("text/x-a", "text/x-c"));
+        assert!(!mime_db.is_subclass_of("text/x-c", "text/x-a"));
+    }
+
+    #[test]
+    fn build_with_interpreters() {
+        let mime_db = SharedMimeInfoBuilder::new()
+            .system_directories(false)
+            .directory(env::current_dir().unwrap().join("test_files"))
+            .interpreter("mypython", "text/x-python3")
+            .interpreter("pypy3", "application/x-shellscript")
+            .interpreter("pypy3", "text/x-python")
+            .build();
+
+        assert_eq!(
+            mime_db.get_mime_type_for_data(b"#!/usr/local/bin/mypython\n"),
+            Some("text/x-python3".to_string())
+        );
+        // The magic rules come first
+        assert_eq!(mime_db.get_mime_type_for_data(b"#!/usr/bin/ruby\n"), Some("application/x-ruby".to_string()));
+        assert_eq!(mime_db.get_mime_type_for_data(b"#!/opt/bin/pypy3\n"), Some("text/x-python".to_string()));
+        assert_eq!(mime_db.get_mime_type_for_data(b"#!/usr/bin/env -S awk -f\n"), Some("application/x-awk".to_string()));
+        assert_eq!(mime_db.get_mime_type_for_data(b"#!/usr/bin/env frob\n"), None);
+    }
+}
@@ -0,0 +1,417 @@
+use std::cell::Cell;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str;
+
+use memmap2::Mmap;
+
+use crate::alias::Alias;
+use crate::glob::Glob;
+use crate::icon::Icon;
+use crate::magic::{self, MagicEntry, MagicRule};
+use crate::parent::Subclass;
+
+// The version of the cache format, as written by update-mime-database
+const MAJOR_VERSION: u16 = 1;
+const MINOR_VERSION: u16 = 2;
+
+// The deepest suffix tree and matchlet tree followed, to stop on the
+// broken files with cycles
+const MAX_DEPTH: usize = 256;
+
+// The smallest node of the suffix tree and of the matchlet tree, in bytes;
+// the trees of a valid file cannot have more nodes than fit in it
+const MIN_NODE_SIZE: usize = 12;
+
+// The flag set in the weight of the case-sensitive globs
+const CASE_SENSITIVE_FLAG: u32 = 0x100;
+
+/// The MIME information of a `mime.cache` file, the binary cache written
+/// by update-mime-database alongside the text files.
+pub struct MimeCache {
+    pub aliases: Vec<Alias>,
+    pub subclasses: Vec<Subclass>,
+    pub icons: Vec<Icon>,
+    pub generic_icons: Vec<Icon>,
+    pub globs: Vec<Glob>,
+    pub magic: Vec<MagicEntry>,
+}
+
+// Reads the big-endian values of the cache, checking all the offsets
+struct Reader<'a> {
+    data: &'a [u8],
+    // The number of tree nodes walked so far, to stop on the broken files
+    // whose nodes share their children
+    nodes: Cell<usize>,
+}
+
+fn invalid_data(offset: usize) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid offset {}", offset))
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader {
+            data,
+            nodes: Cell::new(0),
+        }
+    }
+
+    // Counts the walk of the @count tree nodes at @offset
+    fn visit(&self, offset: usize, count: usize) -> io::Result<()> {
+        let nodes = self.nodes.get().saturating_add(count);
+        if nodes > self.data.len() / MIN_NODE_SIZE {
+            return Err(invalid_data(offset));
+        }
+        self.nodes.set(nodes);
+
+        Ok(())
+    }
+
+    fn u16_at(&self, offset: usize) -> io::Result<u16> {
+        match self.data.get(offset..offset.saturating_add(2)) {
+            Some(b) => Ok(u16::from_be_bytes([b[0], b[1]])),
+            None => Err(invalid_data(offset)),
+        }
+    }
+
+    fn u32_at(&self, offset: usize) -> io::Result<u32> {
+        match self.data.get(offset..offset.saturating_add(4)) {
+            Some(b) => Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]])),
+            None => Err(invalid_data(offset)),
+        }
+    }
+
+    fn offset_at(&self, offset: usize) -> io::Result<usize> {
+        self.u32_at(offset).map(|v| v as usize)
+    }
+
+    // Reads the NUL-terminated string at the offset stored at @offset
+    fn str_at(&self, offset: usize) -> io::Result<&'a str> {
+        let start = self.offset_at(offset)?;
+        let rest = self.data.get(start..).ok_or_else(|| invalid_data(start))?;
+        let len = rest.iter().position(|b| *b == 0).ok_or_else(|| invalid_data(start))?;
+
+        str::from_utf8(&rest[..len]).map_err(|_| invalid_data(start))
+    }
+
+    fn bytes_at(&self, offset: usize, len: usize) -> io::Result<&'a [u8]> {
+        self.data
+            .get(offset..offset.saturating_add(len))
+            .ok_or_else(|| invalid_data(offset))
+    }
+
+    // Iterates over the offsets of the @size-byte entries of the list at
+    // the offset stored at @offset
+    fn list_at(&self, offset: usize, size: usize) -> io::Result<impl Iterator<Item = usize>> {
+        let start = self.offset_at(offset)?;
+        let count = self.offset_at(start)?;
+
+        // Check the whole list fits before iterating
+        self.bytes_at(start + 4, count.saturating_mul(size))?;
+
+        Ok((0..count).map(move |i| start + 4 + i * size))
+    }
+
+    // Reads the pairs of strings of the list at the offset stored at @offset
+    fn pairs_at(&self, offset: usize) -> io::Result<Vec<(&'a str, &'a str)>> {
+        let mut res = Vec::new();
+        for entry in self.list_at(offset, 8)? {
+            res.push((self.str_at(entry)?, self.str_at(entry + 4)?));
+        }
+
+        Ok(res)
+    }
+
+    fn globs_at(&self, offset: usize, res: &mut Vec<Glob>) -> io::Result<()> {
+        for entry in self.list_at(offset, 12)? {
+            let pattern = self.str_at(entry)?;
+            let mime_type = self.str_at(entry + 4)?;
+            let flags = self.u32_at(entry + 8)?;
+
+            res.push(glob(mime_type, pattern.to_string(), flags));
+        }
+
+        Ok(())
+    }
+
+    // Walks the reverse suffix tree nodes at @offset; @suffix is the
+    // reversed suffix of their parent
+    fn suffixes_at(
+        &self,
+        offset: usize,
+        count: usize,
+        suffix: &mut Vec<char>,
+        res: &mut Vec<Glob>,
+    ) -> io::Result<()> {
+        if suffix.len() > MAX_DEPTH {
+            return Err(invalid_data(offset));
+        }
+        self.visit(offset, count)?;
+
+        for i in 0..count {
+            let node = offset + i * 12;
+            let character = self.u32_at(node)?;
+
+            // The leaves hold the MIME types of their parent's suffix
+            if character == 0 {
+                let mime_type = self.str_at(node + 4)?;
+                let flags = self.u32_at(node + 8)?;
+                let pattern: String = std::iter::once('*').chain(suffix.iter().rev().copied()).collect();
+
+                res.push(glob(mime_type, pattern, flags));
+                continue;
+            }
+
+            let c = std::char::from_u32(character).ok_or_else(|| invalid_data(node))?;
+            let n_children = self.offset_at(node + 4)?;
+            let first_child = self.offset_at(node + 8)?;
+
+            suffix.push(c);
+            self.suffixes_at(first_child, n_children, suffix, res)?;
+            suffix.pop();
+        }
+
+        Ok(())
+    }
+
+    // Flattens the matchlets at @offset, and their children, into @rules
+    fn matchlets_at(
+        &self,
+        offset: usize,
+        count: usize,
+        indent: u32,
+        rules: &mut Vec<MagicRule>,
+    ) -> io::Result<()> {
+        if indent as usize > MAX_DEPTH {
+            return Err(invalid_data(offset));
+        }
+        self.visit(offset, count)?;
+
+        for i in 0..count {
+            let matchlet = offset + i * 32;
+            let range_start = self.u32_at(matchlet)?;
+            let range_length = self.u32_at(matchlet + 4)?;
+            let word_size = self.u32_at(matchlet + 8)?;
+            let value_length = self.offset_at(matchlet + 12)?;
+            let value = self.bytes_at(self.offset_at(matchlet + 16)?, value_length)?;
+            let mask = match self.offset_at(matchlet + 20)? {
+                0 => None,
+                v => Some(self.bytes_at(v, value_length)?.to_vec()),
+            };
+            let n_children = self.offset_at(matchlet + 24)?;
+            let first_child = self.offset_at(matchlet + 28)?;
+
+            if value_length > u16::MAX as usize {
+                return Err(invalid_data(matchlet));
+            }
+
+            rules.push(magic::host_rule(MagicRule::new(
+                indent,
+                range_start,
+                value.to_vec(),
+                mask,
+                word_size,
+                range_length,
+            )));
+            self.matchlets_at(first_child, n_children, indent + 1, rules)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn glob(mime_type: &str, pattern: String, flags: u32) -> Glob {
+    Glob::new(
+        mime_type.to_string(),
+        pattern,
+        (flags & 0xff) as i32,
+        flags & CASE_SENSITIVE_FLAG != 0,
+    )
+}
+
+// The offsets of the lists in the header
+const ALIAS_LIST: usize = 4;
+const PARENT_LIST: usize = 8;
+const LITERAL_LIST: usize = 12;
+const REVERSE_SUFFIX_TREE: usize = 16;
+const GLOB_LIST: usize = 20;
+const MAGIC_LIST: usize = 24;
+const ICONS_LIST: usize = 32;
+const GENERIC_ICONS_LIST: usize = 36;
+
+/// Parses the contents of a `mime.cache` file, or returns `None` if it
+/// has a version of the format that is not supported.
+///
+/// The cache does not have the list of types nor the treemagic rules,
+/// which are only in the text files. The magic entries are recorded as
+/// coming from @file_name.
+pub fn read_cache(data: &[u8], file_name: &Path) -> io::Result<Option<MimeCache>> {
+    let reader = Reader::new(data);
+
+    if reader.u16_at(0)? != MAJOR_VERSION || reader.u16_at(2)? != MINOR_VERSION {
+        return Ok(None);
+    }
+
+    let aliases = reader
+        .pairs_at(ALIAS_LIST)?
+        .into_iter()
+        .map(|(alias, mime_type)| Alias::new(alias, mime_type))
+        .collect();
+
+    let mut subclasses = Vec::new();
+    for entry in reader.list_at(PARENT_LIST, 8)? {
+        let mime_type = reader.str_at(entry)?;
+        let parents = reader.offset_at(entry + 4)?;
+        for i in 0..reader.offset_at(parents)? {
+            subclasses.push(Subclass::new(mime_type, reader.str_at(parents + 4 + i * 4)?));
+        }
+    }
+
+    let icons_at = |offset| -> io::Result<Vec<Icon>> {
+        let mut res: Vec<Icon> = reader
+            .pairs_at(offset)?
+            .into_iter()
+            .map(|(mime_type, icon_name)| Icon::new(icon_name, mime_type))
+            .collect();
+        res.sort_unstable();
+
+        Ok(res)
+    };
+    let icons = icons_at(ICONS_LIST)?;
+    let generic_icons = icons_at(GENERIC_ICONS_LIST)?;
+
+    let mut globs = Vec::new();
+    reader.globs_at(LITERAL_LIST, &mut globs)?;
+    let tree = reader.offset_at(REVERSE_SUFFIX_TREE)?;
+    reader.suffixes_at(reader.offset_at(tree + 4)?, reader.offset_at(tree)?, &mut Vec::new(), &mut globs)?;
+    reader.globs_at(GLOB_LIST, &mut globs)?;
+
+    let mut magic = Vec::new();
+    let magic_list = reader.offset_at(MAGIC_LIST)?;
+    let first_match = reader.offset_at(magic_list + 8)?;
+    for i in 0..reader.offset_at(magic_list)? {
+        let entry = first_match + i * 16;
+        let priority = reader.u32_at(entry)?;
+        let mime_type = reader.str_at(entry + 4)?;
+        let n_matchlets = reader.offset_at(entry + 8)?;
+        let first_matchlet = reader.offset_at(entry + 12)?;
+
+        let mut rules = Vec::new();
+        reader.matchlets_at(first_matchlet, n_matchlets, 0, &mut rules)?;
+
+        let mut entry = MagicEntry::new(mime_type, priority, rules, entry);
+        entry.set_source(file_name);
+        magic.push(entry);
+    }
+
+    Ok(Some(MimeCache {
+        aliases,
+        subclasses,
+        icons,
+        generic_icons,
+        globs,
+        magic,
+    }))
+}
+
+/// Maps and parses the `mime.cache` file at @file_name; see
+/// [`read_cache`].
+pub fn read_cache_from_file<P: AsRef<Path>>(file_name: P) -> io::Result<Option<MimeCache>> {
+    let file_name: PathBuf = file_name.as_ref().to_path_buf();
+    let f = File::open(&file_name)?;
+
+    // update-mime-database replaces the cache atomically, so the mapped
+    // file is not modified while it is being read
+    let data = unsafe { Mmap::map(&f)? };
+
+    read_cache(&data, &file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::Loader;
+
+    #[test]
+    fn read_test_cache() {
+        let cache = read_cache_from_file("test_files/mime/mime.cache").unwrap().unwrap();
+        let (section, _) = Loader::new().use_cache(false).load_directory("test_files");
+
+        assert_eq!(cache.aliases.len(), section.aliases().len());
+        assert!(cache.aliases.contains(&Alias::new("application/x-gzip", "application/gzip")));
+        assert_eq!(cache.icons, section.icons());
+        assert_eq!(cache.generic_icons, section.generic_icons());
+        assert_eq!(cache.subclasses.len(), section.subclasses().len());
+        assert!(cache.globs.contains(&Glob::simple("image/png", "*.png")));
+        assert!(cache.globs.contains(&Glob::new("text/x-c++src", "*.C", 50, true)));
+        // The text files also have the case-insensitive duplicates of some
+        // case-sensitive globs
+        assert!(cache.globs.iter().all(|g| section.globs().contains(g)));
+        assert_eq!(cache.magic.len(), section.magic.len());
+        for entry in &section.magic {
+            assert!(cache.magic.contains(entry), "{:?}", entry.mime_type());
+        }
+    }
+
+    #[test]
+    fn invalid_caches() {
+        let file_name = Path::new("mime.cache");
+        assert!(read_cache(b"", file_name).is_err());
+        assert!(read_cache(b"\x00\x01\x00\x01", file_name).unwrap().is_none());
+
+        let mut data = std::fs::read("test_files/mime/mime.cache").unwrap();
+        data.truncate(data.len() / 2);
+        assert!(read_cache(&data, file_name).is_err());
+    }
+
+    #[test]
+    fn shared_nodes() {
+        fn put(data: &mut Vec<u8>, offset: usize, value: u32) {
+            if data.len() < offset + 4 {
+                data.resize(offset + 4, 0);
+            }
+            data[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+        }
+
+        // All the lists are empty, but the reverse suffix tree at 52
+        let mut data = vec![0, 1, 0, 2];
+        for list in [ALIAS_LIST, PARENT_LIST, LITERAL_LIST, GLOB_LIST, MAGIC_LIST, ICONS_LIST, GENERIC_ICONS_LIST] {
+            put(&mut data, list, 40);
+        }
+        put(&mut data, REVERSE_SUFFIX_TREE, 52);
+        put(&mut data, 52, 2);
+        put(&mut data, 56, 64);
+
+        // Both nodes of each level have the nodes of the next level as
+        // children, so walking the tree would visit 2^40 leaves
+        let levels = 40;
+        for level in 0..levels {
+            for i in 0..2 {
+                let node = 64 + level * 24 + i * 12;
+                put(&mut data, node, 'a' as u32);
+                put(&mut data, node + 4, 2);
+                put(&mut data, node + 8, (node - i * 12 + 24) as u32);
+            }
+        }
+        let leaves = 64 + levels * 24;
+        let mime_type = leaves + 24;
+        for i in 0..2 {
+            put(&mut data, leaves + i * 12, 0);
+            put(&mut data, leaves + i * 12 + 4, mime_type as u32);
+            put(&mut data, leaves + i * 12 + 8, 50);
+        }
+        data.extend_from_slice(b"text/x-foo\0");
+
+        assert!(read_cache(&data, Path::new("mime.cache")).is_err());
+
+        // A single chain of the same length is fine
+        put(&mut data, 52, 1);
+        for level in 0..levels {
+            put(&mut data, 64 + level * 24 + 4, 1);
+        }
+        let cache = read_cache(&data, Path::new("mime.cache")).unwrap().unwrap();
+        assert_eq!(cache.globs.len(), 1);
+    }
+}
@@ -0,0 +1,493 @@
+//! Compiling the MIME database into Rust code, from a build script, for
+//! the programs that do not want to load it at run time.
+//!
+//! The `build.rs` of the program writes the database:
+//!
+//! ```ignore
+//! fn main() {
+//!     let out = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("mime_db.rs");
+//!     xdg_mime::codegen::write_static_db(&out, &["/usr/share"]).unwrap();
+//! }
+//! ```
+//!
+//! and the program includes it, defining the `MIME_DB` static, which can
+//! be queried through [`MimeQuery`]:
+//!
+//! ```ignore
+//! include!(concat!(env!("OUT_DIR"), "/mime_db.rs"));
+//! ```
+
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::Path;
+
+use glob::{MatchOptions, Pattern};
+use unicase::UniCase;
+
+use crate::glob::GlobType;
+use crate::magic::{self, Matchlet};
+use crate::query;
+use crate::{GlobMatch, Hierarchy, MimeQuery, SharedMimeInfo};
+
+const HEADER: &str = "// This file was automatically generated by xdg-mime. DO NOT EDIT!\n";
+
+// The path of this module in the generated code
+const MODULE: &str = "::xdg_mime::codegen";
+
+/// How a [`StaticGlob`] matches the file names.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StaticPattern {
+    /// The whole file name, compared case-insensitively.
+    Literal(&'static str),
+    /// The end of the file name, like `.png` for `*.png`.
+    Suffix(&'static str),
+    /// Any other glob pattern, like `*.[ch]`.
+    Glob(&'static str),
+}
+
+/// A glob of a [`StaticMimeDb`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StaticGlob {
+    pub mime_type: &'static str,
+    pub pattern: StaticPattern,
+    pub weight: i32,
+    pub case_sensitive: bool,
+}
+
+impl StaticGlob {
+    // The length of the pattern, to order the matching globs like Glob
+    // does
+    fn len(&self) -> usize {
+        match self.pattern {
+            StaticPattern::Literal(s) | StaticPattern::Glob(s) => s.len(),
+            StaticPattern::Suffix(s) => s.len() + 1,
+        }
+    }
+
+    fn matches(&self, file_name: &str, lc_file_name: &str) -> bool {
+        match self.pattern {
+            StaticPattern::Literal(s) if self.case_sensitive => s == file_name,
+            StaticPattern::Literal(s) => UniCase::new(s) == UniCase::new(file_name),
+            StaticPattern::Suffix(s) => {
+                file_name.ends_with(s) || (!self.case_sensitive && lc_file_name.ends_with(s))
+            }
+            StaticPattern::Glob(s) => {
+                let options = MatchOptions {
+                    case_sensitive: self.case_sensitive,
+                    ..MatchOptions::new()
+                };
+                Pattern::new(s).map(|p| p.matches_with(file_name, options)).unwrap_or(false)
+            }
+        }
+    }
+}
+
+/// A rule of a [`StaticMagicEntry`]; the rules of an entry are a flat
+/// list representing a tree, @indent being the depth of the rule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StaticMagicRule {
+    pub indent: u32,
+    pub start_offset: u32,
+    pub range_length: u32,
+    pub value: &'static [u8],
+    pub mask: Option<&'static [u8]>,
+}
+
+impl Matchlet for StaticMagicRule {
+    fn indent(&self) -> u32 {
+        self.indent
+    }
+
+    fn start_offset(&self) -> u32 {
+        self.start_offset
+    }
+
+    fn range_length(&self) -> u32 {
+        self.range_length
+    }
+
+    fn value(&self) -> &[u8] {
+        self.value
+    }
+
+    fn mask(&self) -> Option<&[u8]> {
+        self.mask
+    }
+}
+
+/// A magic entry of a [`StaticMimeDb`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StaticMagicEntry {
+    pub mime_type: &'static str,
+    pub priority: u32,
+    pub rules: &'static [StaticMagicRule],
+}
+
+/// A MIME database compiled into the program by [`write_static_db`],
+/// with all the MIME types already resolved to their canonical type.
+///
+/// The aliases, parents and generic icons are sorted by MIME type; the
+/// globs and the magic entries are in the order of the loaded database.
+#[derive(Clone, Copy, Debug)]
+pub struct StaticMimeDb {
+    /// The aliases, and the MIME type they alias.
+    pub aliases: &'static [(&'static str, &'static str)],
+    /// The MIME types, and their explicit parents.
+    pub parents: &'static [(&'static str, &'static [&'static str])],
+    /// The MIME types, and their generic icon.
+    pub generic_icons: &'static [(&'static str, &'static str)],
+    pub globs: &'static [StaticGlob],
+    pub magic: &'static [StaticMagicEntry],
+}
+
+impl StaticMimeDb {
+    fn canonical<'a>(&self, mime_type: &'a str) -> &'a str {
+        match self.aliases.binary_search_by_key(&mime_type, |(alias, _)| alias) {
+            Ok(idx) => self.aliases[idx].1,
+            Err(_) => mime_type,
+        }
+    }
+
+    fn parents_of(&self, mime_type: &str) -> &'static [&'static str] {
+        match self.parents.binary_search_by_key(&mime_type, |(mime_type, _)| mime_type) {
+            Ok(idx) => self.parents[idx].1,
+            Err(_) => &[],
+        }
+    }
+
+    fn ancestors<'a>(&self, mime_type: &'a str, hierarchy: Hierarchy) -> Vec<&'a str> {
+        query::ancestors(self.canonical(mime_type), hierarchy, |t| self.parents_of(t).to_vec())
+    }
+}
+
+impl MimeQuery for StaticMimeDb {
+    fn unalias_mime_type(&self, mime_type: &str) -> Option<String> {
+        self.aliases
+            .binary_search_by_key(&mime_type, |(alias, _)| alias)
+            .ok()
+            .map(|idx| self.aliases[idx].1.to_string())
+    }
+
+    fn is_subclass_of(&self, mime_type: &str, base: &str) -> bool {
+        let base = self.canonical(base);
+
+        self.ancestors(mime_type, Hierarchy::Full).contains(&base)
+    }
+
+    fn lookup_generic_icon_name(&self, mime_type: &str) -> Option<String> {
+        let res = self
+            .ancestors(mime_type, Hierarchy::Explicit)
+            .iter()
+            .find_map(|t| {
+                self.generic_icons
+                    .binary_search_by_key(t, |(mime_type, _)| mime_type)
+                    .ok()
+                    .map(|idx| self.generic_icons[idx].1.to_string())
+            })
+            .unwrap_or_else(|| crate::fallback_generic_icon_name(mime_type));
+
+        Some(res)
+    }
+
+    fn lookup_file_name(&self, file_name: &str) -> GlobMatch {
+        let lc_file_name = file_name.to_lowercase();
+
+        let mut matching: Vec<&StaticGlob> = self
+            .globs
+            .iter()
+            .filter(|g| g.matches(file_name, &lc_file_name))
+            .collect();
+        matching.sort_by(|a, b| b.weight.cmp(&a.weight).then_with(|| b.len().cmp(&a.len())));
+
+        let best = match matching.first() {
+            Some(v) => *v,
+            None => return GlobMatch::NoMatch,
+        };
+
+        let mut res: Vec<String> = Vec::new();
+        for glob in matching {
+            if glob.weight != best.weight || glob.len() != best.len() {
+                break;
+            }
+
+            if !res.iter().any(|t| t == glob.mime_type) {
+                res.push(glob.mime_type.to_string());
+            }
+        }
+
+        if res.len() == 1 {
+            GlobMatch::Unique(res.remove(0))
+        } else {
+            res.sort();
+            GlobMatch::Ambiguous(res)
+        }
+    }
+
+    fn get_mime_type_for_data(&self, data: &[u8]) -> Option<String> {
+        self.magic
+            .iter()
+            .find(|entry| magic::rules_match(entry.rules, data))
+            .map(|entry| entry.mime_type.to_string())
+    }
+}
+
+// Writes @data as a byte string literal
+fn byte_string(data: &[u8]) -> String {
+    let mut res = String::from("b\"");
+    for b in data {
+        match *b {
+            b'"' => res.push_str("\\\""),
+            b'\\' => res.push_str("\\\\"),
+            0x20..=0x7e => res.push(*b as char),
+            b => res.push_str(&format!("\\x{:02x}", b)),
+        }
+    }
+    res.push('"');
+
+    res
+}
+
+// Writes the host-order @data of a rule as a byte string literal; the
+// host-endian values are written in both orders, chosen when compiling
+// the generated code, as the target may not have the endianness of the
+// machine generating it
+fn rule_bytes(data: &[u8], word_size: u32) -> String {
+    // The order of the magic files, big-endian
+    let mut big = data.to_vec();
+    magic::swap_words(&mut big, word_size);
+
+    let mut little = big.clone();
+    let word_size = word_size as usize;
+    if word_size > 1 && little.len().is_multiple_of(word_size) {
+        for word in little.chunks_exact_mut(word_size) {
+            word.reverse();
+        }
+    }
+
+    if big == little {
+        byte_string(&big)
+    } else {
+        format!(
+            "if cfg!(target_endian = \"big\") {{ {} }} else {{ {} }}",
+            byte_string(&big),
+            byte_string(&little)
+        )
+    }
+}
+
+// Writes @db as the definition of the `MIME_DB` static
+fn write_db<W: Write>(db: &SharedMimeInfo, writer: &mut W) -> io::Result<()> {
+    writer.write_all(HEADER.as_bytes())?;
+    writeln!(writer, "pub static MIME_DB: {}::StaticMimeDb = {}::StaticMimeDb {{", MODULE, MODULE)?;
+
+    let mut aliases: Vec<(&str, &str)> = db
+        .aliases
+        .iter()
+        .map(|a| (a.alias.as_str(), a.mime_type.as_str()))
+        .collect();
+    aliases.sort_unstable();
+    aliases.dedup_by_key(|(alias, _)| *alias);
+    writeln!(writer, "    aliases: &[")?;
+    for (alias, mime_type) in aliases {
+        writeln!(writer, "        ({:?}, {:?}),", alias, mime_type)?;
+    }
+    writeln!(writer, "    ],")?;
+
+    let mut types: Vec<&str> = db.parents.iter().map(|(mime_type, _)| mime_type.as_str()).collect();
+    types.sort_unstable();
+    writeln!(writer, "    parents: &[")?;
+    for mime_type in types {
+        let parents = db.parents_in(mime_type, Hierarchy::Explicit);
+        if parents.is_empty() {
+            continue;
+        }
+        writeln!(writer, "        ({:?}, &{:?}),", mime_type, parents)?;
+    }
+    writeln!(writer, "    ],")?;
+
+    let mut icons: Vec<(&str, &str)> = db
+        .generic_icons
+        .iter()
+        .map(|i| (i.mime_type(), i.icon_name()))
+        .collect();
+    icons.sort_unstable();
+    icons.dedup_by_key(|(mime_type, _)| *mime_type);
+    writeln!(writer, "    generic_icons: &[")?;
+    for (mime_type, icon_name) in icons {
+        writeln!(writer, "        ({:?}, {:?}),", mime_type, icon_name)?;
+    }
+    writeln!(writer, "    ],")?;
+
+    writeln!(writer, "    globs: &[")?;
+    for glob in db.globs.iter() {
+        let pattern = match glob.glob_type() {
+            GlobType::Literal(s) => format!("Literal({:?})", s),
+            GlobType::Simple(s) => format!("Suffix({:?})", s),
+            GlobType::Full(p) => format!("Glob({:?})", p.as_str()),
+        };
+        writeln!(
+            writer,
+            "        {}::StaticGlob {{ mime_type: {:?}, pattern: {}::StaticPattern::{}, weight: {}, case_sensitive: {} }},",
+            MODULE,
+            glob.mime_type(),
+            MODULE,
+            pattern,
+            glob.weight(),
+            glob.case_sensitive()
+        )?;
+    }
+    writeln!(writer, "    ],")?;
+
+    writeln!(writer, "    magic: &[")?;
+    for entry in &db.magic {
+        writeln!(
+            writer,
+            "        {}::StaticMagicEntry {{ mime_type: {:?}, priority: {}, rules: &[",
+            MODULE,
+            entry.mime_type(),
+            entry.priority()
+        )?;
+        for rule in entry.rules() {
+            let mask = match rule.mask() {
+                Some(m) => format!("Some({})", rule_bytes(m, rule.word_size())),
+                None => "None".to_string(),
+            };
+            writeln!(
+                writer,
+                "            {}::StaticMagicRule {{ indent: {}, start_offset: {}, range_length: {}, value: {}, mask: {} }},",
+                MODULE,
+                rule.indent(),
+                rule.start_offset(),
+                rule.range_length(),
+                rule_bytes(rule.value(), rule.word_size()),
+                mask
+            )?;
+        }
+        writeln!(writer, "        ] }},")?;
+    }
+    writeln!(writer, "    ],")?;
+
+    writeln!(writer, "}};")
+}
+
+/// Loads the MIME database from @data_dirs, in the order of
+/// [`SharedMimeInfoBuilder::directory`][crate::SharedMimeInfoBuilder::directory],
+/// and writes it to @out_path as the Rust definition of a `MIME_DB`
+/// static of type [`StaticMimeDb`].
+///
+/// The XDG base directories are not loaded, so that the build does not
+/// depend on the system it runs on unless they are listed explicitly.
+pub fn write_static_db<P: AsRef<Path>, Q: AsRef<Path>>(out_path: P, data_dirs: &[Q]) -> io::Result<()> {
+    let mut builder = SharedMimeInfo::builder().system_directories(false);
+    for dir in data_dirs {
+        builder = builder.directory(dir);
+    }
+    let db = builder.build();
+
+    let mut f = BufWriter::new(File::create(out_path)?);
+    write_db(&db, &mut f)?;
+
+    f.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use std::fs;
+
+    static RULES: &[StaticMagicRule] = &[
+        StaticMagicRule {
+            indent: 0,
+            start_offset: 0,
+            range_length: 1,
+            value: b"\x89PNG",
+            mask: None,
+        },
+    ];
+
+    static DB: StaticMimeDb = StaticMimeDb {
+        aliases: &[("image/x-png", "image/png")],
+        parents: &[("image/svg+xml", &["application/xml"])],
+        generic_icons: &[("application/xml", "text-html"), ("image/png", "image-x-generic")],
+        globs: &[
+            StaticGlob {
+                mime_type: "image/png",
+                pattern: StaticPattern::Suffix(".png"),
+                weight: 50,
+                case_sensitive: false,
+            },
+            StaticGlob {
+                mime_type: "text/x-c++src",
+                pattern: StaticPattern::Suffix(".C"),
+                weight: 50,
+                case_sensitive: true,
+            },
+            StaticGlob {
+                mime_type: "text/x-makefile",
+                pattern: StaticPattern::Literal("makefile"),
+                weight: 50,
+                case_sensitive: false,
+            },
+            StaticGlob {
+                mime_type: "text/x-csrc",
+                pattern: StaticPattern::Glob("*.[ch]"),
+                weight: 50,
+                case_sensitive: true,
+            },
+        ],
+        magic: &[StaticMagicEntry {
+            mime_type: "image/png",
+            priority: 50,
+            rules: RULES,
+        }],
+    };
+
+    #[test]
+    fn static_queries() {
+        assert_eq!(DB.unalias_mime_type("image/x-png"), Some("image/png".to_string()));
+        assert_eq!(DB.unalias_mime_type("image/png"), None);
+        assert!(DB.is_subclass_of("image/svg+xml", "application/xml"));
+        assert!(DB.is_subclass_of("image/svg+xml", "application/octet-stream"));
+        assert!(DB.is_subclass_of("image/x-png", "image/png"));
+        assert!(!DB.is_subclass_of("image/png", "text/plain"));
+        assert_eq!(DB.lookup_generic_icon_name("image/png"), Some("image-x-generic".to_string()));
+        assert_eq!(DB.lookup_generic_icon_name("text/x-csrc"), Some("text-x-generic".to_string()));
+        assert_eq!(DB.lookup_generic_icon_name("image/svg+xml"), Some("text-html".to_string()));
+
+        assert_eq!(DB.lookup_file_name("A.PNG"), GlobMatch::Unique("image/png".to_string()));
+        assert_eq!(DB.lookup_file_name("a.C"), GlobMatch::Unique("text/x-c++src".to_string()));
+        assert_eq!(DB.lookup_file_name("a.c"), GlobMatch::Unique("text/x-csrc".to_string()));
+        assert_eq!(DB.lookup_file_name("Makefile"), GlobMatch::Unique("text/x-makefile".to_string()));
+        assert_eq!(DB.lookup_file_name("a.txt"), GlobMatch::NoMatch);
+
+        assert_eq!(DB.get_mime_type_for_data(b"\x89PNG\r\n"), Some("image/png".to_string()));
+        assert_eq!(DB.guess_mime_type(Some("a.bin"), Some(b"\x89PNG")), "image/png");
+        assert_eq!(DB.guess_mime_type(Some("a.bin"), Some(b"")), "application/x-zerosize");
+    }
+
+    #[test]
+    fn write_test_db() {
+        let dir = TempDir::new("codegen");
+        let out = dir.join("mime_db.rs");
+
+        write_static_db(&out, &["test_files"]).unwrap();
+        let code = fs::read_to_string(&out).unwrap();
+        assert!(code.starts_with(HEADER));
+        assert!(code.contains("pub static MIME_DB: ::xdg_mime::codegen::StaticMimeDb"));
+        assert!(code.contains("(\"application/x-gzip\", \"application/gzip\"),"));
+        assert!(code.contains("(\"image/svg+xml\", &[\"application/xml\"]),"));
+        assert!(code.contains("pattern: ::xdg_mime::codegen::StaticPattern::Suffix(\".png\")"));
+        assert!(code.contains("mime_type: \"image/png\", priority: 50, rules: &["));
+        assert!(code.contains("value: b\"\\x89PNG\""));
+        // The host-endian values follow the endianness of the target
+        assert!(code.contains("value: if cfg!(target_endian = \"big\") { b\"\\x01\\x10\" } else { b\"\\x10\\x01\" }"));
+    }
+
+    #[test]
+    fn escape_bytes() {
+        assert_eq!(byte_string(b"a\"\\\x00\xff"), "b\"a\\\"\\\\\\x00\\xff\"");
+    }
+}
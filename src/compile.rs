@@ -0,0 +1,633 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::io::prelude::*;
+use std::path::{Component, Path, PathBuf};
+
+use crate::glob::NOGLOBS;
+use crate::magic::NOMAGIC;
+use crate::package::{self, MagicMatch, MatchType, MimeTypeDefinition};
+
+const HEADER: &str = "# This file was automatically generated by xdg-mime. DO NOT EDIT!\n";
+
+// Parses an integer like strtol() with a base of 0 does
+fn parse_number(s: &str) -> Option<u64> {
+    let s = s.trim();
+
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).ok()
+    } else if s.len() > 1 && s.starts_with('0') {
+        u64::from_str_radix(&s[1..], 8).ok()
+    } else {
+        s.parse::<u64>().ok()
+    }
+}
+
+// Unescapes a string value, with the C escape sequences
+fn parse_string_value(s: &str) -> Result<Vec<u8>, String> {
+    let mut res = Vec::new();
+    let bytes = s.as_bytes();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] != b'\\' {
+            res.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+
+        i += 1;
+        let ch = match bytes.get(i) {
+            Some(v) => *v,
+            None => return Err(format!("trailing backslash in \"{}\"", s)),
+        };
+
+        match ch {
+            b'x' => {
+                let digits: Vec<u8> = bytes[i + 1..]
+                    .iter()
+                    .take(2)
+                    .take_while(|c| c.is_ascii_hexdigit())
+                    .cloned()
+                    .collect();
+                if digits.is_empty() {
+                    return Err(format!("invalid hex escape in \"{}\"", s));
+                }
+                // The digits are ASCII, so this cannot fail
+                let digits = String::from_utf8_lossy(&digits).into_owned();
+                res.push(u8::from_str_radix(&digits, 16).unwrap_or(0));
+                i += 1 + digits.len();
+            }
+            b'0'..=b'7' => {
+                let digits: Vec<u8> = bytes[i..]
+                    .iter()
+                    .take(3)
+                    .take_while(|c| (b'0'..=b'7').contains(*c))
+                    .cloned()
+                    .collect();
+                let digits = String::from_utf8_lossy(&digits).into_owned();
+                let value = u32::from_str_radix(&digits, 8).unwrap_or(0);
+                if value > 255 {
+                    return Err(format!("invalid octal escape in \"{}\"", s));
+                }
+                res.push(value as u8);
+                i += digits.len();
+            }
+            _ => {
+                res.push(match ch {
+                    b'n' => b'\n',
+                    b'r' => b'\r',
+                    b't' => b'\t',
+                    b'b' => 0x08,
+                    b'f' => 0x0c,
+                    b'v' => 0x0b,
+                    b'a' => 0x07,
+                    c => c,
+                });
+                i += 1;
+            }
+        }
+    }
+
+    Ok(res)
+}
+
+// Parses a string mask, written as hexadecimal digits prefixed by "0x"
+fn parse_string_mask(s: &str) -> Result<Vec<u8>, String> {
+    let hex = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(v) if !v.is_empty() && v.len() % 2 == 0 => v,
+        _ => return Err(format!("invalid mask \"{}\"", s)),
+    };
+
+    let mut res = Vec::new();
+    // Decoded by bytes, as a non-ASCII character cannot be sliced in pairs
+    for pair in hex.as_bytes().chunks(2) {
+        match (char::from(pair[0]).to_digit(16), char::from(pair[1]).to_digit(16)) {
+            (Some(high), Some(low)) => res.push((high * 16 + low) as u8),
+            _ => return Err(format!("invalid mask \"{}\"", s)),
+        }
+    }
+
+    Ok(res)
+}
+
+fn number_to_bytes(match_type: MatchType, value: u64) -> Vec<u8> {
+    match match_type {
+        MatchType::Byte => vec![value as u8],
+        // Host values are stored big-endian, and swapped by the reader
+        // according to the word size, like swap_words() does
+        MatchType::Big16 | MatchType::Host16 => (value as u16).to_be_bytes().to_vec(),
+        MatchType::Big32 | MatchType::Host32 => (value as u32).to_be_bytes().to_vec(),
+        MatchType::Little16 => (value as u16).to_le_bytes().to_vec(),
+        MatchType::Little32 => (value as u32).to_le_bytes().to_vec(),
+        MatchType::String => Vec::new(),
+    }
+}
+
+fn parse_offset(s: &str) -> Result<(u32, u32), String> {
+    let mut chunks = s.split(':');
+
+    let start = chunks.next().and_then(|v| v.trim().parse::<u32>().ok());
+    let end = match chunks.next() {
+        Some(v) => v.trim().parse::<u32>().ok(),
+        None => start,
+    };
+
+    let range_length = match (start, end, chunks.next()) {
+        (Some(start), Some(end), None) => end.checked_sub(start).and_then(|v| v.checked_add(1)),
+        _ => None,
+    };
+
+    match (start, range_length) {
+        (Some(start), Some(range_length)) => Ok((start, range_length)),
+        _ => Err(format!("invalid offset \"{}\"", s)),
+    }
+}
+
+/// Writes @m, and its children, as rules of a magic file into @out.
+pub fn compile_match(m: &MagicMatch, indent: u32, out: &mut Vec<u8>) -> Result<(), String> {
+    let (start, range_length) = parse_offset(&m.offset)?;
+
+    let (value, mask) = match m.match_type {
+        MatchType::String => {
+            let value = parse_string_value(&m.value)?;
+            let mask = match &m.mask {
+                Some(v) => Some(parse_string_mask(v)?),
+                None => None,
+            };
+            (value, mask)
+        }
+        t => {
+            let value = match parse_number(&m.value) {
+                Some(v) => number_to_bytes(t, v),
+                None => return Err(format!("invalid value \"{}\"", m.value)),
+            };
+            let mask = match &m.mask {
+                Some(v) => match parse_number(v) {
+                    Some(v) => Some(number_to_bytes(t, v)),
+                    None => return Err(format!("invalid mask \"{}\"", v)),
+                },
+                None => None,
+            };
+            (value, mask)
+        }
+    };
+
+    if value.is_empty() || value.len() > u16::MAX as usize {
+        return Err(format!("invalid value length for \"{}\"", m.value));
+    }
+
+    if let Some(mask) = &mask {
+        if mask.len() != value.len() {
+            return Err(format!(
+                "the mask \"{}\" does not match the value length",
+                m.mask.as_ref().unwrap_or(&String::new())
+            ));
+        }
+    }
+
+    let word_size = match m.match_type {
+        MatchType::Host16 => 2,
+        MatchType::Host32 => 4,
+        _ => 1,
+    };
+
+    if indent > 0 {
+        write!(out, "{}", indent).map_err(|e| e.to_string())?;
+    }
+    write!(out, ">{}=", start).map_err(|e| e.to_string())?;
+    out.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    out.extend_from_slice(&value);
+    if let Some(mask) = mask {
+        out.push(b'&');
+        out.extend_from_slice(&mask);
+    }
+    if word_size != 1 {
+        write!(out, "~{}", word_size).map_err(|e| e.to_string())?;
+    }
+    if range_length != 1 {
+        write!(out, "+{}", range_length).map_err(|e| e.to_string())?;
+    }
+    out.push(b'\n');
+
+    for child in &m.children {
+        compile_match(child, indent + 1, out)?;
+    }
+
+    Ok(())
+}
+
+// Merges the definitions of the same MIME type coming from different
+// packages, in order
+pub(crate) fn merge_definitions(packages: Vec<Vec<MimeTypeDefinition>>) -> Vec<MimeTypeDefinition> {
+    let mut res: HashMap<String, MimeTypeDefinition> = HashMap::new();
+
+    for definitions in packages {
+        for def in definitions {
+            let entry = res
+                .entry(def.mime_type.clone())
+                .or_insert_with(|| MimeTypeDefinition {
+                    mime_type: def.mime_type.clone(),
+                    ..Default::default()
+                });
+
+            if def.glob_deleteall {
+                entry.globs.clear();
+                entry.glob_deleteall = true;
+            }
+            if def.magic_deleteall {
+                entry.magic.clear();
+                entry.magic_deleteall = true;
+            }
+
+            for glob in def.globs {
+                if !entry.globs.contains(&glob) {
+                    entry.globs.push(glob);
+                }
+            }
+            entry.magic.extend(def.magic);
+            entry.comments.extend(def.comments);
+            for alias in def.aliases {
+                if !entry.aliases.contains(&alias) {
+                    entry.aliases.push(alias);
+                }
+            }
+            for parent in def.sub_class_of {
+                if !entry.sub_class_of.contains(&parent) {
+                    entry.sub_class_of.push(parent);
+                }
+            }
+            entry.root_xml.extend(def.root_xml);
+            if def.icon.is_some() {
+                entry.icon = def.icon;
+            }
+            if def.generic_icon.is_some() {
+                entry.generic_icon = def.generic_icon;
+            }
+        }
+    }
+
+    let mut res: Vec<MimeTypeDefinition> = res.into_values().collect();
+    res.sort_by(|a, b| a.mime_type.cmp(&b.mime_type));
+
+    res
+}
+
+// Writes @contents to @file_name through a temporary file, so that readers
+// never see a partially written file
+fn write_file(mime_dir: &Path, file_name: &str, contents: &[u8]) -> io::Result<()> {
+    let tmp_path = mime_dir.join(format!(".{}.new", file_name));
+    fs::write(&tmp_path, contents)?;
+    fs::rename(&tmp_path, mime_dir.join(file_name))
+}
+
+fn text_file(lines: Vec<String>) -> Vec<u8> {
+    let mut res = String::from(HEADER);
+    for line in lines {
+        res.push_str(&line);
+        res.push('\n');
+    }
+
+    res.into_bytes()
+}
+
+// Derives the contents of the database files, by file name, from
+// @definitions
+pub(crate) fn database_files(definitions: &[MimeTypeDefinition]) -> io::Result<Vec<(&'static str, Vec<u8>)>> {
+    let mut types = Vec::new();
+    let mut globs = Vec::new();
+    let mut aliases = Vec::new();
+    let mut subclasses = Vec::new();
+    let mut icons = Vec::new();
+    let mut generic_icons = Vec::new();
+    let mut namespaces = Vec::new();
+    let mut magic = Vec::new();
+    let mut nomagic = Vec::new();
+
+    for def in definitions {
+        types.push(def.mime_type.clone());
+
+        // The markers discarding the definitions of the less important
+        // directories
+        if def.glob_deleteall {
+            globs.push((0, def.mime_type.clone(), NOGLOBS.to_string(), false));
+        }
+        if def.magic_deleteall {
+            nomagic.push(&def.mime_type);
+        }
+        for glob in &def.globs {
+            globs.push((
+                glob.weight,
+                def.mime_type.clone(),
+                glob.pattern.clone(),
+                glob.case_sensitive,
+            ));
+        }
+        for alias in &def.aliases {
+            aliases.push(format!("{} {}", alias, def.mime_type));
+        }
+        for parent in &def.sub_class_of {
+            subclasses.push(format!("{} {}", def.mime_type, parent));
+        }
+        if let Some(icon) = &def.icon {
+            icons.push(format!("{}:{}", def.mime_type, icon));
+        }
+        if let Some(icon) = &def.generic_icon {
+            generic_icons.push(format!("{}:{}", def.mime_type, icon));
+        }
+        for (uri, local_name) in &def.root_xml {
+            namespaces.push(format!("{} {} {}", uri, local_name, def.mime_type));
+        }
+        for m in &def.magic {
+            magic.push((m.priority, &def.mime_type, m));
+        }
+    }
+
+    // Higher weights first
+    globs.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then_with(|| a.1.cmp(&b.1))
+            .then_with(|| a.2.cmp(&b.2))
+    });
+    let globs_v1 = globs.iter().map(|g| format!("{}:{}", g.1, g.2)).collect();
+    let globs_v2 = globs
+        .iter()
+        .map(|g| {
+            if g.3 {
+                format!("{}:{}:{}:cs", g.0, g.1, g.2)
+            } else {
+                format!("{}:{}:{}", g.0, g.1, g.2)
+            }
+        })
+        .collect();
+
+    // Higher priorities first
+    magic.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+    let mut magic_data = b"MIME-Magic\0\n".to_vec();
+    for (priority, mime_type, m) in magic {
+        magic_data.extend_from_slice(format!("[{}:{}]\n", priority, mime_type).as_bytes());
+        for rule in &m.matches {
+            if let Err(e) = compile_match(rule, 0, &mut magic_data) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: {}", mime_type, e),
+                ));
+            }
+        }
+    }
+    for mime_type in nomagic {
+        magic_data.extend_from_slice(format!("[0:{}]\n>0=", mime_type).as_bytes());
+        magic_data.extend_from_slice(&(NOMAGIC.len() as u16).to_be_bytes());
+        magic_data.extend_from_slice(NOMAGIC);
+        magic_data.push(b'\n');
+    }
+
+    aliases.sort();
+    subclasses.sort();
+    icons.sort();
+    generic_icons.sort();
+    namespaces.sort();
+
+    Ok(vec![
+        ("types", text_file(types)),
+        ("globs", text_file(globs_v1)),
+        ("globs2", text_file(globs_v2)),
+        ("aliases", text_file(aliases)),
+        ("subclasses", text_file(subclasses)),
+        ("icons", text_file(icons)),
+        ("generic-icons", text_file(generic_icons)),
+        ("XMLnamespaces", text_file(namespaces)),
+        ("magic", magic_data),
+    ])
+}
+
+/// Writes the database files derived from @definitions into @mime_dir.
+pub fn write_database(mime_dir: &Path, definitions: &[MimeTypeDefinition]) -> io::Result<()> {
+    let files = database_files(definitions)?;
+
+    fs::create_dir_all(mime_dir)?;
+    for (file_name, contents) in files {
+        write_file(mime_dir, file_name, &contents)?;
+    }
+
+    // The binary cache would be out of date
+    match fs::remove_file(mime_dir.join("mime.cache")) {
+        Err(ref e) if e.kind() != io::ErrorKind::NotFound => {
+            Err(io::Error::new(e.kind(), e.to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+// Returns the XML packages under the `packages` sub-directory of
+// @mime_dir, in the order they are merged: alphabetically, except for
+// `Override.xml`, which is always merged last
+pub(crate) fn package_files(mime_dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let entries = match fs::read_dir(mime_dir.join("packages")) {
+        Ok(v) => v,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(files),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().map(|e| e == "xml").unwrap_or(false) {
+            files.push(path);
+        }
+    }
+
+    files.sort_by_key(|f| {
+        (
+            f.file_name().map(|n| n == "Override.xml").unwrap_or(false),
+            f.clone(),
+        )
+    });
+
+    Ok(files)
+}
+
+/// Compiles all the XML packages under the `packages` sub-directory of
+/// @mime_dir into the database files, like `update-mime-database` does.
+///
+/// The packages are merged in alphabetical order, except for
+/// `Override.xml`, which is always merged last. Packages that cannot be
+/// parsed are skipped.
+pub fn update_mime_database<P: AsRef<Path>>(mime_dir: P) -> io::Result<()> {
+    let mime_dir = mime_dir.as_ref();
+
+    let mut packages = Vec::new();
+    for file in package_files(mime_dir)? {
+        match package::read_package_from_file(&file) {
+            Ok(v) => packages.push(v),
+            #[cfg(feature = "log")]
+            Err(e) => log::warn!("Skipped invalid MIME package {}: {}", file.display(), e),
+            #[cfg(not(feature = "log"))]
+            Err(_) => {}
+        }
+    }
+
+    write_database(mime_dir, &merge_definitions(packages))
+}
+
+/// Writes @definitions as the XML package @package_name, like
+/// `x-my-app`, into the `packages` sub-directory of @mime_dir, then
+/// regenerates the database files of @mime_dir from all its packages.
+///
+/// The definitions are validated first; an error of kind `InvalidData` is
+/// returned, and nothing is written, if they do not form a valid package.
+pub fn compile_package<P: AsRef<Path>>(
+    mime_dir: P,
+    package_name: &str,
+    definitions: &[MimeTypeDefinition],
+) -> io::Result<()> {
+    let mime_dir = mime_dir.as_ref();
+
+    // The name must be a single file name, which cannot be a drive or a
+    // parent directory on Windows either
+    let mut components = Path::new(package_name).components();
+    let single_name = matches!((components.next(), components.next()), (Some(Component::Normal(_)), None));
+    if !single_name || package_name.contains('/') || package_name.starts_with('.') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid package name \"{}\"", package_name),
+        ));
+    }
+
+    let xml = package::package_to_string(definitions);
+    package::read_package_from_string(&xml)?;
+
+    let packages_dir = mime_dir.join("packages");
+    fs::create_dir_all(&packages_dir)?;
+    write_file(&packages_dir, &format!("{}.xml", package_name), xml.as_bytes())?;
+
+    update_mime_database(mime_dir)
+}
+
+/// Like [`compile_package`], in the `mime` directory of the current user,
+/// `$XDG_DATA_HOME/mime`.
+pub fn compile_user_package(package_name: &str, definitions: &[MimeTypeDefinition]) -> io::Result<()> {
+    match dirs::data_dir() {
+        Some(dir) => compile_package(dir.join("mime"), package_name, definitions),
+        None => Err(io::Error::new(io::ErrorKind::NotFound, "data directory is unset")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::package::GlobDefinition;
+    use crate::test_util::TempDir;
+    use crate::{GlobMatch, SharedMimeInfo};
+
+    #[test]
+    fn compile_definitions() {
+        let dir = TempDir::new("compile");
+
+        let mut def = MimeTypeDefinition {
+            mime_type: "text/x-foo-script".to_string(),
+            comments: vec![(None, "Foo script".to_string())],
+            sub_class_of: vec!["text/plain".to_string()],
+            aliases: vec!["application/x-foo-script".to_string()],
+            ..Default::default()
+        };
+        def.globs.push(GlobDefinition {
+            pattern: "*.foo".to_string(),
+            weight: 50,
+            case_sensitive: false,
+        });
+        compile_package(dir.join("mime"), "x-foo", &[def.clone()]).unwrap();
+
+        let xml = fs::read_to_string(dir.join("mime/packages/x-foo.xml")).unwrap();
+        assert_eq!(package::read_package_from_string(&xml).unwrap(), vec![def.clone()]);
+
+        let mime_db = SharedMimeInfo::new_for_directory(dir.path());
+        assert_eq!(mime_db.lookup_file_name("a.foo"), GlobMatch::Unique("text/x-foo-script".to_string()));
+        assert!(mime_db.is_subclass_of("application/x-foo-script", "text/plain"));
+
+        def.mime_type = "invalid".to_string();
+        let err = compile_package(dir.join("mime"), "x-bar", &[def.clone()]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(!dir.join("mime/packages/x-bar.xml").exists());
+        assert!(compile_package(dir.join("mime"), "../x-bar", &[]).is_err());
+        #[cfg(windows)]
+        for name in ["..\\x-bar", "C:\\x-bar", "C:x-bar"].iter() {
+            assert!(compile_package(dir.join("mime"), name, &[]).is_err());
+        }
+    }
+
+    #[test]
+    fn compile_deleteall() {
+        let definitions = merge_definitions(vec![
+            vec![MimeTypeDefinition {
+                mime_type: "image/png".to_string(),
+                globs: vec![GlobDefinition {
+                    pattern: "*.png".to_string(),
+                    weight: 50,
+                    case_sensitive: false,
+                }],
+                ..Default::default()
+            }],
+            vec![MimeTypeDefinition {
+                mime_type: "image/png".to_string(),
+                glob_deleteall: true,
+                magic_deleteall: true,
+                ..Default::default()
+            }],
+        ]);
+        assert!(definitions[0].globs.is_empty());
+
+        let files = database_files(&definitions).unwrap();
+        let file = |name| &files.iter().find(|f| f.0 == name).unwrap().1;
+        assert!(String::from_utf8_lossy(file("globs2")).contains("\n0:image/png:__NOGLOBS__\n"));
+        assert!(file("magic").ends_with(b"[0:image/png]\n>0=\x00\x0b__NOMAGIC__\n"));
+    }
+
+    #[test]
+    fn string_values() {
+        assert_eq!(parse_string_value("PK\\003\\004").unwrap(), b"PK\x03\x04");
+        assert_eq!(parse_string_value("MSCF\\0\\0").unwrap(), b"MSCF\0\0");
+        assert_eq!(
+            parse_string_value("\\x31\\xbe\\x00").unwrap(),
+            b"\x31\xbe\x00"
+        );
+        assert_eq!(parse_string_value("{\\\\rtf").unwrap(), b"{\\rtf");
+        assert_eq!(
+            parse_string_value("\\211GND\\r\\n").unwrap(),
+            b"\x89GND\r\n"
+        );
+        assert!(parse_string_value("foo\\").is_err());
+    }
+
+    #[test]
+    fn compile_rules() {
+        let rule = MagicMatch {
+            match_type: MatchType::Host32,
+            offset: "0".to_string(),
+            value: "0xa1b2c3d4".to_string(),
+            mask: None,
+            children: vec![MagicMatch {
+                match_type: MatchType::String,
+                offset: "4:10".to_string(),
+                value: "ab".to_string(),
+                mask: Some("0xff00".to_string()),
+                children: Vec::new(),
+            }],
+        };
+
+        let mut out = Vec::new();
+        compile_match(&rule, 0, &mut out).unwrap();
+        assert_eq!(
+            out,
+            b">0=\x00\x04\xa1\xb2\xc3\xd4~4\n1>4=\x00\x02ab&\xff\x00+7\n".to_vec()
+        );
+
+        // The host-endian value matches the data in the byte order of the
+        // host
+        let mut magic_data = b"MIME-Magic\0\n[50:application/x-foo]\n".to_vec();
+        magic_data.extend_from_slice(&out);
+        let (entries, _) = crate::magic::read_magic(&magic_data);
+        let mut data = 0xa1b2c3d4u32.to_ne_bytes().to_vec();
+        data.extend_from_slice(b"ab");
+        assert_eq!(crate::magic::lookup_data_type(&entries, &data[..]), Some("application/x-foo"));
+    }
+}
@@ -0,0 +1,26 @@
+/// The scratch buffers of the lookups, to reuse across the calls of the
+/// `_with` variants of the lookup methods of
+/// [`SharedMimeInfo`][crate::SharedMimeInfo] in tight loops, like when
+/// scanning a directory, instead of allocating new ones on each call.
+///
+/// A context can be used with any database, but only by one lookup at a
+/// time.
+#[derive(Clone, Debug, Default)]
+pub struct LookupContext {
+    // The lowercase version of the looked up file name
+    pub(crate) lowercase: String,
+    // The indices of the matching globs
+    pub(crate) candidates: Vec<usize>,
+    // The beginning of the sniffed file
+    pub(crate) sniff: Vec<u8>,
+    // The key and the value of the last access to the cache of misses
+    pub(crate) cache_key: Vec<u8>,
+    pub(crate) cache_value: Vec<u8>,
+}
+
+impl LookupContext {
+    /// Creates a new context, with empty buffers.
+    pub fn new() -> LookupContext {
+        LookupContext::default()
+    }
+}
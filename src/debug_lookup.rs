@@ -0,0 +1,63 @@
+/// The input of a lookup.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LookupInput {
+    /// A lookup on a file name.
+    FileName(String),
+    /// A lookup on file contents, with the length of the data.
+    Data(usize),
+}
+
+/// The reason why a rule did not win a lookup.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The rule did not match the input.
+    NoMatch,
+    /// The glob matched, but a glob of higher weight matched too.
+    LowerWeight,
+    /// The glob matched, but a glob of the same weight with a longer
+    /// pattern matched too.
+    ShorterPattern,
+    /// The case-sensitive glob would have matched the input in another
+    /// case.
+    CaseMismatch,
+}
+
+/// A rule considered during a lookup.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LookupCandidate {
+    /// The MIME type of the rule.
+    pub mime_type: String,
+    /// The rule, in the format of the database file it comes from; for
+    /// magic entries, only the header is included.
+    pub rule: String,
+    /// The weight of a glob, or priority of a magic entry.
+    pub priority: i32,
+    /// Where the rule comes from, as `<file>:<offset>` for magic entries;
+    /// `None` for globs.
+    pub location: Option<String>,
+}
+
+/// A record of the decisions taken during a single lookup.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LookupRecord {
+    /// The input of the lookup.
+    pub input: LookupInput,
+    /// The rules that matched the input, in the order of the results.
+    pub candidates: Vec<LookupCandidate>,
+    /// The rule that determined the result, if any.
+    pub winner: Option<LookupCandidate>,
+    /// The rules that lost the lookup, and why: for file names, the
+    /// matching globs beaten by the winner and the case-sensitive globs
+    /// matching in another case; for data, the magic entries that did not
+    /// match.
+    pub skipped: Vec<(LookupCandidate, SkipReason)>,
+}
+
+/// A sink receiving a [`LookupRecord`] for every lookup.
+///
+/// The sink is shared by all the lookups on a [`SharedMimeInfo`][crate::SharedMimeInfo],
+/// which can happen on multiple threads.
+pub trait LookupSink: Send + Sync {
+    /// Receives the @record of a lookup.
+    fn record(&self, record: &LookupRecord);
+}
@@ -0,0 +1,60 @@
+use std::path::PathBuf;
+
+use crate::locale;
+use crate::package::{self, MimeTypeDefinition};
+
+// Reads the definition of the canonical @mime_type from the per-type XML
+// file, `mime/<media>/<subtype>.xml`, of the first of @data_dirs that has
+// one
+pub(crate) fn read_definition(data_dirs: &[PathBuf], mime_type: &str) -> Option<MimeTypeDefinition> {
+    if !mime_type.contains('/') || mime_type.contains("..") {
+        return None;
+    }
+
+    data_dirs
+        .iter()
+        .map(|dir| dir.join("mime").join(format!("{}.xml", mime_type)))
+        .find_map(|file| package::read_type_from_file(file).ok())
+}
+
+// Returns the comment of @def in the exact language @lang, or the
+// untranslated comment if @lang is `None`
+pub(crate) fn comment_for(def: &MimeTypeDefinition, lang: Option<&str>) -> Option<String> {
+    def.comments
+        .iter()
+        .find(|(l, _)| l.as_deref() == lang)
+        .map(|(_, c)| c.clone())
+}
+
+// Returns the comment of @def translated in @locale or in one of its less
+// specific variants, falling back to the untranslated comment
+pub(crate) fn localized_comment(def: &MimeTypeDefinition, locale: Option<&str>) -> Option<String> {
+    locale
+        .map(locale::locale_variants)
+        .unwrap_or_default()
+        .iter()
+        .find_map(|l| comment_for(def, Some(l)))
+        .or_else(|| comment_for(def, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments() {
+        let dirs = vec![PathBuf::from("test_files/nonexistent"), PathBuf::from("test_files")];
+        let def = read_definition(&dirs, "image/png").unwrap();
+
+        assert_eq!(comment_for(&def, Some("pt")), Some("imagem PNG".to_string()));
+        assert_eq!(comment_for(&def, Some("de_AT")), None);
+        assert_eq!(localized_comment(&def, Some("pt_BR.UTF-8")), Some("Imagem PNG".to_string()));
+        assert_eq!(localized_comment(&def, Some("de_AT")), Some("PNG-Bild".to_string()));
+        assert_eq!(localized_comment(&def, Some("xx")), Some("PNG image".to_string()));
+        assert_eq!(localized_comment(&def, None), Some("PNG image".to_string()));
+
+        assert!(read_definition(&dirs, "image/gif").is_none());
+        assert!(read_definition(&dirs, "../mime/image/png").is_none());
+        assert!(read_definition(&dirs, "packages").is_none());
+    }
+}
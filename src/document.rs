@@ -0,0 +1,289 @@
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::path::Path;
+
+use crate::alias::Alias;
+use crate::glob::Glob;
+use crate::icon::Icon;
+use crate::parent::Subclass;
+
+/// An entry of a text database file, that can be edited through a
+/// [`Document`].
+pub trait DocumentEntry: Sized + fmt::Display {
+    /// Parses a line of the database file, without its line terminator.
+    fn parse(line: &str) -> Option<Self>;
+}
+
+impl DocumentEntry for Glob {
+    fn parse(line: &str) -> Option<Glob> {
+        Glob::from_v2_string(line)
+    }
+}
+
+impl DocumentEntry for Alias {
+    fn parse(line: &str) -> Option<Alias> {
+        Alias::from_string(line.to_string())
+    }
+}
+
+impl DocumentEntry for Subclass {
+    fn parse(line: &str) -> Option<Subclass> {
+        Subclass::from_string(line.to_string())
+    }
+}
+
+impl DocumentEntry for Icon {
+    fn parse(line: &str) -> Option<Icon> {
+        Icon::from_string(line)
+    }
+}
+
+const BOM: &[u8] = b"\xef\xbb\xbf";
+
+enum Line<T> {
+    Entry {
+        value: T,
+        // The original text, kept until the entry is modified
+        raw: Option<String>,
+        newline: &'static str,
+    },
+    // Comments, empty lines, and lines that cannot be parsed, like the
+    // ones using fields from a newer version of the format
+    Verbatim(Vec<u8>),
+}
+
+/// A lossless model of a text database file, for programmatic edits.
+///
+/// Comments, empty lines, the order of the entries, line terminators and
+/// the lines that cannot be parsed are all preserved, and the entries
+/// that are not modified are written out exactly as they were read.
+pub struct Document<T> {
+    lines: Vec<Line<T>>,
+    // Whether the file starts with a UTF-8 byte order mark
+    bom: bool,
+}
+
+/// A `globs2` file.
+pub type GlobsDocument = Document<Glob>;
+
+/// An `aliases` file.
+pub type AliasesDocument = Document<Alias>;
+
+/// A `subclasses` file.
+pub type SubclassesDocument = Document<Subclass>;
+
+/// An `icons` or `generic-icons` file.
+pub type IconsDocument = Document<Icon>;
+
+impl<T: DocumentEntry> Document<T> {
+    /// Creates an empty document.
+    pub fn new() -> Document<T> {
+        Document {
+            lines: Vec::new(),
+            bom: false,
+        }
+    }
+
+    /// Reads a document from @reader.
+    pub fn from_reader<R: BufRead>(mut reader: R) -> io::Result<Document<T>> {
+        let mut lines = Vec::new();
+        let mut bom = false;
+
+        loop {
+            let mut buf = Vec::new();
+            if reader.read_until(b'\n', &mut buf)? == 0 {
+                break;
+            }
+
+            if lines.is_empty() && !bom && buf.starts_with(BOM) {
+                bom = true;
+                buf.drain(..BOM.len());
+            }
+
+            let (text, newline) = if buf.ends_with(b"\r\n") {
+                (&buf[..buf.len() - 2], "\r\n")
+            } else if buf.ends_with(b"\n") {
+                (&buf[..buf.len() - 1], "\n")
+            } else {
+                (&buf[..], "")
+            };
+
+            let value = match std::str::from_utf8(text) {
+                Ok(s) if !s.is_empty() && !s.starts_with('#') => T::parse(s).map(|v| (v, s)),
+                _ => None,
+            };
+
+            match value {
+                Some((value, s)) => lines.push(Line::Entry {
+                    value,
+                    raw: Some(s.to_string()),
+                    newline,
+                }),
+                None => lines.push(Line::Verbatim(buf)),
+            }
+        }
+
+        Ok(Document { lines, bom })
+    }
+
+    /// Reads a document from the file at @file_name.
+    pub fn from_file<P: AsRef<Path>>(file_name: P) -> io::Result<Document<T>> {
+        let f = File::open(file_name)?;
+
+        Document::from_reader(BufReader::new(f))
+    }
+
+    /// Iterates over the entries of the document.
+    pub fn entries(&self) -> impl Iterator<Item = &T> {
+        self.lines.iter().filter_map(|l| match l {
+            Line::Entry { value, .. } => Some(value),
+            Line::Verbatim(_) => None,
+        })
+    }
+
+    /// Appends @entry at the end of the document.
+    pub fn push(&mut self, entry: T) {
+        // Do not join the new entry to an unterminated last line
+        if let Some(last) = self.lines.last_mut() {
+            match last {
+                Line::Entry { newline, .. } if newline.is_empty() => *newline = "\n",
+                Line::Verbatim(buf) if !buf.ends_with(b"\n") => buf.push(b'\n'),
+                _ => {}
+            }
+        }
+
+        self.lines.push(Line::Entry {
+            value: entry,
+            raw: None,
+            newline: "\n",
+        });
+    }
+
+    /// Removes the entries for which @f returns `false`; all the other
+    /// lines are kept.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        self.lines.retain(|l| match l {
+            Line::Entry { value, .. } => f(value),
+            Line::Verbatim(_) => true,
+        });
+    }
+
+    /// Calls @f on each entry; the entries that are modified lose their
+    /// original formatting.
+    pub fn update<F: FnMut(&mut T)>(&mut self, mut f: F) {
+        for line in self.lines.iter_mut() {
+            if let Line::Entry { value, raw, .. } = line {
+                let before = value.to_string();
+                f(value);
+                if value.to_string() != before {
+                    *raw = None;
+                }
+            }
+        }
+    }
+
+    /// Writes the document to @writer.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        if self.bom {
+            writer.write_all(BOM)?;
+        }
+
+        for line in &self.lines {
+            match line {
+                Line::Entry {
+                    raw: Some(raw),
+                    newline,
+                    ..
+                } => {
+                    writer.write_all(raw.as_bytes())?;
+                    writer.write_all(newline.as_bytes())?;
+                }
+                Line::Entry {
+                    value, newline, ..
+                } => {
+                    write!(writer, "{}", value)?;
+                    writer.write_all(newline.as_bytes())?;
+                }
+                Line::Verbatim(buf) => writer.write_all(buf)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the document to the file at @file_name, replacing it.
+    pub fn save<P: AsRef<Path>>(&self, file_name: P) -> io::Result<()> {
+        let mut buf = Vec::new();
+        self.write_to(&mut buf)?;
+
+        fs::write(file_name, buf)
+    }
+}
+
+impl<T: DocumentEntry> Default for Document<T> {
+    fn default() -> Document<T> {
+        Document::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<T: DocumentEntry>(data: &[u8]) -> Vec<u8> {
+        let doc = Document::<T>::from_reader(data).unwrap();
+
+        let mut res = Vec::new();
+        doc.write_to(&mut res).unwrap();
+        res
+    }
+
+    #[test]
+    fn lossless_round_trip() {
+        let globs = include_bytes!("../test_files/mime/globs2");
+        assert_eq!(round_trip::<Glob>(globs), &globs[..]);
+
+        let aliases = include_bytes!("../test_files/mime/aliases");
+        assert_eq!(round_trip::<Alias>(aliases), &aliases[..]);
+
+        let subclasses = include_bytes!("../test_files/mime/subclasses");
+        assert_eq!(round_trip::<Subclass>(subclasses), &subclasses[..]);
+
+        let icons = include_bytes!("../test_files/mime/generic-icons");
+        assert_eq!(round_trip::<Icon>(icons), &icons[..]);
+
+        let windows = include_bytes!("../test_files/windows/mime/globs2");
+        assert_eq!(round_trip::<Glob>(windows), &windows[..]);
+        assert_eq!(GlobsDocument::from_reader(&windows[..]).unwrap().entries().count(), 2);
+
+        let weird = b"# comment\r\n\r\n50:text/x-foo:*.foo:cs,future\n\xff\xfe\n40:text/x-bar:*.bar\r\n60:text/x-baz:*.baz";
+        assert_eq!(round_trip::<Glob>(weird), &weird[..]);
+    }
+
+    #[test]
+    fn edit_document() {
+        let data = b"# comment\n50:text/x-foo:*.foo:cs,future\n40:text/x-bar:*.bar\r\n60:text/x-baz:*.baz";
+        let mut doc = GlobsDocument::from_reader(&data[..]).unwrap();
+
+        assert_eq!(doc.entries().count(), 2);
+
+        doc.retain(|g| g.mime_type() != "text/x-bar");
+        doc.update(|g| {
+            if g.mime_type() == "text/x-baz" {
+                *g = Glob::new("text/x-baz", "*.baz", 70, true);
+            }
+        });
+        doc.push(Glob::with_weight("text/x-qux", "*.qux", 50));
+
+        let mut res = Vec::new();
+        doc.write_to(&mut res).unwrap();
+        assert_eq!(
+            String::from_utf8(res).unwrap(),
+            "# comment\n50:text/x-foo:*.foo:cs,future\n70:text/x-baz:*.baz:cs\n50:text/x-qux:*.qux\n"
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use crate::MemoryDirectory;
+
+// The name standing for the bundled database in the load warnings
+const EMBEDDED_NAME: &str = "embedded";
+
+/// Returns the shared-mime-info database bundled in the program by the
+/// `embedded-db` feature, to load with
+/// [`SharedMimeInfoBuilder::memory_directory`][crate::SharedMimeInfoBuilder::memory_directory].
+///
+/// The builder already loads it when it finds no system database.
+///
+/// The database comes from the shared-mime-info release named in
+/// `data/VERSION`, and is distributed under the terms of the GNU General
+/// Public License; see `data/COPYING`.
+pub fn embedded_directory() -> MemoryDirectory {
+    MemoryDirectory::new(EMBEDDED_NAME)
+        .aliases(include_bytes!("../data/mime/aliases"))
+        .generic_icons(include_bytes!("../data/mime/generic-icons"))
+        .globs2(include_bytes!("../data/mime/globs2"))
+        .icons(include_bytes!("../data/mime/icons"))
+        .magic(include_bytes!("../data/mime/magic"))
+        .subclasses(include_bytes!("../data/mime/subclasses"))
+        .treemagic(include_bytes!("../data/mime/treemagic"))
+        .types(include_bytes!("../data/mime/types"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{archive, shebang};
+    use crate::{GlobMatch, Loader, SharedMimeInfo, EMPTY_TYPE, TEXT_PLAIN_TYPE, UNKNOWN_TYPE};
+
+    #[test]
+    fn embedded_database() {
+        let (section, warnings) = Loader::new().load_memory_directory(&embedded_directory());
+        assert_eq!(warnings, vec![]);
+        assert!(!section.types().is_empty());
+
+        let mime_db = SharedMimeInfo::builder()
+            .system_directories(false)
+            .memory_directory(embedded_directory())
+            .build();
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+
+        assert_eq!(mime_db.lookup_file_name("foo.txt"), GlobMatch::Unique("text/plain".to_string()));
+        assert_eq!(mime_db.get_mime_type_for_data(png_data), Some("image/png".to_string()));
+        assert_eq!(
+            mime_db.lookup_generic_icon_name("application/x-compressed-tar").as_deref(),
+            Some("package-x-generic")
+        );
+
+        // The types the crate handles specially are all known
+        let special_types = [UNKNOWN_TYPE, EMPTY_TYPE, TEXT_PLAIN_TYPE, "inode/directory", "inode/symlink"];
+        let types = special_types
+            .iter()
+            .chain(archive::ARCHIVE_TYPES)
+            .chain(archive::COMPRESSED_TYPES)
+            .chain(shebang::INTERPRETERS.iter().map(|(_, mime_type)| mime_type));
+        for mime_type in types {
+            assert!(mime_db.knows_type(mime_type), "{}", mime_type);
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use crate::SharedMimeInfo;
+
+// The types running code when opened: the programs, the scripts and the
+// launchers, with their sub-classes; most scripts are sub-classes of
+// `application/x-executable` in the database, but not in all its versions
+const EXECUTABLE_TYPES: &[&str] = &[
+    // The programs and libraries
+    "application/x-executable",
+    "application/x-pie-executable",
+    "application/x-sharedlib",
+    "application/x-ms-dos-executable",
+    "application/x-ms-ne-executable",
+    "application/vnd.microsoft.portable-executable",
+    "application/x-msdownload",
+    "application/x-pef-executable",
+    "application/x-mach-binary",
+    "application/x-java",
+    "application/x-java-archive",
+    "application/x-python-bytecode",
+    "application/vnd.appimage",
+    "application/x-iso9660-appimage",
+    // The scripts
+    "application/x-shellscript",
+    "application/x-csh",
+    "application/x-perl",
+    "application/x-php",
+    "application/x-ruby",
+    "application/x-awk",
+    "application/javascript",
+    "application/ecmascript",
+    "text/x-python",
+    "text/x-python3",
+    "text/x-tcl",
+    "text/x-lua",
+    "text/vbscript",
+    "text/vbscript.encode",
+    "application/x-bat",
+    "text/x-msdos-batch",
+    "application/x-powershell",
+    "text/x-powershell",
+    "application/hta",
+    // The launchers and the files changing the system
+    "application/x-desktop",
+    "application/x-ms-shortcut",
+    "application/x-mswinurl",
+    "application/x-java-jnlp-file",
+    "application/x-ms-application",
+    "text/x-ms-regedit",
+    // The packages installing programs
+    "application/x-msi",
+    "application/x-rpm",
+    "application/vnd.debian.binary-package",
+    "application/vnd.android.package-archive",
+    "application/vnd.flatpak",
+    "application/vnd.flatpak.ref",
+    "application/vnd.snap",
+    "application/x-xpinstall",
+];
+
+// The sub-classes of the executable types that only hold data, like JSON,
+// a sub-class of JavaScript
+const DATA_TYPES: &[&str] = &["application/json"];
+
+// Whether @mime_type can run code when opened
+pub(crate) fn is_potentially_executable(db: &SharedMimeInfo, mime_type: &str) -> bool {
+    let mime_type = db.canonical_ref(mime_type);
+    // The override of the closest type wins
+    let overridden = db
+        .executable_types
+        .iter()
+        .filter_map(|(base, executable)| Some((db.subclass_distance(mime_type, base)?, *executable)))
+        .min_by_key(|(distance, _)| *distance);
+    if let Some((_, executable)) = overridden {
+        return executable;
+    }
+
+    if DATA_TYPES.iter().any(|t| db.is_subclass_of(mime_type, t)) {
+        return false;
+    }
+
+    EXECUTABLE_TYPES.iter().any(|t| db.is_subclass_of(mime_type, t))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn executable_types() {
+        let mime_db = SharedMimeInfo::new_for_directory(env::current_dir().unwrap().join("test_files"));
+
+        let executables = [
+            "application/x-executable",
+            "application/x-csh",
+            "text/x-python3",
+            "application/javascript",
+            "application/vnd.android.package-archive",
+            "application/x-msi",
+            "application/x-deb",
+            "application/x-theme",
+        ];
+        for mime_type in &executables {
+            assert!(is_potentially_executable(&mime_db, mime_type), "{}", mime_type);
+        }
+
+        for mime_type in &["application/json", "text/plain", "image/png", "application/zip", "text/x-csrc"] {
+            assert!(!is_potentially_executable(&mime_db, mime_type), "{}", mime_type);
+        }
+
+        // Types missing from the database
+        let missing = ["text/vbscript", "application/x-ms-shortcut", "application/vnd.microsoft.portable-executable"];
+        for mime_type in &missing {
+            assert!(is_potentially_executable(&mime_db, mime_type), "{}", mime_type);
+        }
+    }
+
+    #[test]
+    fn executable_overrides() {
+        let mime_db = SharedMimeInfo::builder()
+            .system_directories(false)
+            .directory(env::current_dir().unwrap().join("test_files"))
+            .executable_type("application/javascript", false)
+            .executable_type("application/ecmascript", true)
+            .executable_type("application/x-gzip", true)
+            .build();
+
+        // The override of the closest type wins, for the sub-classes too
+        assert!(!is_potentially_executable(&mime_db, "application/javascript"));
+        assert!(is_potentially_executable(&mime_db, "application/ecmascript"));
+        assert!(!is_potentially_executable(&mime_db, "application/x-javascript"));
+        assert!(is_potentially_executable(&mime_db, "application/gzip"));
+        assert!(is_potentially_executable(&mime_db, "application/x-compressed-tar"));
+        assert!(is_potentially_executable(&mime_db, "application/x-executable"));
+    }
+}
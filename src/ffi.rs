@@ -0,0 +1,447 @@
+//! The C API of xdgmime, the reference implementation, on top of the
+//! process-wide [`SharedMimeInfo::shared`] database, or of the directories
+//! given to `xdg_mime_set_dirs`, so that C and C++ applications can link
+//! against this crate instead.
+//!
+//! Build the shared library with `cargo build --release --features cdylib`.
+//!
+//! Like with xdgmime, the returned strings belong to the library and stay
+//! valid until the process exits; the arrays returned by
+//! `xdg_mime_list_mime_parents` must be released with `free()`.
+
+use std::collections::HashSet;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_void};
+use std::path::Path;
+use std::ptr;
+use std::sync::{Mutex, OnceLock};
+
+use crate::{icon, SharedMimeInfo, EMPTY_TYPE, UNKNOWN_TYPE};
+
+extern "C" {
+    fn malloc(size: usize) -> *mut c_void;
+}
+
+#[allow(non_upper_case_globals)]
+#[no_mangle]
+pub static xdg_mime_type_unknown: [u8; 25] = *b"application/octet-stream\0";
+
+#[allow(non_upper_case_globals)]
+#[no_mangle]
+pub static xdg_mime_type_empty: [u8; 23] = *b"application/x-zerosize\0";
+
+#[allow(non_upper_case_globals)]
+#[no_mangle]
+pub static xdg_mime_type_textplain: [u8; 11] = *b"text/plain\0";
+
+// The database set by xdg_mime_set_dirs(), which is never released, as
+// the strings of its types may still be in use
+static DATABASE: Mutex<Option<&'static SharedMimeInfo>> = Mutex::new(None);
+
+// The strings returned to C, which are never released
+static STRINGS: OnceLock<Mutex<HashSet<CString>>> = OnceLock::new();
+
+// Returns a C string equal to @s that lives as long as the process
+fn intern(s: &str) -> *const c_char {
+    let s = match CString::new(s) {
+        Ok(v) => v,
+        Err(_) => return ptr::null(),
+    };
+
+    let strings = STRINGS.get_or_init(|| Mutex::new(HashSet::new()));
+    let mut strings = match strings.lock() {
+        Ok(v) => v,
+        Err(e) => e.into_inner(),
+    };
+
+    // The buffer of a CString does not move with it
+    if let Some(v) = strings.get(&s) {
+        return v.as_ptr();
+    }
+    let res = s.as_ptr();
+    strings.insert(s);
+
+    res
+}
+
+// Returns the database the functions look up
+fn database() -> &'static SharedMimeInfo {
+    let db = match DATABASE.lock() {
+        Ok(v) => *v,
+        Err(e) => *e.into_inner(),
+    };
+
+    db.unwrap_or_else(SharedMimeInfo::shared)
+}
+
+// Returns the string at @s, if it is not NULL and is valid UTF-8
+unsafe fn to_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+
+    CStr::from_ptr(s).to_str().ok()
+}
+
+// Checks @mime_type like xdgmime: a single `/` and the characters allowed
+// in the tokens of RFC 2045
+fn is_valid_mime_type(mime_type: &str) -> bool {
+    let mut slashes = 0;
+
+    for c in mime_type.chars() {
+        if c == '/' {
+            slashes += 1;
+        } else if !c.is_ascii_alphanumeric() && !"!#$%&'*+-.^_`|~".contains(c) {
+            return false;
+        }
+    }
+
+    slashes == 1
+}
+
+fn media_type(mime_type: &str) -> &str {
+    mime_type.split('/').next().unwrap_or(mime_type)
+}
+
+/// Returns the MIME type of the @len bytes at @data, and the priority of
+/// the magic entry that matched in @result_prio, if not NULL.
+///
+/// # Safety
+///
+/// @data must point to @len readable bytes, and @result_prio must be NULL
+/// or point to a writable int.
+#[no_mangle]
+pub unsafe extern "C" fn xdg_mime_get_mime_type_for_data(
+    data: *const c_void,
+    len: usize,
+    result_prio: *mut c_int,
+) -> *const c_char {
+    let db = database();
+    let (mime_type, priority) = if len == 0 || data.is_null() {
+        (EMPTY_TYPE, 100)
+    } else {
+        let data = std::slice::from_raw_parts(data as *const u8, len);
+        match db.matching_magic_for_data(data) {
+            Some(entry) => (entry.mime_type(), entry.priority()),
+            None => (UNKNOWN_TYPE, 0),
+        }
+    };
+
+    if !result_prio.is_null() {
+        *result_prio = priority as c_int;
+    }
+
+    intern(mime_type)
+}
+
+/// Returns the MIME type of the file at @file_name, from its name and, if
+/// needed, its contents; @statbuf is not used.
+///
+/// # Safety
+///
+/// @file_name must be NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn xdg_mime_get_mime_type_for_file(
+    file_name: *const c_char,
+    _statbuf: *const c_void,
+) -> *const c_char {
+    let file_name = match to_str(file_name) {
+        Some(v) => v,
+        None => return intern(UNKNOWN_TYPE),
+    };
+
+    intern(database().guess().path(Path::new(file_name)).guess().mime_type())
+}
+
+/// Returns the MIME type of @file_name from its name only, or the unknown
+/// type if its globs match no type, or several ones.
+///
+/// # Safety
+///
+/// @file_name must be NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn xdg_mime_get_mime_type_from_file_name(file_name: *const c_char) -> *const c_char {
+    let db = database();
+    let mime_types = match to_str(file_name) {
+        Some(v) => db.get_mime_types_from_file_name(v),
+        None => Vec::new(),
+    };
+
+    match mime_types.as_slice() {
+        [mime_type] => intern(mime_type),
+        _ => intern(UNKNOWN_TYPE),
+    }
+}
+
+/// Fills @mime_types with up to @n_mime_types of the MIME types of the best
+/// globs matching @file_name, and returns their number.
+///
+/// # Safety
+///
+/// @file_name must be NULL or a NUL-terminated string, and @mime_types
+/// must point to at least @n_mime_types writable pointers.
+#[no_mangle]
+pub unsafe extern "C" fn xdg_mime_get_mime_types_from_file_name(
+    file_name: *const c_char,
+    mime_types: *mut *const c_char,
+    n_mime_types: c_int,
+) -> c_int {
+    let file_name = match to_str(file_name) {
+        Some(v) => v,
+        None => return 0,
+    };
+    if mime_types.is_null() || n_mime_types <= 0 {
+        return 0;
+    }
+
+    let res = database().get_mime_types_from_file_name(file_name);
+    let count = res.len().min(n_mime_types as usize);
+    for (idx, mime_type) in res.iter().take(count).enumerate() {
+        *mime_types.add(idx) = intern(mime_type);
+    }
+
+    count as c_int
+}
+
+/// Returns whether @mime_type is syntactically a MIME type.
+///
+/// # Safety
+///
+/// @mime_type must be NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn xdg_mime_is_valid_mime_type(mime_type: *const c_char) -> c_int {
+    to_str(mime_type).map(is_valid_mime_type).unwrap_or(false) as c_int
+}
+
+/// Returns whether @mime_a and @mime_b are the same type, once resolved.
+///
+/// # Safety
+///
+/// @mime_a and @mime_b must be NULL or NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn xdg_mime_mime_type_equal(mime_a: *const c_char, mime_b: *const c_char) -> c_int {
+    let db = database();
+
+    match (to_str(mime_a), to_str(mime_b)) {
+        (Some(a), Some(b)) => (db.canonical_ref(a) == db.canonical_ref(b)) as c_int,
+        _ => 0,
+    }
+}
+
+/// Returns whether @mime_a and @mime_b have the same media type, like
+/// `image` for `image/png`.
+///
+/// # Safety
+///
+/// @mime_a and @mime_b must be NULL or NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn xdg_mime_media_type_equal(mime_a: *const c_char, mime_b: *const c_char) -> c_int {
+    match (to_str(mime_a), to_str(mime_b)) {
+        (Some(a), Some(b)) => (media_type(a) == media_type(b)) as c_int,
+        _ => 0,
+    }
+}
+
+/// Returns whether @mime_a is @mime_b or a subclass of it; @mime_b can be
+/// a whole media type, like `image/*`.
+///
+/// # Safety
+///
+/// @mime_a and @mime_b must be NULL or NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn xdg_mime_mime_type_subclass(mime_a: *const c_char, mime_b: *const c_char) -> c_int {
+    let (a, b) = match (to_str(mime_a), to_str(mime_b)) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return 0,
+    };
+
+    if b.ends_with("/*") && media_type(a) == media_type(b) {
+        return 1;
+    }
+
+    database().is_subclass_of(a, b) as c_int
+}
+
+/// Returns the direct parents of @mime, as a NULL-terminated array to be
+/// released with `free()`, or NULL if it has none.
+///
+/// # Safety
+///
+/// @mime must be NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn xdg_mime_list_mime_parents(mime: *const c_char) -> *mut *const c_char {
+    let db = database();
+    let parents = match to_str(mime) {
+        Some(v) => db.get_parents(v).unwrap_or_default(),
+        None => return ptr::null_mut(),
+    };
+
+    // The first type is the canonical type itself
+    let parents = parents.get(1..).unwrap_or(&[]);
+    if parents.is_empty() {
+        return ptr::null_mut();
+    }
+
+    let res = malloc((parents.len() + 1) * std::mem::size_of::<*const c_char>()) as *mut *const c_char;
+    if res.is_null() {
+        return res;
+    }
+    for (idx, parent) in parents.iter().enumerate() {
+        *res.add(idx) = intern(parent);
+    }
+    *res.add(parents.len()) = ptr::null();
+
+    res
+}
+
+/// Returns the type aliased by @mime, or @mime itself.
+///
+/// # Safety
+///
+/// @mime must be NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn xdg_mime_unalias_mime_type(mime: *const c_char) -> *const c_char {
+    match to_str(mime) {
+        Some(v) => intern(database().canonical_ref(v)),
+        None => mime,
+    }
+}
+
+/// Returns the icon listed for @mime in the `icons` files, or NULL.
+///
+/// # Safety
+///
+/// @mime must be NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn xdg_mime_get_icon(mime: *const c_char) -> *const c_char {
+    let db = database();
+
+    match to_str(mime).and_then(|v| icon::find_icon(&db.icons, db.canonical_ref(v))) {
+        Some(v) => intern(&v),
+        None => ptr::null(),
+    }
+}
+
+/// Returns the icon listed for @mime in the `generic-icons` files, or
+/// NULL.
+///
+/// # Safety
+///
+/// @mime must be NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn xdg_mime_get_generic_icon(mime: *const c_char) -> *const c_char {
+    let db = database();
+
+    match to_str(mime).and_then(|v| icon::find_icon(&db.generic_icons, db.canonical_ref(v))) {
+        Some(v) => intern(&v),
+        None => ptr::null(),
+    }
+}
+
+/// Returns the amount of data beyond which sniffing cannot give a
+/// different result.
+#[no_mangle]
+pub extern "C" fn xdg_mime_get_max_buffer_extents() -> c_int {
+    database().max_magic_extent() as c_int
+}
+
+/// Does nothing: the databases and the returned strings live until the
+/// process exits.
+#[no_mangle]
+pub extern "C" fn xdg_mime_shutdown() {}
+
+/// Loads the MIME database from the `mime` sub-directories of the
+/// NULL-terminated array of data directories at @dirs only, instead of the
+/// XDG base directories; with NULL, goes back to the shared database.
+///
+/// # Safety
+///
+/// @dirs must be NULL or point to a NULL-terminated array of C strings.
+#[no_mangle]
+pub unsafe extern "C" fn xdg_mime_set_dirs(dirs: *const *const c_char) {
+    let db = if dirs.is_null() {
+        None
+    } else {
+        let mut builder = SharedMimeInfo::builder().system_directories(false);
+        let mut i = 0;
+        while !(*dirs.add(i)).is_null() {
+            if let Some(dir) = to_str(*dirs.add(i)) {
+                builder = builder.directory(dir);
+            }
+            i += 1;
+        }
+
+        let db: &'static SharedMimeInfo = Box::leak(Box::new(builder.build()));
+        Some(db)
+    };
+
+    match DATABASE.lock() {
+        Ok(mut v) => *v = db,
+        Err(e) => *e.into_inner() = db,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn c(s: &str) -> CString {
+        CString::new(s).unwrap()
+    }
+
+    unsafe fn from_c(s: *const c_char) -> &'static str {
+        CStr::from_ptr(s).to_str().unwrap()
+    }
+
+    #[test]
+    fn c_api() {
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+        let test_files = c("test_files");
+
+        unsafe {
+            xdg_mime_set_dirs([test_files.as_ptr(), ptr::null()].as_ptr());
+
+            let mut prio = 0;
+            let res = xdg_mime_get_mime_type_for_data(png_data.as_ptr().cast(), png_data.len(), &mut prio);
+            assert_eq!(from_c(res), "image/png");
+            assert!(prio > 0);
+            assert_eq!(from_c(xdg_mime_get_mime_type_for_data(ptr::null(), 0, ptr::null_mut())), EMPTY_TYPE);
+
+            // The same string is returned for the same type
+            let path = c("test_files/files/rust-logo.png");
+            assert_eq!(xdg_mime_get_mime_type_for_file(path.as_ptr(), ptr::null()), res);
+            assert_eq!(from_c(xdg_mime_get_mime_type_from_file_name(c("a.png").as_ptr())), "image/png");
+
+            let mut mime_types = [ptr::null(); 4];
+            let count = xdg_mime_get_mime_types_from_file_name(c("a.png").as_ptr(), mime_types.as_mut_ptr(), 4);
+            assert_eq!(count, 1);
+            assert_eq!(from_c(mime_types[0]), "image/png");
+
+            assert_eq!(xdg_mime_is_valid_mime_type(c("image/png").as_ptr()), 1);
+            assert_eq!(xdg_mime_is_valid_mime_type(c("image png").as_ptr()), 0);
+            assert_eq!(xdg_mime_is_valid_mime_type(c("image").as_ptr()), 0);
+            assert_eq!(xdg_mime_mime_type_equal(c("application/x-gzip").as_ptr(), c("application/gzip").as_ptr()), 1);
+            assert_eq!(xdg_mime_media_type_equal(c("image/png").as_ptr(), c("image/gif").as_ptr()), 1);
+            assert_eq!(xdg_mime_mime_type_subclass(c("text/x-csrc").as_ptr(), c("text/plain").as_ptr()), 1);
+            assert_eq!(xdg_mime_mime_type_subclass(c("image/png").as_ptr(), c("image/*").as_ptr()), 1);
+            assert_eq!(xdg_mime_mime_type_subclass(c("image/png").as_ptr(), c("text/plain").as_ptr()), 0);
+            assert_eq!(from_c(xdg_mime_unalias_mime_type(c("application/x-gzip").as_ptr())), "application/gzip");
+
+            let parents = xdg_mime_list_mime_parents(c("text/x-csrc").as_ptr());
+            assert!(!parents.is_null());
+            assert_eq!(from_c(*parents), "text/plain");
+            assert!((*parents.add(1)).is_null());
+            extern "C" {
+                fn free(p: *mut c_void);
+            }
+            free(parents.cast());
+            assert!(xdg_mime_list_mime_parents(c("image/png").as_ptr()).is_null());
+
+            assert_eq!(from_c(xdg_mime_type_unknown.as_ptr().cast()), UNKNOWN_TYPE);
+            assert!(xdg_mime_get_max_buffer_extents() > 0);
+
+            xdg_mime_set_dirs(ptr::null());
+            assert!(ptr::eq(database(), SharedMimeInfo::shared()));
+        }
+    }
+}
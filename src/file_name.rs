@@ -0,0 +1,147 @@
+use crate::glob::GlobType;
+use crate::SharedMimeInfo;
+
+// The longest file name on most file systems, in bytes
+const MAX_FILE_NAME_LEN: usize = 255;
+
+// The name given to the files whose name is made of invalid characters
+const DEFAULT_NAME: &str = "untitled";
+
+// Returns the extension of the simple globs of @mime_type, like the `tar.gz`
+// of `*.tar.gz`, in the order of the globs of the database; the simple
+// globs that are not extensions, like `*~`, are skipped
+fn glob_extensions<'a>(db: &'a SharedMimeInfo, mime_type: &'a str) -> impl Iterator<Item = (&'a str, i32)> {
+    db.globs
+        .iter()
+        .filter(move |g| g.mime_type() == mime_type)
+        .filter_map(|g| match g.glob_type() {
+            GlobType::Simple(s) => match s.strip_prefix('.') {
+                Some(extension) if !extension.is_empty() => Some((extension, g.weight())),
+                _ => None,
+            },
+            _ => None,
+        })
+}
+
+// Returns the extension of the first simple glob of @mime_type in its XML
+// definition, which lists the preferred one first, or else the one of its
+// simple glob of highest weight
+pub(crate) fn preferred_extension(db: &SharedMimeInfo, mime_type: &str) -> Option<String> {
+    let mime_type = db.canonical_ref(mime_type);
+
+    // The definition can list globs deleted by a more important directory
+    if let Some(def) = db.type_definition(mime_type) {
+        let preferred = def.globs.iter().find_map(|g| {
+            let extension = g.pattern.strip_prefix("*.")?;
+            glob_extensions(db, mime_type).find(|(e, _)| *e == extension)
+        });
+        if let Some((extension, _)) = preferred {
+            return Some(extension.to_string());
+        }
+    }
+
+    let mut best: Option<(&str, i32)> = None;
+    for (extension, weight) in glob_extensions(db, mime_type) {
+        if best.is_none_or(|(_, w)| weight > w) {
+            best = Some((extension, weight));
+        }
+    }
+
+    best.map(|(extension, _)| extension.to_string())
+}
+
+// Replaces the path separators and the characters that are invalid on
+// some file systems, drops the control characters, and trims the spaces
+// and the dots that would hide the file or get lost
+pub(crate) fn sanitize(file_name: &str) -> String {
+    let res: String = file_name
+        .chars()
+        .filter(|c| !c.is_control())
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect();
+    let res = res.trim().trim_start_matches('.').trim_end_matches(|c: char| c == '.' || c.is_whitespace());
+
+    if res.is_empty() {
+        DEFAULT_NAME.to_string()
+    } else {
+        truncate(res, MAX_FILE_NAME_LEN).to_string()
+    }
+}
+
+// Shortens @s to at most @len bytes, on a character boundary
+fn truncate(s: &str, len: usize) -> &str {
+    let mut end = s.len().min(len);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    &s[..end]
+}
+
+// Returns the sanitized @file_name, with the preferred extension of
+// @mime_type appended unless it already matches the type
+pub(crate) fn file_name_for_type(db: &SharedMimeInfo, file_name: &str, mime_type: &str) -> String {
+    let name = sanitize(file_name);
+    let mime_type = db.canonical_ref(mime_type);
+
+    let matches = db.globs.lookup_mime_type_for_file_name(&name, db.profile).unwrap_or_default();
+    if matches.iter().any(|t| t == mime_type) {
+        return name;
+    }
+
+    match preferred_extension(db, mime_type) {
+        Some(extension) => {
+            let stem = truncate(&name, MAX_FILE_NAME_LEN.saturating_sub(extension.len() + 1));
+            format!("{}.{}", stem, extension)
+        }
+        None => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn preferred_extensions() {
+        let mime_db = SharedMimeInfo::new_for_directory(env::current_dir().unwrap().join("test_files"));
+
+        assert_eq!(preferred_extension(&mime_db, "image/png").as_deref(), Some("png"));
+        // Without a definition, the glob of highest weight wins
+        assert_eq!(preferred_extension(&mime_db, "text/x-csrc").as_deref(), Some("c"));
+        assert_eq!(preferred_extension(&mime_db, "application/x-gzip").as_deref(), Some("gz"));
+        assert_eq!(preferred_extension(&mime_db, "inode/directory"), None);
+        // `*~` is not an extension
+        assert_eq!(preferred_extension(&mime_db, "application/x-trash").as_deref(), Some("old"));
+    }
+
+    #[test]
+    fn sanitize_names() {
+        assert_eq!(sanitize("report.pdf"), "report.pdf");
+        assert_eq!(sanitize("../etc/passwd"), "_etc_passwd");
+        assert_eq!(sanitize("  .hidden.  "), "hidden");
+        assert_eq!(sanitize("a\u{0}b\nc: d?"), "abc_ d_");
+        assert_eq!(sanitize("..."), DEFAULT_NAME);
+        assert_eq!(sanitize(&"é".repeat(200)).len(), 254);
+    }
+
+    #[test]
+    fn names_for_types() {
+        let mime_db = SharedMimeInfo::new_for_directory(env::current_dir().unwrap().join("test_files"));
+
+        assert_eq!(file_name_for_type(&mime_db, "logo.png", "image/png"), "logo.png");
+        assert_eq!(file_name_for_type(&mime_db, "LOGO.PNG", "image/png"), "LOGO.PNG");
+        assert_eq!(file_name_for_type(&mime_db, "logo", "image/png"), "logo.png");
+        assert_eq!(file_name_for_type(&mime_db, "notes.txt", "image/png"), "notes.txt.png");
+        assert_eq!(file_name_for_type(&mime_db, "a/b", "application/x-gzip"), "a_b.gz");
+        assert_eq!(file_name_for_type(&mime_db, "folder", "inode/directory"), "folder");
+
+        let name = file_name_for_type(&mime_db, &"x".repeat(300), "image/png");
+        assert_eq!(name.len(), MAX_FILE_NAME_LEN);
+        assert!(name.ends_with("x.png"));
+    }
+}
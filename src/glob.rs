@@ -1,13 +1,26 @@
+use std::borrow::Cow;
 use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::fs::File;
+use std::io;
 use std::io::BufRead;
 use std::io::BufReader;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use glob::Pattern;
+use glob::{MatchOptions, Pattern, PatternError};
 use unicase::UniCase;
 
+use crate::lines;
+use crate::loader::LoadWarningKind;
+use crate::memo::{CacheBackend, Fingerprint, LruCache};
+use crate::phash::PerfectMap;
+use crate::suffix_trie::SuffixTrie;
+use crate::LookupContext;
+#[cfg(feature = "metrics")]
+use crate::Metrics;
+
 #[derive(Clone, PartialEq)]
 pub enum GlobType {
     Literal(String),
@@ -27,7 +40,17 @@ impl fmt::Debug for GlobType {
     }
 }
 
-fn determine_type<S: Into<String>>(glob: S) -> GlobType {
+impl fmt::Display for GlobType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GlobType::Literal(name) => write!(f, "{}", name),
+            GlobType::Simple(pattern) => write!(f, "*{}", pattern),
+            GlobType::Full(pattern) => write!(f, "{}", pattern),
+        }
+    }
+}
+
+fn determine_type<S: Into<String>>(glob: S) -> Result<GlobType, PatternError> {
     let mut maybe_simple = false;
     let glob = glob.into();
 
@@ -35,23 +58,53 @@ fn determine_type<S: Into<String>>(glob: S) -> GlobType {
         if idx == 0 && ch == b'*' {
             maybe_simple = true;
         } else if ch == b'\\' || ch == b'[' || ch == b'*' || ch == b'?' {
-            return GlobType::Full(Pattern::new(&glob).unwrap());
+            return Pattern::new(&glob).map(GlobType::Full);
         }
     }
 
     if maybe_simple {
-        GlobType::Simple(glob[1..].to_string())
+        Ok(GlobType::Simple(glob[1..].to_string()))
     } else {
-        GlobType::Literal(glob)
+        Ok(GlobType::Literal(glob))
     }
 }
 
-#[derive(Clone, Eq, PartialEq)]
+// Returns the `application/x-extension-<ext>` type some desktops give to
+// the file names whose extension matches no glob, to remember the
+// applications opening them
+pub(crate) fn extension_type(file_name: &str) -> Option<String> {
+    let (stem, extension) = file_name.rsplit_once('.')?;
+    if stem.is_empty() || extension.is_empty() {
+        return None;
+    }
+    if !extension.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '+') {
+        return None;
+    }
+
+    Some(format!("application/x-extension-{}", extension.to_ascii_lowercase()))
+}
+
+// Globs that are not valid patterns are matched literally
+pub(crate) fn determine_type_or_literal(glob: String) -> GlobType {
+    match determine_type(glob.as_str()) {
+        Ok(v) => v,
+        Err(_) => GlobType::Literal(glob),
+    }
+}
+
+// The pattern of the glob written for the `glob-deleteall` elements of the
+// packages, discarding the globs of its type in the less important data
+// directories
+pub(crate) const NOGLOBS: &str = "__NOGLOBS__";
+
+#[derive(Clone, Eq)]
 pub struct Glob {
     glob: GlobType,
     weight: i32,
     case_sensitive: bool,
     mime_type: String,
+    // The data directory the glob was loaded from
+    source: Option<PathBuf>,
 }
 
 impl fmt::Debug for Glob {
@@ -64,6 +117,28 @@ impl fmt::Debug for Glob {
     }
 }
 
+// Formats the glob as a line of a globs2 file
+impl fmt::Display for Glob {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.weight, self.mime_type, self.glob)?;
+        if self.case_sensitive {
+            write!(f, ":cs")?;
+        }
+
+        Ok(())
+    }
+}
+
+// The globs loaded from different directories are equal
+impl PartialEq for Glob {
+    fn eq(&self, other: &Glob) -> bool {
+        self.glob == other.glob
+            && self.weight == other.weight
+            && self.case_sensitive == other.case_sensitive
+            && self.mime_type == other.mime_type
+    }
+}
+
 impl Ord for Glob {
     fn cmp(&self, other: &Glob) -> Ordering {
         self.weight.cmp(&other.weight)
@@ -72,7 +147,7 @@ impl Ord for Glob {
 
 impl PartialOrd for Glob {
     fn partial_cmp(&self, other: &Glob) -> Option<Ordering> {
-        Some(self.weight.cmp(&other.weight))
+        Some(self.cmp(other))
     }
 }
 
@@ -82,10 +157,11 @@ impl Glob {
         let glob = glob.into();
 
         Glob {
-            mime_type: mime_type,
-            glob: determine_type(glob),
+            mime_type,
+            glob: determine_type_or_literal(glob),
             weight: 50,
             case_sensitive: false,
+            source: None,
         }
     }
 
@@ -94,10 +170,11 @@ impl Glob {
         let glob = glob.into();
 
         Glob {
-            mime_type: mime_type,
-            glob: determine_type(glob),
-            weight: weight,
+            mime_type,
+            glob: determine_type_or_literal(glob),
+            weight,
             case_sensitive: false,
+            source: None,
         }
     }
 
@@ -106,79 +183,92 @@ impl Glob {
         let glob = glob.into();
 
         Glob {
-            mime_type: mime_type,
-            glob: determine_type(glob),
-            weight: weight,
+            mime_type,
+            glob: determine_type_or_literal(glob),
+            weight,
             case_sensitive: cs,
+            source: None,
         }
     }
 
     pub fn from_v1_string<S: Into<String>>(s: S) -> Option<Glob> {
-        let s = s.into();
+        Glob::parse_v1(s.into()).ok()
+    }
 
+    pub fn from_v2_string<S: Into<String>>(s: S) -> Option<Glob> {
+        Glob::parse_v2(s.into()).ok()
+    }
+
+    // Parses a line of a `globs` file, telling why it is invalid
+    pub(crate) fn parse_v1(s: String) -> Result<Glob, LoadWarningKind> {
         if s.is_empty() || !s.contains(':') {
-            return None;
+            return Err(LoadWarningKind::Syntax);
         }
 
         let mut chunks = s.split(':');
 
         let mime_type = match chunks.next() {
             Some(v) => v.to_string(),
-            None => return None,
+            None => return Err(LoadWarningKind::Syntax),
         };
 
         let glob = match chunks.next() {
             Some(v) => v.to_string(),
-            None => return None,
+            None => return Err(LoadWarningKind::Syntax),
         };
 
         if mime_type.is_empty() || glob.is_empty() {
-            return None;
+            return Err(LoadWarningKind::Syntax);
         }
 
         // Consume the leftovers, if any
         if chunks.count() != 0 {
-            return None;
+            return Err(LoadWarningKind::Syntax);
         }
 
-        Some(Glob {
-            glob: determine_type(glob),
-            mime_type: mime_type,
+        let glob = match determine_type(glob) {
+            Ok(v) => v,
+            Err(_) => return Err(LoadWarningKind::InvalidPattern),
+        };
+
+        Ok(Glob {
+            glob,
+            mime_type,
             weight: 50,
             case_sensitive: false,
+            source: None,
         })
     }
 
-    pub fn from_v2_string<S: Into<String>>(s: S) -> Option<Glob> {
-        let s = s.into();
-
+    // Parses a line of a `globs2` file, telling why it is invalid
+    pub(crate) fn parse_v2(s: String) -> Result<Glob, LoadWarningKind> {
         if s.is_empty() || !s.contains(':') {
-            return None;
+            return Err(LoadWarningKind::Syntax);
         }
 
         let mut chunks = s.split(':');
 
         let weight = match chunks.next() {
             Some(v) => v.parse::<i32>().unwrap_or(-1),
-            None => return None,
+            None => return Err(LoadWarningKind::Syntax),
         };
 
         if weight < 0 {
-            return None;
+            return Err(LoadWarningKind::InvalidWeight);
         }
 
         let mime_type = match chunks.next() {
             Some(v) => v.to_string(),
-            None => return None,
+            None => return Err(LoadWarningKind::Syntax),
         };
 
         let glob = match chunks.next() {
             Some(v) => v.to_string(),
-            None => return None,
+            None => return Err(LoadWarningKind::Syntax),
         };
 
         if mime_type.is_empty() || glob.is_empty() {
-            return None;
+            return Err(LoadWarningKind::Syntax);
         }
 
         let case_sensitive = match chunks.next() {
@@ -186,7 +276,7 @@ impl Glob {
                 if v == "cs" {
                     true
                 } else {
-                    return None;
+                    return Err(LoadWarningKind::Syntax);
                 }
             }
             None => false,
@@ -194,39 +284,192 @@ impl Glob {
 
         // Consume the leftovers, if any
         if chunks.count() != 0 {
-            return None;
+            return Err(LoadWarningKind::Syntax);
         }
 
-        Some(Glob {
-            glob: determine_type(glob),
-            weight: weight,
-            case_sensitive: case_sensitive,
-            mime_type: mime_type,
+        let glob = match determine_type(glob) {
+            Ok(v) => v,
+            Err(_) => return Err(LoadWarningKind::InvalidPattern),
+        };
+
+        Ok(Glob {
+            glob,
+            weight,
+            case_sensitive,
+            mime_type,
+            source: None,
         })
     }
 
-    fn compare(&self, file_name: &str) -> bool {
+    pub fn mime_type(&self) -> &str {
+        &self.mime_type
+    }
+
+    pub fn weight(&self) -> i32 {
+        self.weight
+    }
+
+    pub fn pattern(&self) -> String {
+        self.glob.to_string()
+    }
+
+    // The length of the pattern, without formatting it
+    pub(crate) fn pattern_len(&self) -> usize {
+        match &self.glob {
+            GlobType::Literal(name) => name.len(),
+            GlobType::Simple(pattern) => pattern.len() + 1,
+            GlobType::Full(pattern) => pattern.as_str().len(),
+        }
+    }
+
+    pub fn case_sensitive(&self) -> bool {
+        self.case_sensitive
+    }
+
+    /// The data directory the glob was loaded from, if any.
+    pub fn source_directory(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+
+    /// Returns how costly the pattern of the glob is to match: the number
+    /// of its wildcards and of the characters of its character classes;
+    /// 0 for the literal names and the simple `*.ext` globs.
+    pub fn complexity(&self) -> usize {
+        let pattern = match &self.glob {
+            GlobType::Full(p) => p.as_str(),
+            _ => return 0,
+        };
+
+        let mut res = 0;
+        let mut in_class = false;
+        // The characters of the class so far, where a leading `]` or `!`
+        // is not special
+        let mut class_len = 0;
+        let mut chars = pattern.chars();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '\\' if !in_class => {
+                    chars.next();
+                }
+                ']' if in_class && class_len > 0 => in_class = false,
+                '!' if in_class && class_len == 0 => {}
+                _ if in_class => {
+                    class_len += 1;
+                    res += 1;
+                }
+                '[' => {
+                    in_class = true;
+                    class_len = 0;
+                }
+                '*' | '?' => res += 1,
+                _ => {}
+            }
+        }
+
+        res
+    }
+
+    // Whether this is the marker of a `glob-deleteall` element
+    pub(crate) fn is_noglobs(&self) -> bool {
+        matches!(&self.glob, GlobType::Literal(s) if s == NOGLOBS)
+    }
+
+    pub(crate) fn glob_type(&self) -> &GlobType {
+        &self.glob
+    }
+
+    pub(crate) fn set_mime_type(&mut self, mime_type: String) {
+        self.mime_type = mime_type;
+    }
+
+    pub(crate) fn set_source_directory(&mut self, directory: &Path) {
+        self.source = Some(directory.to_path_buf());
+    }
+
+    fn key(&self) -> (String, String, i32, bool) {
+        (self.pattern(), self.mime_type.clone(), self.weight, self.case_sensitive)
+    }
+
+    // Returns the number of characters at the end of a file name that
+    // decide whether it matches the glob, if the rest is irrelevant
+    fn suffix_len(&self) -> Option<usize> {
+        match &self.glob {
+            GlobType::Literal(_) => None,
+            GlobType::Simple(s) => Some(s.chars().count()),
+            GlobType::Full(p) => {
+                let pattern = p.as_str();
+                if !pattern.starts_with('*') {
+                    return None;
+                }
+
+                let mut res = 0;
+                let mut chars = pattern[1..].chars().peekable();
+                while let Some(ch) = chars.next() {
+                    match ch {
+                        '*' => return None,
+                        '[' => {
+                            if chars.peek() == Some(&'!') {
+                                chars.next();
+                            }
+                            // A leading ']' is part of the set
+                            if chars.peek() == Some(&']') {
+                                chars.next();
+                            }
+                            for ch in &mut chars {
+                                if ch == ']' {
+                                    break;
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                    res += 1;
+                }
+
+                Some(res)
+            }
+        }
+    }
+
+    fn compare(&self, file_name: &str, profile: FilesystemProfile) -> bool {
+        self.compare_lowercase(file_name, &file_name.to_lowercase(), profile)
+    }
+
+    // Like compare(), with the lowercase version of @file_name computed
+    // by the caller
+    fn compare_lowercase(&self, file_name: &str, lc_file_name: &str, profile: FilesystemProfile) -> bool {
+        let case_sensitive = self.case_sensitive && profile == FilesystemProfile::CaseSensitive;
+
         match &self.glob {
             GlobType::Literal(s) => {
-                let a = UniCase::new(s);
-                let b = UniCase::new(file_name);
+                if case_sensitive {
+                    return s == file_name;
+                }
 
-                return a == b;
+                return UniCase::new(s) == UniCase::new(file_name);
             }
             GlobType::Simple(s) => {
                 if file_name.ends_with(s) {
                     return true;
                 }
 
-                if !self.case_sensitive {
-                    let lc_file_name = file_name.to_lowercase();
+                if !case_sensitive {
                     if lc_file_name.ends_with(s) {
                         return true;
                     }
+
+                    // Case-sensitive globs are not lowercase
+                    if self.case_sensitive && lc_file_name.ends_with(&s.to_lowercase()) {
+                        return true;
+                    }
                 }
             }
             GlobType::Full(p) => {
-                return p.matches(file_name);
+                let options = MatchOptions {
+                    case_sensitive,
+                    ..MatchOptions::new()
+                };
+                return p.matches_with(file_name, options);
             }
         }
 
@@ -234,28 +477,29 @@ impl Glob {
     }
 }
 
+/// Reads the globs from @reader, in the format of a `globs` file.
+///
+/// Comments and empty lines are skipped; the lines that cannot be parsed
+/// are returned as errors of kind `InvalidData`.
+pub fn read_globs_v1<R: BufRead>(reader: R) -> impl Iterator<Item = io::Result<Glob>> {
+    lines::parse_lines(reader, Glob::from_v1_string).map(|(_, res)| res)
+}
+
+/// Reads the globs from @reader, in the format of a `globs2` file.
+///
+/// Comments and empty lines are skipped; the lines that cannot be parsed
+/// are returned as errors of kind `InvalidData`.
+pub fn read_globs_v2<R: BufRead>(reader: R) -> impl Iterator<Item = io::Result<Glob>> {
+    lines::parse_lines(reader, Glob::from_v2_string).map(|(_, res)| res)
+}
+
 pub fn read_globs_v1_from_file<P: AsRef<Path>>(file_name: P) -> Option<Vec<Glob>> {
     let f = match File::open(file_name) {
         Ok(v) => v,
         Err(_) => return None,
     };
 
-    let mut res = Vec::new();
-    let file = BufReader::new(&f);
-    for line in file.lines() {
-        let line = line.unwrap();
-
-        if line.is_empty() || line.starts_with('#') {
-            continue;
-        }
-
-        match Glob::from_v1_string(line) {
-            Some(v) => res.push(v),
-            None => continue,
-        }
-    }
-
-    Some(res)
+    Some(lines::collect_valid(read_globs_v1(BufReader::new(f))))
 }
 
 pub fn read_globs_v2_from_file<P: AsRef<Path>>(file_name: P) -> Option<Vec<Glob>> {
@@ -264,55 +508,558 @@ pub fn read_globs_v2_from_file<P: AsRef<Path>>(file_name: P) -> Option<Vec<Glob>
         Err(_) => return None,
     };
 
-    let mut res = Vec::new();
-    let file = BufReader::new(&f);
-    for line in file.lines() {
-        let line = line.unwrap();
+    Some(lines::collect_valid(read_globs_v2(BufReader::new(f))))
+}
+
+/// How file names are compared to the globs, depending on the file system
+/// the files come from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FilesystemProfile {
+    /// Follows the case sensitivity of each glob of the database.
+    CaseSensitive,
+    /// Matches all the globs case-insensitively, for the files of
+    /// case-insensitive file systems.
+    CaseInsensitive,
+}
+
+impl FilesystemProfile {
+    /// Detects the profile of the file system containing @path, which must
+    /// exist, by checking whether its name resolves to the same file with
+    /// its case changed.
+    ///
+    /// The default profile is returned if it cannot be determined, e.g.
+    /// when the name of @path has no letters.
+    pub fn detect<P: AsRef<Path>>(path: P) -> FilesystemProfile {
+        let path = path.as_ref();
+
+        let name = match path.file_name().and_then(|n| n.to_str()) {
+            Some(v) => v,
+            None => return FilesystemProfile::default(),
+        };
+
+        let swapped: String = name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_lowercase() {
+                    c.to_ascii_uppercase()
+                } else {
+                    c.to_ascii_lowercase()
+                }
+            })
+            .collect();
+        if swapped == name {
+            return FilesystemProfile::default();
+        }
+
+        match same_file(path, &path.with_file_name(swapped)) {
+            Some(true) => FilesystemProfile::CaseInsensitive,
+            Some(false) => FilesystemProfile::CaseSensitive,
+            None => FilesystemProfile::default(),
+        }
+    }
+}
+
+// Whether @a and @b are the same file, or None if @a does not exist; @b not
+// existing makes them different
+#[cfg(unix)]
+fn same_file(a: &Path, b: &Path) -> Option<bool> {
+    use std::os::unix::fs::MetadataExt;
 
-        if line.is_empty() || line.starts_with('#') {
-            continue;
+    let a = a.metadata().ok()?;
+    Some(b.metadata().is_ok_and(|b| a.dev() == b.dev() && a.ino() == b.ino()))
+}
+
+// The canonical paths name the files with the case they were created with
+#[cfg(not(unix))]
+fn same_file(a: &Path, b: &Path) -> Option<bool> {
+    let a = a.canonicalize().ok()?;
+    Some(b.canonicalize().is_ok_and(|b| a == b))
+}
+
+// The file systems of macOS and Windows are case-insensitive by default
+impl Default for FilesystemProfile {
+    fn default() -> FilesystemProfile {
+        if cfg!(any(target_os = "macos", windows)) {
+            FilesystemProfile::CaseInsensitive
+        } else {
+            FilesystemProfile::CaseSensitive
         }
+    }
+}
+
+/// How the leading dot of hidden files, like `.bashrc`, is treated when
+/// matching the suffix globs, like `*.gz`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum DotfileHandling {
+    /// The leading dot is an ordinary character, as in the reference
+    /// implementation: `.tar.gz` matches `*.tar.gz`, and `.gz` matches
+    /// `*.gz`.
+    #[default]
+    Ordinary,
+    /// The leading dot is not part of the suffix: `.tar.gz` only matches
+    /// `*.gz`, and `.gz` matches no suffix glob.
+    SkipLeadingDot,
+}
+
+/// The result of a file name lookup.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GlobMatch {
+    /// No glob matched the file name.
+    NoMatch,
+    /// The best matching globs all have the same MIME type.
+    Unique(String),
+    /// The best matching globs, of equal weight and length, have different
+    /// MIME types; the contents of the file should be used to decide
+    /// between them.
+    Ambiguous(Vec<String>),
+}
+
+/// A glob matching a looked up file name, with how good a match it is, so
+/// that callers can break the ties themselves.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NameMatch {
+    /// The MIME type of the glob.
+    pub mime_type: String,
+    /// The weight of the glob, from 0 to 100; 50 by default.
+    pub weight: i32,
+    /// The length of the pattern of the glob; between globs of the same
+    /// weight, the longer pattern is the more specific match.
+    pub pattern_len: usize,
+    /// Whether the glob only matches the file names in the same case.
+    pub case_sensitive: bool,
+}
 
-        match Glob::from_v2_string(line) {
-            Some(v) => res.push(v),
-            None => continue,
+impl From<&Glob> for NameMatch {
+    fn from(glob: &Glob) -> NameMatch {
+        NameMatch {
+            mime_type: glob.mime_type.clone(),
+            weight: glob.weight,
+            pattern_len: glob.pattern_len(),
+            case_sensitive: glob.case_sensitive,
         }
     }
+}
+
+// The maximum number of extensions kept in the default cache of misses
+const MAX_CACHED_MISSES: usize = 1024;
 
-    Some(res)
+// Returns the types of the cached miss @value
+fn miss_types(value: &[u8]) -> impl Iterator<Item = &[u8]> {
+    value.split(|b| *b == b'\n').filter(|v| !v.is_empty())
+}
+
+// The key of the cached miss on @extension with @profile, for the globs
+// of @fingerprint, in @key
+fn miss_key(fingerprint: &[u8], extension: &str, profile: FilesystemProfile, key: &mut Vec<u8>) {
+    key.clear();
+    key.extend_from_slice(fingerprint);
+    key.push(b'g');
+    key.push(match profile {
+        FilesystemProfile::CaseSensitive => b's',
+        FilesystemProfile::CaseInsensitive => b'i',
+    });
+    key.extend_from_slice(extension.as_bytes());
 }
 
 pub struct GlobMap {
     globs: Vec<Glob>,
+    // The number of characters at the end of file names each glob looks
+    // at, for the globs that ignore the start of file names
+    suffix_lens: Vec<Option<usize>>,
+    // The simple `*.ext` globs, by lowercase extension, so that looking up
+    // the extension of a file name is a single probe
+    extensions: PerfectMap<Vec<usize>>,
+    // The other simple globs, by lowercase suffix, so that looking them
+    // up only takes a walk from the end of the file name
+    suffixes: SuffixTrie,
+    // The globs that are in neither the extension table nor the suffixes
+    other_globs: Vec<usize>,
+    // The extensions of the file names that did not match any suffix
+    // glob, with the types of the suffix globs that could still match
+    // other file names with the same extension
+    misses: Arc<dyn CacheBackend>,
+    // The fingerprint of the globs and settings the misses depend on
+    fingerprint: [u8; 8],
+    dotfiles: DotfileHandling,
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<dyn Metrics>>,
 }
 
 impl GlobMap {
     pub fn new() -> GlobMap {
-        GlobMap { globs: Vec::new() }
+        GlobMap {
+            globs: Vec::new(),
+            suffix_lens: Vec::new(),
+            extensions: PerfectMap::default(),
+            suffixes: SuffixTrie::new(),
+            other_globs: Vec::new(),
+            misses: Arc::new(LruCache::new(MAX_CACHED_MISSES)),
+            fingerprint: [0; 8],
+            dotfiles: DotfileHandling::default(),
+            #[cfg(feature = "metrics")]
+            metrics: None,
+        }
+    }
+
+    /// Sets how the leading dot of hidden files is treated.
+    pub fn set_dotfile_handling(&mut self, dotfiles: DotfileHandling) {
+        self.dotfiles = dotfiles;
+        self.update_fingerprint();
+    }
+
+    /// Sets the backend of the cache of misses.
+    pub fn set_cache_backend(&mut self, backend: Arc<dyn CacheBackend>) {
+        self.misses = backend;
+    }
+
+    /// Returns the backend of the cache of misses.
+    pub fn cache_backend(&self) -> Arc<dyn CacheBackend> {
+        self.misses.clone()
+    }
+
+    /// Sets the hooks told about the accesses to the cache of misses.
+    #[cfg(feature = "metrics")]
+    pub fn set_metrics(&mut self, metrics: Option<Arc<dyn Metrics>>) {
+        self.metrics = metrics;
     }
 
     pub fn add_glob(&mut self, glob: Glob) {
-        self.globs.push(glob);
+        self.add_globs(vec![glob]);
     }
 
+    /// Adds @globs, skipping the ones identical to a glob already in the
+    /// map, like the same glob installed in several data directories.
     pub fn add_globs(&mut self, globs: Vec<Glob>) {
-        self.globs.extend(globs);
+        let mut known: HashSet<(String, String, i32, bool)> = self.globs.iter().map(|g| g.key()).collect();
+
+        for glob in globs {
+            if known.insert(glob.key()) {
+                self.globs.push(glob);
+            }
+        }
+
+        self.globs_changed();
     }
 
-    pub fn lookup_mime_type_for_file_name(&self, file_name: &str) -> Option<Vec<String>> {
-        let mut matching_globs = Vec::new();
+    fn globs_changed(&mut self) {
+        self.suffix_lens = self.globs.iter().map(|g| g.suffix_len()).collect();
+
+        let mut extensions: HashMap<String, Vec<usize>> = HashMap::new();
+        self.suffixes = SuffixTrie::new();
+        self.other_globs.clear();
+        for (idx, glob) in self.globs.iter().enumerate() {
+            match &glob.glob {
+                GlobType::Simple(s) if s.starts_with('.') && !s[1..].contains('.') => {
+                    extensions.entry(s[1..].to_lowercase()).or_default().push(idx);
+                }
+                // Lowercase like the looked up file names, one character
+                // at a time
+                GlobType::Simple(s) => {
+                    let lowercase: String = s.chars().flat_map(char::to_lowercase).collect();
+                    self.suffixes.insert(lowercase.chars(), idx);
+                }
+                _ => self.other_globs.push(idx),
+            }
+        }
+        self.extensions = PerfectMap::new(extensions.into_iter().collect());
+
+        self.update_fingerprint();
+    }
 
+    // The other versions of the globs keep their own misses
+    fn update_fingerprint(&mut self) {
+        let mut fingerprint = Fingerprint::new();
         for glob in &self.globs {
-            if glob.compare(file_name) {
-                matching_globs.push(glob.clone());
+            fingerprint.write_str(&glob.to_string());
+        }
+        fingerprint.write(match self.dotfiles {
+            DotfileHandling::Ordinary => b"o",
+            DotfileHandling::SkipLeadingDot => b"s",
+        });
+
+        self.fingerprint = fingerprint.finish();
+    }
+
+    // Returns the types of the suffix globs that could match a file name
+    // with @extension, when another file name with it matched none of them
+    fn cached_miss(&self, extension: &str, profile: FilesystemProfile) -> Option<Vec<String>> {
+        let mut value = Vec::new();
+
+        if self.cached_miss_into(extension, profile, &mut Vec::new(), &mut value) {
+            Some(miss_types(&value).map(|v| String::from_utf8_lossy(v).into_owned()).collect())
+        } else {
+            None
+        }
+    }
+
+    // Like cached_miss(), copying the types into @value, with @key as
+    // scratch space
+    fn cached_miss_into(
+        &self,
+        extension: &str,
+        profile: FilesystemProfile,
+        key: &mut Vec<u8>,
+        value: &mut Vec<u8>,
+    ) -> bool {
+        value.clear();
+
+        miss_key(&self.fingerprint, extension, profile, key);
+        self.misses.get(key, value)
+    }
+
+    fn add_miss(&self, extension: &str, profile: FilesystemProfile) {
+        let extension_len = extension.chars().count();
+        let lc_extension = extension.to_lowercase();
+
+        // The suffix globs looking at no more than the extension cannot
+        // match, and neither can the longer ones not ending with it
+        let mut remaining = Vec::new();
+        for (idx, glob) in self.globs.iter().enumerate() {
+            match (self.suffix_lens[idx], &glob.glob) {
+                (Some(len), _) if len <= extension_len => {}
+                (Some(_), GlobType::Simple(s))
+                    if glob.case_sensitive
+                        && profile == FilesystemProfile::CaseSensitive
+                        && !s.ends_with(extension) => {}
+                (Some(_), GlobType::Simple(s))
+                    if !glob.case_sensitive || profile == FilesystemProfile::CaseSensitive =>
+                {
+                    if s.ends_with(extension) || s.ends_with(&lc_extension) {
+                        remaining.push(idx);
+                    }
+                }
+                (Some(_), GlobType::Simple(s)) => {
+                    if s.to_lowercase().ends_with(&lc_extension) {
+                        remaining.push(idx);
+                    }
+                }
+                (Some(_), _) => remaining.push(idx),
+                (None, _) => {}
             }
         }
 
-        if matching_globs.len() == 0 {
-            return None;
+        let mut types: Vec<&str> = remaining.iter().map(|idx| self.globs[*idx].mime_type.as_str()).collect();
+        types.sort_unstable();
+        types.dedup();
+
+        let mut key = Vec::new();
+        miss_key(&self.fingerprint, extension, profile, &mut key);
+        self.misses.insert(&key, types.join("\n").as_bytes());
+    }
+
+    /// Adds the globs from @other for the MIME types that do not have
+    /// any glob in this map.
+    pub fn add_missing_globs(&mut self, other: GlobMap) {
+        let known: HashSet<String> = self.globs.iter().map(|g| g.mime_type.clone()).collect();
+
+        for glob in other.globs {
+            if !known.contains(&glob.mime_type) {
+                self.globs.push(glob);
+            }
+        }
+
+        self.globs_changed();
+    }
+
+    /// Replaces the MIME type of each glob with the result of @canonical,
+    /// if any; the globs that become identical are kept.
+    pub fn canonicalize_types<F: Fn(&str) -> Option<String>>(&mut self, canonical: F) {
+        for glob in self.globs.iter_mut() {
+            if let Some(mime_type) = canonical(&glob.mime_type) {
+                glob.set_mime_type(mime_type);
+            }
+        }
+
+        self.globs_changed();
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Glob> {
+        self.globs.iter()
+    }
+
+    /// Returns the number of globs for @mime_type.
+    pub fn count_for(&self, mime_type: &str) -> usize {
+        self.globs.iter().filter(|g| g.mime_type == mime_type).count()
+    }
+
+    /// Returns all the globs matching @file_name, the best matches first:
+    /// by decreasing weight, then by decreasing pattern length.
+    pub fn matching_globs(&self, file_name: &str, profile: FilesystemProfile) -> Vec<&Glob> {
+        let mut ctx = LookupContext::new();
+        self.matching_indices(file_name, profile, &mut ctx);
+
+        ctx.candidates.iter().map(|idx| &self.globs[*idx]).collect()
+    }
+
+    // Stores the indices of the globs matching @file_name in the
+    // candidates of @ctx, sorted like matching_globs(), using the other
+    // buffers of @ctx as scratch space
+    fn matching_indices(&self, file_name: &str, profile: FilesystemProfile, ctx: &mut LookupContext) {
+        ctx.candidates.clear();
+        ctx.lowercase.clear();
+        ctx.lowercase.extend(file_name.chars().flat_map(char::to_lowercase));
+
+        let skip_dot = self.dotfiles == DotfileHandling::SkipLeadingDot && file_name.starts_with('.');
+        let (suffix_name, lc_suffix_name) = if skip_dot {
+            (&file_name[1..], &ctx.lowercase[1..])
+        } else {
+            (file_name, &ctx.lowercase[..])
+        };
+
+        // After a miss on a file name, the other file names with the same
+        // extension are only checked against the globs that could match
+        let extension = match suffix_name.rfind('.') {
+            Some(idx) => &suffix_name[idx..],
+            None => suffix_name,
+        };
+        let cached = self.cached_miss_into(extension, profile, &mut ctx.cache_key, &mut ctx.cache_value);
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.metrics {
+            metrics.miss_cache(cached);
+        }
+        let mut suffix_matched = false;
+
+        // Only the `*.ext` globs for the last extension of the file name,
+        // and the other simple globs for its suffixes, can match it
+        let indexed: &[usize] = match lc_suffix_name.rfind('.') {
+            Some(idx) => self.extensions.get(&lc_suffix_name[idx + 1..]).map_or(&[], |v| v.as_slice()),
+            None => &[],
+        };
+        let suffixes = self.suffixes.matches(lc_suffix_name);
+
+        for idx in indexed.iter().copied().chain(suffixes).chain(self.other_globs.iter().copied()) {
+            let glob = &self.globs[idx];
+            let is_suffix_glob = self.suffix_lens[idx].is_some();
+            // The other globs of the remaining types may be checked too,
+            // which is only slower
+            if cached && is_suffix_glob && !miss_types(&ctx.cache_value).any(|t| t == glob.mime_type.as_bytes()) {
+                continue;
+            }
+
+            let (name, lc_name) = if is_suffix_glob {
+                (suffix_name, lc_suffix_name)
+            } else {
+                (file_name, &ctx.lowercase[..])
+            };
+            if glob.compare_lowercase(name, lc_name, profile) {
+                suffix_matched |= is_suffix_glob;
+                ctx.candidates.push(idx);
+            }
+        }
+
+        if !cached && !suffix_matched {
+            self.add_miss(extension, profile);
+        }
+
+        let globs = &self.globs;
+        ctx.candidates.sort_unstable_by(|a, b| {
+            let (ga, gb) = (&globs[*a], &globs[*b]);
+            gb.weight
+                .cmp(&ga.weight)
+                .then_with(|| gb.pattern_len().cmp(&ga.pattern_len()))
+                .then_with(|| a.cmp(b))
+        });
+    }
+
+    /// Looks up the MIME type of @file_name like lookup_best_match(),
+    /// using the buffers of @ctx instead of allocating new ones: returns
+    /// the first of the best matching types by name, and whether there are
+    /// other ones.
+    pub fn best_match_with<'a>(
+        &'a self,
+        file_name: &str,
+        profile: FilesystemProfile,
+        ctx: &mut LookupContext,
+    ) -> Option<(&'a str, bool)> {
+        self.matching_indices(file_name, profile, ctx);
+
+        let best = &self.globs[*ctx.candidates.first()?];
+        let mut res: &str = &best.mime_type;
+        let mut ambiguous = false;
+        for glob in ctx.candidates.iter().map(|idx| &self.globs[*idx]) {
+            if glob.weight != best.weight || glob.pattern_len() != best.pattern_len() {
+                break;
+            }
+
+            if glob.mime_type != res {
+                ambiguous = true;
+                res = res.min(&glob.mime_type);
+            }
+        }
+
+        Some((res, ambiguous))
+    }
+
+    /// Stores the MIME types of all the globs matching @file_name in @res,
+    /// like lookup_mime_type_for_file_name(), using the buffers of @ctx
+    /// instead of allocating new ones.
+    pub fn matching_types_into<'a>(
+        &'a self,
+        file_name: &str,
+        profile: FilesystemProfile,
+        ctx: &mut LookupContext,
+        res: &mut Vec<Cow<'a, str>>,
+    ) {
+        self.matching_indices(file_name, profile, ctx);
+
+        res.clear();
+        res.extend(ctx.candidates.iter().map(|idx| Cow::Borrowed(self.globs[*idx].mime_type.as_str())));
+    }
+
+    // Checks whether one of the candidates of @ctx with the same weight
+    // and length as the best one has the MIME type @mime_type
+    pub(crate) fn is_best_candidate(&self, ctx: &LookupContext, mime_type: &str) -> bool {
+        let best = match ctx.candidates.first() {
+            Some(idx) => &self.globs[*idx],
+            None => return false,
+        };
+
+        ctx.candidates.iter().map(|idx| &self.globs[*idx]).any(|g| {
+            g.weight == best.weight && g.pattern_len() == best.pattern_len() && g.mime_type == mime_type
+        })
+    }
+
+    /// Looks up the MIME type of @file_name, using only the globs with the
+    /// highest weight and longest pattern among the matching ones.
+    pub fn lookup_best_match(&self, file_name: &str, profile: FilesystemProfile) -> GlobMatch {
+        let matching_globs = self.matching_globs(file_name, profile);
+
+        let best = match matching_globs.first() {
+            Some(v) => *v,
+            None => return GlobMatch::NoMatch,
+        };
+
+        let mut res: Vec<String> = Vec::new();
+        for glob in matching_globs {
+            if glob.weight != best.weight || glob.pattern_len() != best.pattern_len() {
+                break;
+            }
+
+            if !res.contains(&glob.mime_type) {
+                res.push(glob.mime_type.clone());
+            }
+        }
+
+        if res.len() == 1 {
+            GlobMatch::Unique(res.remove(0))
+        } else {
+            res.sort();
+            GlobMatch::Ambiguous(res)
         }
+    }
+
+    /// Returns the globs matching @file_name with their weight and
+    /// specificity, from the best match to the worst one.
+    pub fn lookup_name_matches(&self, file_name: &str, profile: FilesystemProfile) -> Vec<NameMatch> {
+        self.matching_globs(file_name, profile).into_iter().map(NameMatch::from).collect()
+    }
 
-        matching_globs.sort();
+    pub fn lookup_mime_type_for_file_name(&self, file_name: &str, profile: FilesystemProfile) -> Option<Vec<String>> {
+        let matching_globs = self.matching_globs(file_name, profile);
+
+        if matching_globs.is_empty() {
+            return None;
+        }
 
         let mut res = Vec::new();
         for glob in matching_globs {
@@ -328,7 +1075,7 @@ impl fmt::Debug for GlobMap {
         let mut lines = String::new();
         for glob in &self.globs {
             lines.push_str(&format!("{:?}", glob));
-            lines.push_str("\n");
+            lines.push('\n');
         }
 
         write!(f, "Globs:\n{}", lines)
@@ -339,22 +1086,30 @@ impl fmt::Debug for GlobMap {
 mod tests {
     use super::*;
 
+    const CS: FilesystemProfile = FilesystemProfile::CaseSensitive;
+
     #[test]
     fn glob_type() {
         assert_eq!(
-            determine_type("Makefile"),
+            determine_type("Makefile").unwrap(),
             GlobType::Literal("Makefile".to_string())
         );
 
         assert_eq!(
-            determine_type("*.gif"),
+            determine_type("*.gif").unwrap(),
             GlobType::Simple(".gif".to_string())
         );
 
         assert_eq!(
-            determine_type("x*.[ch]"),
+            determine_type("x*.[ch]").unwrap(),
             GlobType::Full(Pattern::new("x*.[ch]").unwrap())
-        )
+        );
+
+        assert!(determine_type("*.[ch").is_err());
+        assert_eq!(
+            determine_type_or_literal("*.[ch".to_string()),
+            GlobType::Literal("*.[ch".to_string())
+        );
     }
 
     #[test]
@@ -398,30 +1153,281 @@ mod tests {
         assert_eq!(Glob::from_v2_string(":"), None);
         assert_eq!(Glob::from_v2_string("foo:bar:baz"), None);
         assert_eq!(Glob::from_v2_string("foo:bar:baz:blah"), None);
+        assert_eq!(Glob::from_v2_string("50:text/x-foo:*.[ch"), None);
     }
 
     #[test]
     fn compare() {
         // Literal
         let copying = Glob::new("text/x-copying", "copying", 50, false);
-        assert_eq!(copying.compare(&"COPYING".to_string()), true);
+        assert!(copying.compare("COPYING", CS));
+        let core = Glob::new("application/x-core", "core", 50, true);
+        assert!(core.compare("core", CS));
+        assert!(!core.compare("Core", CS));
 
         // Simple, case-insensitive
         let c_src = Glob::new("text/x-csrc", "*.c", 50, false);
-        assert_eq!(c_src.compare(&"foo.c".to_string()), true);
-        assert_eq!(c_src.compare(&"FOO.C".to_string()), true);
+        assert!(c_src.compare("foo.c", CS));
+        assert!(c_src.compare("FOO.C", CS));
 
         // Simple, case-sensitive
         let cplusplus_src = Glob::new("text/x-c++src", "*.C", 50, true);
-        assert_eq!(cplusplus_src.compare(&"foo.C".to_string()), true);
-        assert_eq!(cplusplus_src.compare(&"foo.c".to_string()), false);
-        assert_eq!(cplusplus_src.compare(&"foo.h".to_string()), false);
+        assert!(cplusplus_src.compare("foo.C", CS));
+        assert!(!cplusplus_src.compare("foo.c", CS));
+        assert!(!cplusplus_src.compare("foo.h", CS));
 
         // Full
         let video_x_anim = Glob::new("video/x-anim", "*.anim[1-9j]", 50, false);
-        assert_eq!(video_x_anim.compare(&"foo.anim0".to_string()), false);
-        assert_eq!(video_x_anim.compare(&"foo.anim8".to_string()), true);
-        assert_eq!(video_x_anim.compare(&"foo.animk".to_string()), false);
-        assert_eq!(video_x_anim.compare(&"foo.animj".to_string()), true);
+        assert!(!video_x_anim.compare("foo.anim0", CS));
+        assert!(video_x_anim.compare("foo.anim8", CS));
+        assert!(!video_x_anim.compare("foo.animk", CS));
+        assert!(video_x_anim.compare("foo.animj", CS));
+        assert!(video_x_anim.compare("FOO.ANIMJ", CS));
+        let vdr = Glob::new("video/mpeg", "[A-Z][0-9].VDR", 50, true);
+        assert!(vdr.compare("A1.VDR", CS));
+        assert!(!vdr.compare("a1.vdr", CS));
+    }
+
+    #[test]
+    fn case_insensitive_profile() {
+        let ci = FilesystemProfile::CaseInsensitive;
+
+        let cplusplus_src = Glob::new("text/x-c++src", "*.C", 50, true);
+        assert!(cplusplus_src.compare("foo.c", ci));
+        let vdr = Glob::new("video/mpeg", "[A-Z][0-9].VDR", 50, true);
+        assert!(vdr.compare("a1.vdr", ci));
+        let core = Glob::new("application/x-core", "core", 50, true);
+        assert!(core.compare("CORE", ci));
+
+        let mut globs = GlobMap::new();
+        globs.add_globs(vec![cplusplus_src]);
+        assert_eq!(globs.lookup_mime_type_for_file_name("foo.c", CS), None);
+        assert_eq!(
+            globs.lookup_mime_type_for_file_name("foo.c", ci),
+            Some(vec!["text/x-c++src".to_string()])
+        );
+    }
+
+    #[test]
+    fn detect_profile() {
+        let dir = crate::test_util::TempDir::new("profile");
+        let file = dir.write("Foo.txt", "foo");
+        let expected = if dir.join("FOO.TXT").exists() {
+            FilesystemProfile::CaseInsensitive
+        } else {
+            FilesystemProfile::CaseSensitive
+        };
+        assert_eq!(FilesystemProfile::detect(&file), expected);
+        assert_eq!(FilesystemProfile::detect(dir.join("123")), FilesystemProfile::default());
+    }
+
+    #[test]
+    fn suffix_len() {
+        assert_eq!(Glob::simple("text/x-foo", "*.foo").suffix_len(), Some(4));
+        assert_eq!(Glob::simple("text/x-foo", "*.anim[1-9j]").suffix_len(), Some(6));
+        assert_eq!(Glob::simple("text/x-foo", "*.[]x]").suffix_len(), Some(2));
+        assert_eq!(Glob::simple("text/x-foo", "*.x*").suffix_len(), None);
+        assert_eq!(Glob::simple("text/x-foo", "README*").suffix_len(), None);
+        assert_eq!(Glob::simple("text/x-foo", "Makefile").suffix_len(), None);
+    }
+
+    #[test]
+    fn complexity() {
+        assert_eq!(Glob::simple("text/x-foo", "*.foo").complexity(), 0);
+        assert_eq!(Glob::simple("text/x-foo", "Makefile").complexity(), 0);
+        assert_eq!(Glob::simple("text/x-foo", "README*").complexity(), 1);
+        assert_eq!(Glob::simple("text/x-foo", "*.anim[1-9]").complexity(), 4);
+        assert_eq!(Glob::simple("text/x-foo", "*.[a*?]?").complexity(), 5);
+        assert_eq!(Glob::simple("text/x-foo", "*.\\*?").complexity(), 2);
+        assert_eq!(Glob::simple("text/x-foo", "*.[]x]").complexity(), 3);
+        assert_eq!(Glob::simple("text/x-foo", "*.[!ab]").complexity(), 3);
+    }
+
+    #[test]
+    fn pattern_len() {
+        for pattern in &["*.foo", "Makefile", "README*", "*.anim[1-9]", "*.\\*?"] {
+            let glob = Glob::simple("text/x-foo", pattern);
+            assert_eq!(glob.pattern_len(), glob.pattern().len());
+        }
+    }
+
+    #[test]
+    fn cached_misses() {
+        let mut globs = GlobMap::new();
+        globs.add_globs(vec![
+            Glob::simple("application/gzip", "*.gz"),
+            Glob::simple("application/x-compressed-tar", "*.tar.gz"),
+            Glob::simple("text/x-readme", "README*"),
+            Glob::new("text/x-foo", "*.TXT", 50, true),
+            Glob::simple("text/x-bar", "*.anim[1-9]"),
+        ]);
+
+        assert_eq!(globs.lookup_mime_type_for_file_name("foo.txt", CS), None);
+        assert_eq!(globs.cached_miss(".txt", CS), Some(vec!["text/x-bar".to_string()]));
+        assert_eq!(globs.lookup_mime_type_for_file_name("bar.txt", CS), None);
+        assert_eq!(
+            globs.lookup_mime_type_for_file_name("README.txt", CS),
+            Some(vec!["text/x-readme".to_string()])
+        );
+
+        assert_eq!(globs.lookup_mime_type_for_file_name("foo.gz", CS), Some(vec!["application/gzip".to_string()]));
+        assert_eq!(globs.cached_miss(".gz", CS), None);
+
+        assert_eq!(globs.lookup_mime_type_for_file_name("foo.unknown", CS), None);
+        assert_eq!(globs.cached_miss(".unknown", CS), Some(vec![]));
+
+        globs.add_glob(Glob::simple("text/plain", "*.txt"));
+        assert_eq!(globs.cached_miss(".txt", CS), None);
+        assert_eq!(
+            globs.lookup_mime_type_for_file_name("bar.txt", CS),
+            Some(vec!["text/plain".to_string()])
+        );
+    }
+
+    #[test]
+    fn best_match_with_context() {
+        let mut globs = GlobMap::new();
+        globs.add_globs(vec![
+            Glob::simple("application/gzip", "*.gz"),
+            Glob::simple("application/x-compressed-tar", "*.tar.gz"),
+            Glob::simple("text/x-b", "*.ab"),
+            Glob::simple("text/x-a", "*.ab"),
+        ]);
+
+        let mut ctx = LookupContext::new();
+        assert_eq!(
+            globs.best_match_with("foo.tar.gz", CS, &mut ctx),
+            Some(("application/x-compressed-tar", false))
+        );
+        assert_eq!(globs.best_match_with("FOO.GZ", CS, &mut ctx), Some(("application/gzip", false)));
+        assert_eq!(globs.best_match_with("foo.ab", CS, &mut ctx), Some(("text/x-a", true)));
+        assert_eq!(globs.best_match_with("foo.txt", CS, &mut ctx), None);
+        assert_eq!(globs.best_match_with("bar.txt", CS, &mut ctx), None);
+    }
+
+    #[test]
+    fn dotfiles() {
+        let mut globs = GlobMap::new();
+        globs.add_globs(vec![
+            Glob::simple("application/gzip", "*.gz"),
+            Glob::simple("application/x-compressed-tar", "*.tar.gz"),
+            Glob::simple("text/x-bash", ".bashrc"),
+            Glob::simple("text/x-readme", "README*"),
+        ]);
+
+        let lookup = |globs: &GlobMap, name| globs.lookup_best_match(name, CS);
+
+        assert_eq!(lookup(&globs, ".tar.gz"), GlobMatch::Unique("application/x-compressed-tar".to_string()));
+        assert_eq!(lookup(&globs, ".gz"), GlobMatch::Unique("application/gzip".to_string()));
+        assert_eq!(lookup(&globs, ".bashrc"), GlobMatch::Unique("text/x-bash".to_string()));
+
+        globs.set_dotfile_handling(DotfileHandling::SkipLeadingDot);
+        assert_eq!(lookup(&globs, ".tar.gz"), GlobMatch::Unique("application/gzip".to_string()));
+        assert_eq!(lookup(&globs, ".gz"), GlobMatch::NoMatch);
+        assert_eq!(lookup(&globs, "foo.gz"), GlobMatch::Unique("application/gzip".to_string()));
+        assert_eq!(lookup(&globs, ".bashrc"), GlobMatch::Unique("text/x-bash".to_string()));
+        assert_eq!(lookup(&globs, ".foo.tar.gz"), GlobMatch::Unique("application/x-compressed-tar".to_string()));
+    }
+
+    #[test]
+    fn deduplicate_globs() {
+        let mut globs = GlobMap::new();
+        globs.add_globs(vec![
+            Glob::with_weight("text/x-foo", "*.foo", 50),
+            Glob::with_weight("text/x-foo", "*.foo", 50),
+        ]);
+        globs.add_globs(vec![
+            Glob::with_weight("text/x-foo", "*.foo", 50),
+            Glob::with_weight("text/x-foo", "*.foo", 60),
+            Glob::new("text/x-foo", "*.foo", 50, true),
+        ]);
+
+        assert_eq!(globs.count_for("text/x-foo"), 3);
+    }
+
+    #[test]
+    fn best_match() {
+        let mut globs = GlobMap::new();
+        globs.add_globs(vec![
+            Glob::with_weight("text/x-foo", "*.foo", 40),
+            Glob::with_weight("text/x-bar", "*.foo", 60),
+            Glob::with_weight("text/x-baz", "*.x.foo", 60),
+            Glob::with_weight("text/x-qux", "*.x.foo", 60),
+            Glob::with_weight("text/x-qux", "*.X.FOO", 60),
+        ]);
+
+        assert_eq!(globs.lookup_best_match("a.txt", CS), GlobMatch::NoMatch);
+        assert_eq!(globs.lookup_best_match("a.foo", CS), GlobMatch::Unique("text/x-bar".to_string()));
+        assert_eq!(
+            globs.lookup_best_match("a.x.foo", CS),
+            GlobMatch::Ambiguous(vec!["text/x-baz".to_string(), "text/x-qux".to_string()])
+        );
+        assert_eq!(
+            globs.lookup_mime_type_for_file_name("a.foo", CS),
+            Some(vec!["text/x-bar".to_string(), "text/x-foo".to_string()])
+        );
+    }
+
+    #[test]
+    fn name_matches() {
+        let mut globs = GlobMap::new();
+        globs.add_globs(vec![
+            Glob::simple("application/gzip", "*.gz"),
+            Glob::simple("application/x-compressed-tar", "*.tar.gz"),
+            Glob::new("application/x-weak-gzip", "*.gz", 20, true),
+        ]);
+
+        let res = globs.lookup_name_matches("a.tar.gz", CS);
+        assert_eq!(
+            res,
+            vec![
+                NameMatch {
+                    mime_type: "application/x-compressed-tar".to_string(),
+                    weight: 50,
+                    pattern_len: 8,
+                    case_sensitive: false,
+                },
+                NameMatch {
+                    mime_type: "application/gzip".to_string(),
+                    weight: 50,
+                    pattern_len: 4,
+                    case_sensitive: false,
+                },
+                NameMatch {
+                    mime_type: "application/x-weak-gzip".to_string(),
+                    weight: 20,
+                    pattern_len: 4,
+                    case_sensitive: true,
+                },
+            ]
+        );
+        assert!(globs.lookup_name_matches("a.GZ", CS).iter().all(|m| !m.case_sensitive));
+        assert!(globs.lookup_name_matches("a.txt", CS).is_empty());
+    }
+
+    #[test]
+    fn extension_table() {
+        let mut globs = GlobMap::new();
+        globs.add_globs(vec![
+            Glob::simple("image/png", "*.png"),
+            Glob::new("text/x-c++src", "*.C", 50, true),
+            Glob::simple("application/x-compressed-tar", "*.tar.gz"),
+            Glob::simple("application/gzip", "*.gz"),
+            Glob::simple("text/x-readme", "README*"),
+        ]);
+
+        assert_eq!(globs.extensions.len(), 3);
+        assert_eq!(globs.other_globs.len(), 1);
+        assert_eq!(globs.lookup_best_match("A.PNG", CS), GlobMatch::Unique("image/png".to_string()));
+        assert_eq!(globs.lookup_best_match("a.c", CS), GlobMatch::NoMatch);
+        assert_eq!(globs.lookup_best_match("a.C", CS), GlobMatch::Unique("text/x-c++src".to_string()));
+        assert_eq!(
+            globs.lookup_best_match("a.tar.gz", CS),
+            GlobMatch::Unique("application/x-compressed-tar".to_string())
+        );
+        assert_eq!(
+            globs.lookup_mime_type_for_file_name("README.png", CS),
+            Some(vec!["text/x-readme".to_string(), "image/png".to_string()])
+        );
     }
 }
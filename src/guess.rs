@@ -0,0 +1,737 @@
+use std::collections::HashMap;
+use std::fs::{File, FileType, Metadata};
+use std::io;
+use std::io::Read;
+#[cfg(unix)]
+use std::os::unix::fs::{FileExt, FileTypeExt};
+#[cfg(windows)]
+use std::os::windows::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use crate::xattr::read_mime_xattr;
+use crate::{GlobMatch, SharedMimeInfo, XattrPolicy};
+
+// The amount of data the infer crate looks at
+#[cfg(feature = "infer")]
+pub(crate) const INFER_EXTENT: usize = 8192;
+
+/// The result of guessing the MIME type of a file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Guess {
+    mime_type: String,
+    uncertain: bool,
+}
+
+impl Guess {
+    pub(crate) fn new<S: Into<String>>(mime_type: S, uncertain: bool) -> Guess {
+        Guess {
+            mime_type: mime_type.into(),
+            uncertain,
+        }
+    }
+
+    /// The guessed MIME type.
+    pub fn mime_type(&self) -> &str {
+        &self.mime_type
+    }
+
+    /// Whether the contents of the file could not be used to confirm the
+    /// guess, e.g. because reading them failed or took too long.
+    pub fn uncertain(&self) -> bool {
+        self.uncertain
+    }
+}
+
+/// Limits on the I/O done when guessing the MIME type of a file from its
+/// path, for the file systems that can be slow or hang, like network or
+/// FUSE file systems.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IoBudget {
+    pub(crate) max_read: usize,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) metadata_only: bool,
+}
+
+impl IoBudget {
+    /// Creates a budget reading up to 16 KiB, without a timeout.
+    pub fn new() -> IoBudget {
+        IoBudget {
+            max_read: 16 * 1024,
+            timeout: None,
+            metadata_only: false,
+        }
+    }
+
+    /// Sets the maximum number of bytes read from a file.
+    pub fn max_read(mut self, max_read: usize) -> IoBudget {
+        self.max_read = max_read;
+        self
+    }
+
+    /// Sets the time after which the guess gives up on reading the file,
+    /// and falls back to its name.
+    ///
+    /// The file is then read on a separate thread, as blocking reads cannot
+    /// be interrupted. When the timeout expires, that thread is left
+    /// behind until the read returns, which may be never on a hung file
+    /// system; each expired guess leaks one thread, and its open file.
+    pub fn timeout(mut self, timeout: Duration) -> IoBudget {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets whether the file is never opened, the guess only using its
+    /// name and its metadata, e.g. on hierarchical storage where opening a
+    /// file recalls it from tape.
+    pub fn metadata_only(mut self, metadata_only: bool) -> IoBudget {
+        self.metadata_only = metadata_only;
+        self
+    }
+}
+
+impl Default for IoBudget {
+    fn default() -> IoBudget {
+        IoBudget::new()
+    }
+}
+
+// The beginning of a file, or the type of the files that are not regular
+// files
+#[derive(Debug, PartialEq)]
+pub(crate) enum Head {
+    Data(Vec<u8>),
+    Inode(&'static str),
+}
+
+// Reads the beginning of the file at @path
+fn read_head(path: &Path, max_read: usize) -> io::Result<Head> {
+    let mut res = Vec::new();
+
+    match read_head_into(path, max_read, &mut res)? {
+        Some(mime_type) => Ok(Head::Inode(mime_type)),
+        None => Ok(Head::Data(res)),
+    }
+}
+
+/// Reads the beginning of the file at @path into @buf, reusing its
+/// allocation, or returns the type of the file if it is not a regular
+/// file.
+pub(crate) fn read_head_into(path: &Path, max_read: usize, buf: &mut Vec<u8>) -> io::Result<Option<&'static str>> {
+    buf.clear();
+
+    // Opening a fifo could block until the other end opens it
+    if let Some(mime_type) = inode_type(&path.metadata()?) {
+        return Ok(Some(mime_type));
+    }
+
+    File::open(path)?.take(max_read as u64).read_to_end(buf)?;
+
+    Ok(None)
+}
+
+// Reads into @buf at @offset of @file, leaving the position of the file
+// where it was
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    file.read_at(buf, offset)
+}
+
+// The positioned reads of Windows move the position, which is put back
+#[cfg(windows)]
+fn read_at(mut file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    use std::io::{Seek, SeekFrom};
+
+    let position = file.stream_position()?;
+    let res = file.seek_read(buf, offset);
+    file.seek(SeekFrom::Start(position))?;
+
+    res
+}
+
+// Reads up to @max_read bytes at the beginning of @file with positioned
+// reads, which leave the position of the file where it was
+fn read_head_at(file: &File, max_read: usize) -> io::Result<Vec<u8>> {
+    let mut res = vec![0; max_read];
+    let mut len = 0;
+
+    while len < max_read {
+        match read_at(file, &mut res[len..], len as u64) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    res.truncate(len);
+
+    Ok(res)
+}
+
+/// Reads the beginning of the file at @path within @budget.
+///
+/// With a timeout, the file is read on a separate thread, which is left
+/// behind, blocked, if the timeout expires.
+pub(crate) fn read_head_within(path: &Path, budget: &IoBudget) -> io::Result<Head> {
+    let timeout = match budget.timeout {
+        Some(v) => v,
+        None => return read_head(path, budget.max_read),
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    let path = path.to_path_buf();
+    let max_read = budget.max_read;
+    thread::spawn(move || {
+        let _ = sender.send(read_head(&path, max_read));
+    });
+
+    match receiver.recv_timeout(timeout) {
+        Ok(res) => res,
+        Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "reading the file timed out")),
+    }
+}
+
+/// How the file name and the contents of a file are weighed against each
+/// other by a [`GuessBuilder`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum GuessPolicy {
+    /// The file name decides when its best match is unambiguous, and the
+    /// contents decide between the types matching the name otherwise.
+    #[default]
+    NameFirst,
+    /// The contents decide when they match a magic entry, and the file
+    /// name otherwise, e.g. for files with untrusted names.
+    DataFirst,
+}
+
+/// The content of a [`GuessBuilder`] without data nor path.
+pub struct NoContent;
+
+/// The content of a [`GuessBuilder`] with the data at the beginning of
+/// the file.
+pub struct WithData<'a>(&'a [u8]);
+
+/// The content of a [`GuessBuilder`] reading the beginning of a file.
+pub struct FromPath<'a> {
+    path: &'a Path,
+    budget: IoBudget,
+    xattr: XattrPolicy,
+}
+
+/// The content of a [`GuessBuilder`] reading the beginning of a stream.
+pub struct FromReader<'a, R: Read + ?Sized>(&'a mut R);
+
+/// The content of a [`GuessBuilder`] reading the beginning of an open
+/// file, without moving its position.
+pub struct FromFile<'a>(&'a File);
+
+/// Guesses the MIME type of a file from all the information available
+/// about it, created by [`SharedMimeInfo::guess`].
+///
+/// The contents are given either as data or as a path to read them from,
+/// but not both, and the I/O limits can only be set when reading from
+/// a path; the other combinations do not compile.
+pub struct GuessBuilder<'a, C = NoContent> {
+    db: &'a SharedMimeInfo,
+    file_name: Option<&'a str>,
+    metadata: Option<&'a Metadata>,
+    policy: GuessPolicy,
+    content: C,
+}
+
+// Returns the MIME type of the files that are not regular files
+pub(crate) fn inode_type(metadata: &Metadata) -> Option<&'static str> {
+    let file_type = metadata.file_type();
+
+    if file_type.is_dir() {
+        Some("inode/directory")
+    } else if file_type.is_symlink() {
+        Some("inode/symlink")
+    } else {
+        special_file_type(&file_type)
+    }
+}
+
+// Returns the MIME type of the special files, like the FIFOs
+#[cfg(unix)]
+fn special_file_type(file_type: &FileType) -> Option<&'static str> {
+    if file_type.is_fifo() {
+        Some("inode/fifo")
+    } else if file_type.is_socket() {
+        Some("inode/socket")
+    } else if file_type.is_block_device() {
+        Some("inode/blockdevice")
+    } else if file_type.is_char_device() {
+        Some("inode/chardevice")
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+fn special_file_type(_file_type: &FileType) -> Option<&'static str> {
+    None
+}
+
+impl<'a, C> GuessBuilder<'a, C> {
+    /// Sets the name of the file.
+    pub fn file_name(mut self, file_name: &'a str) -> GuessBuilder<'a, C> {
+        self.file_name = Some(file_name);
+        self
+    }
+
+    /// Sets the metadata of the file, which decides the type of the files
+    /// that are not regular files, and of the empty ones.
+    pub fn metadata(mut self, metadata: &'a Metadata) -> GuessBuilder<'a, C> {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Sets how the file name and the contents are weighed.
+    pub fn policy(mut self, policy: GuessPolicy) -> GuessBuilder<'a, C> {
+        self.policy = policy;
+        self
+    }
+
+    fn with_content<D>(self, content: D) -> GuessBuilder<'a, D> {
+        GuessBuilder {
+            db: self.db,
+            file_name: self.file_name,
+            metadata: self.metadata,
+            policy: self.policy,
+            content,
+        }
+    }
+
+    // Guesses from the file name and metadata, and the @data if known
+    pub(crate) fn resolve(&self, data: Option<&[u8]>, uncertain: bool) -> Guess {
+        let mut data = data;
+
+        if let Some(metadata) = self.metadata {
+            if let Some(mime_type) = inode_type(metadata) {
+                return Guess::new(mime_type, false);
+            }
+
+            if metadata.len() == 0 && data.is_none() {
+                data = Some(&[]);
+            }
+        }
+
+        if self.policy == GuessPolicy::DataFirst {
+            if let Some(mime_type) = data.and_then(|d| self.db.get_mime_type_for_data(d)) {
+                return Guess::new(mime_type, uncertain);
+            }
+        }
+
+        let mime_type = self.db.guess_known_type(self.file_name, data);
+
+        // When the database only knows the data is binary, the infer crate
+        // may still recognize it, with less confidence
+        #[cfg(feature = "infer")]
+        {
+            if mime_type == crate::UNKNOWN_TYPE {
+                if let Some(v) = data.and_then(infer::get) {
+                    let mime_type = self.db.unalias_mime_type(v.mime_type());
+
+                    return Guess::new(mime_type.unwrap_or_else(|| v.mime_type().to_string()), true);
+                }
+            }
+        }
+
+        Guess::new(self.db.or_fallback_type(mime_type, self.file_name), uncertain)
+    }
+}
+
+impl<'a> GuessBuilder<'a, NoContent> {
+    pub(crate) fn new(db: &'a SharedMimeInfo) -> GuessBuilder<'a, NoContent> {
+        GuessBuilder {
+            db,
+            file_name: None,
+            metadata: None,
+            policy: GuessPolicy::default(),
+            content: NoContent,
+        }
+    }
+
+    /// Sets the @data at the beginning of the file.
+    pub fn data(self, data: &'a [u8]) -> GuessBuilder<'a, WithData<'a>> {
+        self.with_content(WithData(data))
+    }
+
+    /// Sets the @path of the file to read the contents from, when the
+    /// file name is not enough; the file name defaults to the one of
+    /// @path.
+    pub fn path(self, path: &'a Path) -> GuessBuilder<'a, FromPath<'a>> {
+        self.with_content(FromPath {
+            path,
+            budget: IoBudget::default(),
+            xattr: XattrPolicy::default(),
+        })
+    }
+
+    /// Sets the @reader to read the contents from, like a large file or a
+    /// network stream, when the file name is not enough.
+    ///
+    /// Only the beginning of the stream is read, up to the largest extent
+    /// of the magic entries of the database.
+    pub fn reader<R: Read + ?Sized>(self, reader: &'a mut R) -> GuessBuilder<'a, FromReader<'a, R>> {
+        self.with_content(FromReader(reader))
+    }
+
+    /// Sets the open @file to read the contents from, when the file name is
+    /// not enough, like a file descriptor received without its path from a
+    /// sandbox portal.
+    ///
+    /// The beginning of the file is read whatever its current position,
+    /// which is left unchanged.
+    pub fn file_handle(self, file: &'a File) -> GuessBuilder<'a, FromFile<'a>> {
+        self.with_content(FromFile(file))
+    }
+
+    /// Guesses the MIME type, without the contents of the file.
+    pub fn guess(self) -> Guess {
+        self.resolve(None, false)
+    }
+}
+
+impl<'a> GuessBuilder<'a, WithData<'a>> {
+    /// Guesses the MIME type.
+    pub fn guess(self) -> Guess {
+        self.resolve(Some(self.content.0), false)
+    }
+}
+
+impl<'a, R: Read + ?Sized> GuessBuilder<'a, FromReader<'a, R>> {
+    /// Guesses the MIME type, reading the beginning of the stream if
+    /// needed.
+    ///
+    /// If reading the stream fails, the guess only uses the file name, and
+    /// is marked as uncertain.
+    pub fn guess(self) -> Guess {
+        if let Some(mime_type) = self.metadata.and_then(inode_type) {
+            return Guess::new(mime_type, false);
+        }
+
+        // The stream is not read when the name is enough
+        if let (GuessPolicy::NameFirst, Some(name)) = (self.policy, self.file_name) {
+            if let GlobMatch::Unique(mime_type) = self.db.lookup_file_name(name) {
+                return Guess::new(mime_type, false);
+            }
+        }
+
+        let mut data = Vec::new();
+        let max_read = self.db.max_magic_extent();
+        #[cfg(feature = "infer")]
+        let max_read = max_read.max(INFER_EXTENT);
+        match (&mut *self.content.0).take(max_read as u64).read_to_end(&mut data) {
+            Ok(_) => {
+                #[cfg(feature = "metrics")]
+                self.db.count_sniffed_bytes(data.len());
+                self.resolve(Some(&data), false)
+            }
+            Err(_) => self.resolve(None, true),
+        }
+    }
+}
+
+impl<'a> GuessBuilder<'a, FromFile<'a>> {
+    /// Guesses the MIME type, reading the beginning of the file if needed.
+    ///
+    /// If reading the file fails, the guess only uses the file name, and is
+    /// marked as uncertain.
+    pub fn guess(self) -> Guess {
+        // The files that cannot be read at an offset, like pipes, are not
+        // regular files
+        let metadata = match self.metadata {
+            Some(metadata) => Ok(metadata.clone()),
+            None => self.content.0.metadata(),
+        };
+        if let Some(mime_type) = metadata.as_ref().ok().and_then(inode_type) {
+            return Guess::new(mime_type, false);
+        }
+
+        // The file is not read when the name is enough
+        if let (GuessPolicy::NameFirst, Some(name)) = (self.policy, self.file_name) {
+            if let GlobMatch::Unique(mime_type) = self.db.lookup_file_name(name) {
+                return Guess::new(mime_type, false);
+            }
+        }
+
+        let max_read = self.db.max_magic_extent();
+        #[cfg(feature = "infer")]
+        let max_read = max_read.max(INFER_EXTENT);
+        match read_head_at(self.content.0, max_read) {
+            Ok(data) => {
+                #[cfg(feature = "metrics")]
+                self.db.count_sniffed_bytes(data.len());
+                self.resolve(Some(&data), false)
+            }
+            Err(_) => self.resolve(None, true),
+        }
+    }
+}
+
+impl<'a> GuessBuilder<'a, FromPath<'a>> {
+    /// Sets the limits on the I/O done when reading the file.
+    pub fn limits(mut self, budget: IoBudget) -> GuessBuilder<'a, FromPath<'a>> {
+        self.content.budget = budget;
+        self
+    }
+
+    /// Sets how the `user.mime_type` extended attribute of the file is
+    /// used; by default, it is ignored.
+    pub fn xattr(mut self, policy: XattrPolicy) -> GuessBuilder<'a, FromPath<'a>> {
+        self.content.xattr = policy;
+        self
+    }
+
+    /// Guesses the MIME type, reading the file if needed.
+    ///
+    /// If the contents cannot be read within the limits, the guess only
+    /// uses the file name, and is marked as uncertain.
+    pub fn guess(mut self) -> Guess {
+        if self.file_name.is_none() {
+            self.file_name = self.content.path.file_name().and_then(|n| n.to_str());
+        }
+
+        if let Some(mime_type) = self.metadata.and_then(inode_type) {
+            return Guess::new(mime_type, false);
+        }
+
+        let hint = match self.content.xattr {
+            XattrPolicy::Ignore => None,
+            _ => read_mime_xattr(self.content.path).map(|v| self.db.canonical_type(&v)),
+        };
+        if let (XattrPolicy::Trust, Some(mime_type)) = (self.content.xattr, &hint) {
+            return Guess::new(mime_type.as_str(), false);
+        }
+
+        // The file is not read when the name is enough
+        if let (GuessPolicy::NameFirst, Some(name), None) = (self.policy, self.file_name, &hint) {
+            if let GlobMatch::Unique(mime_type) = self.db.lookup_file_name(name) {
+                return Guess::new(mime_type, false);
+            }
+        }
+
+        if self.content.budget.metadata_only {
+            let metadata = match self.metadata {
+                Some(metadata) => Ok(metadata.clone()),
+                None => self.content.path.metadata(),
+            };
+            match metadata {
+                Ok(metadata) => {
+                    if let Some(mime_type) = inode_type(&metadata) {
+                        return Guess::new(mime_type, false);
+                    }
+                    if metadata.len() == 0 {
+                        return self.resolve(Some(&[]), false);
+                    }
+                }
+                Err(_) => return self.resolve(None, true),
+            }
+
+            if let Some(hint) = hint {
+                return Guess::new(hint, true);
+            }
+            let unique = matches!(self.file_name.map(|n| self.db.lookup_file_name(n)), Some(GlobMatch::Unique(_)));
+            return self.resolve(None, !unique);
+        }
+
+        match read_head_within(self.content.path, &self.content.budget) {
+            Ok(Head::Data(data)) => {
+                #[cfg(feature = "metrics")]
+                self.db.count_sniffed_bytes(data.len());
+
+                // The attribute may be stale, or set by the sender of the
+                // file
+                if let Some(hint) = hint {
+                    let compatible = |c: &str| self.db.is_subclass_of(c, &hint) || self.db.is_subclass_of(&hint, c);
+                    match self.db.get_mime_type_for_data(&data) {
+                        Some(content) if !compatible(&content) => {}
+                        _ => return Guess::new(hint, false),
+                    }
+                }
+
+                self.resolve(Some(&data), false)
+            }
+            Ok(Head::Inode(mime_type)) => Guess::new(mime_type, false),
+            Err(_) => match hint {
+                Some(hint) => Guess::new(hint, true),
+                None => self.resolve(None, true),
+            },
+        }
+    }
+}
+
+// The databases used by the one-shot guesses, by list of directories
+static DATABASES: OnceLock<Mutex<HashMap<Vec<PathBuf>, Arc<SharedMimeInfo>>>> = OnceLock::new();
+
+fn database_for_dirs(dirs: &[PathBuf]) -> Arc<SharedMimeInfo> {
+    let databases = DATABASES.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut databases = match databases.lock() {
+        Ok(v) => v,
+        Err(e) => e.into_inner(),
+    };
+
+    let db = databases.entry(dirs.to_vec()).or_insert_with(|| {
+        let mut builder = SharedMimeInfo::builder().system_directories(false);
+        for dir in dirs {
+            builder = builder.directory(dir);
+        }
+        Arc::new(builder.build())
+    });
+
+    db.clone()
+}
+
+/// Guesses the MIME type of a file from its @file_name and the @data at
+/// its beginning, using only the MIME information of the data directories
+/// in @dirs, instead of the XDG base directories.
+///
+/// The database of each list of directories is loaded on the first guess
+/// and kept for the lifetime of the process.
+pub fn guess_with_dirs(dirs: &[PathBuf], file_name: Option<&str>, data: Option<&[u8]>) -> String {
+    database_for_dirs(dirs).guess_mime_type(file_name, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use std::env;
+
+    #[test]
+    fn guess_for_dirs() {
+        let dirs = vec![env::current_dir().unwrap().join("test_files")];
+
+        assert_eq!(guess_with_dirs(&dirs, Some("bar.gif"), None), "image/gif");
+
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+        assert_eq!(guess_with_dirs(&dirs, Some("rust-logo"), Some(png_data)), "image/png");
+        assert!(Arc::ptr_eq(&database_for_dirs(&dirs), &database_for_dirs(&dirs)));
+
+        assert_eq!(guess_with_dirs(&[], Some("bar.gif"), Some(png_data)), "application/octet-stream");
+    }
+
+    #[test]
+    fn guess_builder() {
+        let mime_db = SharedMimeInfo::new_for_directory(env::current_dir().unwrap().join("test_files"));
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+
+        assert_eq!(mime_db.guess().file_name("bar.gif").guess(), Guess::new("image/gif", false));
+        assert_eq!(mime_db.guess().guess(), Guess::new("application/octet-stream", false));
+
+        let guess = mime_db.guess().file_name("bar.gif").data(png_data).guess();
+        assert_eq!(guess.mime_type(), "image/gif");
+        let guess = mime_db
+            .guess()
+            .file_name("bar.gif")
+            .data(png_data)
+            .policy(GuessPolicy::DataFirst)
+            .guess();
+        assert_eq!(guess.mime_type(), "image/png");
+
+        let png_file = Path::new("test_files/files/rust-logo.png");
+        let guess = mime_db.guess().path(png_file).limits(IoBudget::new().max_read(64)).guess();
+        assert_eq!(guess, Guess::new("image/png", false));
+
+        let metadata = Path::new("test_files").metadata().unwrap();
+        let guess = mime_db.guess().file_name("foo.gif").metadata(&metadata).guess();
+        assert_eq!(guess.mime_type(), "inode/directory");
+
+        let dir = TempDir::new("empty");
+        let metadata = dir.write("empty", "").metadata().unwrap();
+        let guess = mime_db.guess().file_name("empty").metadata(&metadata).guess();
+        assert_eq!(guess.mime_type(), "application/x-zerosize");
+    }
+
+    #[test]
+    fn guess_from_reader() {
+        let mime_db = SharedMimeInfo::new_for_directory(env::current_dir().unwrap().join("test_files"));
+
+        // A stream longer than anything the magic entries look at
+        let mut data = include_bytes!("../test_files/files/rust-logo.png").to_vec();
+        data.resize(mime_db.max_magic_extent() * 4, 0);
+        let mut reader = io::Cursor::new(&data);
+        assert_eq!(mime_db.guess().reader(&mut reader).guess(), Guess::new("image/png", false));
+        assert_eq!(reader.position() as usize, mime_db.max_magic_extent());
+
+        let mut reader = io::Cursor::new(&data);
+        assert_eq!(
+            mime_db.guess().file_name("bar.gif").reader(&mut reader).guess(),
+            Guess::new("image/gif", false)
+        );
+        assert_eq!(reader.position(), 0);
+
+        struct Failing;
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("broken stream"))
+            }
+        }
+        assert_eq!(mime_db.guess().reader(&mut Failing).guess(), Guess::new("application/octet-stream", true));
+    }
+
+    #[test]
+    fn guess_from_file_handle() {
+        use std::io::{Seek, SeekFrom};
+
+        let mime_db = SharedMimeInfo::new_for_directory(env::current_dir().unwrap().join("test_files"));
+
+        let mut file = File::open("test_files/files/rust-logo.png").unwrap();
+        file.seek(SeekFrom::Start(10)).unwrap();
+        assert_eq!(mime_db.guess().file_handle(&file).guess(), Guess::new("image/png", false));
+        assert_eq!(file.stream_position().unwrap(), 10);
+
+        let guess = mime_db.guess().file_name("bar.gif").file_handle(&file).guess();
+        assert_eq!(guess, Guess::new("image/gif", false));
+
+        let dir = File::open("test_files").unwrap();
+        assert_eq!(mime_db.guess().file_handle(&dir).guess().mime_type(), "inode/directory");
+    }
+
+    #[cfg(feature = "infer")]
+    #[test]
+    fn infer_fallback() {
+        let mime_db = SharedMimeInfo::new_for_directory(env::current_dir().unwrap().join("test_files/infer"));
+
+        let bz2_data = b"BZh91AY&SY";
+        assert_eq!(mime_db.guess().data(bz2_data).guess(), Guess::new("application/x-bzip", true));
+        let mut reader = io::Cursor::new(&bz2_data[..]);
+        assert_eq!(mime_db.guess().reader(&mut reader).guess(), Guess::new("application/x-bzip", true));
+        assert_eq!(mime_db.guess().data(b"\x00\x01").guess(), Guess::new("application/octet-stream", false));
+    }
+
+    #[test]
+    fn read_within_budget() {
+        let budget = IoBudget::new().max_read(4);
+        let png_file = Path::new("test_files/files/rust-logo.png");
+        assert_eq!(read_head_within(png_file, &budget).unwrap(), Head::Data(b"\x89PNG".to_vec()));
+        let slow_budget = budget.timeout(Duration::from_secs(10));
+        assert_eq!(read_head_within(png_file, &slow_budget).unwrap(), Head::Data(b"\x89PNG".to_vec()));
+        assert_eq!(read_head_within(Path::new("test_files"), &budget).unwrap(), Head::Inode("inode/directory"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn read_fifo() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let budget = IoBudget::new().max_read(4);
+
+        // Opening a FIFO without a writer would block
+        let dir = TempDir::new("fifo");
+        let fifo = dir.join("fifo");
+        let c_fifo = std::ffi::CString::new(fifo.as_os_str().as_bytes()).unwrap();
+        if unsafe { libc::mkfifo(c_fifo.as_ptr(), 0o600) } == 0 {
+            assert_eq!(read_head_within(&fifo, &budget).unwrap(), Head::Inode("inode/fifo"));
+            let mime_db = SharedMimeInfo::new_for_directory(env::current_dir().unwrap().join("test_files"));
+            assert_eq!(mime_db.guess().path(&fifo).guess(), Guess::new("inode/fifo", false));
+            let mut ctx = crate::LookupContext::new();
+            assert_eq!(mime_db.guess_mime_type_for_path_with(&fifo, 64, &mut ctx), "inode/fifo");
+        }
+    }
+}
@@ -1,10 +1,18 @@
 use std::cmp::Ordering;
+use std::collections::HashSet;
 use std::fmt;
 use std::fs::File;
+use std::io;
 use std::io::BufRead;
 use std::io::BufReader;
 use std::path::Path;
 
+use crate::lines;
+
+// The icon name removing the icon of its type set in the less important
+// data directories, which then falls back to the default icon
+pub(crate) const NOICON: &str = "__NOICON__";
+
 #[derive(Clone, Eq)]
 pub struct Icon {
     icon_name: String,
@@ -13,7 +21,14 @@ pub struct Icon {
 
 impl fmt::Debug for Icon {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Icon for {}: {}\n", self.mime_type, self.icon_name)
+        writeln!(f, "Icon for {}: {}", self.mime_type, self.icon_name)
+    }
+}
+
+// Formats the icon as a line of an icons file
+impl fmt::Display for Icon {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.mime_type, self.icon_name)
     }
 }
 
@@ -31,7 +46,7 @@ impl Ord for Icon {
 
 impl PartialOrd for Icon {
     fn partial_cmp(&self, other: &Icon) -> Option<Ordering> {
-        Some(self.mime_type.cmp(&other.mime_type))
+        Some(self.cmp(other))
     }
 }
 
@@ -43,6 +58,19 @@ impl Icon {
         }
     }
 
+    pub fn icon_name(&self) -> &str {
+        &self.icon_name
+    }
+
+    pub fn mime_type(&self) -> &str {
+        &self.mime_type
+    }
+
+    // Whether this is the marker removing the icon of its type
+    pub(crate) fn is_noicon(&self) -> bool {
+        self.icon_name == NOICON
+    }
+
     pub fn from_string(s: &str) -> Option<Icon> {
         let mut chunks = s.split(':');
 
@@ -65,36 +93,77 @@ impl Icon {
         }
 
         Some(Icon {
-            icon_name: icon_name,
-            mime_type: mime_type,
+            icon_name,
+            mime_type,
         })
     }
 }
 
+/// Reads the icons from @reader, in the format of an `icons` or
+/// `generic-icons` file.
+///
+/// Comments and empty lines are skipped; the lines that cannot be parsed
+/// are returned as errors of kind `InvalidData`.
+pub fn read_icons<R: BufRead>(reader: R) -> impl Iterator<Item = io::Result<Icon>> {
+    lines::parse_lines(reader, |line| Icon::from_string(&line)).map(|(_, res)| res)
+}
+
 pub fn read_icons_from_file<P: AsRef<Path>>(file_name: P) -> Vec<Icon> {
     let f = match File::open(file_name) {
         Ok(v) => v,
         Err(_) => return Vec::new(),
     };
 
-    let mut res = Vec::new();
-    let file = BufReader::new(&f);
-    for line in file.lines() {
-        let line = line.unwrap();
+    let mut res = lines::collect_valid(read_icons(BufReader::new(f)));
+
+    res.sort_unstable();
+
+    res
+}
 
-        if line.is_empty() || line.starts_with('#') {
-            continue;
+/// Adds the icons from @other for the MIME types that do not have an icon
+/// in the sorted @icons; the first icon of a type in @other wins.
+pub fn add_missing_icons(icons: &mut Vec<Icon>, other: Vec<Icon>) {
+    let len = icons.len();
+    for icon in other {
+        if icons[..len].binary_search(&icon).is_err() {
+            icons.push(icon);
         }
+    }
+
+    // The stable sort keeps the icons added first ahead of the others of
+    // their type
+    icons.sort();
+    icons.dedup();
+}
+
+/// Adds the icons of a data directory less important than the ones
+/// already loaded in @icons, skipping the types whose icon was removed in
+/// @removed; the removal markers of @other are added to @removed.
+pub(crate) fn add_directory_icons(icons: &mut Vec<Icon>, removed: &mut HashSet<String>, other: Vec<Icon>) {
+    let (markers, mut other): (Vec<Icon>, Vec<Icon>) = other.into_iter().partition(|i| i.is_noicon());
+    other.retain(|i| !removed.contains(&i.mime_type));
+    removed.extend(markers.into_iter().map(|i| i.mime_type));
+    add_missing_icons(icons, other);
+}
 
-        match Icon::from_string(&line) {
-            Some(v) => res.push(v),
-            None => continue,
+/// Resolves the aliases in the MIME types of @icons with @canonical; the
+/// icons of a canonical type take precedence over the icons of its
+/// aliases.
+pub fn canonicalize_icons<F: Fn(&str) -> Option<String>>(icons: &mut Vec<Icon>, canonical: F) {
+    let mut res: Vec<Icon> = icons.iter().filter(|i| canonical(&i.mime_type).is_none()).cloned().collect();
+
+    for icon in icons.iter() {
+        if let Some(mime_type) = canonical(&icon.mime_type) {
+            let icon = Icon::new(icon.icon_name.clone(), mime_type);
+            if !res.contains(&icon) {
+                res.push(icon);
+            }
         }
     }
 
     res.sort_unstable();
-
-    res
+    *icons = res;
 }
 
 pub fn find_icon(icons: &Vec<Icon>, mime_type: &str) -> Option<String> {
@@ -118,4 +187,31 @@ mod tests {
             Icon::new("text-html", "application/rss+xml")
         );
     }
+
+    #[test]
+    fn directory_icons() {
+        let mut icons = Vec::new();
+        let mut removed = HashSet::new();
+
+        add_directory_icons(
+            &mut icons,
+            &mut removed,
+            vec![Icon::new("my-png", "image/png"), Icon::new(NOICON, "text/plain")],
+        );
+        add_directory_icons(
+            &mut icons,
+            &mut removed,
+            vec![
+                Icon::new("text-x-generic", "text/plain"),
+                Icon::new("image-png", "image/png"),
+                Icon::new("gzip", "application/gzip"),
+            ],
+        );
+
+        // The first directory wins, and its markers are not icons
+        assert_eq!(find_icon(&icons, "image/png").as_deref(), Some("my-png"));
+        assert_eq!(find_icon(&icons, "text/plain"), None);
+        assert_eq!(find_icon(&icons, "application/gzip").as_deref(), Some("gzip"));
+        assert_eq!(icons.len(), 2);
+    }
 }
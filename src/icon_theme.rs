@@ -0,0 +1,185 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use crate::SharedMimeInfo;
+
+// The extensions of the icon files, by order of preference
+const ICON_EXTENSIONS: &[&str] = &["svg", "png", "xpm"];
+
+// The theme every other theme implicitly inherits from
+const FALLBACK_THEME: &str = "hicolor";
+
+/// An icon theme, following the [Icon Theme][icon-theme] specification,
+/// to check which icons are actually provided.
+///
+/// [icon-theme]: https://specifications.freedesktop.org/icon-theme-spec/latest/
+#[derive(Clone, Debug)]
+pub struct IconTheme {
+    name: String,
+    base_dirs: Vec<PathBuf>,
+}
+
+// The keys of the `[Icon Theme]` section of an index.theme file
+#[derive(Default)]
+struct ThemeIndex {
+    directories: Vec<String>,
+    inherits: Vec<String>,
+}
+
+fn read_index<P: AsRef<Path>>(file_name: P) -> Option<ThemeIndex> {
+    let f = File::open(file_name).ok()?;
+
+    let mut res = ThemeIndex::default();
+    let mut in_theme_section = false;
+    for line in BufReader::new(f).lines() {
+        let line = match line {
+            Ok(v) => v,
+            Err(_) => break,
+        };
+        let line = line.trim();
+
+        if line.starts_with('[') {
+            in_theme_section = line == "[Icon Theme]";
+            continue;
+        }
+        if !in_theme_section {
+            continue;
+        }
+
+        let (key, value) = match line.find('=') {
+            Some(v) => (line[..v].trim(), line[v + 1..].trim()),
+            None => continue,
+        };
+        let list = || -> Vec<String> {
+            value
+                .split(',')
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+                .collect()
+        };
+        match key {
+            "Directories" | "ScaledDirectories" => res.directories.extend(list()),
+            "Inherits" => res.inherits = list(),
+            _ => {}
+        }
+    }
+
+    Some(res)
+}
+
+impl IconTheme {
+    /// Creates the icon theme @name, looked up in the standard base
+    /// directories: `$HOME/.icons`, then the `icons` sub-directory of the
+    /// XDG data directories.
+    pub fn new(name: &str) -> IconTheme {
+        let mut base_dirs = Vec::new();
+        if let Some(home) = dirs::home_dir() {
+            base_dirs.push(home.join(".icons"));
+        }
+        base_dirs.extend(SharedMimeInfo::xdg_data_dirs().iter().map(|d| d.join("icons")));
+
+        IconTheme::with_base_dirs(name, &base_dirs)
+    }
+
+    /// Creates the icon theme @name, looked up in @base_dirs.
+    pub fn with_base_dirs<P: AsRef<Path>>(name: &str, base_dirs: &[P]) -> IconTheme {
+        IconTheme {
+            name: name.to_string(),
+            base_dirs: base_dirs.iter().map(|d| d.as_ref().to_path_buf()).collect(),
+        }
+    }
+
+    /// Creates the icon theme of the current user, as set in the GTK
+    /// settings, or the `hicolor` fallback theme.
+    pub fn current() -> IconTheme {
+        let name = dirs::config_dir()
+            .and_then(|dir| File::open(dir.join("gtk-3.0/settings.ini")).ok())
+            .and_then(|f| {
+                BufReader::new(f).lines().map_while(Result::ok).find_map(|line| {
+                    let (key, value) = line.split_at(line.find('=')?);
+                    if key.trim() == "gtk-icon-theme-name" {
+                        Some(value[1..].trim().trim_matches('"').to_string())
+                    } else {
+                        None
+                    }
+                })
+            })
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| FALLBACK_THEME.to_string());
+
+        IconTheme::new(&name)
+    }
+
+    /// The name of the theme.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Checks whether the theme, the themes it inherits from, or the
+    /// `hicolor` fallback theme provide the icon @icon_name, in any size.
+    pub fn has_icon(&self, icon_name: &str) -> bool {
+        let mut visited = HashSet::new();
+        if self.theme_has_icon(&self.name, icon_name, &mut visited) {
+            return true;
+        }
+
+        self.theme_has_icon(FALLBACK_THEME, icon_name, &mut visited)
+    }
+
+    fn theme_has_icon(&self, theme: &str, icon_name: &str, visited: &mut HashSet<String>) -> bool {
+        if !visited.insert(theme.to_string()) {
+            return false;
+        }
+
+        let theme_dirs: Vec<PathBuf> = self.base_dirs.iter().map(|d| d.join(theme)).collect();
+        let index = match theme_dirs.iter().find_map(|d| read_index(d.join("index.theme"))) {
+            Some(v) => v,
+            None => return false,
+        };
+
+        for dir in &theme_dirs {
+            for sub_dir in &index.directories {
+                for ext in ICON_EXTENSIONS {
+                    if dir.join(sub_dir).join(format!("{}.{}", icon_name, ext)).is_file() {
+                        return true;
+                    }
+                }
+            }
+        }
+
+        index
+            .inherits
+            .iter()
+            .any(|parent| self.theme_has_icon(parent, icon_name, visited))
+    }
+}
+
+/// A symbolic icon name, and whether an icon theme provides it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SymbolicIcon {
+    /// The name of the icon, like `text-x-generic-symbolic`.
+    pub name: String,
+    /// Whether the icon theme provides the icon.
+    pub in_theme: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn theme_lookup() {
+        let dir = env::current_dir().unwrap().join("test_files/icons");
+
+        let theme = IconTheme::with_base_dirs("Custom", &[&dir]);
+        assert_eq!(theme.name(), "Custom");
+        assert!(theme.has_icon("text-x-generic-symbolic"));
+        assert!(theme.has_icon("image-x-generic-symbolic"));
+        assert!(theme.has_icon("image-png"));
+        assert!(!theme.has_icon("image-png-symbolic"));
+    }
+}
@@ -1,4 +1,4 @@
-#![cfg(any(unix, target_os = "redox"))]
+#![cfg(any(unix, windows, target_os = "redox"))]
 // FIXME: Remove
 #![allow(dead_code)]
 
@@ -11,26 +11,149 @@
 /// a given MIME type; and the various sub-classes of a MIME type.
 ///
 /// [xdg-mime]: https://specifications.freedesktop.org/shared-mime-info-spec/shared-mime-info-spec-latest.html
+use std::borrow::Cow;
+use std::collections::{HashSet, VecDeque};
 use std::env;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::OnceLock;
+#[cfg(feature = "metrics")]
+use std::time::{Duration, Instant};
 
 extern crate dirs;
 #[macro_use] extern crate nom;
 
+mod accept;
 mod alias;
+mod app_associations;
+mod apps;
+mod archive;
+#[cfg(feature = "async")]
+mod async_guess;
+pub mod bench;
+mod builder;
+mod cache;
+pub mod codegen;
+mod compile;
+mod context;
+#[cfg(feature = "debug-lookup")]
+mod debug_lookup;
+mod description;
+mod document;
+#[cfg(feature = "embedded-db")]
+mod embedded;
+mod executable;
+mod file_name;
+#[cfg(feature = "cdylib")]
+pub mod ffi;
 mod glob;
+mod guess;
 mod icon;
+#[cfg(feature = "icon-theme")]
+mod icon_theme;
+mod lines;
+mod loader;
+mod locale;
 mod parent;
+mod phash;
 mod magic;
+mod mail;
+mod mapped;
+mod memo;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod mime_type;
+mod package;
+mod query;
+#[cfg(windows)]
+mod registry;
+#[cfg(feature = "remote")]
+mod remote;
+#[cfg(feature = "serde")]
+mod serialize;
+mod shebang;
+mod snapshot;
+mod sniff;
+mod suffix_trie;
+mod summary;
+#[cfg(test)]
+mod test_util;
+mod thumbnailer;
+mod treemagic;
+mod types;
+mod uri;
+#[cfg(all(feature = "uti", target_os = "macos"))]
+mod uti;
+mod xattr;
+#[cfg(feature = "watch")]
+mod watch;
+
+pub use accept::Negotiation;
+pub use alias::{read_aliases, Alias};
+pub use app_associations::AppAssociations;
+#[cfg(feature = "async")]
+pub use async_guess::{AsyncFromFile, AsyncFromPath, AsyncGuessBuilder};
+pub use apps::{MimeApps, VolumeHandlers};
+pub use builder::SharedMimeInfoBuilder;
+pub use compile::{compile_package, compile_user_package};
+pub use context::LookupContext;
+pub use document::{
+    AliasesDocument, Document, DocumentEntry, GlobsDocument, IconsDocument, SubclassesDocument,
+};
+#[cfg(feature = "embedded-db")]
+pub use embedded::embedded_directory;
+pub use glob::{read_globs_v1, read_globs_v2, DotfileHandling, FilesystemProfile, Glob, GlobMatch, NameMatch};
+pub use guess::{
+    guess_with_dirs, FromFile, FromPath, FromReader, Guess, GuessBuilder, GuessPolicy, IoBudget, NoContent, WithData,
+};
+pub use icon::{read_icons, Icon};
+#[cfg(feature = "icon-theme")]
+pub use icon_theme::{IconTheme, SymbolicIcon};
+pub use loader::{DatabaseSection, LoadError, LoadWarning, LoadWarningKind, Loader, MemoryDirectory};
+pub use locale::{locale_chain, locale_chain_from_env, Format, TranslationCoverage};
+pub use magic::{ChunkedData, MagicEntry, MagicInput, MagicSummary};
+pub use mail::PartVerdict;
+pub use mapped::MappedSnapshot;
+pub use memo::{CacheBackend, LruCache};
+pub use mime_type::MimeType;
+pub use package::{
+    package_to_string, read_package_from_string, GlobDefinition, MagicDefinition, MagicMatch, MatchType,
+    MimeTypeDefinition,
+};
+pub use parent::{read_subclasses, Hierarchy, Subclass};
+pub use query::MimeQuery;
+#[cfg(windows)]
+pub use registry::registry_directory;
+#[cfg(feature = "debug-lookup")]
+pub use debug_lookup::{LookupCandidate, LookupInput, LookupRecord, LookupSink, SkipReason};
+#[cfg(feature = "metrics")]
+pub use metrics::{LookupKind, Metrics, RuleHits};
+#[cfg(feature = "remote")]
+pub use remote::RemoteDatabase;
+pub use sniff::{sniff, Sniff};
+pub use summary::TypeUsage;
+pub use treemagic::{read_treemagic, DirSnapshot, SnapshotEntry, TreeFileType, TreeMagicRule};
+pub use uri::{file_name_from_uri, percent_decode};
+#[cfg(all(feature = "uti", target_os = "macos"))]
+pub use uti::uti_mime_type;
+pub use xattr::{write_mime_xattr, XattrPolicy, MIME_XATTR};
+#[cfg(feature = "watch")]
+pub use watch::DatabaseWatcher;
 
 /// Convenience identifier for an unknown MIME type.
-pub static UNKNOWN_TYPE: &'static str = "application/octet-stream";
+pub static UNKNOWN_TYPE: &str = "application/octet-stream";
 
 /// Convenience identifier for the MIME type for an empty file.
-pub static EMPTY_TYPE: &'static str = "application/x-zerosize";
+pub static EMPTY_TYPE: &str = "application/x-zerosize";
 
 /// Convenience identifier for the MIME type for a plain text file.
-pub static TEXT_PLAIN_TYPE: &'static str = "text/plain";
+pub static TEXT_PLAIN_TYPE: &str = "text/plain";
 
 pub struct SharedMimeInfo {
     aliases: alias::AliasesList,
@@ -39,52 +162,167 @@ pub struct SharedMimeInfo {
     generic_icons: Vec<icon::Icon>,
     globs: glob::GlobMap,
     magic: Vec<magic::MagicEntry>,
+    treemagic: Vec<TreeMagicRule>,
+    types: HashSet<String>,
+    thumbnailer_types: HashSet<String>,
+    profile: FilesystemProfile,
+    // The data directories, by decreasing order of importance
+    data_dirs: Vec<PathBuf>,
+    locales: Vec<String>,
+    loaded_files: Vec<PathBuf>,
+    load_warnings: Vec<LoadWarning>,
+    source: SharedMimeInfoBuilder,
+    // The interpreters of the scripts, by name, before the default ones
+    interpreters: Vec<(String, String)>,
+    // Whether the types and their sub-classes can run code, before the
+    // built-in lists
+    executable_types: Vec<(String, bool)>,
+    // Whether the unmatched extensions give `application/x-extension-*`
+    // types
+    extension_types: bool,
+    // The backend set with set_cache_backend(), caching the types of data
+    // too
+    cache_backend: Option<Arc<dyn CacheBackend>>,
+    // The fingerprint of the magic entries and interpreters, and the
+    // number of bytes of data they look at
+    data_fingerprint: OnceLock<([u8; 8], usize)>,
+    // The types whose globs or magic were deleted by the directories
+    // loaded so far
+    noglobs: HashSet<String>,
+    nomagic: HashSet<String>,
+    // The types whose icons were removed by the directories loaded so far
+    noicons: HashSet<String>,
+    nogeneric_icons: HashSet<String>,
+    #[cfg(feature = "debug-lookup")]
+    lookup_sink: Option<Box<dyn LookupSink>>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<dyn Metrics>>,
+    #[cfg(feature = "metrics")]
+    rule_counters: Option<metrics::RuleCounters>,
+}
+
+/// The location where [`SharedMimeInfo::install_package`] installs a
+/// package.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Scope {
+    /// The data directory of the current user, `$XDG_DATA_HOME`.
+    User,
+    /// The first system data directory in `$XDG_DATA_DIRS`.
+    System,
+    /// A custom data directory.
+    Directory(PathBuf),
 }
 
 impl SharedMimeInfo {
-    fn load_directory<P: AsRef<Path>>(&mut self, directory: P) {
-        let mut mime_path = PathBuf::new();
-        mime_path.push(directory);
-        mime_path.push("mime");
-
-        let mut alias_file = mime_path.clone();
-        alias_file.push("aliases");
-        let aliases = alias::read_aliases_from_file(alias_file);
-        self.aliases.add_aliases(aliases);
-
-        let mut icons_file = mime_path.clone();
-        icons_file.push("icons");
-        let icons = icon::read_icons_from_file(icons_file);
-        self.icons.extend(icons);
-
-        icons_file = mime_path.clone();
-        icons_file.push("generic-icons");
-        let generic_icons = icon::read_icons_from_file(icons_file);
-        self.generic_icons.extend(generic_icons);
-
-        let mut subclasses_file = mime_path.clone();
-        subclasses_file.push("subclasses");
-        let subclasses = parent::read_subclasses_from_file(subclasses_file);
-        self.parents.add_subclasses(subclasses);
-
-        let mut glob_v2_file = mime_path.clone();
-        glob_v2_file.push("globs2");
-        let globs = match glob::read_globs_v2_from_file(glob_v2_file) {
-            Some(v) => v,
-            None => {
-                let mut glob_v1_file = mime_path.clone();
-                glob_v1_file.push("globs");
+    fn load_directory<P: AsRef<Path>>(&mut self, directory: P, loader: &Loader) {
+        let (section, warnings) = loader.load_directory(directory);
 
-                glob::read_globs_v1_from_file(glob_v1_file).unwrap_or(Vec::new())
-            }
-        };
+        self.thumbnailer_types
+            .extend(thumbnailer::read_thumbnailer_types(&section.directory));
+        self.data_dirs.push(section.directory.clone());
+        self.add_section(section, warnings);
+    }
 
+    // Memory directories have no data directory on disk, with the XML
+    // packages or the thumbnailers
+    fn load_memory_directory(&mut self, directory: &MemoryDirectory, loader: &Loader) {
+        let (section, warnings) = loader.load_memory_directory(directory);
+
+        self.add_section(section, warnings);
+    }
+
+    // Adds @section, less important than the ones loaded so far
+    fn add_section(&mut self, section: DatabaseSection, warnings: Vec<LoadWarning>) {
+        self.aliases.add_aliases(section.aliases);
+        // The icons of a type in this directory override the ones of the
+        // less important directories, and so do its `__NOICON__` markers
+        icon::add_directory_icons(&mut self.icons, &mut self.noicons, section.icons);
+        icon::add_directory_icons(&mut self.generic_icons, &mut self.nogeneric_icons, section.generic_icons);
+        self.parents
+            .add_subclasses_from(section.subclasses, &section.directory.join("mime/subclasses"));
+
+        // The markers of the `glob-deleteall` and `magic-deleteall`
+        // elements discard the globs and magic of their type coming from
+        // the less important directories, loaded after this one
+        let (markers, mut globs): (Vec<Glob>, Vec<Glob>) = section.globs.into_iter().partition(|g| g.is_noglobs());
+        globs.retain(|g| !self.noglobs.contains(g.mime_type()));
+        self.noglobs.extend(markers.iter().map(|g| g.mime_type().to_string()));
         self.globs.add_globs(globs);
 
-        let mut magic_file = mime_path.clone();
-        magic_file.push("magic");
-        let magic_entries = magic::read_magic_from_file(magic_file);
-        self.magic.extend(magic_entries);
+        let (markers, mut magic): (Vec<MagicEntry>, Vec<MagicEntry>) =
+            section.magic.into_iter().partition(|e| e.is_nomagic());
+        magic.retain(|e| !self.nomagic.contains(e.mime_type()));
+        self.nomagic.extend(markers.iter().map(|e| e.mime_type().to_string()));
+        self.magic.extend(magic);
+
+        self.treemagic.extend(section.treemagic);
+        self.types.extend(section.types);
+        self.loaded_files.extend(section.files);
+        self.load_warnings.extend(warnings);
+    }
+
+    // Replaces the aliases used as the MIME type of globs and magic
+    // entries, e.g. in custom packages, with the canonical types
+    fn canonicalize_types(&mut self) {
+        let aliases = &self.aliases;
+        self.globs.canonicalize_types(|mime_type| aliases.unalias_mime_type(mime_type));
+        self.parents.canonicalize_types(|mime_type| aliases.unalias_mime_type(mime_type));
+        icon::canonicalize_icons(&mut self.icons, |mime_type| aliases.unalias_mime_type(mime_type));
+        icon::canonicalize_icons(&mut self.generic_icons, |mime_type| aliases.unalias_mime_type(mime_type));
+
+        for entry in self.magic.iter_mut() {
+            if let Some(mime_type) = aliases.unalias_mime_type(entry.mime_type()) {
+                entry.set_mime_type(mime_type);
+            }
+        }
+
+        for rule in self.treemagic.iter_mut() {
+            if let Some(mime_type) = aliases.unalias_mime_type(rule.mime_type()) {
+                rule.set_mime_type(mime_type);
+            }
+        }
+
+        self.thumbnailer_types = self
+            .thumbnailer_types
+            .iter()
+            .map(|t| aliases.unalias_mime_type(t).unwrap_or_else(|| t.clone()))
+            .collect();
+    }
+
+    // Removes the subclass relationships making cycles, which can only come
+    // from broken custom packages, and reports them as load warnings
+    fn break_parent_cycles(&mut self) {
+        for (subclass, source) in self.parents.break_cycles() {
+            self.load_warnings.push(LoadWarning {
+                file: source.unwrap_or_default(),
+                line: None,
+                kind: LoadWarningKind::SubclassCycle,
+                reason: format!(
+                    "subclass cycle: ignoring {} as parent of {}",
+                    subclass.parent_type(),
+                    subclass.mime_type()
+                ),
+            });
+        }
+    }
+
+    /// Fills the gaps in this database with the contents of @other, one
+    /// kind of information at a time: e.g. only the MIME types that have
+    /// no globs here get the globs of @other, and only the ones that have
+    /// no magic here get its magic.
+    fn add_missing(&mut self, other: SharedMimeInfo) {
+        self.aliases.add_missing_aliases(other.aliases);
+        self.parents.add_missing_parents(other.parents);
+        icon::add_missing_icons(&mut self.icons, other.icons);
+        icon::add_missing_icons(&mut self.generic_icons, other.generic_icons);
+        self.globs.add_missing_globs(other.globs);
+        magic::add_missing_entries(&mut self.magic, other.magic);
+        treemagic::add_missing_rules(&mut self.treemagic, other.treemagic);
+        self.types.extend(other.types);
+        self.thumbnailer_types.extend(other.thumbnailer_types);
+        self.data_dirs.extend(other.data_dirs);
+        self.loaded_files.extend(other.loaded_files);
+        self.load_warnings.extend(other.load_warnings);
     }
 
     fn create() -> SharedMimeInfo {
@@ -95,6 +333,55 @@ impl SharedMimeInfo {
             generic_icons: Vec::new(),
             globs: glob::GlobMap::new(),
             magic: Vec::new(),
+            treemagic: Vec::new(),
+            types: HashSet::new(),
+            thumbnailer_types: HashSet::new(),
+            profile: FilesystemProfile::default(),
+            data_dirs: Vec::new(),
+            locales: Vec::new(),
+            loaded_files: Vec::new(),
+            load_warnings: Vec::new(),
+            source: SharedMimeInfoBuilder::new().system_directories(false),
+            interpreters: Vec::new(),
+            executable_types: Vec::new(),
+            noglobs: HashSet::new(),
+            nomagic: HashSet::new(),
+            extension_types: false,
+            cache_backend: None,
+            data_fingerprint: OnceLock::new(),
+            noicons: HashSet::new(),
+            nogeneric_icons: HashSet::new(),
+            #[cfg(feature = "debug-lookup")]
+            lookup_sink: None,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+            #[cfg(feature = "metrics")]
+            rule_counters: None,
+        }
+    }
+
+    /// Returns the list of data directories to search for MIME information,
+    /// following the [XDG base directories][xdg-base-dir] specification.
+    ///
+    /// [xdg-base-dir]: http://standards.freedesktop.org/basedir-spec/basedir-spec-latest.html
+    pub(crate) fn xdg_data_dirs() -> Vec<PathBuf> {
+        let mut res = Vec::new();
+
+        // The data home is skipped if it cannot be determined, e.g. if
+        // $HOME is unset
+        if let Some(data_home) = dirs::data_dir() {
+            res.push(data_home);
+        }
+
+        res.extend(SharedMimeInfo::xdg_system_data_dirs());
+
+        res
+    }
+
+    fn xdg_system_data_dirs() -> Vec<PathBuf> {
+        match env::var_os("XDG_DATA_DIRS") {
+            Some(v) => env::split_paths(&v).collect(),
+            None => vec![PathBuf::from("/usr/local/share"), PathBuf::from("/usr/share")],
         }
     }
 
@@ -103,84 +390,141 @@ impl SharedMimeInfo {
     ///
     /// [xdg-base-dir]: http://standards.freedesktop.org/basedir-spec/basedir-spec-latest.html
     pub fn new() -> SharedMimeInfo {
-        let mut db = SharedMimeInfo::create();
+        SharedMimeInfoBuilder::new().build()
+    }
 
-        let data_home = dirs::data_dir().expect("Data directory is unset");
-        db.load_directory(data_home);
+    /// Creates a new SharedMimeInfo database like [`new`](#method.new),
+    /// failing if any problem was found while loading it, like a corrupt
+    /// or missing file, instead of skipping the content causing it.
+    ///
+    /// See [`SharedMimeInfoBuilder::strict`].
+    pub fn try_new() -> Result<SharedMimeInfo, LoadError> {
+        SharedMimeInfoBuilder::new().strict(true).try_build()
+    }
 
-        let data_dirs = match env::var_os("XDG_DATA_DIRS") {
-            Some(v) => {
-                env::split_paths(&v).collect()
-            }
-            None => {
-                vec![PathBuf::from("/usr/local/share"),
-                     PathBuf::from("/usr/share")]
-            }
-        };
+    /// Returns a database of the XDG base directories shared by the whole
+    /// process, loaded on the first call, so that the components of an
+    /// application can look up MIME types from any thread without loading
+    /// their own.
+    ///
+    /// The shared database is never reloaded.
+    pub fn shared() -> &'static SharedMimeInfo {
+        static SHARED: OnceLock<SharedMimeInfo> = OnceLock::new();
 
-        for dir in data_dirs {
-            db.load_directory(dir)
-        }
+        SHARED.get_or_init(SharedMimeInfo::new)
+    }
 
-        db
+    /// Loads a database from the snapshot at @path, written by
+    /// [`save_snapshot`](#method.save_snapshot), without reading the data
+    /// directories, e.g. for the tools started many times in a row.
+    ///
+    /// An error of kind `InvalidData` is returned if the file is not a
+    /// snapshot, or was written by an incompatible version of the format.
+    ///
+    /// See [`MappedSnapshot`] to query a snapshot in place instead, sharing
+    /// it between processes.
+    pub fn load_snapshot<P: AsRef<Path>>(path: P) -> io::Result<SharedMimeInfo> {
+        snapshot::load(path.as_ref())
+    }
+
+    /// Writes a binary snapshot of the merged MIME information of this
+    /// database to @path, replacing it atomically.
+    ///
+    /// The snapshot is independent of the endianness of the machines; the
+    /// settings of the lookups, like the locales or the file system
+    /// profile, are not part of it.
+    pub fn save_snapshot<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        snapshot::save(self, path.as_ref())
     }
 
+    /// Creates a new SharedMimeInfoBuilder, to control where the MIME
+    /// information is loaded from.
+    pub fn builder() -> SharedMimeInfoBuilder {
+        SharedMimeInfoBuilder::new()
+    }
 
     /// Load all the MIME information under @directory, and create a new
     /// SharedMimeInfo for it. This method is only really useful for
     /// testing purposes.
     pub fn new_for_directory<P: AsRef<Path>>(directory: P) -> SharedMimeInfo {
-        let mut mime_path = PathBuf::new();
-        mime_path.push(directory);
-        mime_path.push("mime");
-
-        let mut alias_file = mime_path.clone();
-        alias_file.push("aliases");
-        let mut alias_list = alias::AliasesList::new();
-        let aliases = alias::read_aliases_from_file(alias_file);
-        alias_list.add_aliases(aliases);
-
-        let mut icons_file = mime_path.clone();
-        icons_file.push("icons");
-        let icons = icon::read_icons_from_file(icons_file);
-
-        icons_file = mime_path.clone();
-        icons_file.push("generic-icons");
-        let generic_icons = icon::read_icons_from_file(icons_file);
-
-        let mut subclasses_file = mime_path.clone();
-        subclasses_file.push("subclasses");
-        let mut parents_map = parent::ParentsMap::new();
-        let subclasses = parent::read_subclasses_from_file(subclasses_file);
-        parents_map.add_subclasses(subclasses);
-
-        let mut glob_v2_file = mime_path.clone();
-        glob_v2_file.push("globs2");
-        let globs = match glob::read_globs_v2_from_file(glob_v2_file) {
+        SharedMimeInfoBuilder::new()
+            .system_directories(false)
+            .directory(directory)
+            .build()
+    }
+
+    /// Reloads all the MIME information from the directories this database
+    /// was created for, e.g. after the database files have been updated.
+    pub fn reload(&mut self) {
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        let db = self.source.clone().build();
+
+        self.replace_with(db);
+        #[cfg(feature = "metrics")]
+        self.count_reload(start.elapsed());
+    }
+
+    // Replaces this database with @db, a newer version of it, keeping the
+    // settings that are not part of its source
+    #[allow(unused_mut)]
+    fn replace_with(&mut self, mut db: SharedMimeInfo) {
+        db.globs.set_cache_backend(self.globs.cache_backend());
+        db.cache_backend = self.cache_backend.take();
+        #[cfg(feature = "debug-lookup")]
+        {
+            db.lookup_sink = self.lookup_sink.take();
+        }
+        #[cfg(feature = "metrics")]
+        {
+            db.globs.set_metrics(self.metrics.clone());
+            db.metrics = self.metrics.take();
+            db.rule_counters = self.rule_counters.take();
+        }
+
+        *self = db;
+    }
+
+    /// Installs the shared-mime-info XML package at @xml_path into the
+    /// `mime/packages` directory of @scope, regenerates the database files
+    /// of that directory from all its packages, and reloads this database.
+    ///
+    /// The package is validated before being installed; an error of kind
+    /// `InvalidData` is returned if it is not a valid package.
+    pub fn install_package<P: AsRef<Path>>(&mut self, xml_path: P, scope: Scope) -> io::Result<()> {
+        let xml_path = xml_path.as_ref();
+        package::read_package_from_file(xml_path)?;
+
+        let file_name = match xml_path.file_name() {
             Some(v) => v,
-            None => {
-                let mut glob_v1_file = mime_path.clone();
-                glob_v1_file.push("globs");
+            None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid package path")),
+        };
 
-                glob::read_globs_v1_from_file(glob_v1_file).unwrap_or(Vec::new())
-            }
+        let data_dir = match scope {
+            Scope::User => dirs::data_dir(),
+            Scope::System => SharedMimeInfo::xdg_system_data_dirs().into_iter().next(),
+            Scope::Directory(v) => Some(v),
+        };
+        let mime_dir = match data_dir {
+            Some(v) => v.join("mime"),
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, "data directory is unset")),
         };
 
-        let mut glob_map = glob::GlobMap::new();
-        glob_map.add_globs(globs);
+        let packages_dir = mime_dir.join("packages");
+        fs::create_dir_all(&packages_dir)?;
+        fs::copy(xml_path, packages_dir.join(file_name))?;
 
-        let mut magic_file = mime_path.clone();
-        magic_file.push("magic");
-        let magic_entries = magic::read_magic_from_file(magic_file);
+        compile::update_mime_database(&mime_dir)?;
 
-        SharedMimeInfo {
-            aliases: alias_list,
-            parents: parents_map,
-            globs: glob_map,
-            icons: icons,
-            generic_icons: generic_icons,
-            magic: magic_entries,
-        }
+        self.reload();
+
+        Ok(())
+    }
+
+    /// Returns the problems found while loading the database, whose
+    /// content was skipped.
+    pub fn load_warnings(&self) -> &[LoadWarning] {
+        &self.load_warnings
     }
 
     /// Retrieves the MIME type aliased by @mime_type, if any.
@@ -188,173 +532,2543 @@ impl SharedMimeInfo {
         self.aliases.unalias_mime_type(mime_type)
     }
 
+    /// Retrieves the aliases of @mime_type, sorted by name.
+    pub fn aliases_of(&self, mime_type: &str) -> &[String] {
+        self.aliases.aliases_for(mime_type)
+    }
+
+    /// Retrieves all the aliases pointing at the canonical type of
+    /// @mime_type, sorted by name; unlike [`aliases_of`](#method.aliases_of),
+    /// @mime_type can itself be an alias.
+    pub fn list_aliases_for(&self, mime_type: &str) -> Vec<String> {
+        self.aliases.aliases_for(self.canonical_ref(mime_type)).to_vec()
+    }
+
+    /// Checks whether @mime_type, or the type it is an alias for, is
+    /// known to the database, even if it has no globs or magic.
+    pub fn knows_type(&self, mime_type: &str) -> bool {
+        let mime_type = self.canonical_type(mime_type);
+
+        self.types.contains(&mime_type)
+            || self.globs.count_for(&mime_type) > 0
+            || self.magic.iter().any(|m| m.mime_type() == mime_type)
+            || self.parents.lookup(mime_type.as_str()).is_some()
+    }
+
+    /// Iterates over all the MIME types known to the database, sorted by
+    /// name, e.g. to present a picker of the registered types.
+    ///
+    /// The types come from the `types` files, and from the globs, magic,
+    /// aliases and sub-classes of the directories that have none; the
+    /// aliases themselves are not listed.
+    pub fn mime_types(&self) -> impl Iterator<Item = &str> {
+        let mut types: Vec<&str> = self.types.iter().map(String::as_str).collect();
+        types.extend(self.globs.iter().map(Glob::mime_type));
+        types.extend(self.magic.iter().map(MagicEntry::mime_type));
+        types.extend(self.treemagic.iter().map(TreeMagicRule::mime_type));
+        types.extend(self.aliases.iter().map(|a| a.mime_type.as_str()));
+        types.extend(self.parents.iter().map(|(mime_type, _)| mime_type.as_str()));
+        types.sort_unstable();
+        types.dedup();
+
+        types.into_iter()
+    }
+
+    /// Returns the chain of locales the descriptions are looked up with.
+    pub fn locales(&self) -> &[String] {
+        &self.locales
+    }
+
+    /// Sets the chain of locales the descriptions are looked up with, from
+    /// the most to the least preferred, like `["pt_BR", "pt"]`.
+    pub fn set_locales(&mut self, locales: Vec<String>) {
+        self.locales = locales;
+    }
+
+    // Reads the definition of @mime_type from the per-type XML file of the
+    // most important data directory that has one
+    fn type_definition(&self, mime_type: &str) -> Option<package::MimeTypeDefinition> {
+        description::read_definition(&self.data_dirs, self.canonical_ref(mime_type))
+    }
+
+    /// Returns the comment of @mime_type, the human-readable description
+    /// like "PNG image", translated in @locale or in one of its less
+    /// specific variants, like `pt` for `pt_BR.UTF-8`; without a locale or
+    /// a translation, the untranslated comment is returned.
+    pub fn get_comment(&self, mime_type: &str, locale: Option<&str>) -> Option<String> {
+        let def = self.type_definition(mime_type)?;
+
+        description::localized_comment(&def, locale)
+    }
+
+    /// Returns the description of @mime_type, like "PNG image", translated
+    /// in the first locale of [`locales`](#method.locales) that has a
+    /// translation, if any.
+    pub fn description(&self, mime_type: &str) -> Option<String> {
+        self.description_in(mime_type, &self.locales)
+    }
+
+    /// Returns the description of @mime_type, translated in the first of
+    /// @locales that has a translation, if any.
+    pub fn description_in<S: AsRef<str>>(&self, mime_type: &str, locales: &[S]) -> Option<String> {
+        let def = self.type_definition(mime_type)?;
+
+        locales
+            .iter()
+            .find_map(|l| description::comment_for(&def, Some(l.as_ref())))
+            .or_else(|| description::comment_for(&def, None))
+    }
+
+    /// Reports how many of the known MIME types have a description
+    /// translated in @locale, or in one of its less specific variants, like
+    /// `pt` for `pt_BR`.
+    pub fn translation_coverage(&self, locale: &str) -> TranslationCoverage {
+        let variants = locale::locale_variants(locale);
+
+        let mut types: Vec<&String> = self.types.iter().collect();
+        types.sort_unstable();
+
+        let mut missing = Vec::new();
+        for mime_type in &types {
+            let translated = match self.type_definition(mime_type) {
+                Some(def) => variants.iter().any(|l| description::comment_for(&def, Some(l)).is_some()),
+                None => false,
+            };
+            if !translated {
+                missing.push(mime_type.to_string());
+            }
+        }
+
+        TranslationCoverage {
+            locale: locale.to_string(),
+            total: types.len(),
+            translated: types.len() - missing.len(),
+            missing,
+        }
+    }
+
+    /// Writes the descriptions of all the known MIME types, translated in
+    /// @locale or one of its less specific variants, to @writer in @format.
+    ///
+    /// The types without a description are skipped; in the PO catalogs,
+    /// the missing translations are left empty, and in the JSON objects,
+    /// they are replaced with the untranslated descriptions.
+    pub fn export_descriptions<W: Write>(&self, locale: &str, format: Format, writer: &mut W) -> io::Result<()> {
+        let variants = locale::locale_variants(locale);
+
+        let mut types: Vec<&String> = self.types.iter().collect();
+        types.sort_unstable();
+
+        let mut descriptions = Vec::new();
+        for mime_type in types {
+            let def = match self.type_definition(mime_type) {
+                Some(v) => v,
+                None => continue,
+            };
+            let comment = match description::comment_for(&def, None) {
+                Some(v) => v,
+                None => continue,
+            };
+            let translation = variants.iter().find_map(|l| description::comment_for(&def, Some(l)));
+
+            descriptions.push((mime_type.to_string(), comment, translation));
+        }
+
+        locale::write_descriptions(locale, &descriptions, format, writer)
+    }
+
+    /// Iterates over all the globs of the database, with the data directory
+    /// each of them was loaded from.
+    pub fn globs(&self) -> impl Iterator<Item = &Glob> {
+        self.globs.iter()
+    }
+
+    /// Retrieves the preferred file extension of @mime_type, without its
+    /// dot, like `jpg` for `image/jpeg`, if it has one.
+    pub fn preferred_extension(&self, mime_type: &str) -> Option<String> {
+        file_name::preferred_extension(self, mime_type)
+    }
+
+    /// Makes a name to save a file of @mime_type as from the name given by
+    /// the user, @file_name, e.g. in a "Save As" dialog.
+    ///
+    /// The path separators and the characters that are invalid on some
+    /// file systems are replaced, and the name is shortened to 255 bytes;
+    /// if the name does not match @mime_type, its preferred extension is
+    /// appended, when it has one.
+    pub fn file_name_for_type(&self, file_name: &str, mime_type: &str) -> String {
+        file_name::file_name_for_type(self, file_name, mime_type)
+    }
+
     /// Looks up the icons associated to a MIME type.
     ///
     /// The icons can be looked up within the current icon theme.
     pub fn lookup_icon_names(&self, mime_type: &str) -> Vec<String> {
+        let mime_type = self.canonical_ref(mime_type);
         let mut res = Vec::new();
 
-        match icon::find_icon(&self.icons, &mime_type) {
-            Some(v) => res.push(v),
-            None => {}
-        };
+        if let Some(v) = icon::find_icon(&self.icons, mime_type) {
+            res.push(v);
+        }
 
         res.push(mime_type.replace("/", "-"));
 
-        match icon::find_icon(&self.generic_icons, mime_type) {
-            Some(v) => res.push(v),
-            None => {
-                let split_type = mime_type.split('/').collect::<Vec<&str>>();
-
-                let generic = format!("{}-x-generic", split_type.get(0).unwrap());
-                res.push(generic);
-            }
-        };
+        res.push(self.generic_icon(mime_type));
 
         res
     }
 
+    /// Looks up the icon name of @mime_type: the one listed in the `icons`
+    /// files, or the MIME type with the `/` replaced by a `-`, like
+    /// `image-png`, resolving the aliases.
+    pub fn lookup_icon_name(&self, mime_type: &str) -> String {
+        let mime_type = self.canonical_ref(mime_type);
+
+        icon::find_icon(&self.icons, mime_type).unwrap_or_else(|| mime_type.replace('/', "-"))
+    }
+
+    /// Looks up the symbolic variants of the icons associated to a MIME
+    /// type, in the same order as
+    /// [`lookup_icon_names`](#method.lookup_icon_names), like
+    /// `image-png-symbolic` and `image-x-generic-symbolic`.
+    pub fn lookup_symbolic_icon_names(&self, mime_type: &str) -> Vec<String> {
+        self.lookup_icon_names(mime_type)
+            .into_iter()
+            .map(|name| format!("{}-symbolic", name))
+            .collect()
+    }
+
+    /// Looks up the symbolic variants of the icons associated to a MIME
+    /// type, along with whether @theme provides them.
+    #[cfg(feature = "icon-theme")]
+    pub fn lookup_symbolic_icons(&self, mime_type: &str, theme: &IconTheme) -> Vec<SymbolicIcon> {
+        self.lookup_symbolic_icon_names(mime_type)
+            .into_iter()
+            .map(|name| SymbolicIcon {
+                in_theme: theme.has_icon(&name),
+                name,
+            })
+            .collect()
+    }
+
     /// Looks up the generic icon associated to a MIME type.
     ///
     /// The icon can be looked up within the current icon theme.
     pub fn lookup_generic_icon_name(&self, mime_type: &str) -> Option<String> {
-        let res = match icon::find_icon(&self.generic_icons, mime_type) {
-            Some(v) => v,
-            None => {
-                let split_type = mime_type.split('/').collect::<Vec<&str>>();
+        Some(self.generic_icon(mime_type))
+    }
 
-                format!("{}-x-generic", split_type.get(0).unwrap())
-            }
-        };
+    // Returns the generic icon listed in the `generic-icons` files for
+    // @mime_type or, failing that, for its closest ancestor, or the
+    // generic icon of its media type, like `image-x-generic`
+    fn generic_icon(&self, mime_type: &str) -> String {
+        let mime_type = self.canonical_ref(mime_type);
+        if let Some(v) = icon::find_icon(&self.generic_icons, mime_type) {
+            return v;
+        }
 
-        Some(res)
+        self.ancestors_in(mime_type, Hierarchy::Explicit)
+            .iter()
+            .find_map(|ancestor| icon::find_icon(&self.generic_icons, ancestor))
+            .unwrap_or_else(|| fallback_generic_icon_name(mime_type))
     }
 
-    /// Looks up all the parent MIME types associated to @mime_type
-    pub fn get_parents(&self, mime_type: &str) -> Option<Vec<String>> {
-        let unaliased = match self.aliases.unalias_mime_type(mime_type) {
+    // Like canonical_type(), without copying the MIME type
+    fn canonical_ref<'a>(&'a self, mime_type: &'a str) -> &'a str {
+        self.aliases.unalias_mime_type_ref(mime_type).unwrap_or(mime_type)
+    }
+
+    // Resolves @mime_type if it is an alias; all the hierarchy lookups
+    // use the canonical types
+    fn canonical_type(&self, mime_type: &str) -> String {
+        match self.aliases.unalias_mime_type(mime_type) {
             Some(v) => v,
-            None => return None,
-        };
+            None => mime_type.to_string(),
+        }
+    }
 
-        let mut res = Vec::new();
-        res.push(unaliased.clone());
+    /// Looks up all the parent MIME types associated to @mime_type, after
+    /// the canonical type of @mime_type itself, or `None` if the type is
+    /// not known.
+    pub fn get_parents(&self, mime_type: &str) -> Option<Vec<String>> {
+        if !self.knows_type(mime_type) {
+            return None;
+        }
 
-        match self.parents.lookup(unaliased) {
-            Some(v) => {
-                for parent in v {
-                    res.push(parent.clone());
-                }
+        let canonical = self.canonical_type(mime_type);
+        let mut res = vec![canonical.clone()];
+
+        if let Some(v) = self.parents.lookup(canonical) {
+            for parent in v {
+                res.push(self.canonical_type(parent));
             }
-            None => {}
-        };
+        }
 
         Some(res)
     }
 
-    /// Retrieves the list of matching MIME types for the given file name,
-    /// without looking at the data inside the file.
-    pub fn get_mime_types_from_file_name(&self, file_name: &str) -> Vec<String> {
-        let matching_types = match self.globs.lookup_mime_type_for_file_name(file_name) {
-            Some(v) => v,
-            None => {
-                let mut res = Vec::new();
-                res.push(UNKNOWN_TYPE.to_string());
-                res
-            }
-        };
+    // Returns the canonical parents of the canonical @mime_type; with the
+    // full hierarchy, the implicit ones come after the explicit ones
+    fn parents_in(&self, mime_type: &str, hierarchy: Hierarchy) -> Vec<String> {
+        let mut res: Vec<String> = Vec::new();
+        self.for_each_parent(mime_type, hierarchy, |parent| res.push(parent.to_string()));
 
-        matching_types
+        res
     }
 
-    /// Retrieves the MIME type for the given data.
-    pub fn get_mime_type_for_data(&self, data: &[u8]) -> Option<String> {
-        let mime_type = match magic::lookup_data(&self.magic, data) {
-            Some(v) => v.0,
-            None => return None,
-        };
+    // Calls @f on each of the canonical parents of @mime_type in
+    // @hierarchy, without allocating
+    fn for_each_parent<'a, F: FnMut(&'a str)>(&'a self, mime_type: &str, hierarchy: Hierarchy, mut f: F) {
+        let parents = self.parents.parents_of(mime_type);
+
+        let mut count = 0;
+        let mut has_text_plain = false;
+        for (i, parent) in parents.iter().enumerate() {
+            let parent = self.canonical_ref(parent);
+            if parents[..i].iter().any(|p| self.canonical_ref(p) == parent) {
+                continue;
+            }
 
-        Some(mime_type)
-    }
-}
+            has_text_plain |= parent == TEXT_PLAIN_TYPE;
+            count += 1;
+            f(parent);
+        }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::env;
+        if hierarchy == Hierarchy::Full {
+            if mime_type.starts_with("text/") && mime_type != TEXT_PLAIN_TYPE && !has_text_plain {
+                count += 1;
+                f(TEXT_PLAIN_TYPE);
+            }
 
-    fn load_test_data() -> SharedMimeInfo {
-        let cwd = env::current_dir().unwrap().to_string_lossy().into_owned();
-        let dir = PathBuf::from(&format!("{}/test_files", cwd));
-        SharedMimeInfo::new_for_directory(dir)
+            // Only the roots of the explicit hierarchy need the edge, the
+            // other types reach it through their parents
+            if count == 0 && mime_type != UNKNOWN_TYPE && !mime_type.starts_with("inode/") {
+                f(UNKNOWN_TYPE);
+            }
+        }
     }
 
-    #[test]
-    fn load_from_directory() {
-        let cwd = env::current_dir().unwrap().to_string_lossy().into_owned();
-        let dir = PathBuf::from(&format!("{}/test_files", cwd));
-        SharedMimeInfo::new_for_directory(dir);
+    /// Returns the ancestors of @mime_type, resolving the aliases: its
+    /// parents in declaration order, then their own parents breadth-first,
+    /// each type being listed once.
+    ///
+    /// The closest ancestors always come first, so that applications can
+    /// fall back on them in order. The implicit `text/plain` and
+    /// `application/octet-stream` ancestors are included, so even the types
+    /// missing from the database have `application/octet-stream` as their
+    /// ancestor; see [`SharedMimeInfo::ancestors_in`] to only follow the
+    /// explicit relationships.
+    pub fn ancestors(&self, mime_type: &str) -> Vec<String> {
+        self.ancestors_in(mime_type, Hierarchy::Full)
     }
 
-    #[test]
-    fn load_system() {
-        SharedMimeInfo::new();
+    /// Returns the ancestors of @mime_type like
+    /// [`SharedMimeInfo::ancestors`], following the relationships of
+    /// @hierarchy.
+    pub fn ancestors_in(&self, mime_type: &str, hierarchy: Hierarchy) -> Vec<String> {
+        let mime_type = self.canonical_type(mime_type);
+
+        let mut res: Vec<String> = Vec::new();
+        let mut pending = VecDeque::new();
+        pending.push_back(mime_type.clone());
+        while let Some(current) = pending.pop_front() {
+            for parent in self.parents_in(&current, hierarchy) {
+                if parent != mime_type && !res.contains(&parent) {
+                    res.push(parent.clone());
+                    pending.push_back(parent);
+                }
+            }
+        }
+
+        res
     }
 
-    #[test]
-    fn lookup_generic_icons() {
-        let mime_db = load_test_data();
+    /// Stores the ancestors of @mime_type in @res, like
+    /// [`SharedMimeInfo::ancestors`], reusing its allocation instead of
+    /// allocating a new list.
+    pub fn ancestors_into<'a>(&'a self, mime_type: &'a str, res: &mut Vec<&'a str>) {
+        res.clear();
+
+        let mime_type = self.canonical_ref(mime_type);
+        let mut current = mime_type;
+        let mut next = 0;
+        loop {
+            self.for_each_parent(current, Hierarchy::Full, |parent| {
+                if parent != mime_type && !res.contains(&parent) {
+                    res.push(parent);
+                }
+            });
 
-        assert_eq!(
-            mime_db.lookup_generic_icon_name("application/json"),
-            Some("text-x-script".to_string())
-        );
-        assert_eq!(
-            mime_db.lookup_generic_icon_name("text/plain"),
-            Some("text-x-generic".to_string())
-        );
+            match res.get(next) {
+                Some(v) => current = v,
+                None => break,
+            }
+            next += 1;
+        }
     }
 
-    #[test]
-    fn unalias() {
-        let mime_db = load_test_data();
+    /// Decides whether @mime_type, e.g. the detected type of a resource, is
+    /// acceptable according to the HTTP `Accept` @header, following its
+    /// wildcards and quality values, or else which of its ancestors is the
+    /// closest acceptable one.
+    ///
+    /// An empty header, or one without any valid media range, accepts all
+    /// the types, like a missing header.
+    pub fn negotiate(&self, mime_type: &str, header: &str) -> Negotiation {
+        let mut ranges = accept::parse_accept(header);
+        if ranges.is_empty() {
+            return Negotiation::Acceptable { quality: 1000 };
+        }
 
-        assert_eq!(
-            mime_db.unalias_mime_type("application/ics"),
-            Some("text/calendar".to_string())
-        );
-        assert_eq!(mime_db.unalias_mime_type("text/plain"), None);
+        for range in ranges.iter_mut().filter(|r| !r.range.ends_with("/*")) {
+            range.range = self.canonical_type(&range.range);
+        }
+
+        let mime_type = self.canonical_type(&mime_type.to_ascii_lowercase());
+        match accept::quality_of(&ranges, &mime_type) {
+            Some(quality) if quality > 0 => return Negotiation::Acceptable { quality },
+            _ => {}
+        }
+
+        for ancestor in self.ancestors(&mime_type) {
+            match accept::quality_of(&ranges, &ancestor) {
+                Some(quality) if quality > 0 => {
+                    return Negotiation::Ancestor {
+                        mime_type: ancestor,
+                        quality,
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Negotiation::NotAcceptable
     }
 
-    #[test]
-    fn mime_type_for_file_name() {
-        let mime_db = load_test_data();
+    /// Checks whether @mime_type is @base, or a sub-class of it, resolving
+    /// the aliases of both; e.g. to tell whether an application handling
+    /// @base can open a file of @mime_type.
+    ///
+    /// The sub-classes are followed transitively, so `text/x-vala` is a
+    /// sub-class of `text/plain` through `text/x-csrc`. As per the
+    /// specification, all the `text/*` types are also sub-classes of
+    /// `text/plain`, and all the types outside of `inode/*` are sub-classes
+    /// of `application/octet-stream`, even the ones missing from the
+    /// database.
+    pub fn is_subclass_of(&self, mime_type: &str, base: &str) -> bool {
+        self.is_subclass_in(mime_type, base, Hierarchy::Full)
+    }
 
-        assert_eq!(
-            mime_db.get_mime_types_from_file_name("foo.txt"),
-            vec!["text/plain".to_string()]
-        );
+    /// Checks whether @mime_type is @base, or a sub-class of it, following
+    /// the relationships of @hierarchy.
+    pub fn is_subclass_in(&self, mime_type: &str, base: &str, hierarchy: Hierarchy) -> bool {
+        let mime_type = self.canonical_ref(mime_type);
+        let base = self.canonical_ref(base);
+        if mime_type == base {
+            return true;
+        }
 
-        assert_eq!(
-            mime_db.get_mime_types_from_file_name("bar.gif"),
-            vec!["image/gif".to_string()]
-        );
+        // Walk the ancestors breadth-first, until @base is found
+        let mut visited = vec![mime_type];
+        let mut next = 0;
+        while let Some(current) = visited.get(next).copied() {
+            let mut found = false;
+            self.for_each_parent(current, hierarchy, |parent| {
+                found |= parent == base;
+                if !visited.contains(&parent) {
+                    visited.push(parent);
+                }
+            });
+            if found {
+                return true;
+            }
+            next += 1;
+        }
+
+        false
     }
 
-    #[test]
-    fn mime_type_for_file_data() {
-        let mime_db = load_test_data();
+    /// Checks whether a preview of the files of type @mime_type can be
+    /// generated: either @mime_type or one of its ancestors is an image or
+    /// a video, or is handled by one of the thumbnailers installed in the
+    /// `thumbnailers` sub-directory of the data directories.
+    pub fn can_thumbnail(&self, mime_type: &str) -> bool {
+        let mime_type = self.canonical_type(mime_type);
+
+        std::iter::once(mime_type.clone())
+            .chain(self.ancestors(&mime_type))
+            .any(|t| {
+                t.starts_with("image/") || t.starts_with("video/") || self.thumbnailer_types.contains(&t)
+            })
+    }
 
-        let svg_data = include_bytes!("../test_files/files/rust-logo.svg");
-        assert_eq!(
-            mime_db.get_mime_type_for_data(svg_data),
-            Some("image/svg+xml".to_string())
-        );
+    /// Returns the number of sub-class relationships between @child and
+    /// its ancestor @ancestor, following the shortest path, or `None` if
+    /// @child is not a sub-class of @ancestor.
+    ///
+    /// The distance of a type to itself is 0, and the implicit
+    /// relationships count like the explicit ones; the smaller the
+    /// distance, the more specific a handler for @ancestor is.
+    pub fn subclass_distance(&self, child: &str, ancestor: &str) -> Option<u32> {
+        let child = self.canonical_type(child);
+        let ancestor = self.canonical_type(ancestor);
+
+        let mut visited = HashSet::new();
+        let mut pending = VecDeque::new();
+        pending.push_back((child, 0));
+        while let Some((current, distance)) = pending.pop_front() {
+            if current == ancestor {
+                return Some(distance);
+            }
 
-        let png_data = include_bytes!("../test_files/files/rust-logo.png");
-        assert_eq!(
-            mime_db.get_mime_type_for_data(png_data),
+            if !visited.insert(current.clone()) {
+                continue;
+            }
+
+            for parent in self.parents_in(&current, Hierarchy::Full) {
+                pending.push_back((parent, distance + 1));
+            }
+        }
+
+        None
+    }
+
+    /// Returns the nearest type that both @a and @b are, or are sub-classes
+    /// of, resolving the aliases, or `None` if they have no common ancestor,
+    /// e.g. for `inode/*` types.
+    ///
+    /// The nearest ancestor is the one with the shortest total distance
+    /// to both types; ties are broken by the order of
+    /// [`SharedMimeInfo::ancestors`] for @a.
+    pub fn common_ancestor(&self, a: &str, b: &str) -> Option<String> {
+        let mut candidates = vec![self.canonical_type(a)];
+        candidates.extend(self.ancestors(a));
+
+        let mut res: Option<(u32, String)> = None;
+        for candidate in candidates {
+            let distance = match (
+                self.subclass_distance(a, &candidate),
+                self.subclass_distance(b, &candidate),
+            ) {
+                (Some(da), Some(db)) => da + db,
+                _ => continue,
+            };
+
+            match res {
+                Some((best, _)) if best <= distance => {}
+                _ => res = Some((distance, candidate)),
+            }
+        }
+
+        res.map(|(_, mime_type)| mime_type)
+    }
+
+    /// Retrieves the list of matching MIME types for the given file name,
+    /// without looking at the data inside the file.
+    pub fn get_mime_types_from_file_name(&self, file_name: &str) -> Vec<String> {
+        #[cfg(feature = "debug-lookup")]
+        self.record_file_name_lookup(file_name);
+        #[cfg(feature = "metrics")]
+        self.count_file_name_lookup(file_name);
+
+        match self.globs.lookup_mime_type_for_file_name(file_name, self.profile) {
+            Some(v) => v,
+            None => vec![self.fallback_type(Some(file_name)).map_or_else(|| UNKNOWN_TYPE.to_string(), Cow::into_owned)],
+        }
+    }
+
+    /// Looks up the MIME type of the given file name, without looking at
+    /// the data inside the file, telling apart the case where the best
+    /// matches have different types.
+    pub fn lookup_file_name(&self, file_name: &str) -> GlobMatch {
+        #[cfg(feature = "debug-lookup")]
+        self.record_file_name_lookup(file_name);
+        #[cfg(feature = "metrics")]
+        self.count_file_name_lookup(file_name);
+
+        self.globs.lookup_best_match(file_name, self.profile)
+    }
+
+    /// Like [`lookup_file_name`](#method.lookup_file_name), comparing
+    /// @file_name to the globs according to @profile instead of the
+    /// profile of the database, e.g. for a file on a network mount.
+    pub fn lookup_file_name_with_profile(&self, file_name: &str, profile: FilesystemProfile) -> GlobMatch {
+        #[cfg(feature = "debug-lookup")]
+        self.record_file_name_lookup(file_name);
+        #[cfg(feature = "metrics")]
+        self.count_file_name_lookup(file_name);
+
+        self.globs.lookup_best_match(file_name, profile)
+    }
+
+    /// Looks up the MIME type of the best glob matching @file_name, like
+    /// [`get_mime_types_from_file_name`](#method.get_mime_types_from_file_name),
+    /// using the buffers of @ctx instead of allocating new ones.
+    ///
+    /// Only the types made up for the file names matching no glob, like
+    /// the `application/x-extension-*` ones, are owned.
+    pub fn lookup_file_name_with(&self, file_name: &str, ctx: &mut LookupContext) -> Option<Cow<'_, str>> {
+        #[cfg(feature = "debug-lookup")]
+        self.record_file_name_lookup(file_name);
+        #[cfg(feature = "metrics")]
+        self.count_file_name_lookup(file_name);
+
+        match self.globs.best_match_with(file_name, self.profile, ctx) {
+            Some((mime_type, _)) => Some(Cow::Borrowed(mime_type)),
+            None => self.fallback_type(Some(file_name)),
+        }
+    }
+
+    /// Stores the MIME types of all the globs matching @file_name in @res,
+    /// like [`get_mime_types_from_file_name`](#method.get_mime_types_from_file_name),
+    /// using the buffers of @ctx and reusing the allocation of @res.
+    ///
+    /// Only the types made up for the file names matching no glob, like
+    /// the `application/x-extension-*` ones, are owned.
+    pub fn get_mime_types_from_file_name_into<'a>(
+        &'a self,
+        file_name: &str,
+        ctx: &mut LookupContext,
+        res: &mut Vec<Cow<'a, str>>,
+    ) {
+        #[cfg(feature = "debug-lookup")]
+        self.record_file_name_lookup(file_name);
+        #[cfg(feature = "metrics")]
+        self.count_file_name_lookup(file_name);
+
+        self.globs.matching_types_into(file_name, self.profile, ctx, res);
+        if res.is_empty() {
+            res.push(self.fallback_type(Some(file_name)).unwrap_or(Cow::Borrowed(UNKNOWN_TYPE)));
+        }
+    }
+
+    /// Guesses the MIME type of the file at @path like
+    /// [`guess_mime_type`](#method.guess_mime_type), reading at most
+    /// @max_read bytes of the file into the buffers of @ctx instead of
+    /// allocating new ones.
+    ///
+    /// There is no timeout on the reads; see
+    /// [`guess_mime_type_for_path`](#method.guess_mime_type_for_path) for
+    /// the slow file systems.
+    pub fn guess_mime_type_for_path_with<P: AsRef<Path>>(
+        &self,
+        path: P,
+        max_read: usize,
+        ctx: &mut LookupContext,
+    ) -> &str {
+        let path = path.as_ref();
+
+        #[cfg(feature = "metrics")]
+        self.count_file_name_lookup(path.file_name().and_then(|n| n.to_str()).unwrap_or_default());
+        let best = match path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => self.globs.best_match_with(name, self.profile, ctx),
+            None => {
+                ctx.candidates.clear();
+                None
+            }
+        };
+        if let Some((mime_type, false)) = best {
+            return mime_type;
+        }
+        let fallback = best.map(|v| v.0).unwrap_or(UNKNOWN_TYPE);
+
+        match guess::read_head_into(path, max_read, &mut ctx.sniff) {
+            Ok(None) => {}
+            Ok(Some(mime_type)) => return mime_type,
+            Err(_) => return fallback,
+        }
+        #[cfg(feature = "metrics")]
+        self.count_sniffed_bytes(ctx.sniff.len());
+
+        if ctx.sniff.is_empty() {
+            return EMPTY_TYPE;
+        }
+
+        #[cfg(feature = "debug-lookup")]
+        self.record_data_lookup(&ctx.sniff[..]);
+        #[cfg(feature = "metrics")]
+        self.count_data_lookup(&ctx.sniff[..]);
+
+        match self.data_type(&ctx.sniff[..]) {
+            Some(mime_type) if best.is_none() || self.globs.is_best_candidate(ctx, mime_type) => mime_type,
+            _ => fallback,
+        }
+    }
+
+    /// Counts the files of the directory tree at @root by MIME type, with
+    /// their total size, from the largest to the smallest total, e.g. for
+    /// disk usage analyzers.
+    ///
+    /// The types are guessed like
+    /// [`guess_mime_type_for_path_with`](#method.guess_mime_type_for_path_with)
+    /// does; the symbolic links are counted as `inode/symlink`, without
+    /// being followed, and the entries that cannot be read are skipped.
+    /// An error is only returned if @root itself cannot be read.
+    pub fn summarize_tree<P: AsRef<Path>>(&self, root: P) -> io::Result<Vec<TypeUsage>> {
+        summary::summarize_tree(self, root.as_ref())
+    }
+
+    /// Returns all the globs matching @file_name, after merging the data
+    /// directories, from the best match to the worst one: by decreasing
+    /// weight, then by decreasing pattern length.
+    ///
+    /// Unlike [`get_mime_types_from_file_name`](#method.get_mime_types_from_file_name),
+    /// the weight and pattern of each glob are kept, e.g. to tell apart
+    /// a weak match.
+    pub fn matching_globs_for_name(&self, file_name: &str) -> Vec<&Glob> {
+        #[cfg(feature = "debug-lookup")]
+        self.record_file_name_lookup(file_name);
+        #[cfg(feature = "metrics")]
+        self.count_file_name_lookup(file_name);
+
+        self.globs.matching_globs(file_name, self.profile)
+    }
+
+    /// Returns the magic entry matching @data, after merging the data
+    /// directories, if any, e.g. to report the priority and location of
+    /// the rule deciding a type.
+    pub fn matching_magic_for_data(&self, data: &[u8]) -> Option<&MagicEntry> {
+        #[cfg(feature = "debug-lookup")]
+        self.record_data_lookup(data);
+        #[cfg(feature = "metrics")]
+        self.count_data_lookup(data);
+
+        magic::matching_entry(&self.magic, data)
+    }
+
+    /// Returns all the magic entries matching @data, from the highest
+    /// priority to the lowest one, e.g. to report the ambiguous files
+    /// matching several types, like polyglot files.
+    ///
+    /// Unlike [`get_mime_type_for_data`](#method.get_mime_type_for_data),
+    /// the entries matching after the best one are kept.
+    pub fn matching_magic_entries_for_data(&self, data: &[u8]) -> Vec<&MagicEntry> {
+        #[cfg(feature = "debug-lookup")]
+        self.record_data_lookup(data);
+        #[cfg(feature = "metrics")]
+        self.count_data_lookup(data);
+
+        magic::matching_entries(&self.magic, data).collect()
+    }
+
+    /// Returns the MIME types of all the globs matching @file_name, with
+    /// the weight and specificity of each match, from the best match to the
+    /// worst one, e.g. for file indexers breaking the ties themselves.
+    pub fn lookup_file_name_matches(&self, file_name: &str) -> Vec<NameMatch> {
+        #[cfg(feature = "debug-lookup")]
+        self.record_file_name_lookup(file_name);
+        #[cfg(feature = "metrics")]
+        self.count_file_name_lookup(file_name);
+
+        self.globs.lookup_name_matches(file_name, self.profile)
+    }
+
+    /// Sets the profile of the file system the looked up file names come
+    /// from.
+    pub fn set_filesystem_profile(&mut self, profile: FilesystemProfile) {
+        self.profile = profile;
+    }
+
+    /// Sets how the leading dot of hidden files is treated when matching
+    /// the suffix globs.
+    pub fn set_dotfile_handling(&mut self, dotfiles: DotfileHandling) {
+        self.globs.set_dotfile_handling(dotfiles);
+    }
+
+    /// Returns aggregate information about the magic entries of the
+    /// database, e.g. to choose how much data to read from files.
+    pub fn magic_summary(&self) -> MagicSummary {
+        MagicSummary::new(&self.magic)
+    }
+
+    /// Retrieves the MIME type for the given data.
+    pub fn get_mime_type_for_data(&self, data: &[u8]) -> Option<String> {
+        self.get_mime_type_for_input(data)
+    }
+
+    /// Retrieves the MIME type for the given data, which can be made of
+    /// several chunks, like [`ChunkedData`].
+    ///
+    /// The scripts the magic rules miss are recognized from the interpreter
+    /// of their `#!` line, like `#!/usr/bin/env python3`; see
+    /// [`SharedMimeInfoBuilder::interpreter`].
+    pub fn get_mime_type_for_input<D: MagicInput + ?Sized>(&self, data: &D) -> Option<String> {
+        #[cfg(feature = "debug-lookup")]
+        self.record_data_lookup(data);
+        #[cfg(feature = "metrics")]
+        self.count_data_lookup(data);
+
+        self.data_type(data).map(str::to_string)
+    }
+
+    // Returns the type of @data from its magic or its `#!` line, through
+    // the backend set with set_cache_backend(), if any
+    fn data_type<D: MagicInput + ?Sized>(&self, data: &D) -> Option<&str> {
+        let backend = match &self.cache_backend {
+            Some(v) => v,
+            None => return self.uncached_data_type(data),
+        };
+
+        // The bytes beyond the extent cannot change the type
+        let (fingerprint, extent) = self.data_fingerprint.get_or_init(|| {
+            let mut fingerprint = memo::Fingerprint::new();
+            magic::write_fingerprint(&self.magic, &mut fingerprint);
+            for (name, mime_type) in &self.interpreters {
+                fingerprint.write_str(name);
+                fingerprint.write_str(mime_type);
+            }
+            (fingerprint.finish(), self.max_magic_extent().max(shebang::MAX_LINE_LEN))
+        });
+        let len = data.len().min(*extent);
+        let mut key = Vec::with_capacity(fingerprint.len() + 1 + len);
+        key.extend_from_slice(fingerprint);
+        key.push(b'm');
+        match data.as_slice() {
+            Some(v) => key.extend_from_slice(&v[..len]),
+            None => key.extend((0..len).map(|i| data.byte_at(i))),
+        }
+
+        // The values of a shared backend may be broken, and are only used
+        // when they are types of the database
+        let mut value = Vec::new();
+        if backend.get(&key, &mut value) {
+            match str::from_utf8(&value) {
+                Ok("") => return None,
+                Ok(v) => {
+                    if let Some(mime_type) = self.data_type_ref(v) {
+                        return Some(mime_type);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let res = self.uncached_data_type(data);
+        backend.insert(&key, res.unwrap_or("").as_bytes());
+
+        res
+    }
+
+    // Returns the type of the magic entries or interpreters equal to
+    // @mime_type, which the lookups of data can return
+    fn data_type_ref(&self, mime_type: &str) -> Option<&str> {
+        let magic_types = self.magic.iter().map(|e| e.mime_type());
+        let interpreter_types = self.interpreters.iter().map(|(_, t)| t.as_str());
+
+        magic_types.chain(interpreter_types).find(|t| *t == mime_type)
+    }
+
+    fn uncached_data_type<D: MagicInput + ?Sized>(&self, data: &D) -> Option<&str> {
+        magic::lookup_data_type(&self.magic, data).or_else(|| shebang::script_type(self, data))
+    }
+
+    /// Returns the number of bytes at the beginning of a file beyond which
+    /// the magic entries cannot give a different type, i.e. how much data
+    /// to read for a reliable lookup.
+    pub fn max_magic_extent(&self) -> usize {
+        self.magic.iter().map(|e| e.extent()).max().unwrap_or(0)
+    }
+
+    /// Returns the magic entries of the database, after merging the data
+    /// directories.
+    pub fn magic_entries(&self) -> &[MagicEntry] {
+        &self.magic
+    }
+
+    /// Retrieves the highest priority of the magic entries of @mime_type,
+    /// resolving its aliases, if it has any entry.
+    pub fn magic_priority(&self, mime_type: &str) -> Option<u32> {
+        let mime_type = self.canonical_ref(mime_type);
+
+        self.magic
+            .iter()
+            .filter(|e| e.mime_type() == mime_type)
+            .map(|e| e.priority())
+            .max()
+    }
+
+    /// Guesses the MIME type of a file from its @file_name and the @data at
+    /// its beginning, either of which can be omitted.
+    ///
+    /// The file name is used when its best match is unambiguous; otherwise
+    /// the data decides between the types matching the file name, if any.
+    pub fn guess_mime_type(&self, file_name: Option<&str>, data: Option<&[u8]>) -> String {
+        let mime_type = self.guess_known_type(file_name, data);
+
+        self.or_fallback_type(mime_type, file_name)
+    }
+
+    // Like guess_mime_type(), without making up a type from the extension
+    // of @file_name
+    pub(crate) fn guess_known_type(&self, file_name: Option<&str>, data: Option<&[u8]>) -> String {
+        #[cfg(feature = "metrics")]
+        if let Some(name) = file_name {
+            self.count_file_name_lookup(name);
+        }
+        let name_match = file_name.map(|name| self.globs.lookup_best_match(name, self.profile));
+
+        query::guess_from(name_match, data, |data| self.get_mime_type_for_data(data))
+    }
+
+    // Replaces the unknown @mime_type with the fallback type of @file_name
+    pub(crate) fn or_fallback_type(&self, mime_type: String, file_name: Option<&str>) -> String {
+        if mime_type != UNKNOWN_TYPE {
+            return mime_type;
+        }
+
+        match self.fallback_type(file_name) {
+            Some(v) => v.into_owned(),
+            None => mime_type,
+        }
+    }
+
+    // Returns the type of the Uniform Type Identifier of @file_name on
+    // macOS, or the `application/x-extension-*` type of its extension when
+    // the builder asked for it, for the file names matching no glob
+    fn fallback_type(&self, file_name: Option<&str>) -> Option<Cow<'_, str>> {
+        let file_name = file_name?;
+        let mut mime_type = None;
+
+        #[cfg(all(feature = "uti", target_os = "macos"))]
+        if let Some(v) = uti::file_name_type(file_name) {
+            mime_type = Some(self.unalias_mime_type(&v).unwrap_or(v));
+        }
+
+        if mime_type.is_none() && self.extension_types {
+            mime_type = glob::extension_type(file_name);
+        }
+
+        mime_type.map(Cow::Owned)
+    }
+
+    /// Checks whether @mime_type is an archive, like `application/zip`, or
+    /// a compressed stream, like `application/gzip`, including the formats
+    /// stored in one of them, like the OpenDocument files in a zip archive.
+    pub fn is_archive(&self, mime_type: &str) -> bool {
+        archive::is_archive(self, mime_type)
+    }
+
+    /// Checks whether a file of @mime_type can run code when opened, like
+    /// the programs, the scripts, the desktop launchers and the software
+    /// packages, e.g. to warn before opening a download.
+    ///
+    /// The types holding data that are sub-classes of an executable type,
+    /// like JSON, a sub-class of JavaScript, are not executable. The lists
+    /// can be overridden with
+    /// [`SharedMimeInfoBuilder::executable_type`].
+    pub fn is_potentially_executable(&self, mime_type: &str) -> bool {
+        executable::is_potentially_executable(self, mime_type)
+    }
+
+    /// Retrieves the type of the stream compressed in @mime_type, like the
+    /// `application/x-tar` of `application/x-compressed-tar`, from its
+    /// `+gzip` or `+zstd` suffix or from the globs of its compressor.
+    pub fn inner_type_of_compressed(&self, mime_type: &str) -> Option<String> {
+        archive::inner_type_of_compressed(self, mime_type)
+    }
+
+    /// Guesses the MIME type of the member @member_name of an archive of
+    /// @archive_mime, like a zip or tar file, from its path in the archive
+    /// and @member_prefix, the beginning of its contents, without writing
+    /// it to a file.
+    ///
+    /// The members whose path ends with a `/` are directories; the type of
+    /// the nameless stream of a compressed container, like the tar inside
+    /// a compressed tar, follows from the type of the container.
+    pub fn guess_for_archive_member(&self, archive_mime: &str, member_name: &str, member_prefix: &[u8]) -> Guess {
+        archive::guess_member(self, archive_mime, member_name, member_prefix)
+    }
+
+    /// Classifies a MIME part of an email, for mail clients: its @declared
+    /// `Content-Type` is resolved through the aliases, and cross-checked
+    /// with the type of its @file_name parameter and the magic rules on
+    /// @body_prefix, the beginning of its decoded body, to flag the
+    /// spoofed attachments.
+    pub fn classify_mime_part(
+        &self,
+        declared: Option<&str>,
+        file_name: Option<&str>,
+        body_prefix: &[u8],
+    ) -> PartVerdict {
+        mail::classify(self, declared, file_name, body_prefix)
+    }
+
+    /// Guesses the MIME type of the file at @uri from its name, e.g. for
+    /// the URI lists of drag-and-drop operations; the file name is
+    /// extracted with [`file_name_from_uri`].
+    pub fn guess_for_uri(&self, uri: &str) -> String {
+        let file_name = file_name_from_uri(uri);
+
+        self.guess_mime_type(file_name.as_deref(), None)
+    }
+
+    /// Guesses the MIME type of the file at @path, from its name and, when
+    /// the name is not enough, from its contents, reading no more than
+    /// allowed by @budget.
+    ///
+    /// If the contents cannot be read within the budget, the guess only
+    /// uses the file name, and is marked as uncertain.
+    pub fn guess_mime_type_for_path<P: AsRef<Path>>(&self, path: P, budget: &IoBudget) -> Guess {
+        self.guess().path(path.as_ref()).limits(*budget).guess()
+    }
+
+    /// Starts guessing the MIME type of a file, from any combination of
+    /// its name, contents and metadata.
+    pub fn guess(&self) -> GuessBuilder<'_> {
+        GuessBuilder::new(self)
+    }
+
+    /// Starts guessing the MIME type of a file like [`guess`](#method.guess),
+    /// reading its contents with the asynchronous I/O of tokio, so that the
+    /// executor is not blocked.
+    #[cfg(feature = "async")]
+    pub fn guess_mime_type_async(&self) -> AsyncGuessBuilder<'_> {
+        AsyncGuessBuilder::new(self)
+    }
+
+    /// Guesses the `x-content/*` types of the directory tree mounted at
+    /// @root, e.g. a removable medium, from the treemagic rules.
+    ///
+    /// Like `g_content_type_guess_for_tree()`, all the matching types are
+    /// returned, by decreasing priority; a tree can match several types,
+    /// e.g. a music player with a camera.
+    pub fn guess_content_type_for_mount<P: AsRef<Path>>(&self, root: P) -> Vec<String> {
+        let root = root.as_ref();
+
+        let budget = IoBudget::new();
+        let snapshot = treemagic::snapshot_for_rules(root, &self.treemagic, |path| {
+            self.guess_mime_type_for_path(path, &budget).mime_type().to_string()
+        });
+
+        let mut rules: Vec<&TreeMagicRule> = self.treemagic.iter().filter(|r| r.matches(&snapshot)).collect();
+        rules.sort_by_key(|r| std::cmp::Reverse(r.priority()));
+
+        let mut res: Vec<String> = Vec::new();
+        for rule in rules {
+            if !res.iter().any(|t| t == rule.mime_type()) {
+                res.push(rule.mime_type().to_string());
+            }
+        }
+
+        res
+    }
+
+    /// Guesses the `x-content/*` type of the directory tree at @root, like
+    /// a DVD video or a photo CD, from the treemagic rules: returns the
+    /// matching type with the highest priority, if any.
+    ///
+    /// See [`SharedMimeInfo::guess_content_type_for_mount`] for all the
+    /// matching types.
+    pub fn guess_mime_type_for_tree(&self, root: &Path) -> Option<String> {
+        self.guess_content_type_for_mount(root).into_iter().next()
+    }
+
+    /// Returns the `x-content/*` types of the volume mounted at @root, as
+    /// guessed by [`SharedMimeInfo::guess_content_type_for_mount`], with
+    /// the applications of @apps handling each of them.
+    pub fn handlers_for_volume<P: AsRef<Path>>(&self, root: P, apps: &MimeApps) -> Vec<VolumeHandlers> {
+        self.guess_content_type_for_mount(root)
+            .into_iter()
+            .map(|content_type| VolumeHandlers {
+                default_application: apps.default_application(&content_type).map(|a| a.to_string()),
+                applications: apps.registered_applications(&content_type).to_vec(),
+                content_type,
+            })
+            .collect()
+    }
+
+    // Returns the canonical @mime_type and its ancestors, each followed by
+    // its aliases, as the associations can name any of them
+    fn association_types(&self, mime_type: &str) -> Vec<String> {
+        let mime_type = self.canonical_type(mime_type);
+
+        let mut res = Vec::new();
+        for t in std::iter::once(mime_type.clone()).chain(self.ancestors(&mime_type)) {
+            // The applications of application/octet-stream, like hex
+            // editors, are not a useful default for the other types
+            if t == UNKNOWN_TYPE && mime_type != UNKNOWN_TYPE {
+                continue;
+            }
+
+            let aliases = self.aliases_of(&t).to_vec();
+            res.push(t);
+            res.extend(aliases);
+        }
+
+        res
+    }
+
+    /// Returns the default application of @apps for @mime_type, as the ID
+    /// of its desktop file, or the default application of its closest
+    /// ancestor, like `text/plain` for a C source file.
+    pub fn default_application_for<'a>(&self, mime_type: &str, apps: &'a MimeApps) -> Option<&'a str> {
+        self.association_types(mime_type)
+            .iter()
+            .find_map(|t| apps.default_application(t))
+    }
+
+    /// Returns the applications of @apps that can open a file of
+    /// @mime_type, as the IDs of their desktop files, by decreasing order
+    /// of preference: the default application, the ones registered for
+    /// the type, then the ones registered for its ancestors.
+    pub fn applications_for<'a>(&self, mime_type: &str, apps: &'a MimeApps) -> Vec<&'a str> {
+        let types = self.association_types(mime_type);
+
+        let mut res: Vec<&str> = Vec::new();
+        let registered = types.iter().flat_map(|t| apps.registered_applications(t));
+        for app in self.default_application_for(mime_type, apps).into_iter().chain(registered.map(|a| a.as_str())) {
+            if !res.contains(&app) {
+                res.push(app);
+            }
+        }
+
+        res
+    }
+
+    /// Sets the @backend storing the results memoized by the lookups,
+    /// replacing the default in-memory [`LruCache`], e.g. to share them
+    /// between the servers using the database files.
+    ///
+    /// Unlike the default cache, the backend also stores the types of the
+    /// data looked up, keyed by the beginning of the data, up to the
+    /// [`max_magic_extent`](#method.max_magic_extent). The keys start with
+    /// a fingerprint of the database files, so the backend is never
+    /// cleared: it is kept across the reloads, and can be shared by the
+    /// databases of other versions of the files.
+    pub fn set_cache_backend(&mut self, backend: Arc<dyn CacheBackend>) {
+        self.globs.set_cache_backend(backend.clone());
+        self.cache_backend = Some(backend);
+    }
+
+    /// Sets the sink receiving a record of the decisions taken by each
+    /// lookup, replacing any previously set sink.
+    #[cfg(feature = "debug-lookup")]
+    pub fn set_lookup_sink(&mut self, sink: Box<dyn LookupSink>) {
+        self.lookup_sink = Some(sink);
+    }
+
+    /// Sets the hooks receiving the counters and timings of the lookups and
+    /// reloads, replacing any previously set hooks.
+    #[cfg(feature = "metrics")]
+    pub fn set_metrics(&mut self, metrics: Arc<dyn Metrics>) {
+        self.globs.set_metrics(Some(metrics.clone()));
+        self.metrics = Some(metrics);
+    }
+
+    /// Starts counting how many lookups each glob and magic entry decides,
+    /// or stops counting and drops the counters if @enabled is `false`;
+    /// see [`hot_rules`](#method.hot_rules).
+    ///
+    /// Counting matches the input again to find the deciding rule, so it
+    /// slows down the lookups; the counters are kept across reloads.
+    #[cfg(feature = "metrics")]
+    pub fn set_rule_counting(&mut self, enabled: bool) {
+        match (enabled, &self.rule_counters) {
+            (true, None) => self.rule_counters = Some(metrics::RuleCounters::default()),
+            (false, _) => self.rule_counters = None,
+            (true, Some(_)) => {}
+        }
+    }
+
+    /// Returns the @count rules that decided the most lookups since
+    /// [`set_rule_counting`](#method.set_rule_counting) was called, from
+    /// the most decisive one, e.g. to tune the caches or the priorities
+    /// of the database.
+    #[cfg(feature = "metrics")]
+    pub fn hot_rules(&self, count: usize) -> Vec<RuleHits> {
+        match &self.rule_counters {
+            Some(counters) => counters.top(count),
+            None => Vec::new(),
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    fn count_file_name_lookup(&self, file_name: &str) {
+        if let Some(metrics) = &self.metrics {
+            metrics.lookup(LookupKind::FileName);
+        }
+
+        if let Some(counters) = &self.rule_counters {
+            if let Some(glob) = self.globs.matching_globs(file_name, self.profile).first() {
+                counters.count(LookupKind::FileName, glob.mime_type(), glob.to_string(), None);
+            }
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    fn count_data_lookup<D: MagicInput + ?Sized>(&self, data: &D) {
+        if let Some(metrics) = &self.metrics {
+            metrics.lookup(LookupKind::Data);
+        }
+
+        if let Some(counters) = &self.rule_counters {
+            if let Some(entry) = magic::matching_entry(&self.magic, data) {
+                let rule = format!("[{}:{}]", entry.priority(), entry.mime_type());
+                counters.count(LookupKind::Data, entry.mime_type(), rule, Some(entry.location()));
+            }
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    fn count_sniffed_bytes(&self, bytes: usize) {
+        if let Some(metrics) = &self.metrics {
+            metrics.sniffed_bytes(bytes);
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    fn count_reload(&self, duration: Duration) {
+        if let Some(metrics) = &self.metrics {
+            metrics.reload(duration);
+        }
+    }
+
+    #[cfg(feature = "debug-lookup")]
+    fn record_file_name_lookup(&self, file_name: &str) {
+        let sink = match &self.lookup_sink {
+            Some(v) => v,
+            None => return,
+        };
+
+        let to_candidate = |g: &Glob| LookupCandidate {
+            mime_type: g.mime_type().to_string(),
+            rule: g.to_string(),
+            priority: g.weight(),
+            location: None,
+        };
+
+        let matching = self.globs.matching_globs(file_name, self.profile);
+        let mut skipped = Vec::new();
+        if let Some(best) = matching.first() {
+            for glob in &matching {
+                if glob.weight() < best.weight() {
+                    skipped.push((to_candidate(glob), SkipReason::LowerWeight));
+                } else if glob.pattern_len() < best.pattern_len() {
+                    skipped.push((to_candidate(glob), SkipReason::ShorterPattern));
+                }
+            }
+        }
+        if self.profile == FilesystemProfile::CaseSensitive {
+            for glob in self.globs.matching_globs(file_name, FilesystemProfile::CaseInsensitive) {
+                if !matching.contains(&glob) {
+                    skipped.push((to_candidate(glob), SkipReason::CaseMismatch));
+                }
+            }
+        }
+
+        let candidates: Vec<LookupCandidate> = matching.iter().map(|g| to_candidate(g)).collect();
+        sink.record(&LookupRecord {
+            input: LookupInput::FileName(file_name.to_string()),
+            winner: candidates.first().cloned(),
+            candidates,
+            skipped,
+        });
+    }
+
+    #[cfg(feature = "debug-lookup")]
+    fn record_data_lookup<D: MagicInput + ?Sized>(&self, data: &D) {
+        let sink = match &self.lookup_sink {
+            Some(v) => v,
+            None => return,
+        };
+
+        let to_candidate = |e: &magic::MagicEntry| LookupCandidate {
+            mime_type: e.mime_type().to_string(),
+            rule: format!("[{}:{}]", e.priority(), e.mime_type()),
+            priority: e.priority() as i32,
+            location: Some(e.location()),
+        };
+
+        let (winner, skipped) = magic::explain_data(&self.magic, data);
+        let winner = winner.map(to_candidate);
+
+        sink.record(&LookupRecord {
+            input: LookupInput::Data(data.len()),
+            candidates: winner.iter().cloned().collect(),
+            winner,
+            skipped: skipped
+                .into_iter()
+                .map(|e| (to_candidate(e), SkipReason::NoMatch))
+                .collect(),
+        });
+    }
+
+    /// Writes the subclass hierarchy under @root into @writer, as an
+    /// indented tree listing the aliases and number of globs of each type.
+    pub fn print_tree<W: Write>(&self, root: &str, writer: &mut W) -> io::Result<()> {
+        let root = self.canonical_type(root);
+
+        let mut path = Vec::new();
+        self.print_subtree(&root, &mut path, writer)
+    }
+
+    fn print_subtree<'a, W: Write>(
+        &'a self,
+        mime_type: &'a str,
+        path: &mut Vec<&'a str>,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        write!(writer, "{}{}", "  ".repeat(path.len()), mime_type)?;
+
+        let aliases = self.aliases.aliases_for(mime_type);
+        if !aliases.is_empty() {
+            write!(writer, " [aliases: {}]", aliases.join(", "))?;
+        }
+
+        match self.globs.count_for(mime_type) {
+            0 => {}
+            1 => write!(writer, " (1 glob)")?,
+            n => write!(writer, " ({} globs)", n)?,
+        }
+
+        // Do not descend into cycles in the hierarchy
+        if path.contains(&mime_type) {
+            return writeln!(writer, " (cycle)");
+        }
+
+        writeln!(writer)?;
+
+        path.push(mime_type);
+        for child in self.parents.children_of(mime_type) {
+            self.print_subtree(child, path, writer)?;
+        }
+        path.pop();
+
+        Ok(())
+    }
+
+    /// Writes the merged state of the database into @directory, to be
+    /// attached to bug reports about wrong detections.
+    ///
+    /// The following files are created:
+    ///
+    ///  - `globs2`, `aliases`, `subclasses`, `icons` and `generic-icons`,
+    ///    in the same format as the database files
+    ///  - `magic`, with the header and number of rules of each entry
+    ///  - `files`, with the list of loaded database files
+    ///  - `environment`, with the variables affecting the lookups
+    pub fn dump_state<P: AsRef<Path>>(&self, directory: P) -> io::Result<()> {
+        let directory = directory.as_ref();
+        fs::create_dir_all(directory)?;
+
+        dump_lines(directory.join("globs2"), self.globs.iter())?;
+        dump_lines(directory.join("magic"), self.magic.iter())?;
+        dump_lines(directory.join("aliases"), self.aliases.iter())?;
+        dump_lines(directory.join("icons"), self.icons.iter())?;
+        dump_lines(directory.join("generic-icons"), self.generic_icons.iter())?;
+
+        let mut subclasses = Vec::new();
+        for (mime_type, parents) in self.parents.iter() {
+            for parent in parents {
+                subclasses.push(format!("{} {}", mime_type, parent));
+            }
+        }
+        subclasses.sort();
+        dump_lines(directory.join("subclasses"), subclasses)?;
+
+        dump_lines(directory.join("files"), self.loaded_files.iter().map(|f| f.display()))?;
+
+        let mut environment = vec![format!("xdg-mime {}", env!("CARGO_PKG_VERSION"))];
+        for var in &["XDG_DATA_HOME", "XDG_DATA_DIRS", "LANGUAGE", "LC_ALL", "LC_MESSAGES", "LANG"] {
+            match env::var_os(var) {
+                Some(v) => environment.push(format!("{}={}", var, v.to_string_lossy())),
+                None => environment.push(format!("{} is unset", var)),
+            }
+        }
+        dump_lines(directory.join("environment"), environment)?;
+
+        Ok(())
+    }
+}
+
+// The generic icon of a MIME type without an explicit one is derived
+// from its media type, like "image-x-generic" for "image/png"
+fn fallback_generic_icon_name(mime_type: &str) -> String {
+    let media_type = mime_type.split('/').next().unwrap_or(mime_type);
+
+    format!("{}-x-generic", media_type)
+}
+
+fn dump_lines<P, I, T>(file_name: P, lines: I) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = T>,
+    T: fmt::Display,
+{
+    let mut f = BufWriter::new(File::create(file_name)?);
+    for line in lines {
+        writeln!(f, "{}", line)?;
+    }
+
+    f.flush()
+}
+
+impl Default for SharedMimeInfo {
+    fn default() -> SharedMimeInfo {
+        SharedMimeInfo::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use std::env;
+
+    fn load_test_data() -> SharedMimeInfo {
+        let cwd = env::current_dir().unwrap().to_string_lossy().into_owned();
+        let dir = PathBuf::from(&format!("{}/test_files", cwd));
+        SharedMimeInfo::new_for_directory(dir)
+    }
+
+    #[test]
+    fn load_from_directory() {
+        let cwd = env::current_dir().unwrap().to_string_lossy().into_owned();
+        let dir = PathBuf::from(&format!("{}/test_files", cwd));
+        SharedMimeInfo::new_for_directory(dir);
+    }
+
+    #[test]
+    fn load_system() {
+        SharedMimeInfo::new();
+    }
+
+    #[test]
+    fn aliases_of() {
+        let mime_db = load_test_data();
+
+        assert!(mime_db.aliases_of("application/gzip").contains(&"application/x-gzip".to_string()));
+        assert!(mime_db.aliases_of("application/x-gzip").is_empty());
+    }
+
+    #[test]
+    fn canonical_types() {
+        let mime_db = SharedMimeInfo::builder()
+            .system_directories(false)
+            .directory("test_files")
+            .directory("test_files/canonical")
+            .build();
+        assert_eq!(
+            mime_db.get_mime_types_from_file_name("foo.xdg-gz"),
+            vec!["application/gzip".to_string()]
+        );
+
+        // Both the input and the output of the icon lookups are canonical,
+        // and the icons of the canonical types win
+        assert_eq!(mime_db.lookup_icon_name("application/gzip"), "gzip-archive");
+        assert_eq!(
+            mime_db.lookup_icon_names("application/x-gzip"),
+            vec!["gzip-archive", "application-gzip", "package-x-generic"]
+        );
+
+        let aliases = mime_db.list_aliases_for("application/x-gzip");
+        assert!(aliases.contains(&"application/x-gzip".to_string()));
+        assert_eq!(aliases, mime_db.list_aliases_for("application/gzip"));
+    }
+
+    #[test]
+    fn parents() {
+        let mime_db = load_test_data();
+
+        assert_eq!(
+            mime_db.get_parents("application/x-gzip"),
+            Some(vec!["application/gzip".to_string()])
+        );
+        assert_eq!(
+            mime_db.get_parents("application/rss+xml"),
+            Some(vec!["application/rss+xml".to_string(), "application/xml".to_string()])
+        );
+        assert_eq!(mime_db.get_parents("application/vnd.xdg-mime.nonexistent"), None);
+    }
+
+    #[test]
+    fn is_subclass_of() {
+        let mime_db = load_test_data();
+
+        assert!(mime_db.is_subclass_of("application/rss+xml", "application/xml"));
+        assert!(mime_db.is_subclass_of("application/rss+xml", "text/plain"));
+        assert!(mime_db.is_subclass_of("text/rss", "text/xml"));
+        assert!(mime_db.is_subclass_of("text/x-csrc", "text/plain"));
+        assert!(mime_db.is_subclass_of("image/png", "application/octet-stream"));
+        assert!(mime_db.is_subclass_of("application/x-gzip", "application/gzip"));
+        assert!(!mime_db.is_subclass_of("inode/directory", "application/octet-stream"));
+        assert!(!mime_db.is_subclass_of("application/xml", "application/rss+xml"));
+        assert!(!mime_db.is_subclass_of("image/png", "text/plain"));
+
+        assert!(mime_db.is_subclass_in("application/rss+xml", "text/plain", Hierarchy::Explicit));
+        assert!(mime_db.is_subclass_of("text/vnd.graphviz", "text/plain"));
+        assert!(!mime_db.is_subclass_in("text/vnd.graphviz", "text/plain", Hierarchy::Explicit));
+        assert!(!mime_db.is_subclass_in("image/png", "application/octet-stream", Hierarchy::Explicit));
+
+        // Through text/x-csrc, then the implicit rules
+        assert!(mime_db.is_subclass_of("text/x-vala", "text/x-csrc"));
+        assert!(mime_db.is_subclass_of("text/x-vala", "text/plain"));
+        assert!(mime_db.is_subclass_of("text/x-vala", "application/octet-stream"));
+        assert!(mime_db.is_subclass_of("text/x-unknown-script", "text/plain"));
+        assert!(mime_db.is_subclass_of("text/x-unknown-script", "application/octet-stream"));
+        assert!(!mime_db.is_subclass_of("text/plain", "text/x-csrc"));
+    }
+
+    #[test]
+    fn ancestors() {
+        let mime_db = load_test_data();
+
+        assert_eq!(
+            mime_db.ancestors("image/svg+xml"),
+            vec![
+                "application/xml".to_string(),
+                "text/plain".to_string(),
+                "application/octet-stream".to_string()
+            ]
+        );
+        assert_eq!(
+            mime_db.ancestors("application/x-perl"),
+            vec![
+                "application/x-executable".to_string(),
+                "text/plain".to_string(),
+                "application/octet-stream".to_string()
+            ]
+        );
+        assert_eq!(
+            mime_db.ancestors_in("application/x-compressed-tar", Hierarchy::Explicit),
+            vec!["application/gzip".to_string()]
+        );
+        assert!(mime_db.ancestors_in("application/x-gzip", Hierarchy::Explicit).is_empty());
+        assert!(mime_db.ancestors_in("application/vnd.xdg-mime.nonexistent", Hierarchy::Explicit).is_empty());
+        // Like all the types outside of inode/*, the unknown ones are
+        // application/octet-stream
+        assert_eq!(
+            mime_db.ancestors("application/vnd.xdg-mime.nonexistent"),
+            vec!["application/octet-stream".to_string()]
+        );
+        assert!(mime_db.ancestors("application/octet-stream").is_empty());
+        assert!(mime_db.ancestors("inode/directory").is_empty());
+    }
+
+    #[test]
+    fn subclass_distance() {
+        let mime_db = load_test_data();
+
+        assert_eq!(mime_db.subclass_distance("image/svg+xml", "image/svg+xml"), Some(0));
+        assert_eq!(mime_db.subclass_distance("image/svg+xml", "application/xml"), Some(1));
+        assert_eq!(mime_db.subclass_distance("image/svg+xml", "text/plain"), Some(2));
+        assert_eq!(mime_db.subclass_distance("image/svg+xml", "application/octet-stream"), Some(3));
+        assert_eq!(mime_db.subclass_distance("application/x-perl", "text/plain"), Some(1));
+        assert_eq!(mime_db.subclass_distance("application/x-compressed-tar", "application/x-gzip"), Some(1));
+        assert_eq!(mime_db.subclass_distance("application/xml", "image/svg+xml"), None);
+    }
+
+    #[test]
+    fn common_ancestor() {
+        let mime_db = load_test_data();
+
+        assert_eq!(
+            mime_db.common_ancestor("image/svg+xml", "application/rss+xml"),
+            Some("application/xml".to_string())
+        );
+        assert_eq!(
+            mime_db.common_ancestor("application/xml", "image/svg+xml"),
+            Some("application/xml".to_string())
+        );
+        assert_eq!(
+            mime_db.common_ancestor("image/svg+xml", "text/x-csrc"),
+            Some("text/plain".to_string())
+        );
+        assert_eq!(
+            mime_db.common_ancestor("image/png", "image/jpeg"),
+            Some("application/octet-stream".to_string())
+        );
+        assert_eq!(
+            mime_db.common_ancestor("application/x-gzip", "application/gzip"),
+            Some("application/gzip".to_string())
+        );
+        assert_eq!(mime_db.common_ancestor("inode/directory", "image/png"), None);
+    }
+
+    #[test]
+    fn magic_summary() {
+        let mime_db = load_test_data();
+        let summary = mime_db.magic_summary();
+
+        assert_eq!(summary.rules_per_type().get("image/png"), Some(&1));
+        assert_eq!(summary.entries_within(summary.max_extent()), summary.entry_count());
+
+        assert_eq!(mime_db.max_magic_extent(), summary.max_extent());
+        assert_eq!(mime_db.magic_entries().len(), summary.entry_count());
+        assert_eq!(mime_db.magic_priority("application/zip"), Some(60));
+        assert_eq!(mime_db.magic_priority("application/x-gzip"), Some(50));
+        assert_eq!(mime_db.magic_priority("text/x-csrc"), Some(30));
+        assert_eq!(mime_db.magic_priority("text/csv"), None);
+    }
+
+    #[test]
+    fn mime_type_for_chunks() {
+        use std::io::IoSlice;
+
+        let mime_db = load_test_data();
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+
+        let slices = [IoSlice::new(&png_data[..2]), IoSlice::new(&png_data[2..])];
+        let chunks = ChunkedData::new(slices.iter().map(|s| &**s));
+        assert_eq!(mime_db.get_mime_type_for_input(&chunks), Some("image/png".to_string()));
+        assert_eq!(mime_db.get_mime_type_for_input(&png_data[..]), Some("image/png".to_string()));
+    }
+
+    #[test]
+    fn guess_content_type_for_mount() {
+        let mime_db = load_test_data();
+
+        let dir = TempDir::new("mount");
+        let root = dir.path().to_path_buf();
+        fs::create_dir_all(root.join("DCIM/100CANON")).unwrap();
+        assert_eq!(mime_db.guess_content_type_for_mount(&root), vec!["x-content/image-dcf".to_string()]);
+
+        fs::create_dir_all(root.join("VIDEO_TS")).unwrap();
+        fs::write(root.join("VIDEO_TS/VIDEO_TS.IFO"), "").unwrap();
+        assert_eq!(
+            mime_db.guess_content_type_for_mount(&root),
+            vec!["x-content/image-dcf".to_string(), "x-content/video-dvd".to_string()]
+        );
+        assert_eq!(mime_db.guess_mime_type_for_tree(&root), Some("x-content/image-dcf".to_string()));
+
+        drop(dir);
+        assert!(mime_db.guess_content_type_for_mount(&root).is_empty());
+        assert_eq!(mime_db.guess_mime_type_for_tree(&root), None);
+    }
+
+    #[test]
+    fn handlers_for_volume() {
+        let mime_db = load_test_data();
+
+        let dir = TempDir::new("volume");
+        let root = dir.join("mount");
+        fs::create_dir_all(root.join("DCIM/100CANON")).unwrap();
+        dir.write(
+            "config/mimeapps.list",
+            "[Added Associations]\nx-content/image-dcf=shotwell.desktop;photos.desktop;\n",
+        );
+
+        let apps = MimeApps::load(&[dir.join("config")], &[], &[]);
+        let handlers = mime_db.handlers_for_volume(&root, &apps);
+        assert_eq!(
+            handlers,
+            vec![VolumeHandlers {
+                content_type: "x-content/image-dcf".to_string(),
+                default_application: None,
+                applications: vec!["shotwell.desktop".to_string(), "photos.desktop".to_string()],
+            }]
+        );
+    }
+
+    #[test]
+    fn deleteall_markers() {
+        let mime_db = SharedMimeInfo::builder()
+            .system_directories(false)
+            .directory("test_files/deleteall")
+            .directory("test_files")
+            .build();
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+
+        // The globs and magic of the less important directory are gone
+        assert_eq!(mime_db.lookup_file_name("a.png"), GlobMatch::NoMatch);
+        assert_eq!(mime_db.lookup_file_name("a.portable"), GlobMatch::Unique("image/png".to_string()));
+        assert_ne!(mime_db.get_mime_type_for_data(png_data), Some("image/png".to_string()));
+        assert_eq!(mime_db.get_mime_type_for_data(b"PNG!"), Some("image/png".to_string()));
+        assert!(mime_db.magic.iter().all(|e| !e.is_nomagic()));
+
+        // The markers only apply to the less important directories
+        let mime_db = SharedMimeInfo::builder()
+            .system_directories(false)
+            .directory("test_files")
+            .directory("test_files/deleteall")
+            .build();
+        assert_eq!(mime_db.lookup_file_name("a.png"), GlobMatch::Unique("image/png".to_string()));
+        assert_eq!(mime_db.get_mime_type_for_data(png_data), Some("image/png".to_string()));
+    }
+
+    #[test]
+    fn icon_overrides() {
+        let mime_db = SharedMimeInfo::builder()
+            .system_directories(false)
+            .directory("test_files/icon-overrides")
+            .directory("test_files")
+            .build();
+
+        // The more important directory wins, and can remove an icon, which
+        // then comes from the parent type
+        let visio = "application/vnd.ms-visio.template.main+xml";
+        assert_eq!(mime_db.lookup_icon_name("image/png"), "my-png");
+        assert_eq!(
+            mime_db.lookup_generic_icon_name("application/x-compressed-tar").as_deref(),
+            Some("my-archive")
+        );
+        assert_eq!(mime_db.lookup_generic_icon_name(visio).as_deref(), Some("package-x-generic"));
+        assert!(mime_db.generic_icons.iter().all(|i| !i.is_noicon()));
+
+        // The removal only applies to the less important directories
+        let mime_db = SharedMimeInfo::builder()
+            .system_directories(false)
+            .directory("test_files")
+            .directory("test_files/icon-overrides")
+            .build();
+        assert_eq!(
+            mime_db.lookup_generic_icon_name("application/x-compressed-tar").as_deref(),
+            Some("package-x-generic")
+        );
+        assert_eq!(mime_db.lookup_generic_icon_name(visio).as_deref(), Some("image-x-generic"));
+    }
+
+    #[test]
+    fn extension_types() {
+        let mime_db = SharedMimeInfo::builder()
+            .system_directories(false)
+            .directory("test_files")
+            .extension_types(true)
+            .build();
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+
+        assert_eq!(
+            mime_db.get_mime_types_from_file_name("notes.FOO"),
+            vec!["application/x-extension-foo".to_string()]
+        );
+        assert_eq!(mime_db.guess_mime_type(Some("notes.foo"), None), "application/x-extension-foo");
+        assert_eq!(mime_db.guess().file_name("notes.foo").guess().mime_type(), "application/x-extension-foo");
+        let mut ctx = LookupContext::new();
+        assert_eq!(
+            mime_db.lookup_file_name_with("notes.foo", &mut ctx).as_deref(),
+            Some("application/x-extension-foo")
+        );
+        let mut res = Vec::new();
+        mime_db.get_mime_types_from_file_name_into("notes.foo", &mut ctx, &mut res);
+        assert_eq!(res, vec!["application/x-extension-foo"]);
+
+        // The globs and the data still come first
+        assert_eq!(mime_db.guess_mime_type(Some("notes.txt"), None), "text/plain");
+        assert_eq!(mime_db.guess_mime_type(Some("logo.foo"), Some(png_data)), "image/png");
+
+        // Only the file names with an extension get one
+        for name in &["notes", ".foo", "notes.", "notes.a b"] {
+            assert_eq!(mime_db.guess_mime_type(Some(name), None), UNKNOWN_TYPE);
+        }
+
+        let mime_db = load_test_data();
+        assert_eq!(mime_db.guess_mime_type(Some("notes.foo"), None), UNKNOWN_TYPE);
+    }
+
+    #[test]
+    fn applications_for_type() {
+        let mime_db = load_test_data();
+
+        let dir = env::current_dir().unwrap().join("test_files/handlers");
+        let apps = MimeApps::load(&[dir.join("config")], &[dir.join("data")], &[]);
+        assert_eq!(mime_db.default_application_for("text/x-csrc", &apps), Some("gedit.desktop"));
+        assert_eq!(
+            mime_db.applications_for("text/x-csrc", &apps),
+            vec!["gedit.desktop", "ide.desktop", "kate.desktop"]
+        );
+        // The associations can name the aliases
+        assert_eq!(mime_db.applications_for("application/gzip", &apps), vec!["archiver.desktop"]);
+        assert_eq!(mime_db.default_application_for("image/png", &apps), None);
+        assert_eq!(mime_db.default_application_for("application/octet-stream", &apps), Some("hexedit.desktop"));
+    }
+
+    #[test]
+    fn matching_globs_for_name() {
+        let mime_db = load_test_data();
+
+        let globs = mime_db.matching_globs_for_name("foo.tar.gz");
+        let patterns: Vec<(String, i32)> = globs.iter().map(|g| (g.pattern(), g.weight())).collect();
+        assert_eq!(patterns[0], ("*.tar.gz".to_string(), 50));
+        assert!(patterns.contains(&("*.gz".to_string(), 50)));
+        assert_eq!(globs[0].mime_type(), "application/x-compressed-tar");
+
+        assert!(mime_db.matching_globs_for_name("foo.vnd-xdg-mime-nonexistent").is_empty());
+    }
+
+    #[test]
+    fn matching_magic_for_data() {
+        let mime_db = load_test_data();
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+
+        let entry = mime_db.matching_magic_for_data(png_data).unwrap();
+        assert_eq!(entry.mime_type(), "image/png");
+        assert_eq!(entry.priority(), 50);
+        assert!(entry.location().contains("test_files/mime/"));
+
+        assert!(mime_db.matching_magic_for_data(b"\x00\x01\x02").is_none());
+
+        // An epub is also a zip file
+        let mut epub_data = b"PK\x03\x04".to_vec();
+        epub_data.resize(30, 0);
+        epub_data.extend_from_slice(b"mimetypeapplication/epub+zip");
+        let entries = mime_db.matching_magic_entries_for_data(&epub_data);
+        let types: Vec<(&str, u32)> = entries.iter().map(|e| (e.mime_type(), e.priority())).collect();
+        assert_eq!(types[0].0, "application/epub+zip");
+        assert!(types.iter().any(|t| t.0 == "application/zip"));
+        assert!(types.windows(2).all(|w| w[0].1 >= w[1].1));
+        assert!(mime_db.matching_magic_entries_for_data(b"\x00\x01\x02").is_empty());
+    }
+
+    #[test]
+    fn hidden_files() {
+        let mut mime_db = load_test_data();
+
+        // Like the reference implementation, the leading dot is part of
+        // the suffix by default
+        assert_eq!(
+            mime_db.lookup_file_name(".tar.gz"),
+            GlobMatch::Unique("application/x-compressed-tar".to_string())
+        );
+        assert_eq!(mime_db.lookup_file_name(".gz"), GlobMatch::Unique("application/gzip".to_string()));
+        assert_eq!(mime_db.lookup_file_name(".bashrc"), GlobMatch::NoMatch);
+
+        mime_db.set_dotfile_handling(DotfileHandling::SkipLeadingDot);
+        assert_eq!(mime_db.lookup_file_name(".tar.gz"), GlobMatch::Unique("application/gzip".to_string()));
+        assert_eq!(mime_db.lookup_file_name(".gz"), GlobMatch::NoMatch);
+    }
+
+    #[test]
+    fn guess_for_uri() {
+        let mime_db = load_test_data();
+
+        assert_eq!(mime_db.guess_for_uri("file:///tmp/bar%20baz.gif"), "image/gif");
+        assert_eq!(mime_db.guess_for_uri("https://example.com/a.tar.gz?dl=1"), "application/x-compressed-tar");
+        assert_eq!(mime_db.guess_for_uri("https://example.com/"), "application/octet-stream");
+    }
+
+    #[test]
+    fn descriptions() {
+        let mut mime_db = load_test_data();
+
+        assert_eq!(mime_db.description("image/png"), Some("PNG image".to_string()));
+        assert_eq!(mime_db.description_in("image/png", &["de_AT", "de"]), Some("PNG-Bild".to_string()));
+        assert_eq!(mime_db.description("image/gif"), None);
+
+        mime_db.set_locales(locale_chain(None, None, None, Some("fr_FR.UTF-8")));
+        assert_eq!(mime_db.description("image/svg+xml"), Some("image SVG".to_string()));
+
+        let mime_db = SharedMimeInfo::builder()
+            .system_directories(false)
+            .directory("test_files")
+            .locales(vec!["xx".to_string(), "de".to_string()])
+            .build();
+        assert_eq!(mime_db.description("image/png"), Some("PNG-Bild".to_string()));
+
+        assert_eq!(mime_db.get_comment("image/png", Some("pt_BR.UTF-8")), Some("Imagem PNG".to_string()));
+        assert_eq!(mime_db.get_comment("image/png", None), Some("PNG image".to_string()));
+        assert_eq!(mime_db.get_comment("image/gif", Some("de")), None);
+    }
+
+    #[test]
+    fn translation_coverage() {
+        let mime_db = load_test_data();
+
+        let coverage = mime_db.translation_coverage("de_DE");
+        assert_eq!(coverage.translated, 2);
+        assert_eq!(coverage.total, coverage.missing.len() + 2);
+        assert!(coverage.missing.contains(&"image/gif".to_string()));
+        assert!(!coverage.missing.contains(&"image/png".to_string()));
+        assert!(coverage.ratio() < 1.0);
+
+        assert_eq!(mime_db.translation_coverage("xx").translated, 0);
+    }
+
+    #[test]
+    fn export_descriptions() {
+        let mime_db = load_test_data();
+
+        let mut po = Vec::new();
+        mime_db.export_descriptions("fr_FR", Format::Po, &mut po).unwrap();
+        let po = String::from_utf8(po).unwrap();
+        assert!(po.contains("msgctxt \"image/png\"\nmsgid \"PNG image\"\nmsgstr \"image PNG\"\n"));
+        assert!(!po.contains("image/gif"));
+
+        let mut json = Vec::new();
+        mime_db.export_descriptions("xx", Format::Json, &mut json).unwrap();
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            "{\n  \"image/png\": \"PNG image\",\n  \"image/svg+xml\": \"SVG image\"\n}\n"
+        );
+    }
+
+    #[test]
+    fn knows_type() {
+        let mime_db = load_test_data();
+
+        assert!(mime_db.knows_type("image/gif"));
+        assert!(mime_db.knows_type("application/x-gzip"));
+        assert!(!mime_db.knows_type("application/vnd.xdg-mime.nonexistent"));
+
+        let empty_db = SharedMimeInfo::new_for_directory("test_files/nonexistent");
+        assert!(!empty_db.knows_type("image/gif"));
+    }
+
+    #[test]
+    fn mime_types() {
+        let mime_db = load_test_data();
+
+        let types: Vec<&str> = mime_db.mime_types().collect();
+        assert!(types.windows(2).all(|w| w[0] < w[1]));
+        assert!(types.contains(&"image/gif"));
+        assert!(types.contains(&"text/x-csrc"));
+        // Only the canonical type of an alias is listed
+        assert!(types.contains(&"application/gzip"));
+        assert!(!types.contains(&"application/x-gzip"));
+        assert!(types.iter().all(|t| mime_db.knows_type(t)));
+
+        // Without a types file, the types come from the other files
+        let db = SharedMimeInfo::new_for_directory(env::current_dir().unwrap().join("test_files/types"));
+        assert_eq!(db.mime_types().collect::<Vec<_>>(), vec!["image/apng", "image/png"]);
+    }
+
+    #[test]
+    fn cache_backend() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct Counting {
+            cache: LruCache,
+            hits: AtomicUsize,
+            inserts: AtomicUsize,
+        }
+
+        impl CacheBackend for Counting {
+            fn get(&self, key: &[u8], value: &mut Vec<u8>) -> bool {
+                let hit = self.cache.get(key, value);
+                if hit {
+                    self.hits.fetch_add(1, Ordering::Relaxed);
+                }
+                hit
+            }
+
+            fn insert(&self, key: &[u8], value: &[u8]) {
+                self.inserts.fetch_add(1, Ordering::Relaxed);
+                self.cache.insert(key, value);
+            }
+
+            fn clear(&self) {
+                self.cache.clear();
+            }
+        }
+
+        let mut mime_db = load_test_data();
+        let backend = Arc::new(Counting {
+            cache: LruCache::new(16),
+            hits: AtomicUsize::new(0),
+            inserts: AtomicUsize::new(0),
+        });
+        mime_db.set_cache_backend(backend.clone());
+
+        assert_eq!(mime_db.lookup_file_name("foo.xdg-unknown"), GlobMatch::NoMatch);
+        assert_eq!(mime_db.lookup_file_name("bar.xdg-unknown"), GlobMatch::NoMatch);
+        assert_eq!(backend.inserts.load(Ordering::Relaxed), 1);
+        assert_eq!(backend.hits.load(Ordering::Relaxed), 1);
+
+        // The backend is kept, and not cleared, by the reloads
+        mime_db.reload();
+        assert_eq!(mime_db.lookup_file_name("foo.xdg-unknown"), GlobMatch::NoMatch);
+        assert_eq!(backend.inserts.load(Ordering::Relaxed), 1);
+        assert_eq!(backend.hits.load(Ordering::Relaxed), 2);
+        assert_eq!(mime_db.lookup_file_name("foo.c"), GlobMatch::Unique("text/x-csrc".to_string()));
+
+        // The types of data are cached too
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+        assert_eq!(mime_db.get_mime_type_for_data(png_data), Some("image/png".to_string()));
+        assert_eq!(mime_db.get_mime_type_for_data(png_data), Some("image/png".to_string()));
+        assert_eq!(mime_db.get_mime_type_for_data(b"\x00\x01"), None);
+        assert_eq!(mime_db.get_mime_type_for_data(b"\x00\x01"), None);
+        assert_eq!(backend.inserts.load(Ordering::Relaxed), 3);
+        assert_eq!(backend.hits.load(Ordering::Relaxed), 4);
+        let dir = TempDir::new("cache-backend");
+        dir.write("logo", &png_data[..]);
+        let mut ctx = LookupContext::new();
+        for _ in 0..2 {
+            assert_eq!(mime_db.guess_mime_type_for_path_with(dir.join("logo"), 4096, &mut ctx), "image/png");
+        }
+        // The file name and the data both hit the second time
+        assert_eq!(backend.hits.load(Ordering::Relaxed), 6);
+
+        // Another version of the files does not get the entries of this
+        // one
+        dir.write("mime/globs2", "50:text/x-unknown:*.xdg-unknown\n");
+        let mut other_db = SharedMimeInfo::new_for_directory(dir.path());
+        other_db.set_cache_backend(backend.clone());
+        let expected = GlobMatch::Unique("text/x-unknown".to_string());
+        assert_eq!(other_db.lookup_file_name("foo.xdg-unknown"), expected);
+        assert_eq!(other_db.get_mime_type_for_data(png_data), None);
+        assert_eq!(backend.hits.load(Ordering::Relaxed), 6);
+        assert_eq!(mime_db.lookup_file_name("bar.xdg-unknown"), GlobMatch::NoMatch);
+        assert_eq!(backend.hits.load(Ordering::Relaxed), 7);
+    }
+
+    #[test]
+    fn broken_cache_backend() {
+        // A backend returning a type the database does not know
+        struct Broken;
+
+        impl CacheBackend for Broken {
+            fn get(&self, _key: &[u8], value: &mut Vec<u8>) -> bool {
+                value.extend_from_slice(b"application/x-not-in-the-db");
+                true
+            }
+
+            fn insert(&self, _key: &[u8], _value: &[u8]) {}
+
+            fn clear(&self) {}
+        }
+
+        let mut mime_db = load_test_data();
+        mime_db.set_cache_backend(Arc::new(Broken));
+
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+        assert_eq!(mime_db.get_mime_type_for_data(png_data), Some("image/png".to_string()));
+    }
+
+    #[test]
+    fn symbolic_icons() {
+        let mime_db = load_test_data();
+
+        assert_eq!(
+            mime_db.lookup_symbolic_icon_names("application/json"),
+            vec![
+                "application-json-symbolic".to_string(),
+                "text-x-script-symbolic".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn can_thumbnail() {
+        let mime_db = load_test_data();
+
+        assert!(mime_db.can_thumbnail("image/gif"));
+        assert!(mime_db.can_thumbnail("image/svg+xml-compressed"));
+        assert!(mime_db.can_thumbnail("application/x-gzip"));
+        assert!(mime_db.can_thumbnail("application/x-compressed-tar"));
+        assert!(mime_db.can_thumbnail("application/x-wwf"));
+        assert!(!mime_db.can_thumbnail("text/plain"));
+        assert!(!mime_db.can_thumbnail("application/octet-stream"));
+    }
+
+    #[test]
+    fn glob_source_directories() {
+        let dir = Path::new("test_files/sources");
+        let mime_db = SharedMimeInfo::builder()
+            .system_directories(false)
+            .directory("test_files")
+            .directory(dir)
+            .build();
+
+        let sources: Vec<(&str, Option<&Path>)> = mime_db
+            .globs()
+            .filter(|g| g.pattern() == "*.foo" || g.pattern() == "*.txt")
+            .map(|g| (g.mime_type(), g.source_directory()))
+            .collect();
+        assert_eq!(
+            sources,
+            vec![
+                ("text/plain", Some(Path::new("test_files"))),
+                ("text/x-foo", Some(dir)),
+            ]
+        );
+    }
+
+    #[test]
+    fn lookup_generic_icons() {
+        let mime_db = load_test_data();
+
+        assert_eq!(
+            mime_db.lookup_generic_icon_name("application/json"),
+            Some("text-x-script".to_string())
+        );
+        assert_eq!(
+            mime_db.lookup_generic_icon_name("text/plain"),
+            Some("text-x-generic".to_string())
+        );
+        // From the generic icon of application/gzip
+        assert_eq!(
+            mime_db.lookup_generic_icon_name("application/x-compressed-tar"),
+            Some("package-x-generic".to_string())
+        );
+        assert_eq!(
+            mime_db.lookup_generic_icon_name("application/x-xdg-mime-unknown"),
+            Some("application-x-generic".to_string())
+        );
+
+        assert_eq!(mime_db.lookup_icon_name("image/png"), "image-png");
+        assert_eq!(mime_db.lookup_icon_name("application/x-gzip"), "application-gzip");
+    }
+
+    #[test]
+    fn unalias() {
+        let mime_db = load_test_data();
+
+        assert_eq!(
+            mime_db.unalias_mime_type("application/ics"),
+            Some("text/calendar".to_string())
+        );
+        assert_eq!(mime_db.unalias_mime_type("text/plain"), None);
+    }
+
+    #[test]
+    fn mime_type_for_file_name() {
+        let mime_db = load_test_data();
+
+        assert_eq!(
+            mime_db.get_mime_types_from_file_name("foo.txt"),
+            vec!["text/plain".to_string()]
+        );
+
+        assert_eq!(
+            mime_db.get_mime_types_from_file_name("bar.gif"),
+            vec!["image/gif".to_string()]
+        );
+    }
+
+    #[test]
+    fn lookup_file_name() {
+        let mime_db = load_test_data();
+
+        assert_eq!(
+            mime_db.lookup_file_name("bar.gif"),
+            GlobMatch::Unique("image/gif".to_string())
+        );
+        assert_eq!(mime_db.lookup_file_name("bar.unknown"), GlobMatch::NoMatch);
+    }
+
+    #[test]
+    fn mime_type_for_file_data() {
+        let mime_db = load_test_data();
+
+        let svg_data = include_bytes!("../test_files/files/rust-logo.svg");
+        assert_eq!(
+            mime_db.get_mime_type_for_data(svg_data),
+            Some("image/svg+xml".to_string())
+        );
+
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+        assert_eq!(
+            mime_db.get_mime_type_for_data(png_data),
             Some("image/png".to_string())
         );
     }
+
+    #[test]
+    fn guess_mime_type() {
+        let mime_db = load_test_data();
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+
+        assert_eq!(mime_db.guess_mime_type(Some("bar.gif"), Some(png_data)), "image/gif");
+        assert_eq!(mime_db.guess_mime_type(None, Some(png_data)), "image/png");
+        assert_eq!(mime_db.guess_mime_type(Some("foo"), Some(b"")), EMPTY_TYPE);
+        assert_eq!(mime_db.guess_mime_type(None, None), UNKNOWN_TYPE);
+    }
+
+    #[test]
+    fn lookups_with_context() {
+        let mime_db = load_test_data();
+        let mut ctx = LookupContext::new();
+
+        assert_eq!(
+            mime_db.lookup_file_name_with("foo.tar.gz", &mut ctx).as_deref(),
+            Some("application/x-compressed-tar")
+        );
+        assert_eq!(mime_db.lookup_file_name_with("foo.unknown-ext", &mut ctx), None);
+
+        let dir = TempDir::new("context");
+        fs::copy("test_files/files/rust-logo.png", dir.join("rust-logo")).unwrap();
+        fs::copy("test_files/files/rust-logo.png", dir.join("rust-logo.gif")).unwrap();
+        dir.write("empty", "");
+
+        assert_eq!(mime_db.guess_mime_type_for_path_with(dir.join("rust-logo"), 64, &mut ctx), "image/png");
+        let capacity = ctx.sniff.capacity();
+        assert_eq!(mime_db.guess_mime_type_for_path_with(dir.join("rust-logo.gif"), 64, &mut ctx), "image/gif");
+        assert_eq!(mime_db.guess_mime_type_for_path_with(dir.join("rust-logo"), 64, &mut ctx), "image/png");
+        assert_eq!(ctx.sniff.capacity(), capacity);
+        assert_eq!(mime_db.guess_mime_type_for_path_with(dir.join("empty"), 64, &mut ctx), EMPTY_TYPE);
+        assert_eq!(mime_db.guess_mime_type_for_path_with(dir.path(), 64, &mut ctx), "inode/directory");
+        assert_eq!(mime_db.guess_mime_type_for_path_with(dir.join("nonexistent"), 64, &mut ctx), UNKNOWN_TYPE);
+    }
+
+    #[test]
+    fn into_variants() {
+        let mime_db = load_test_data();
+        let mut ctx = LookupContext::new();
+        let mut res = Vec::new();
+
+        let mut types = Vec::new();
+        for name in &["foo.tar.gz", "foo.txt", "foo.unknown-ext", "Makefile"] {
+            mime_db.get_mime_types_from_file_name_into(name, &mut ctx, &mut types);
+            assert_eq!(types, mime_db.get_mime_types_from_file_name(name));
+        }
+
+        for mime_type in &["image/svg+xml", "application/x-perl", "application/x-gzip", "text/vnd.graphviz", "inode/directory"] {
+            mime_db.ancestors_into(mime_type, &mut res);
+            assert_eq!(res, mime_db.ancestors(mime_type));
+        }
+    }
+
+    #[test]
+    fn guess_mime_type_for_path() {
+        let mime_db = load_test_data();
+        let budget = IoBudget::new();
+
+        let guess = mime_db.guess_mime_type_for_path("test_files/files/rust-logo.png", &budget);
+        assert_eq!(guess, Guess::new("image/png", false));
+
+        let dir = TempDir::new("path");
+        fs::copy("test_files/files/rust-logo.png", dir.join("rust-logo")).unwrap();
+
+        let guess = mime_db.guess_mime_type_for_path(dir.join("rust-logo"), &budget);
+        assert_eq!(guess, Guess::new("image/png", false));
+        let guess = mime_db.guess_mime_type_for_path(dir.path(), &budget);
+        assert_eq!(guess, Guess::new("inode/directory", false));
+        let guess = mime_db.guess_mime_type_for_path(dir.join("nonexistent"), &budget);
+        assert_eq!(guess, Guess::new(UNKNOWN_TYPE, true));
+    }
+
+    #[test]
+    fn shared_database() {
+        let threads: Vec<_> = (0..4)
+            .map(|_| std::thread::spawn(|| SharedMimeInfo::shared() as *const SharedMimeInfo as usize))
+            .collect();
+        let shared = SharedMimeInfo::shared() as *const SharedMimeInfo as usize;
+
+        for thread in threads {
+            assert_eq!(thread.join().unwrap(), shared);
+        }
+    }
+
+    #[test]
+    fn guess_mime_type_from_metadata() {
+        let mime_db = load_test_data();
+        let budget = IoBudget::new().metadata_only(true);
+
+        let dir = TempDir::new("metadata");
+        fs::copy("test_files/files/rust-logo.png", dir.join("rust-logo")).unwrap();
+        fs::copy("test_files/files/rust-logo.png", dir.join("rust-logo.png")).unwrap();
+        dir.write("empty", b"");
+
+        // The contents are never read
+        let guess = mime_db.guess_mime_type_for_path(dir.join("rust-logo"), &budget);
+        assert_eq!(guess, Guess::new(UNKNOWN_TYPE, true));
+        let guess = mime_db.guess_mime_type_for_path(dir.join("rust-logo.png"), &budget);
+        assert_eq!(guess, Guess::new("image/png", false));
+        let guess = mime_db
+            .guess()
+            .policy(GuessPolicy::DataFirst)
+            .path(&dir.join("rust-logo.png"))
+            .limits(budget)
+            .guess();
+        assert_eq!(guess, Guess::new("image/png", false));
+        let guess = mime_db.guess_mime_type_for_path(dir.path(), &budget);
+        assert_eq!(guess, Guess::new("inode/directory", false));
+        let guess = mime_db.guess_mime_type_for_path(dir.join("empty"), &budget);
+        assert_eq!(guess.mime_type(), EMPTY_TYPE);
+        let guess = mime_db.guess_mime_type_for_path(dir.join("nonexistent"), &budget);
+        assert_eq!(guess, Guess::new(UNKNOWN_TYPE, true));
+    }
+
+    #[cfg(feature = "debug-lookup")]
+    #[test]
+    fn lookup_sink() {
+        use std::sync::{Arc, Mutex};
+
+        struct Sink(Arc<Mutex<Vec<LookupRecord>>>);
+
+        impl LookupSink for Sink {
+            fn record(&self, record: &LookupRecord) {
+                self.0.lock().unwrap().push(record.clone());
+            }
+        }
+
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let mut mime_db = load_test_data();
+        mime_db.set_lookup_sink(Box::new(Sink(records.clone())));
+
+        mime_db.get_mime_types_from_file_name("bar.gif");
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+        mime_db.get_mime_type_for_data(png_data);
+
+        let sink_records = records.clone();
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 2);
+
+        assert_eq!(records[0].input, LookupInput::FileName("bar.gif".to_string()));
+        let winner = records[0].winner.as_ref().unwrap();
+        assert_eq!(winner.mime_type, "image/gif");
+        assert_eq!(winner.rule, "50:image/gif:*.gif");
+
+        assert_eq!(records[1].input, LookupInput::Data(png_data.len()));
+        assert_eq!(records[1].winner.as_ref().unwrap().mime_type, "image/png");
+        let location = records[1].winner.as_ref().unwrap().location.clone().unwrap();
+        assert!(location.contains("test_files/mime/mime.cache:"));
+        assert_eq!(winner.location, None);
+        assert!(records[1]
+            .skipped
+            .iter()
+            .all(|(c, r)| c.priority >= 50 && *r == SkipReason::NoMatch));
+        drop(records);
+
+        let reasons = |file_name: &str| -> Vec<(String, SkipReason)> {
+            sink_records.lock().unwrap().clear();
+            mime_db.get_mime_types_from_file_name(file_name);
+            let records = sink_records.lock().unwrap();
+            records[0].skipped.iter().map(|(c, r)| (c.rule.clone(), *r)).collect()
+        };
+        assert!(reasons("bar.gif").is_empty());
+        assert_eq!(
+            reasons("makefile.py"),
+            vec![
+                ("50:text/x-python3:*.py".to_string(), SkipReason::LowerWeight),
+                ("10:text/x-makefile:makefile.*".to_string(), SkipReason::LowerWeight)
+            ]
+        );
+        assert_eq!(
+            reasons("foo.tar.gz"),
+            vec![("50:application/gzip:*.gz".to_string(), SkipReason::ShorterPattern)]
+        );
+        assert_eq!(
+            reasons("foo.c"),
+            vec![("50:text/x-c++src:*.C:cs".to_string(), SkipReason::CaseMismatch)]
+        );
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn metrics() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[derive(Default)]
+        struct Counters {
+            file_names: AtomicUsize,
+            data: AtomicUsize,
+            cache_hits: AtomicUsize,
+            bytes: AtomicUsize,
+            reloads: AtomicUsize,
+        }
+
+        impl Metrics for Counters {
+            fn lookup(&self, kind: LookupKind) {
+                match kind {
+                    LookupKind::FileName => self.file_names.fetch_add(1, Ordering::Relaxed),
+                    LookupKind::Data => self.data.fetch_add(1, Ordering::Relaxed),
+                };
+            }
+
+            fn miss_cache(&self, hit: bool) {
+                if hit {
+                    self.cache_hits.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+
+            fn sniffed_bytes(&self, bytes: usize) {
+                self.bytes.fetch_add(bytes, Ordering::Relaxed);
+            }
+
+            fn reload(&self, _duration: Duration) {
+                self.reloads.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let counters = Arc::new(Counters::default());
+        let mut mime_db = load_test_data();
+        mime_db.set_metrics(counters.clone());
+
+        mime_db.get_mime_types_from_file_name("a.nonexistent-ext");
+        mime_db.get_mime_types_from_file_name("b.nonexistent-ext");
+        mime_db.get_mime_type_for_data(b"\x89PNG");
+        assert_eq!(counters.file_names.load(Ordering::Relaxed), 2);
+        assert_eq!(counters.data.load(Ordering::Relaxed), 1);
+        assert_eq!(counters.cache_hits.load(Ordering::Relaxed), 1);
+
+        // The hooks are kept across reloads
+        mime_db.reload();
+        assert_eq!(counters.reloads.load(Ordering::Relaxed), 1);
+
+        let png_file = env::current_dir().unwrap().join("test_files/files/rust-logo.png");
+        let guess = mime_db
+            .guess()
+            .path(&png_file)
+            .policy(GuessPolicy::DataFirst)
+            .limits(IoBudget::new().max_read(64))
+            .guess();
+        assert_eq!(guess.mime_type(), "image/png");
+        assert_eq!(counters.bytes.load(Ordering::Relaxed), 64);
+        assert_eq!(counters.data.load(Ordering::Relaxed), 2);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn hot_rules() {
+        let mut mime_db = load_test_data();
+        mime_db.get_mime_types_from_file_name("a.png");
+        assert!(mime_db.hot_rules(10).is_empty());
+
+        mime_db.set_rule_counting(true);
+        for name in ["a.png", "b.png", "c.png", "a.gz", "a.nonexistent-ext"] {
+            mime_db.get_mime_types_from_file_name(name);
+        }
+        mime_db.get_mime_type_for_data(b"\x89PNG\r\n\x1a\n");
+        mime_db.reload();
+        mime_db.get_mime_type_for_data(b"\x89PNG\r\n\x1a\n");
+
+        let hot = mime_db.hot_rules(2);
+        assert_eq!(hot.len(), 2);
+        assert_eq!((hot[0].kind, hot[0].mime_type.as_str(), hot[0].hits), (LookupKind::FileName, "image/png", 3));
+        assert_eq!(hot[0].location, None);
+        assert_eq!((hot[1].kind, hot[1].mime_type.as_str(), hot[1].hits), (LookupKind::Data, "image/png", 2));
+        assert!(hot[1].location.is_some());
+        assert_eq!(mime_db.hot_rules(10).len(), 3);
+
+        mime_db.set_rule_counting(false);
+        assert!(mime_db.hot_rules(10).is_empty());
+    }
+
+    #[test]
+    fn adversarial_inputs() {
+        let dir = TempDir::new("adversarial");
+        let mime_dir = dir.join("mime");
+
+        dir.write(
+            "mime/globs2",
+            &b"\xff\xfe:x/y:*.z\n50:text/x-bad:*.[ch\n99999999999:a/b:*.x\n::\n50:x/y:\0*\n50:x/y:*\n"[..],
+        );
+        dir.write(
+            "mime/magic",
+            &b"MIME-Magic\0\n[50:x/y]\n>4294967295=\x00\x04abcd+4294967295\n>0=\x00\x01a\n[50:x/z]\n>0=\xff"[..],
+        );
+        dir.write("mime/aliases", &b"\xff\xff\na\na/b a/b\n"[..]);
+        dir.write("mime/subclasses", "a/b a/b\na/b\n \n");
+        dir.write("mime/icons", ":\n::\na/b:\n");
+        // A directory in place of a file
+        fs::create_dir_all(mime_dir.join("generic-icons")).unwrap();
+
+        let mime_db = SharedMimeInfo::builder()
+            .system_directories(false)
+            .directory(dir.path())
+            .directory("/nonexistent\0")
+            .build();
+
+        let long_name = "a".repeat(65536);
+        let names = ["", "\0", ".", "..", "*", "[", "foo\0.txt", "🦀.PNG", "x.", long_name.as_str()];
+        for name in names.iter() {
+            mime_db.get_mime_types_from_file_name(name);
+        }
+
+        let mime_types = ["", "/", "//", "a", "a/b", "a/b/c", "\0/\0", "🦀/🦀"];
+        for mime_type in mime_types.iter() {
+            mime_db.unalias_mime_type(mime_type);
+            mime_db.lookup_icon_names(mime_type);
+            mime_db.lookup_generic_icon_name(mime_type);
+            mime_db.get_parents(mime_type);
+        }
+
+        let data: [&[u8]; 4] = [b"", b"a", b"\xff\xff\xff\xff", &[0u8; 8192]];
+        for d in data.iter() {
+            mime_db.get_mime_type_for_data(d);
+        }
+
+        assert!(mime_db.dump_state(mime_dir.join("globs2")).is_err());
+
+        // Packages compiled when loading, as there are no generated files
+        let dir = TempDir::new("adversarial-packages");
+        let matches = [
+            r#"<match type="string" value="a" offset="0" mask="0x1é1"/>"#,
+            r#"<match type="string" value="a" offset="0:4294967295"/>"#,
+            r#"<match type="string" value="a" offset="1:0"/>"#,
+        ];
+        for (i, m) in matches.iter().enumerate() {
+            let xml = format!(
+                "<mime-info xmlns=\"{}\"><mime-type type=\"x/y{}\"><magic>{}</magic></mime-type></mime-info>",
+                package::FREEDESKTOP_NAMESPACE,
+                i,
+                m
+            );
+            assert!(package::read_package_from_string(&xml).is_err());
+            dir.write(format!("mime/packages/x-y{}.xml", i), xml);
+        }
+
+        let mime_db = SharedMimeInfo::builder()
+            .system_directories(false)
+            .directory(dir.path())
+            .build();
+        assert_eq!(mime_db.load_warnings().len(), matches.len());
+    }
+
+    #[test]
+    fn print_tree() {
+        let mime_db = load_test_data();
+
+        let mut res = Vec::new();
+        mime_db.print_tree("application/x-gzip", &mut res).unwrap();
+        let res = String::from_utf8(res).unwrap();
+
+        let mut lines = res.lines();
+        assert_eq!(
+            lines.next(),
+            Some("application/gzip [aliases: application/x-gzip] (1 glob)")
+        );
+        assert!(lines.all(|l| l.starts_with("  ")));
+        assert!(res.contains("\n  application/x-compressed-tar"));
+    }
+
+    #[test]
+    fn install_package() {
+        let dir = TempDir::new("install");
+
+        let mut mime_db = SharedMimeInfo::builder()
+            .system_directories(false)
+            .directory("test_files")
+            .directory(dir.path())
+            .build();
+        assert_eq!(
+            mime_db.get_mime_types_from_file_name("foo.xdgtest"),
+            vec![UNKNOWN_TYPE.to_string()]
+        );
+
+        mime_db
+            .install_package("test_files/packages/x-xdg-mime-test.xml", Scope::Directory(dir.path().to_path_buf()))
+            .unwrap();
+        assert!(dir.join("mime/packages/x-xdg-mime-test.xml").is_file());
+
+        assert_eq!(
+            mime_db.get_mime_types_from_file_name("foo.xdgtest"),
+            vec!["application/x-xdg-mime-test".to_string()]
+        );
+        assert_eq!(
+            mime_db.get_mime_type_for_data(b"XDG\0TEST\0\0\x01\x02"),
+            Some("application/x-xdg-mime-test".to_string())
+        );
+        assert_eq!(
+            mime_db.unalias_mime_type("application/x-xdg-test"),
+            Some("application/x-xdg-mime-test".to_string())
+        );
+
+        let bad_package = dir.write("bad.xml", "<mime-info>");
+        let res = mime_db.install_package(&bad_package, Scope::Directory(dir.path().to_path_buf()));
+        assert_eq!(res.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert!(!dir.join("mime/packages/bad.xml").exists());
+    }
+
+    #[test]
+    fn dump_state() {
+        let mime_db = load_test_data();
+
+        let dir = TempDir::new("dump");
+        mime_db.dump_state(dir.path()).unwrap();
+
+        let files = fs::read_to_string(dir.join("files")).unwrap();
+        assert!(files.lines().any(|l| l.ends_with("test_files/mime/mime.cache")));
+        assert!(files.lines().any(|l| l.ends_with("test_files/mime/types")));
+        assert!(!files.lines().any(|l| l.ends_with("test_files/mime/globs")));
+
+        let globs = fs::read_to_string(dir.join("globs2")).unwrap();
+        assert!(globs.lines().any(|l| l == "50:image/gif:*.gif"));
+
+        let aliases = fs::read_to_string(dir.join("aliases")).unwrap();
+        assert!(aliases.lines().any(|l| l == "application/ics text/calendar"));
+
+        let magic = fs::read_to_string(dir.join("magic")).unwrap();
+        assert!(magic.lines().any(|l| l.starts_with("[50:image/png] ")));
+    }
 }
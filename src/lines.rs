@@ -0,0 +1,94 @@
+use std::io;
+use std::io::BufRead;
+
+/// Parses each line of @reader with @parse, skipping comments and empty
+/// lines, along with the line numbers.
+///
+/// A UTF-8 byte order mark at the beginning of @reader, and the carriage
+/// returns of CRLF line endings, are ignored.
+///
+/// The lines that cannot be parsed, including the ones that are not valid
+/// UTF-8, are returned as errors of kind `InvalidData`.
+pub fn parse_lines<R, T, F>(reader: R, parse: F) -> impl Iterator<Item = (usize, io::Result<T>)>
+where
+    R: BufRead,
+    F: Fn(String) -> Option<T>,
+{
+    reader.lines().enumerate().filter_map(move |(idx, line)| {
+        let mut line = match line {
+            Ok(v) => v,
+            Err(e) => return Some((idx + 1, Err(e))),
+        };
+
+        if idx == 0 && line.starts_with('\u{feff}') {
+            line.drain(..'\u{feff}'.len_utf8());
+        }
+        // Only the \r of a \r\n is removed, not the one of a last line
+        if line.ends_with('\r') {
+            line.pop();
+        }
+
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        match parse(line) {
+            Some(v) => Some((idx + 1, Ok(v))),
+            None => Some((
+                idx + 1,
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "invalid entry",
+                )),
+            )),
+        }
+    })
+}
+
+/// Collects the valid entries, stopping at the first I/O error.
+pub fn collect_valid<T, I: Iterator<Item = io::Result<T>>>(entries: I) -> Vec<T> {
+    let mut res = Vec::new();
+
+    for entry in entries {
+        match entry {
+            Ok(v) => res.push(v),
+            // Skip the invalid lines
+            Err(ref e) if e.kind() == io::ErrorKind::InvalidData => continue,
+            Err(_) => break,
+        }
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let data = b"# comment\n\nfoo\nbar\n\xff\nfoo\n";
+        let res: Vec<(usize, Result<String, io::ErrorKind>)> =
+            parse_lines(&data[..], |s| if s == "foo" { Some(s) } else { None })
+                .map(|(n, r)| (n, r.map_err(|e| e.kind())))
+                .collect();
+
+        assert_eq!(
+            res,
+            vec![
+                (3, Ok("foo".to_string())),
+                (4, Err(io::ErrorKind::InvalidData)),
+                (5, Err(io::ErrorKind::InvalidData)),
+                (6, Ok("foo".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn bom_and_crlf() {
+        let data = "\u{feff}foo\r\nfoo\r\nbar\u{feff}\r";
+        let res: Vec<String> = parse_lines(data.as_bytes(), Some).filter_map(|(_, r)| r.ok()).collect();
+
+        assert_eq!(res, vec!["foo", "foo", "bar\u{feff}"]);
+    }
+}
@@ -0,0 +1,803 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::{BufReader, Cursor};
+use std::path::{Path, PathBuf};
+
+use crate::alias::Alias;
+use crate::cache::{self, MimeCache};
+use crate::compile;
+use crate::glob::Glob;
+use crate::icon::Icon;
+use crate::lines;
+use crate::magic::{self, MagicEntry};
+use crate::package;
+use crate::parent::Subclass;
+use crate::treemagic::{self, TreeMagicRule};
+use crate::types;
+
+/// The kind of a [`LoadWarning`], to tell the problems apart without
+/// parsing their description.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadWarningKind {
+    /// A file could not be read.
+    Io(io::ErrorKind),
+    /// A line or an entry is malformed, or not valid UTF-8.
+    Syntax,
+    /// The weight of a glob is not a positive number.
+    InvalidWeight,
+    /// The pattern of a glob is not a valid glob.
+    InvalidPattern,
+    /// The pattern of a glob is above the
+    /// [maximum complexity](Loader::max_glob_complexity).
+    ComplexGlob,
+    /// A file of the database is missing from the data directory.
+    MissingFile,
+    /// The binary cache is invalid; the text files are used instead.
+    InvalidCache,
+    /// An XML package cannot be read or compiled.
+    InvalidPackage,
+    /// A sub-class relationship makes a cycle; it is ignored.
+    SubclassCycle,
+    /// A remote database cannot be fetched.
+    Remote,
+}
+
+/// A problem found while loading a data directory; the content causing it
+/// is skipped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoadWarning {
+    /// The file with the problem.
+    pub file: PathBuf,
+    /// The line of the problem, starting at 1, for the text files.
+    pub line: Option<usize>,
+    /// The kind of the problem.
+    pub kind: LoadWarningKind,
+    /// The description of the problem.
+    pub reason: String,
+}
+
+// Formats the warning like a compiler diagnostic
+impl fmt::Display for LoadWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.file.display(), line, self.reason),
+            None => write!(f, "{}: {}", self.file.display(), self.reason),
+        }
+    }
+}
+
+/// The MIME information loaded from a single data directory.
+pub struct DatabaseSection {
+    pub(crate) directory: PathBuf,
+    pub(crate) aliases: Vec<Alias>,
+    pub(crate) subclasses: Vec<Subclass>,
+    pub(crate) icons: Vec<Icon>,
+    pub(crate) generic_icons: Vec<Icon>,
+    pub(crate) globs: Vec<Glob>,
+    pub(crate) magic: Vec<MagicEntry>,
+    pub(crate) treemagic: Vec<TreeMagicRule>,
+    pub(crate) types: Vec<String>,
+    pub(crate) files: Vec<PathBuf>,
+}
+
+impl DatabaseSection {
+    /// The data directory the section was loaded from.
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    pub fn aliases(&self) -> &[Alias] {
+        &self.aliases
+    }
+
+    pub fn subclasses(&self) -> &[Subclass] {
+        &self.subclasses
+    }
+
+    pub fn icons(&self) -> &[Icon] {
+        &self.icons
+    }
+
+    pub fn generic_icons(&self) -> &[Icon] {
+        &self.generic_icons
+    }
+
+    pub fn globs(&self) -> &[Glob] {
+        &self.globs
+    }
+
+    pub fn treemagic(&self) -> &[TreeMagicRule] {
+        &self.treemagic
+    }
+
+    pub fn types(&self) -> &[String] {
+        &self.types
+    }
+
+    /// The database files that were found in the directory.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+}
+
+/// The database files of a data directory held in memory, like the files
+/// embedded in a program with `include_bytes!`, for the systems without
+/// any MIME database on disk.
+///
+/// The files have the formats of the ones written by
+/// `update-mime-database` in the `mime` sub-directory of a data directory.
+#[derive(Clone)]
+pub struct MemoryDirectory {
+    name: PathBuf,
+    files: HashMap<&'static str, Vec<u8>>,
+}
+
+impl MemoryDirectory {
+    /// Creates an empty directory; @name stands for its path in the load
+    /// warnings and the source of its globs, and is never read.
+    pub fn new<P: AsRef<Path>>(name: P) -> MemoryDirectory {
+        MemoryDirectory {
+            name: name.as_ref().to_path_buf(),
+            files: HashMap::new(),
+        }
+    }
+
+    fn file(mut self, name: &'static str, data: &[u8]) -> MemoryDirectory {
+        self.files.insert(name, data.to_vec());
+        self
+    }
+
+    /// Sets the contents of the `globs2` file.
+    pub fn globs2(self, data: &[u8]) -> MemoryDirectory {
+        self.file("globs2", data)
+    }
+
+    /// Adds the globs of @extensions, pairs of a file extension and its
+    /// MIME type like the `Content Type` values of the extension keys of
+    /// `HKEY_CLASSES_ROOT` on Windows, to the `globs2` file set so far.
+    ///
+    /// The pairs that are not an extension and a MIME type are skipped.
+    pub fn extensions<I, S>(mut self, extensions: I) -> MemoryDirectory
+    where
+        I: IntoIterator<Item = (S, S)>,
+        S: AsRef<str>,
+    {
+        let globs = self.files.entry("globs2").or_default();
+        if !globs.is_empty() && !globs.ends_with(b"\n") {
+            globs.push(b'\n');
+        }
+        for (extension, mime_type) in extensions {
+            let extension = extension.as_ref().trim_start_matches('.');
+            let mime_type = mime_type.as_ref().trim();
+            let valid = |s: &str| !s.is_empty() && !s.contains(|c: char| c == ':' || c.is_whitespace());
+            if !valid(extension) || !valid(mime_type) || !mime_type.contains('/') {
+                continue;
+            }
+
+            globs.extend_from_slice(format!("50:{}:*.{}\n", mime_type.to_ascii_lowercase(), extension).as_bytes());
+        }
+
+        self
+    }
+
+    /// Sets the contents of the `globs` file, only used without a
+    /// `globs2` file.
+    pub fn globs(self, data: &[u8]) -> MemoryDirectory {
+        self.file("globs", data)
+    }
+
+    /// Sets the contents of the `magic` file.
+    pub fn magic(self, data: &[u8]) -> MemoryDirectory {
+        self.file("magic", data)
+    }
+
+    /// Sets the contents of the `aliases` file.
+    pub fn aliases(self, data: &[u8]) -> MemoryDirectory {
+        self.file("aliases", data)
+    }
+
+    /// Sets the contents of the `subclasses` file.
+    pub fn subclasses(self, data: &[u8]) -> MemoryDirectory {
+        self.file("subclasses", data)
+    }
+
+    /// Sets the contents of the `icons` file.
+    pub fn icons(self, data: &[u8]) -> MemoryDirectory {
+        self.file("icons", data)
+    }
+
+    /// Sets the contents of the `generic-icons` file.
+    pub fn generic_icons(self, data: &[u8]) -> MemoryDirectory {
+        self.file("generic-icons", data)
+    }
+
+    /// Sets the contents of the `treemagic` file.
+    pub fn treemagic(self, data: &[u8]) -> MemoryDirectory {
+        self.file("treemagic", data)
+    }
+
+    /// Sets the contents of the `types` file.
+    pub fn types(self, data: &[u8]) -> MemoryDirectory {
+        self.file("types", data)
+    }
+
+    /// The path standing for the directory.
+    pub fn name(&self) -> &Path {
+        &self.name
+    }
+}
+
+// The files written by update-mime-database
+const EXPECTED_FILES: &[&str] = &[
+    "aliases",
+    "generic-icons",
+    "globs2",
+    "icons",
+    "magic",
+    "subclasses",
+    "types",
+];
+
+// The generated files whose absence means that update-mime-database was
+// never run on a directory
+const GENERATED_FILES: &[&str] = &["mime.cache", "globs2", "globs", "magic"];
+
+/// Loads the MIME information of data directories, reporting the content
+/// that cannot be loaded.
+#[derive(Clone, Default)]
+pub struct Loader {
+    report_missing_files: bool,
+    ignore_cache: bool,
+    max_glob_complexity: Option<usize>,
+}
+
+/// The error returned when a database cannot be loaded in strict mode.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoadError {
+    warnings: Vec<LoadWarning>,
+}
+
+impl LoadError {
+    pub(crate) fn new(warnings: Vec<LoadWarning>) -> LoadError {
+        LoadError { warnings }
+    }
+
+    /// The problems found while loading the database.
+    pub fn warnings(&self) -> &[LoadWarning] {
+        &self.warnings
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid MIME database")?;
+        for warning in &self.warnings {
+            write!(f, "\n{}", warning)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+// The state of the loading of a single directory
+struct Load {
+    mime_dir: PathBuf,
+    files: Vec<PathBuf>,
+    warnings: Vec<LoadWarning>,
+    // The database files compiled in memory from the XML packages, which
+    // replace the missing generated files
+    compiled: HashMap<&'static str, Vec<u8>>,
+    // Whether the files are only in memory, with none on disk
+    in_memory: bool,
+}
+
+impl Load {
+    fn warn(&mut self, file: &Path, line: Option<usize>, kind: LoadWarningKind, reason: String) {
+        let warning = LoadWarning {
+            file: file.to_path_buf(),
+            line,
+            kind,
+            reason,
+        };
+
+        #[cfg(feature = "log")]
+        log::warn!("Skipped MIME database content: {}", warning);
+
+        self.warnings.push(warning);
+    }
+
+    // Opens the database file @name, which is not an error if missing
+    fn open(&mut self, name: &str) -> Option<(PathBuf, Box<dyn Read>)> {
+        let file_name = self.mime_dir.join(name);
+
+        if let Some(data) = self.compiled.get(name) {
+            return Some((file_name, Box::new(Cursor::new(data.clone()))));
+        }
+        if self.in_memory {
+            return None;
+        }
+
+        match File::open(&file_name) {
+            Ok(f) => {
+                self.files.push(file_name.clone());
+                Some((file_name, Box::new(f)))
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => {
+                self.warn(&file_name, None, LoadWarningKind::Io(e.kind()), e.to_string());
+                None
+            }
+        }
+    }
+
+    fn load_lines<T, F>(&mut self, name: &str, parse: F) -> Option<Vec<T>>
+    where
+        F: Fn(String) -> Option<T>,
+    {
+        self.load_entries(name, |l| parse(l).ok_or(LoadWarningKind::Syntax))
+    }
+
+    // Like load_lines(), with @parse telling why the invalid lines are
+    fn load_entries<T, F>(&mut self, name: &str, parse: F) -> Option<Vec<T>>
+    where
+        F: Fn(String) -> Result<T, LoadWarningKind>,
+    {
+        let (file_name, f) = self.open(name)?;
+
+        let mut res = Vec::new();
+        for (line, entry) in lines::parse_lines(BufReader::new(f), |l| Some(parse(l))) {
+            match entry {
+                Ok(Ok(v)) => res.push(v),
+                Ok(Err(kind)) => {
+                    let reason = match kind {
+                        LoadWarningKind::InvalidWeight => "invalid weight",
+                        LoadWarningKind::InvalidPattern => "invalid pattern",
+                        _ => "invalid entry",
+                    };
+                    self.warn(&file_name, Some(line), kind, reason.to_string());
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::InvalidData => {
+                    self.warn(&file_name, Some(line), LoadWarningKind::Syntax, e.to_string());
+                }
+                // Stop at the first I/O error
+                Err(e) => {
+                    self.warn(&file_name, Some(line), LoadWarningKind::Io(e.kind()), e.to_string());
+                    break;
+                }
+            }
+        }
+
+        Some(res)
+    }
+
+    // Reads the whole database file @name, which is not an error if missing
+    fn read(&mut self, name: &str) -> Option<(PathBuf, Vec<u8>)> {
+        let (file_name, mut f) = self.open(name)?;
+
+        let mut data = Vec::new();
+        if let Err(e) = f.read_to_end(&mut data) {
+            self.warn(&file_name, None, LoadWarningKind::Io(e.kind()), e.to_string());
+            return None;
+        }
+
+        Some((file_name, data))
+    }
+
+    fn load_magic(&mut self) -> Vec<MagicEntry> {
+        let (file_name, data) = match self.read("magic") {
+            Some(v) => v,
+            None => return Vec::new(),
+        };
+
+        let (mut entries, unparsed) = magic::read_magic(&data);
+        if unparsed > 0 {
+            let reason = format!(
+                "invalid magic entry at offset {}, {} bytes skipped",
+                data.len() - unparsed,
+                unparsed
+            );
+            self.warn(&file_name, None, LoadWarningKind::Syntax, reason);
+        }
+        for entry in entries.iter_mut() {
+            entry.set_source(&file_name);
+        }
+
+        entries
+    }
+
+    // Compiles the XML packages of the directory in memory, like
+    // update-mime-database does, if it never ran; returns whether there
+    // were packages
+    fn compile_packages(&mut self) -> bool {
+        if GENERATED_FILES.iter().any(|name| self.mime_dir.join(name).exists()) {
+            return false;
+        }
+
+        let files = match compile::package_files(&self.mime_dir) {
+            Ok(v) => v,
+            Err(e) => {
+                let dir = self.mime_dir.join("packages");
+                self.warn(&dir, None, LoadWarningKind::InvalidPackage, e.to_string());
+                return false;
+            }
+        };
+        if files.is_empty() {
+            return false;
+        }
+
+        let mut packages = Vec::new();
+        for file in files {
+            match package::read_package_from_file(&file) {
+                Ok(v) => packages.push(v),
+                Err(e) => self.warn(&file, None, LoadWarningKind::InvalidPackage, e.to_string()),
+            }
+            self.files.push(file);
+        }
+
+        match compile::database_files(&compile::merge_definitions(packages)) {
+            Ok(v) => self.compiled = v.into_iter().collect(),
+            Err(e) => {
+                let dir = self.mime_dir.join("packages");
+                self.warn(&dir, None, LoadWarningKind::InvalidPackage, e.to_string());
+            }
+        }
+
+        true
+    }
+
+    // Loads the binary cache, if there is a valid one
+    fn load_cache(&mut self) -> Option<MimeCache> {
+        let file_name = self.mime_dir.join("mime.cache");
+
+        match cache::read_cache_from_file(&file_name) {
+            Ok(Some(v)) => {
+                self.files.push(file_name);
+                Some(v)
+            }
+            // The text files are used with the other versions
+            Ok(None) => None,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => {
+                let reason = format!("invalid cache, using the text files: {}", e);
+                self.warn(&file_name, None, LoadWarningKind::InvalidCache, reason);
+                None
+            }
+        }
+    }
+
+    fn load_treemagic(&mut self) -> Vec<TreeMagicRule> {
+        let (file_name, data) = match self.read("treemagic") {
+            Some(v) => v,
+            None => return Vec::new(),
+        };
+
+        let (rules, unparsed) = treemagic::read_treemagic(&data);
+        if unparsed > 0 {
+            let reason = format!(
+                "invalid treemagic entry at offset {}, {} bytes skipped",
+                data.len() - unparsed,
+                unparsed
+            );
+            self.warn(&file_name, None, LoadWarningKind::Syntax, reason);
+        }
+
+        rules
+    }
+
+    // Loads the section of @directory, from the @cache if there is one,
+    // skipping the globs more complex than @max_glob_complexity
+    fn load_section(
+        &mut self,
+        directory: &Path,
+        cache: Option<MimeCache>,
+        max_glob_complexity: Option<usize>,
+    ) -> DatabaseSection {
+        let has_cache = cache.is_some();
+        let (aliases, subclasses, icons, generic_icons, mut globs, magic) = match cache {
+            Some(c) => (c.aliases, c.subclasses, c.icons, c.generic_icons, c.globs, c.magic),
+            None => {
+                let aliases = self.load_lines("aliases", Alias::from_string).unwrap_or_default();
+                let mut icons = self.load_lines("icons", |l| Icon::from_string(&l)).unwrap_or_default();
+                icons.sort_unstable();
+                let mut generic_icons =
+                    self.load_lines("generic-icons", |l| Icon::from_string(&l)).unwrap_or_default();
+                generic_icons.sort_unstable();
+                let subclasses = self.load_lines("subclasses", Subclass::from_string).unwrap_or_default();
+
+                // The globs file is only used without a globs2 file
+                let globs = match self.load_entries("globs2", Glob::parse_v2) {
+                    Some(v) => v,
+                    None => self.load_entries("globs", Glob::parse_v1).unwrap_or_default(),
+                };
+
+                (aliases, subclasses, icons, generic_icons, globs, self.load_magic())
+            }
+        };
+        for glob in globs.iter_mut() {
+            glob.set_source_directory(directory);
+        }
+        if let Some(max) = max_glob_complexity {
+            let globs_file = if has_cache {
+                "mime.cache"
+            } else if self.mime_dir.join("globs2").exists() || self.compiled.contains_key("globs2") {
+                "globs2"
+            } else {
+                "globs"
+            };
+            let file_name = self.mime_dir.join(globs_file);
+            let (simple, complex): (Vec<Glob>, Vec<Glob>) = globs.into_iter().partition(|g| g.complexity() <= max);
+            for glob in complex {
+                let reason = format!("glob too complex: {} (complexity {}, at most {})", glob, glob.complexity(), max);
+                self.warn(&file_name, None, LoadWarningKind::ComplexGlob, reason);
+            }
+            globs = simple;
+        }
+
+        let treemagic = self.load_treemagic();
+        let types = self.load_lines("types", types::parse_type).unwrap_or_default();
+
+        DatabaseSection {
+            directory: directory.to_path_buf(),
+            aliases,
+            subclasses,
+            icons,
+            generic_icons,
+            globs,
+            magic,
+            treemagic,
+            types,
+            files: std::mem::take(&mut self.files),
+        }
+    }
+}
+
+impl Loader {
+    /// Creates a new loader.
+    pub fn new() -> Loader {
+        Loader {
+            report_missing_files: false,
+            ignore_cache: false,
+            max_glob_complexity: None,
+        }
+    }
+
+    /// Sets whether the database files missing from a data directory are
+    /// reported as warnings.
+    pub fn report_missing_files(mut self, report: bool) -> Loader {
+        self.report_missing_files = report;
+        self
+    }
+
+    /// Sets whether the binary `mime.cache` file of the data directories
+    /// is used, when present, instead of the text files it is generated
+    /// from; it is by default.
+    pub fn use_cache(mut self, use_cache: bool) -> Loader {
+        self.ignore_cache = !use_cache;
+        self
+    }
+
+    /// Sets the largest [complexity](Glob::complexity) of the globs loaded,
+    /// to protect the lookups from the pathological patterns of untrusted
+    /// packages; the globs above it are skipped, and reported as warnings.
+    pub fn max_glob_complexity(mut self, max: usize) -> Loader {
+        self.max_glob_complexity = Some(max);
+        self
+    }
+
+    /// Loads the MIME information from the `mime` sub-directory of the
+    /// data directory @directory.
+    ///
+    /// The aliases, sub-classes, icons, globs and magic entries are read
+    /// from the binary `mime.cache` file if there is a valid one, which is
+    /// faster than parsing the text files; the types and the treemagic
+    /// rules are always read from the text files.
+    ///
+    /// If `update-mime-database` never ran on the directory, leaving it
+    /// without any generated file, the XML packages of its `packages`
+    /// sub-directory are compiled in memory instead.
+    ///
+    /// Missing files are not reported unless requested, since a data
+    /// directory does not need to have them all.
+    pub fn load_directory<P: AsRef<Path>>(&self, directory: P) -> (DatabaseSection, Vec<LoadWarning>) {
+        let directory = directory.as_ref();
+        let mut load = Load {
+            mime_dir: directory.join("mime"),
+            files: Vec::new(),
+            warnings: Vec::new(),
+            compiled: HashMap::new(),
+            in_memory: false,
+        };
+
+        let from_packages = load.compile_packages();
+        let cache = if self.ignore_cache || from_packages { None } else { load.load_cache() };
+        let section = load.load_section(directory, cache, self.max_glob_complexity);
+
+        if self.report_missing_files && !from_packages {
+            for name in EXPECTED_FILES {
+                let file_name = load.mime_dir.join(name);
+                // The files replaced by the cache are not read
+                if !section.files.contains(&file_name) && !file_name.exists() {
+                    load.warn(&file_name, None, LoadWarningKind::MissingFile, "missing file".to_string());
+                }
+            }
+        }
+
+        (section, load.warnings)
+    }
+
+    /// Loads the MIME information from the files of @directory, held in
+    /// memory; the missing files are never reported.
+    pub fn load_memory_directory(&self, directory: &MemoryDirectory) -> (DatabaseSection, Vec<LoadWarning>) {
+        let mut load = Load {
+            mime_dir: directory.name.join("mime"),
+            files: Vec::new(),
+            warnings: Vec::new(),
+            compiled: directory.files.clone(),
+            in_memory: true,
+        };
+
+        let section = load.load_section(&directory.name, None, self.max_glob_complexity);
+
+        (section, load.warnings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use std::fs;
+
+    #[test]
+    fn load_test_data() {
+        let (section, warnings) = Loader::new().load_directory("test_files");
+
+        assert_eq!(warnings, vec![]);
+        assert_eq!(section.directory(), Path::new("test_files"));
+        assert!(section.aliases().contains(&Alias::new("application/x-gzip", "application/gzip")));
+        assert_eq!(section.types().len(), 790);
+        assert!(!section.magic.is_empty());
+        assert!(!section.files().contains(&PathBuf::from("test_files/mime/globs")));
+    }
+
+    #[test]
+    fn load_memory_files() {
+        let directory = MemoryDirectory::new("embedded")
+            .globs2(b"50:text/x-foo:*.foo\nfoo\n")
+            .aliases(include_bytes!("../test_files/mime/aliases"));
+
+        let (section, warnings) = Loader::new().report_missing_files(true).load_memory_directory(&directory);
+        assert_eq!(section.directory(), Path::new("embedded"));
+        assert_eq!(section.globs(), &[Glob::with_weight("text/x-foo", "*.foo", 50)]);
+        assert!(section.aliases().contains(&Alias::new("application/x-gzip", "application/gzip")));
+        assert!(section.files().is_empty());
+
+        // Only the invalid line is reported, and nothing is read from disk
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].file, PathBuf::from("embedded/mime/globs2"));
+        assert_eq!(warnings[0].line, Some(2));
+
+        let (section, _) = Loader::new().load_memory_directory(&MemoryDirectory::new("test_files"));
+        assert!(section.globs().is_empty());
+    }
+
+    #[test]
+    fn memory_extensions() {
+        let directory = MemoryDirectory::new("registry")
+            .globs2(b"50:text/x-foo:*.foo")
+            .extensions(vec![(".bar", "Text/X-Bar"), ("baz", "not a type"), ("", "text/x-qux")])
+            .extensions(vec![("qux", "text/x-qux")]);
+
+        let (section, warnings) = Loader::new().load_memory_directory(&directory);
+        assert_eq!(warnings, vec![]);
+        assert_eq!(
+            section.globs(),
+            &[
+                Glob::with_weight("text/x-foo", "*.foo", 50),
+                Glob::with_weight("text/x-bar", "*.bar", 50),
+                Glob::with_weight("text/x-qux", "*.qux", 50),
+            ]
+        );
+    }
+
+    #[test]
+    fn load_windows_files() {
+        let (section, warnings) = Loader::new().load_directory("test_files/windows");
+
+        assert_eq!(warnings, vec![]);
+        assert_eq!(section.globs()[0], Glob::new("text/x-c++src", "*.C", 50, true));
+        assert_eq!(section.aliases(), &[Alias::new("application/x-c", "text/x-csrc")]);
+        assert_eq!(section.subclasses().len(), 1);
+        assert_eq!(section.generic_icons()[0].icon_name(), "text-x-generic");
+        assert_eq!(section.types(), &["text/x-c++src".to_string(), "text/x-csrc".to_string()]);
+    }
+
+    #[test]
+    fn missing_files() {
+        let loader = Loader::new().report_missing_files(true);
+
+        let (_, warnings) = loader.load_directory("test_files");
+        assert_eq!(warnings, vec![]);
+
+        let (_, warnings) = loader.load_directory("test_files/nonexistent");
+        assert_eq!(warnings.len(), EXPECTED_FILES.len());
+        assert_eq!(warnings[0].file, PathBuf::from("test_files/nonexistent/mime/aliases"));
+        assert_eq!(warnings[0].kind, LoadWarningKind::MissingFile);
+        assert_eq!(warnings[0].reason, "missing file");
+    }
+
+    #[test]
+    fn load_warnings() {
+        let dir = TempDir::new("loader");
+        fs::create_dir_all(dir.join("mime")).unwrap();
+        fs::write(
+            dir.join("mime/globs2"),
+            b"# globs\n50:text/x-foo:*.foo\nfoo\n\xff\nheavy:text/x-bar:*.bar\n50:text/x-baz:[.baz\n",
+        )
+        .unwrap();
+        fs::write(dir.join("mime/magic"), "MIME-Magic\0\n[50:text/x-foo]\ngarbage").unwrap();
+
+        let (section, warnings) = Loader::new().load_directory(dir.path());
+        assert_eq!(section.globs().len(), 1);
+        assert!(section.magic.is_empty());
+
+        let globs_file = dir.join("mime/globs2");
+        assert_eq!(warnings.len(), 5);
+        assert_eq!(warnings[0].file, globs_file);
+        assert_eq!(warnings[0].line, Some(3));
+        assert_eq!(warnings[0].kind, LoadWarningKind::Syntax);
+        assert_eq!(warnings[0].reason, "invalid entry");
+        assert_eq!(warnings[1].line, Some(4));
+        assert_eq!(warnings[1].kind, LoadWarningKind::Syntax);
+        assert_eq!(warnings[2].kind, LoadWarningKind::InvalidWeight);
+        assert_eq!(warnings[2].reason, "invalid weight");
+        assert_eq!(warnings[3].line, Some(6));
+        assert_eq!(warnings[3].kind, LoadWarningKind::InvalidPattern);
+        assert_eq!(warnings[4].file, dir.join("mime/magic"));
+        assert_eq!(warnings[4].line, None);
+        assert_eq!(warnings[4].kind, LoadWarningKind::Syntax);
+        assert_eq!(
+            warnings[0].to_string(),
+            format!("{}:3: invalid entry", globs_file.display())
+        );
+    }
+
+    #[test]
+    fn load_packages() {
+        let dir = TempDir::new("loader-packages");
+        fs::create_dir_all(dir.join("mime/packages")).unwrap();
+        let package_file = dir.join("mime/packages/x-xdg-mime-test.xml");
+        fs::copy("test_files/packages/x-xdg-mime-test.xml", &package_file).unwrap();
+        fs::write(dir.join("mime/packages/bad.xml"), "<mime-info>").unwrap();
+
+        let (section, warnings) = Loader::new().report_missing_files(true).load_directory(dir.path());
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].file, dir.join("mime/packages/bad.xml"));
+        assert_eq!(section.globs()[0], Glob::with_weight("application/x-xdg-mime-test", "*.xdgtest", 60));
+        assert_eq!(
+            section.aliases(),
+            &[Alias::new("application/x-xdg-test", "application/x-xdg-mime-test")]
+        );
+        assert_eq!(section.generic_icons()[0].icon_name(), "text-x-generic");
+        assert_eq!(section.types(), &["application/x-xdg-mime-test".to_string()]);
+        assert!(section.files().contains(&package_file));
+
+        let mime_db = crate::SharedMimeInfo::new_for_directory(dir.path());
+        assert_eq!(
+            mime_db.get_mime_type_for_data(b"XDG\0TEST\x01\x02"),
+            Some("application/x-xdg-mime-test".to_string())
+        );
+        assert!(mime_db.is_subclass_of("application/x-xdg-test", "text/plain"));
+
+        // The generated files take precedence
+        fs::write(dir.join("mime/globs2"), "50:text/x-foo:*.foo\n").unwrap();
+        let (section, _) = Loader::new().load_directory(dir.path());
+        assert_eq!(section.globs().len(), 1);
+        assert_eq!(section.globs()[0].mime_type(), "text/x-foo");
+    }
+}
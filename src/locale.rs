@@ -0,0 +1,259 @@
+use std::env;
+use std::io;
+use std::io::prelude::*;
+
+// Returns the variants of @locale, like `sr_RS.UTF-8@latin`, that can be
+// used as the `xml:lang` of a comment, from the most to the least specific:
+// `sr_RS@latin`, `sr_RS`, `sr@latin`, `sr`
+pub(crate) fn locale_variants(locale: &str) -> Vec<String> {
+    let (rest, modifier) = match locale.find('@') {
+        Some(idx) => (&locale[..idx], Some(&locale[idx..])),
+        None => (locale, None),
+    };
+    // The codeset is not used in the XML files
+    let rest = match rest.find('.') {
+        Some(idx) => &rest[..idx],
+        None => rest,
+    };
+    let (lang, country) = match rest.find('_') {
+        Some(idx) => (&rest[..idx], Some(&rest[idx..])),
+        None => (rest, None),
+    };
+
+    let mut res = Vec::new();
+    if lang.is_empty() {
+        return res;
+    }
+
+    if let (Some(country), Some(modifier)) = (country, modifier) {
+        res.push(format!("{}{}{}", lang, country, modifier));
+    }
+    if let Some(country) = country {
+        res.push(format!("{}{}", lang, country));
+    }
+    if let Some(modifier) = modifier {
+        res.push(format!("{}{}", lang, modifier));
+    }
+    res.push(lang.to_string());
+
+    res
+}
+
+/// How many of the known MIME types have a description translated in a
+/// locale, as returned by
+/// [`SharedMimeInfo::translation_coverage`][crate::SharedMimeInfo::translation_coverage].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TranslationCoverage {
+    /// The locale of the report.
+    pub locale: String,
+    /// The number of known MIME types.
+    pub total: usize,
+    /// The number of MIME types with a translated description.
+    pub translated: usize,
+    /// The MIME types without a translated description, sorted by name.
+    pub missing: Vec<String>,
+}
+
+impl TranslationCoverage {
+    /// Returns the ratio of translated types, between 0 and 1.
+    pub fn ratio(&self) -> f64 {
+        if self.total == 0 {
+            return 1.0;
+        }
+
+        self.translated as f64 / self.total as f64
+    }
+}
+
+/// The formats of [`SharedMimeInfo::export_descriptions`][crate::SharedMimeInfo::export_descriptions].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// A gettext PO catalog, with the untranslated descriptions as
+    /// `msgid` and the MIME types as `msgctxt`.
+    Po,
+    /// A JSON object mapping the MIME types to their descriptions.
+    Json,
+}
+
+// A description to export: the MIME type, its untranslated comment and its
+// translation, if any
+pub(crate) type Description = (String, String, Option<String>);
+
+fn escape_po(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\t' => res.push_str("\\t"),
+            c => res.push(c),
+        }
+    }
+
+    res
+}
+
+fn escape_json(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            c if (c as u32) < 0x20 => res.push_str(&format!("\\u{:04x}", c as u32)),
+            c => res.push(c),
+        }
+    }
+
+    res
+}
+
+/// Writes @descriptions for @locale to @writer in @format.
+pub(crate) fn write_descriptions<W: Write>(
+    locale: &str,
+    descriptions: &[Description],
+    format: Format,
+    writer: &mut W,
+) -> io::Result<()> {
+    match format {
+        Format::Po => {
+            writeln!(writer, "msgid \"\"")?;
+            writeln!(writer, "msgstr \"\"")?;
+            writeln!(writer, "\"Language: {}\\n\"", escape_po(locale))?;
+            writeln!(writer, "\"MIME-Version: 1.0\\n\"")?;
+            writeln!(writer, "\"Content-Type: text/plain; charset=UTF-8\\n\"")?;
+            writeln!(writer, "\"Content-Transfer-Encoding: 8bit\\n\"")?;
+
+            for (mime_type, comment, translation) in descriptions {
+                writeln!(writer)?;
+                writeln!(writer, "msgctxt \"{}\"", escape_po(mime_type))?;
+                writeln!(writer, "msgid \"{}\"", escape_po(comment))?;
+                writeln!(
+                    writer,
+                    "msgstr \"{}\"",
+                    escape_po(translation.as_deref().unwrap_or(""))
+                )?;
+            }
+        }
+        Format::Json => {
+            write!(writer, "{{")?;
+            for (i, (mime_type, comment, translation)) in descriptions.iter().enumerate() {
+                let sep = if i == 0 { "" } else { "," };
+                write!(
+                    writer,
+                    "{}\n  \"{}\": \"{}\"",
+                    sep,
+                    escape_json(mime_type),
+                    escape_json(translation.as_ref().unwrap_or(comment))
+                )?;
+            }
+            writeln!(writer, "\n}}")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the chain of locales to look up the translated comments with,
+/// for the given values of the `LANGUAGE`, `LC_ALL`, `LC_MESSAGES` and
+/// `LANG` environment variables.
+///
+/// Like gettext, the first set of `LC_ALL`, `LC_MESSAGES` and `LANG`
+/// selects the locale, and the colon-separated list of `LANGUAGE` takes
+/// precedence over it, unless the locale is `C` or `POSIX`, in which case
+/// the chain is empty.
+pub fn locale_chain(
+    language: Option<&str>,
+    lc_all: Option<&str>,
+    lc_messages: Option<&str>,
+    lang: Option<&str>,
+) -> Vec<String> {
+    let locale = match [lc_all, lc_messages, lang]
+        .iter()
+        .flatten()
+        .find(|v| !v.is_empty())
+    {
+        Some(v) => *v,
+        None => return Vec::new(),
+    };
+
+    let is_c = |l: &str| l == "C" || l == "POSIX" || l.starts_with("C.");
+    if is_c(locale) {
+        return Vec::new();
+    }
+
+    let mut res: Vec<String> = Vec::new();
+    let languages = language.unwrap_or("").split(':').filter(|l| !l.is_empty() && !is_c(l));
+    for l in languages.chain(std::iter::once(locale)) {
+        for variant in locale_variants(l) {
+            if !res.contains(&variant) {
+                res.push(variant);
+            }
+        }
+    }
+
+    res
+}
+
+/// Returns the chain of locales to look up the translated comments with,
+/// from the environment of the process; see [`locale_chain`].
+pub fn locale_chain_from_env() -> Vec<String> {
+    let var = |name: &str| env::var(name).ok();
+
+    locale_chain(
+        var("LANGUAGE").as_deref(),
+        var("LC_ALL").as_deref(),
+        var("LC_MESSAGES").as_deref(),
+        var("LANG").as_deref(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variants() {
+        assert_eq!(
+            locale_variants("sr_RS.UTF-8@latin"),
+            vec!["sr_RS@latin", "sr_RS", "sr@latin", "sr"]
+        );
+        assert_eq!(locale_variants("pt_BR"), vec!["pt_BR", "pt"]);
+        assert_eq!(locale_variants("de"), vec!["de"]);
+        assert!(locale_variants("").is_empty());
+    }
+
+    #[test]
+    fn write_formats() {
+        let descriptions = vec![
+            ("image/png".to_string(), "PNG image".to_string(), Some("PNG-Bild".to_string())),
+            ("text/x-q".to_string(), "\"quoted\" text".to_string(), None),
+        ];
+
+        let mut po = Vec::new();
+        write_descriptions("de", &descriptions, Format::Po, &mut po).unwrap();
+        let po = String::from_utf8(po).unwrap();
+        assert!(po.contains("\"Language: de\\n\""));
+        assert!(po.contains("msgctxt \"image/png\"\nmsgid \"PNG image\"\nmsgstr \"PNG-Bild\"\n"));
+        assert!(po.contains("msgid \"\\\"quoted\\\" text\"\nmsgstr \"\"\n"));
+
+        let mut json = Vec::new();
+        write_descriptions("de", &descriptions, Format::Json, &mut json).unwrap();
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            "{\n  \"image/png\": \"PNG-Bild\",\n  \"text/x-q\": \"\\\"quoted\\\" text\"\n}\n"
+        );
+    }
+
+    #[test]
+    fn chain() {
+        assert_eq!(
+            locale_chain(Some("fr:de_AT"), None, Some("en_GB.UTF-8"), Some("de_DE.UTF-8")),
+            vec!["fr", "de_AT", "de", "en_GB", "en"]
+        );
+        assert_eq!(locale_chain(None, Some(""), None, Some("pt_BR.UTF-8")), vec!["pt_BR", "pt"]);
+        assert!(locale_chain(Some("fr"), Some("C.UTF-8"), None, Some("de_DE")).is_empty());
+        assert!(locale_chain(Some("fr"), None, None, None).is_empty());
+    }
+}
@@ -1,14 +1,18 @@
 use nom::character::is_hex_digit;
 use nom::character::complete::line_ending;
 use nom::number::streaming::{be_u16};
-use std::cmp::Ordering;
+use nom::{IResult, Offset};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, HashSet};
 use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str;
 use std::vec::Vec;
 
+use crate::memo::Fingerprint;
+
 pub fn to_string(s: &[u8]) -> std::result::Result<&str, std::str::Utf8Error> {
     str::from_utf8(s)
 }
@@ -24,8 +28,86 @@ pub fn buf_to_u32(s: &[u8], or_default: u32) -> u32 {
     to_u32(to_string(s), or_default)
 }
 
+/// The data to match the magic rules against, which does not need to be
+/// contiguous in memory.
+pub trait MagicInput {
+    /// The length of the data, in bytes.
+    fn len(&self) -> usize;
+
+    /// The byte at @index, lower than the length of the data.
+    fn byte_at(&self, index: usize) -> u8;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The data as a single slice, if it is contiguous in memory; the
+    /// matching is faster when it is.
+    fn as_slice(&self) -> Option<&[u8]> {
+        None
+    }
+}
+
+impl MagicInput for [u8] {
+    fn len(&self) -> usize {
+        <[u8]>::len(self)
+    }
+
+    fn byte_at(&self, index: usize) -> u8 {
+        self[index]
+    }
+
+    fn as_slice(&self) -> Option<&[u8]> {
+        Some(self)
+    }
+}
+
+/// Data made of several chunks, e.g. the buffers of a vectored read,
+/// matched without being copied together.
+pub struct ChunkedData<'a> {
+    chunks: Vec<&'a [u8]>,
+    // The offset of the end of each chunk
+    ends: Vec<usize>,
+}
+
+impl<'a> ChunkedData<'a> {
+    /// Creates the data from its @chunks, in order.
+    pub fn new<I: IntoIterator<Item = &'a [u8]>>(chunks: I) -> ChunkedData<'a> {
+        let mut res = ChunkedData {
+            chunks: Vec::new(),
+            ends: Vec::new(),
+        };
+
+        let mut end = 0;
+        for chunk in chunks {
+            if chunk.is_empty() {
+                continue;
+            }
+
+            end += chunk.len();
+            res.chunks.push(chunk);
+            res.ends.push(end);
+        }
+
+        res
+    }
+}
+
+impl<'a> MagicInput for ChunkedData<'a> {
+    fn len(&self) -> usize {
+        self.ends.last().copied().unwrap_or(0)
+    }
+
+    fn byte_at(&self, index: usize) -> u8 {
+        let chunk = self.ends.partition_point(|end| *end <= index);
+        let start = if chunk == 0 { 0 } else { self.ends[chunk - 1] };
+
+        self.chunks[chunk][index - start]
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
-struct MagicRule {
+pub(crate) struct MagicRule {
     indent: u32,
     start_offset: u32,
     value_length: u16,
@@ -36,24 +118,68 @@ struct MagicRule {
 }
 
 impl MagicRule {
-    fn matches_data(&self, data: &[u8]) -> bool {
-        let start: usize = self.start_offset as usize;
-        let end: usize = self.start_offset as usize + self.range_length as usize;
+    pub(crate) fn new(
+        indent: u32,
+        start_offset: u32,
+        value: Vec<u8>,
+        mask: Option<Vec<u8>>,
+        word_size: u32,
+        range_length: u32,
+    ) -> MagicRule {
+        MagicRule {
+            indent,
+            start_offset,
+            value_length: value.len() as u16,
+            value,
+            mask,
+            word_size,
+            range_length,
+        }
+    }
+
+    pub(crate) fn word_size(&self) -> u32 {
+        self.word_size
+    }
+
+    // The number of bytes of data needed to evaluate the rule
+    fn extent(&self) -> usize {
+        (self.start_offset as usize)
+            .saturating_add((self.range_length as usize).saturating_sub(1))
+            .saturating_add(self.value_length as usize)
+    }
+}
+
+// The parts of a rule that decide whether it matches, shared by the
+// loaded rules and the static ones of the generated databases
+pub(crate) trait Matchlet {
+    fn indent(&self) -> u32;
+    fn start_offset(&self) -> u32;
+    fn range_length(&self) -> u32;
+    fn value(&self) -> &[u8];
+    fn mask(&self) -> Option<&[u8]>;
+
+    fn matches_data<D: MagicInput + ?Sized>(&self, data: &D) -> bool {
+        if let Some(slice) = data.as_slice() {
+            return self.matches_slice(slice);
+        }
+
+        let start: usize = self.start_offset() as usize;
+        let end: usize = start.saturating_add(self.range_length() as usize);
 
         for i in start .. end {
             let mut res: bool = true;
 
-            let value_len: usize = self.value_length as usize; 
+            let value_len: usize = self.value().len();
 
             if i + value_len > data.len() {
                 return false;
             }
 
-            match &self.mask {
+            match self.mask() {
                 Some(m) => {
-                    for j in 0 .. value_len {
-                        let masked_value = self.value[j] & m[j];
-                        let masked_data = data[j + i] & m[j];
+                    for (j, (value, mask)) in self.value().iter().zip(m).enumerate() {
+                        let masked_value = value & mask;
+                        let masked_data = data.byte_at(j + i) & mask;
                         if masked_value != masked_data {
                             res = false;
                             break;
@@ -61,8 +187,8 @@ impl MagicRule {
                     }
                 },
                 None => {
-                    for j in 0 .. value_len {
-                        if data[j + i] != self.value[j] {
+                    for (j, value) in self.value().iter().enumerate() {
+                        if data.byte_at(j + i) != *value {
                             res = false;
                             break;
                         }
@@ -77,6 +203,119 @@ impl MagicRule {
 
         false
     }
+
+    // Same as matches_data(), on contiguous data: the comparisons work on
+    // whole windows of the range, which lets them be vectorized
+    fn matches_slice(&self, data: &[u8]) -> bool {
+        let start: usize = self.start_offset() as usize;
+        let end: usize = start.saturating_add(self.range_length() as usize);
+        let value_len: usize = self.value().len();
+
+        if start >= end || start.saturating_add(value_len) > data.len() {
+            return false;
+        }
+
+        if value_len == 0 {
+            return true;
+        }
+
+        // The range covers the values starting up to its last offset
+        let window_end = (end - 1).saturating_add(value_len).min(data.len());
+        let window = &data[start..window_end];
+
+        match self.mask() {
+            Some(m) => masked_contains(window, self.value(), m),
+            None => contains(window, self.value()),
+        }
+    }
+}
+
+impl Matchlet for MagicRule {
+    fn indent(&self) -> u32 {
+        self.indent
+    }
+
+    fn start_offset(&self) -> u32 {
+        self.start_offset
+    }
+
+    fn range_length(&self) -> u32 {
+        self.range_length
+    }
+
+    fn value(&self) -> &[u8] {
+        &self.value
+    }
+
+    fn mask(&self) -> Option<&[u8]> {
+        self.mask.as_deref()
+    }
+}
+
+#[cfg(feature = "simd")]
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    memchr::memmem::find(haystack, needle).is_some()
+}
+
+#[cfg(not(feature = "simd"))]
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+fn masked_eq(data: &[u8], value: &[u8], mask: &[u8]) -> bool {
+    data.iter().zip(value).zip(mask).all(|((d, v), m)| d & m == v & m)
+}
+
+// Checks whether a window of @haystack is equal to @needle under @mask
+fn masked_windows(haystack: &[u8], needle: &[u8], mask: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| masked_eq(w, needle, mask))
+}
+
+// Same as masked_windows(), only comparing the windows whose byte under
+// the first full byte of the mask is equal, found with memchr's
+// vectorized search
+#[cfg(feature = "simd")]
+fn masked_contains(haystack: &[u8], needle: &[u8], mask: &[u8]) -> bool {
+    let anchor = match mask.iter().position(|m| *m == 0xff) {
+        Some(v) => v,
+        None => return masked_windows(haystack, needle, mask),
+    };
+    if haystack.len() < needle.len() {
+        return false;
+    }
+
+    let last = haystack.len() - needle.len();
+    memchr::memchr_iter(needle[anchor], &haystack[anchor..=anchor + last])
+        .any(|idx| masked_eq(&haystack[idx..idx + needle.len()], needle, mask))
+}
+
+#[cfg(not(feature = "simd"))]
+fn masked_contains(haystack: &[u8], needle: &[u8], mask: &[u8]) -> bool {
+    masked_windows(haystack, needle, mask)
+}
+
+// Swaps the bytes of @value in groups of @word_size on little-endian
+// hosts, as the host-endian values are stored big-endian in the magic
+// files; the values whose length is not a multiple of it are kept
+pub(crate) fn swap_words(value: &mut [u8], word_size: u32) {
+    let word_size = word_size as usize;
+    if cfg!(target_endian = "big") || word_size < 2 || !value.len().is_multiple_of(word_size) {
+        return;
+    }
+
+    for word in value.chunks_exact_mut(word_size) {
+        word.reverse();
+    }
+}
+
+// Returns @rule, read from a file, with its value and mask in host order
+pub(crate) fn host_rule(mut rule: MagicRule) -> MagicRule {
+    swap_words(&mut rule.value, rule.word_size);
+    if let Some(mask) = rule.mask.as_mut() {
+        swap_words(mask, rule.word_size);
+    }
+
+    rule
 }
 
 // Indentation level, can be 0
@@ -142,19 +381,19 @@ named!(
         _value_length: be_u16 >>
         _value: do_parse!(
             res: take!(_value_length) >>
-            (res.iter().map(|&x| x).collect())
+            (res.to_vec())
         ) >>
         _mask: opt!(
             do_parse!(
                 char!('&') >>
                 res: take!(_value_length) >>
-                (res.iter().map(|&x| x).collect())
+                (res.to_vec())
 	    )
         ) >>
         _word_size: word_size >>
         _range_length: range_length >>
         line_ending >>
-        (MagicRule {
+        (host_rule(MagicRule {
             indent: _indent,
             start_offset: _start_offset,
             value_length: _value_length,
@@ -162,15 +401,25 @@ named!(
             mask: _mask,
             word_size: _word_size.unwrap_or(1),
             range_length: _range_length.unwrap_or(1),
-        })
+        }))
     )
 );
 
-#[derive(Clone, Eq, PartialEq)]
+#[derive(Clone, Eq)]
 pub struct MagicEntry {
     mime_type: String,
     priority: u32,
     rules: Vec<MagicRule>,
+    // The offset of the entry header in its file
+    offset: usize,
+    source: Option<PathBuf>,
+}
+
+// The location of the entry is not part of its identity
+impl PartialEq for MagicEntry {
+    fn eq(&self, other: &MagicEntry) -> bool {
+        self.mime_type == other.mime_type && self.priority == other.priority && self.rules == other.rules
+    }
 }
 
 impl fmt::Debug for MagicEntry {
@@ -183,6 +432,18 @@ impl fmt::Debug for MagicEntry {
     }
 }
 
+// The value of the rule written for the `magic-deleteall` elements of the
+// packages, discarding the magic of its type in the less important data
+// directories
+pub(crate) const NOMAGIC: &[u8] = b"__NOMAGIC__";
+
+// Formats a summary of the entry, with its header and number of rules
+impl fmt::Display for MagicEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}:{}] {} rules", self.priority, self.mime_type, self.rules.len())
+    }
+}
+
 impl Ord for MagicEntry {
     fn cmp(&self, other: &MagicEntry) -> Ordering {
         self.priority.cmp(&other.priority)
@@ -191,46 +452,106 @@ impl Ord for MagicEntry {
 
 impl PartialOrd for MagicEntry {
     fn partial_cmp(&self, other: &MagicEntry) -> Option<Ordering> {
-        Some(self.priority.cmp(&other.priority))
+        Some(self.cmp(other))
     }
 }
 
 impl MagicEntry {
-    fn matches(&self, data: &[u8]) -> Option<(&String, u32)> {
+    pub(crate) fn new(mime_type: &str, priority: u32, rules: Vec<MagicRule>, offset: usize) -> MagicEntry {
+        MagicEntry {
+            mime_type: mime_type.to_string(),
+            priority,
+            rules,
+            offset,
+            source: None,
+        }
+    }
+
+    pub fn mime_type(&self) -> &str {
+        &self.mime_type
+    }
+
+    /// The priority of the entry, from 0 to 100; the entries with a higher
+    /// priority are tried first.
+    pub fn priority(&self) -> u32 {
+        self.priority
+    }
+
+    // Whether this is the marker of a `magic-deleteall` element
+    pub(crate) fn is_nomagic(&self) -> bool {
+        self.rules.first().is_some_and(|r| r.value == NOMAGIC)
+    }
+
+    pub(crate) fn set_mime_type(&mut self, mime_type: String) {
+        self.mime_type = mime_type;
+    }
+
+    /// The byte offset of the entry in the magic file it was read from.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The magic file the entry was loaded from, if known.
+    pub fn source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+
+    pub(crate) fn set_source(&mut self, file_name: &Path) {
+        self.source = Some(file_name.to_path_buf());
+    }
+
+    /// Formats the location of the entry, as `<file>:<offset>`.
+    pub fn location(&self) -> String {
+        match &self.source {
+            Some(v) => format!("{}:{}", v.display(), self.offset),
+            None => format!("<unknown>:{}", self.offset),
+        }
+    }
+
+    /// The number of bytes of data needed to evaluate all the rules of
+    /// the entry.
+    pub fn extent(&self) -> usize {
+        self.rules.iter().map(MagicRule::extent).max().unwrap_or(0)
+    }
+
+    fn matches<D: MagicInput + ?Sized>(&self, data: &D) -> Option<(&String, u32)> {
+        if rules_match(&self.rules, data) {
+            Some((&self.mime_type, self.priority))
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn rules(&self) -> &[MagicRule] {
+        &self.rules
+    }
+}
+
+// Checks whether one of the branches of the tree of @rules matches @data
+pub(crate) fn rules_match<R: Matchlet, D: MagicInput + ?Sized>(rules: &[R], data: &D) -> bool {
         let mut current_level = 0;
 
-        let mut iter = (&self.rules).into_iter().peekable();
-        while let Some(rule) = iter.next() {
+        for (idx, rule) in rules.iter().enumerate() {
             // The rules are a flat list that represent a tree; the "indent"
-            // is the depth of the rule in the tree. If a rule matches at a
-            // certain level, we increase the level and iterate to the next
-            // rule at that level. If this is the last rule, we traversed the
-            // branch; otherwise, we go back one level and keep matching.
-            if rule.indent == current_level {
-                if rule.matches_data(data) {
-                    current_level += 1;
-                    match iter.peek() {
-                        Some(next) => {
-                            // go back one level
-                            if next.indent < current_level {
-                                current_level -= 1;
-                            }
-                        },
-                        None => {
-                            // last rule
-                            return Some((&self.mime_type, self.priority));
-                        },
-                    };
-                }
-                else {
-                    // No match at the current level, start from scratch
-                    current_level = 0;
+            // is the depth of the rule in the tree. The rules deeper than
+            // the current level are the children of a rule that did not
+            // match, and are skipped; the other ones are siblings of the
+            // current rule or of one of its ancestors.
+            if rule.indent() > current_level {
+                continue;
+            }
+            current_level = rule.indent();
+
+            if rule.matches_data(data) {
+                // A matching leaf completes a branch of the tree
+                match rules.get(idx + 1) {
+                    Some(next) if next.indent() > rule.indent() => current_level += 1,
+                    _ => return true,
                 }
             }
         }
 
-        None
-    }
+        false
 }
 
 named!(priority<u32>,
@@ -271,17 +592,36 @@ named!(magic_entry<MagicEntry>,
             priority: _header.0,
             mime_type: _header.1.into(),
             rules: _rules,
+            offset: 0,
+            source: None,
         })
     )
 );
 
-named!(from_u8_to_entries<Vec<MagicEntry>>,
-    do_parse!(
-	tag!("MIME-Magic\0\n") >>
-	res: many0!(complete!(magic_entry)) >>
-	(res)
-    )
-);
+named!(magic_file_header, tag!("MIME-Magic\0\n"));
+
+// Parses all the entries of @data, recording their offset
+fn from_u8_to_entries(data: &[u8]) -> IResult<&[u8], Vec<MagicEntry>> {
+    let (mut rest, _) = magic_file_header(data)?;
+
+    let mut res = Vec::new();
+    while let Ok((next, mut entry)) = magic_entry(rest) {
+        entry.offset = data.offset(rest);
+        res.push(entry);
+        rest = next;
+    }
+
+    Ok((rest, res))
+}
+
+/// Parses the contents of a magic file, returning the entries and the
+/// number of bytes left unparsed, if any.
+pub fn read_magic(data: &[u8]) -> (Vec<MagicEntry>, usize) {
+    match from_u8_to_entries(data) {
+        Ok((rest, entries)) => (entries, rest.len()),
+        Err(_) => (Vec::new(), data.len()),
+    }
+}
 
 pub fn read_magic_from_file<P: AsRef<Path>>(file_name: P) -> Vec<MagicEntry> {
     let mut f = match File::open(file_name) {
@@ -291,7 +631,10 @@ pub fn read_magic_from_file<P: AsRef<Path>>(file_name: P) -> Vec<MagicEntry> {
 
     let mut magic_buf = Vec::<u8>::new();
 
-    f.read_to_end(&mut magic_buf).unwrap();
+    if f.read_to_end(&mut magic_buf).is_err() {
+        return Vec::new();
+    }
+
     let entries = match from_u8_to_entries(magic_buf.as_slice()) {
         Ok(v) => v.1,
         Err(_) => return Vec::new(),
@@ -300,26 +643,202 @@ pub fn read_magic_from_file<P: AsRef<Path>>(file_name: P) -> Vec<MagicEntry> {
     entries
 }
 
-pub fn lookup_data(entries: &Vec<MagicEntry>, data: &[u8]) -> Option<(String, u32)> {
+/// Adds the magic entries from @other for the MIME types that do not have
+/// any entry in @entries.
+pub fn add_missing_entries(entries: &mut Vec<MagicEntry>, other: Vec<MagicEntry>) {
+    let known: HashSet<String> = entries.iter().map(|e| e.mime_type.clone()).collect();
+
+    for entry in other {
+        if !known.contains(&entry.mime_type) {
+            entries.push(entry);
+        }
+    }
+
+    entries.sort_by_key(|e| Reverse(e.priority));
+}
+
+/// Aggregate information about the magic entries of a database.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MagicSummary {
+    rules: BTreeMap<String, usize>,
+    priorities: BTreeMap<u32, usize>,
+    extents: BTreeMap<usize, usize>,
+}
+
+impl MagicSummary {
+    pub(crate) fn new(entries: &[MagicEntry]) -> MagicSummary {
+        let mut res = MagicSummary::default();
+
+        for entry in entries {
+            *res.rules.entry(entry.mime_type.clone()).or_default() += entry.rules.len();
+            *res.priorities.entry(entry.priority).or_default() += 1;
+            *res.extents.entry(entry.extent()).or_default() += 1;
+        }
+
+        res
+    }
+
+    /// The number of magic entries.
+    pub fn entry_count(&self) -> usize {
+        self.priorities.values().sum()
+    }
+
+    /// The number of magic rules, over all the entries.
+    pub fn rule_count(&self) -> usize {
+        self.rules.values().sum()
+    }
+
+    /// The number of rules of each MIME type with magic.
+    pub fn rules_per_type(&self) -> &BTreeMap<String, usize> {
+        &self.rules
+    }
+
+    /// The number of entries for each priority.
+    pub fn priorities(&self) -> &BTreeMap<u32, usize> {
+        &self.priorities
+    }
+
+    /// The number of entries for each extent, the number of bytes of data
+    /// needed to evaluate all their rules.
+    pub fn extents(&self) -> &BTreeMap<usize, usize> {
+        &self.extents
+    }
+
+    /// The largest extent of all the entries, i.e. the amount of data
+    /// beyond which sniffing cannot give a different result.
+    pub fn max_extent(&self) -> usize {
+        self.extents.keys().next_back().copied().unwrap_or(0)
+    }
+
+    /// The number of entries that can be fully evaluated with @len bytes
+    /// of data.
+    pub fn entries_within(&self, len: usize) -> usize {
+        self.extents.range(..=len).map(|(_, count)| count).sum()
+    }
+}
+
+pub fn lookup_data<D: MagicInput + ?Sized>(entries: &Vec<MagicEntry>, data: &D) -> Option<(String, u32)> {
     for entry in entries {
-        match entry.matches(data) {
-            Some(v) => return Some((v.0.clone(), v.1)),
-            None => {},
-        };
+        if let Some(v) = entry.matches(data) {
+            return Some((v.0.clone(), v.1));
+        }
     }
 
     None
 }
 
+// Writes the rules of @entries that decide the types of the data to
+// @fingerprint, in order
+pub(crate) fn write_fingerprint(entries: &[MagicEntry], fingerprint: &mut Fingerprint) {
+    for entry in entries {
+        fingerprint.write_str(&entry.mime_type);
+        fingerprint.write(&entry.priority.to_le_bytes());
+        for rule in &entry.rules {
+            for v in &[rule.indent, rule.start_offset, rule.word_size, rule.range_length] {
+                fingerprint.write(&v.to_le_bytes());
+            }
+            fingerprint.write(&(rule.value.len() as u32).to_le_bytes());
+            fingerprint.write(&rule.value);
+            match &rule.mask {
+                Some(mask) => {
+                    fingerprint.write(b"&");
+                    fingerprint.write(mask);
+                }
+                None => fingerprint.write(b"="),
+            }
+        }
+        fingerprint.write(b"]");
+    }
+}
+
+/// Like lookup_data(), but returns the matching entry.
+pub fn matching_entry<'a, D: MagicInput + ?Sized>(entries: &'a [MagicEntry], data: &D) -> Option<&'a MagicEntry> {
+    entries.iter().find(|entry| entry.matches(data).is_some())
+}
+
+/// Like lookup_data(), but returns all the matching entries, from the
+/// highest priority to the lowest one.
+pub fn matching_entries<'a, 'b, D: MagicInput + ?Sized>(
+    entries: &'a [MagicEntry],
+    data: &'b D,
+) -> impl Iterator<Item = &'a MagicEntry> + 'b
+where
+    'a: 'b,
+{
+    entries.iter().filter(move |entry| entry.matches(data).is_some())
+}
+
+/// Like lookup_data(), without copying the MIME type.
+pub fn lookup_data_type<'a, D: MagicInput + ?Sized>(entries: &'a [MagicEntry], data: &D) -> Option<&'a str> {
+    entries.iter().find_map(|entry| entry.matches(data)).map(|v| v.0.as_str())
+}
+
+/// Like lookup_data(), but also returns the entries that were evaluated
+/// without matching before finding the result.
+#[cfg(feature = "debug-lookup")]
+pub fn explain_data<'a, D: MagicInput + ?Sized>(
+    entries: &'a [MagicEntry],
+    data: &D,
+) -> (Option<&'a MagicEntry>, Vec<&'a MagicEntry>) {
+    let mut skipped = Vec::new();
+
+    for entry in entries {
+        if entry.matches(data).is_some() {
+            return (Some(entry), skipped);
+        }
+
+        skipped.push(entry);
+    }
+
+    (None, skipped)
+}
+
 #[cfg(test)]
 mod tests {
     use nom::HexDisplay;
     use nom::Offset;
     use super::*;
 
+    #[test]
+    fn masked_search() {
+        let haystack = b"xxAbCdxxaBcDxx\x01\x02";
+        let mask = b"\xff\xdf\xff\xdf";
+        assert!(masked_contains(haystack, b"abcd", mask));
+        assert!(masked_contains(haystack, b"aBcD", mask));
+        assert!(!masked_contains(haystack, b"abce", mask));
+        // Without a full byte in the mask
+        assert!(masked_contains(haystack, b"\x00\x02", b"\x00\x0f"));
+        assert!(!masked_contains(haystack, b"abcd", b"\xff\xff\xff\xff"));
+        let cases = [
+            (&b"aBcD"[..], &b"\xdf\xff\xdf\xff"[..]),
+            (b"Cdxx", b"\xff\xff\x00\x00"),
+            (b"\x01\x02", b"\x0f\xff"),
+        ];
+        for len in 0..haystack.len() {
+            for (needle, mask) in cases {
+                assert_eq!(
+                    masked_contains(&haystack[..len], needle, mask),
+                    masked_windows(&haystack[..len], needle, mask),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn host_words() {
+        let (entries, rest) = read_magic(b"MIME-Magic\0\n[50:application/x-foo]\n>0=\x00\x04\x12\x34\x56\x78~2\n");
+        assert_eq!(rest, 0);
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&0x1234u16.to_ne_bytes());
+        data.extend_from_slice(&0x5678u16.to_ne_bytes());
+        assert_eq!(lookup_data_type(&entries, &data[..]), Some("application/x-foo"));
+        assert_eq!(lookup_data_type(&entries, &b"\x12\x34\x56\x78"[..]).is_some(), cfg!(target_endian = "big"));
+    }
+
     #[test]
     fn parse_magic_header() {
-        let res = magic_header(&"[50:application/x-yaml]\n".as_bytes());
+        let res = magic_header("[50:application/x-yaml]\n".as_bytes());
 
         match res {
             Ok((i, o)) => {
@@ -417,6 +936,101 @@ mod tests {
         }
     }
 
+    #[test]
+    fn entry_offsets() {
+        let data = include_bytes!("../test_files/mime/magic");
+        let (entries, _) = read_magic(data);
+
+        assert_eq!(entries[0].offset(), 12);
+        for entry in &entries {
+            let header = format!("[{}:{}]\n", entry.priority(), entry.mime_type());
+            assert!(data[entry.offset()..].starts_with(header.as_bytes()));
+        }
+        assert_eq!(entries[0].source(), None);
+    }
+
+    #[test]
+    fn chunked_data() {
+        let (entries, _) = read_magic(include_bytes!("../test_files/mime/magic"));
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+
+        let chunks = ChunkedData::new(vec![&png_data[..1], &[][..], &png_data[1..3], &png_data[3..]]);
+        assert_eq!(chunks.len(), png_data.len());
+        assert_eq!(chunks.byte_at(0), png_data[0]);
+        assert_eq!(chunks.byte_at(2), png_data[2]);
+        assert_eq!(chunks.byte_at(3), png_data[3]);
+        assert_eq!(lookup_data(&entries, &chunks), lookup_data(&entries, &png_data[..]));
+
+        assert!(ChunkedData::new(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn slice_and_chunks_agree() {
+        let rule = |start_offset, range_length, value: &[u8], mask: Option<&[u8]>| MagicRule {
+            indent: 0,
+            start_offset,
+            value_length: value.len() as u16,
+            value: value.to_vec(),
+            mask: mask.map(|m| m.to_vec()),
+            word_size: 1,
+            range_length,
+        };
+
+        let rules = vec![
+            rule(0, 1, b"abc", None),
+            rule(1, 1, b"abc", None),
+            rule(0, 8, b"def", None),
+            rule(0, 3, b"def", None),
+            rule(0, 8, b"gh", None),
+            rule(2, 8, b"", None),
+            rule(12, 8, b"", None),
+            rule(0, 8, b"\x60\x60", Some(b"\xf0\xf0")),
+            rule(0, 8, b"\x10", Some(b"\xf0")),
+            rule(0, 0, b"a", None),
+        ];
+
+        let data = b"abcdefgh";
+        let chunks = ChunkedData::new(vec![&data[..3], &data[3..]]);
+        let results: Vec<bool> = rules.iter().map(|r| r.matches_data(&data[..])).collect();
+        for (rule, res) in rules.iter().zip(&results) {
+            assert_eq!(rule.matches_data(&chunks), *res, "{:?}", rule);
+        }
+        assert_eq!(
+            results,
+            vec![true, false, true, false, true, true, false, true, false, false]
+        );
+    }
+
+    #[test]
+    fn summary() {
+        let (entries, _) = read_magic(include_bytes!("../test_files/mime/magic"));
+        let summary = MagicSummary::new(&entries);
+
+        assert_eq!(summary.entry_count(), entries.len());
+        assert_eq!(summary.rules_per_type().get("image/png"), Some(&1));
+        assert!(summary.priorities().contains_key(&50));
+        assert_eq!(summary.entries_within(summary.max_extent()), entries.len());
+        assert!(summary.entries_within(4) < entries.len());
+
+        let png = entries.iter().find(|e| e.mime_type() == "image/png").unwrap();
+        assert_eq!(png.extent(), 4);
+
+        assert_eq!(MagicSummary::new(&[]).max_extent(), 0);
+    }
+
+    #[test]
+    fn match_rule_tree() {
+        let rule = |indent, start_offset, value: &[u8]| MagicRule::new(indent, start_offset, value.to_vec(), None, 1, 1);
+        // A matching leaf is enough, even when it is not the last rule
+        let rules = vec![rule(0, 0, b"PK"), rule(1, 2, b"mt"), rule(1, 2, b"xx"), rule(0, 0, b"ZZ")];
+
+        assert!(rules_match(&rules, &b"PKmt"[..]));
+        assert!(rules_match(&rules, &b"PKxx"[..]));
+        assert!(!rules_match(&rules, &b"PKab"[..]));
+        assert!(rules_match(&rules, &b"ZZab"[..]));
+        assert!(!rules_match(&rules, &b"ABmt"[..]));
+    }
+
     #[test]
     fn parse_magic_file() {
         let data = include_bytes!("../test_files/mime/magic");
@@ -0,0 +1,124 @@
+use crate::{GlobMatch, SharedMimeInfo, UNKNOWN_TYPE};
+
+/// The verdict on a MIME part of an email, as returned by
+/// [`SharedMimeInfo::classify_mime_part`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PartVerdict {
+    /// The MIME type the part should be handled as.
+    pub mime_type: String,
+    /// The canonical type of the declared `Content-Type`, without its
+    /// parameters, if it is valid.
+    pub declared: Option<String>,
+    /// The type of the file name of the part, if it is unambiguous.
+    pub from_file_name: Option<String>,
+    /// The type recognized from the contents of the part by the magic
+    /// rules, if any.
+    pub from_content: Option<String>,
+    /// Whether the contents contradict the declared type or the file name,
+    /// like an executable attached as `invoice.pdf`.
+    pub spoofed: bool,
+}
+
+// Returns the canonical type of a `Content-Type` @header, without its
+// parameters
+fn parse_content_type(db: &SharedMimeInfo, header: &str) -> Option<String> {
+    let mime_type = header.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+
+    match mime_type.find('/') {
+        Some(idx) if idx > 0 && idx < mime_type.len() - 1 => Some(db.canonical_type(&mime_type)),
+        _ => None,
+    }
+}
+
+// Returns the file name of a `filename` or `name` @param, which some mail
+// clients send with the path of the file
+fn base_name(param: &str) -> &str {
+    match param.rfind(['/', '\\']) {
+        Some(idx) => &param[idx + 1..],
+        None => param,
+    }
+}
+
+// Classifies a MIME part from its @declared type, its @file_name and the
+// beginning of its @body, with the MIME information of @db
+pub(crate) fn classify(db: &SharedMimeInfo, declared: Option<&str>, file_name: Option<&str>, body: &[u8]) -> PartVerdict {
+    let declared = declared.and_then(|d| parse_content_type(db, d));
+    let from_file_name = file_name.and_then(|name| match db.lookup_file_name(base_name(name)) {
+        GlobMatch::Unique(v) => Some(v),
+        _ => None,
+    });
+    let from_content = db.get_mime_type_for_data(body);
+
+    // The generic type many mail clients declare tells nothing
+    let claimed: Vec<&String> = declared
+        .iter()
+        .filter(|d| d.as_str() != UNKNOWN_TYPE)
+        .chain(from_file_name.iter())
+        .collect();
+
+    let compatible = |a: &str, b: &str| db.is_subclass_of(a, b) || db.is_subclass_of(b, a);
+    let spoofed = match &from_content {
+        Some(content) => claimed.iter().any(|c| !compatible(content, c)),
+        None => false,
+    };
+
+    // The contents are the most reliable, then the declared type; the
+    // other claims can only refine them, e.g. a document based on zip
+    let mut mime_type = from_content
+        .clone()
+        .or_else(|| claimed.first().map(|c| c.to_string()))
+        .unwrap_or_else(|| UNKNOWN_TYPE.to_string());
+    for c in &claimed {
+        if c.as_str() != mime_type && db.is_subclass_of(c, &mime_type) {
+            mime_type = c.to_string();
+        }
+    }
+
+    PartVerdict {
+        mime_type,
+        declared,
+        from_file_name,
+        from_content,
+        spoofed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn classify_parts() {
+        let mime_db = SharedMimeInfo::new_for_directory(env::current_dir().unwrap().join("test_files"));
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+
+        let res = classify(&mime_db, Some("image/png; name=\"logo.png\""), Some("logo.png"), png_data);
+        assert_eq!(res.mime_type, "image/png");
+        assert_eq!(res.declared.as_deref(), Some("image/png"));
+        assert_eq!(res.from_file_name.as_deref(), Some("image/png"));
+        assert_eq!(res.from_content.as_deref(), Some("image/png"));
+        assert!(!res.spoofed);
+
+        // The generic declared type and the path of the file name
+        let res = classify(&mime_db, Some("Application/Octet-Stream"), Some("C:\\tmp\\logo.png"), png_data);
+        assert_eq!(res.mime_type, "image/png");
+        assert_eq!(res.declared.as_deref(), Some("application/octet-stream"));
+        assert!(!res.spoofed);
+
+        let res = classify(&mime_db, Some("application/x-gzip"), Some("report.gif"), png_data);
+        assert_eq!(res.mime_type, "image/png");
+        assert_eq!(res.declared.as_deref(), Some("application/gzip"));
+        assert!(res.spoofed);
+
+        // Without magic, the file name refines the declared type
+        let res = classify(&mime_db, Some("text/plain"), Some("main.c"), b"int main() {}\n");
+        assert_eq!(res.mime_type, "text/x-csrc");
+        assert_eq!(res.from_content, None);
+        assert!(!res.spoofed);
+
+        let res = classify(&mime_db, Some("bogus"), None, b"\x00\x01");
+        assert_eq!(res.mime_type, UNKNOWN_TYPE);
+        assert_eq!(res.declared, None);
+    }
+}
@@ -0,0 +1,124 @@
+// The library is only built on Unix and Windows
+#![cfg_attr(not(any(unix, windows, target_os = "redox")), allow(unused_imports))]
+
+use std::env;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+use std::process;
+
+#[cfg(any(unix, windows, target_os = "redox"))]
+use xdg_mime::SharedMimeInfo;
+
+#[cfg(any(unix, windows, target_os = "redox"))]
+fn usage() -> ! {
+    eprintln!("Usage: xdg-mime [--data-dir DIR]... COMMAND");
+    eprintln!();
+    eprintln!("Commands:");
+    eprintln!("  tree MIMETYPE    Print the subclass hierarchy under MIMETYPE");
+    eprintln!("  query filetype [--verbose] FILE");
+    eprintln!("                   Print the MIME type of FILE, with the glob and");
+    eprintln!("                   magic rule that matched it in verbose mode");
+    process::exit(1);
+}
+
+// Prints the MIME type of the file at @path, and with @verbose the best
+// glob and the magic entry matching it, for the bug reports on the data
+#[cfg(any(unix, windows, target_os = "redox"))]
+fn query_filetype<W: Write>(mime_db: &SharedMimeInfo, path: &str, verbose: bool, out: &mut W) -> io::Result<()> {
+    let path = Path::new(path);
+    let guess = mime_db.guess().path(path).guess();
+
+    writeln!(out, "{}", guess.mime_type())?;
+    if !verbose {
+        return Ok(());
+    }
+    if guess.uncertain() {
+        writeln!(out, "  uncertain: the contents could not be read")?;
+    }
+
+    let globs = match path.file_name().and_then(|n| n.to_str()) {
+        Some(name) => mime_db.matching_globs_for_name(name),
+        None => Vec::new(),
+    };
+    match globs.first() {
+        Some(glob) => writeln!(
+            out,
+            "  glob: {} (weight {}) -> {}",
+            glob.pattern(),
+            glob.weight(),
+            glob.mime_type()
+        )?,
+        None => writeln!(out, "  glob: none")?,
+    }
+
+    // A directory or an unreadable file has no contents to match
+    let mut data = Vec::new();
+    if let Ok(file) = File::open(path) {
+        let _ = file.take(mime_db.magic_summary().max_extent() as u64).read_to_end(&mut data);
+    }
+    match mime_db.matching_magic_for_data(&data) {
+        Some(entry) => writeln!(
+            out,
+            "  magic: priority {} at {} -> {}",
+            entry.priority(),
+            entry.location(),
+            entry.mime_type()
+        )?,
+        None => writeln!(out, "  magic: none")?,
+    }
+
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows, target_os = "redox")))]
+fn main() {
+    eprintln!("xdg-mime: the shared MIME database is only available on Unix and Windows");
+    process::exit(1);
+}
+
+#[cfg(any(unix, windows, target_os = "redox"))]
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut data_dirs = Vec::new();
+    let mut command = Vec::new();
+
+    while let Some(arg) = args.next() {
+        if arg == "--data-dir" {
+            match args.next() {
+                Some(v) => data_dirs.push(v),
+                None => usage(),
+            }
+        } else {
+            command.push(arg);
+        }
+    }
+
+    let command: Vec<&str> = command.iter().map(|s| s.as_str()).collect();
+    if command.is_empty() {
+        usage();
+    }
+
+    // The XDG base directories are only used without --data-dir
+    let mut builder = SharedMimeInfo::builder().system_directories(data_dirs.is_empty());
+    for dir in &data_dirs {
+        builder = builder.directory(dir);
+    }
+    let mime_db = builder.build();
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    let res = match command.as_slice() {
+        ["tree", root] => mime_db.print_tree(root, &mut out),
+        ["query", "filetype", file] => query_filetype(&mime_db, file, false, &mut out),
+        ["query", "filetype", "--verbose", file] => query_filetype(&mime_db, file, true, &mut out),
+        _ => usage(),
+    };
+
+    if let Err(e) = res {
+        eprintln!("xdg-mime: {}", e);
+        process::exit(1);
+    }
+}
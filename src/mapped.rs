@@ -0,0 +1,467 @@
+use std::fs::File;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+use ::glob::MatchOptions;
+use memmap2::Mmap;
+use unicase::UniCase;
+
+use crate::glob::{self, GlobType};
+use crate::magic::{self, Matchlet};
+use crate::query;
+use crate::snapshot::{self, Reader};
+use crate::{fallback_generic_icon_name, GlobMatch, Hierarchy, MimeQuery};
+
+// How a glob of the snapshot matches the file names; only the patterns
+// with wildcards are compiled when indexing the snapshot
+#[derive(Debug)]
+enum PatternKind {
+    Literal,
+    Suffix,
+    Glob(::glob::Pattern),
+}
+
+#[derive(Debug)]
+struct MappedGlob {
+    mime_type: Range<usize>,
+    pattern: Range<usize>,
+    kind: PatternKind,
+    weight: i32,
+    case_sensitive: bool,
+}
+
+#[derive(Debug)]
+struct MappedRule {
+    indent: u32,
+    start_offset: u32,
+    range_length: u32,
+    value: Range<usize>,
+    mask: Option<Range<usize>>,
+    // The value and the mask in host order, for the host-endian rules of
+    // a little-endian host, as the snapshot holds them big-endian
+    host: Option<(Vec<u8>, Option<Vec<u8>>)>,
+}
+
+#[derive(Debug)]
+struct MappedEntry {
+    mime_type: Range<usize>,
+    // The rules of the entry in the rules of the index
+    rules: Range<usize>,
+}
+
+// The positions of the values of a snapshot, in the mapped file; the
+// aliases, parents and generic icons are sorted by MIME type, and the
+// globs and the magic entries are in the order of the saved database
+#[derive(Debug, Default)]
+struct Index {
+    types: Vec<Range<usize>>,
+    aliases: Vec<(Range<usize>, Range<usize>)>,
+    parents: Vec<(Range<usize>, Vec<Range<usize>>)>,
+    generic_icons: Vec<(Range<usize>, Range<usize>)>,
+    globs: Vec<MappedGlob>,
+    magic: Vec<MappedEntry>,
+    rules: Vec<MappedRule>,
+}
+
+#[derive(Debug)]
+struct Mapping {
+    data: Mmap,
+    index: Index,
+}
+
+// A rule of an entry, with the snapshot holding its value
+struct MappedMatchlet<'a> {
+    rule: &'a MappedRule,
+    data: &'a [u8],
+}
+
+impl Matchlet for MappedMatchlet<'_> {
+    fn indent(&self) -> u32 {
+        self.rule.indent
+    }
+
+    fn start_offset(&self) -> u32 {
+        self.rule.start_offset
+    }
+
+    fn range_length(&self) -> u32 {
+        self.rule.range_length
+    }
+
+    fn value(&self) -> &[u8] {
+        match &self.rule.host {
+            Some((value, _)) => value,
+            None => &self.data[self.rule.value.clone()],
+        }
+    }
+
+    fn mask(&self) -> Option<&[u8]> {
+        match &self.rule.host {
+            Some((_, mask)) => mask.as_deref(),
+            None => self.rule.mask.clone().map(|m| &self.data[m]),
+        }
+    }
+}
+
+// Sorts the pairs of strings of @data by their first string, keeping the
+// first pair of each
+fn sort_pairs(data: &[u8], pairs: &mut Vec<(Range<usize>, Range<usize>)>) {
+    pairs.sort_by(|a, b| data[a.0.clone()].cmp(&data[b.0.clone()]));
+    pairs.dedup_by(|a, b| data[a.0.clone()] == data[b.0.clone()]);
+}
+
+// Reads the positions of the values of the snapshot in @data, checking
+// the whole snapshot
+fn read_index(data: &[u8]) -> io::Result<Index> {
+    let mut r = Reader::new(data);
+    snapshot::read_header(&mut r)?;
+
+    let mut index = Index::default();
+
+    // The data directories and the loaded files
+    for _ in 0..2 {
+        r.list(|r| r.bytes().map(drop))?;
+    }
+    index.types = r.list(|r| r.str_range())?;
+    r.list(|r| r.str().map(drop))?;
+
+    index.aliases = r.list(|r| Ok((r.str_range()?, r.str_range()?)))?;
+    sort_pairs(data, &mut index.aliases);
+
+    index.parents = r.list(|r| Ok((r.str_range()?, r.list(|r| r.str_range())?)))?;
+    index.parents.sort_by(|a, b| data[a.0.clone()].cmp(&data[b.0.clone()]));
+
+    r.list(|r| Ok((r.str()?, r.str()?)))?;
+    index.generic_icons = r.list(|r| Ok((r.str_range()?, r.str_range()?)))?;
+    sort_pairs(data, &mut index.generic_icons);
+
+    index.globs = r.list(|r| {
+        let mime_type = r.str_range()?;
+        let pattern = r.str_range()?;
+        let weight = r.u32()? as i32;
+        let case_sensitive = r.bool()?;
+        r.opt_bytes()?;
+
+        let text = String::from_utf8_lossy(&data[pattern.clone()]).into_owned();
+        let kind = match glob::determine_type_or_literal(text) {
+            GlobType::Literal(_) => PatternKind::Literal,
+            GlobType::Simple(_) => PatternKind::Suffix,
+            GlobType::Full(p) => PatternKind::Glob(p),
+        };
+
+        Ok(MappedGlob {
+            mime_type,
+            pattern,
+            kind,
+            weight,
+            case_sensitive,
+        })
+    })?;
+
+    let rules = &mut index.rules;
+    index.magic = r.list(|r| {
+        let mime_type = r.str_range()?;
+        r.u32()?;
+        r.len()?;
+        r.opt_bytes()?;
+
+        let start = rules.len();
+        for _ in 0..r.len()? {
+            let indent = r.u32()?;
+            let start_offset = r.u32()?;
+            let value = r.bytes_range()?;
+            let mask = r.opt_bytes_range()?;
+            let word_size = r.u32()?;
+            let range_length = r.u32()?;
+
+            if value.len() > u16::MAX as usize || mask.as_ref().is_some_and(|m| m.len() != value.len()) {
+                return Err(snapshot::invalid_data(format!("invalid magic rule at {}", value.start)));
+            }
+
+            let host = if word_size > 1 && cfg!(target_endian = "little") {
+                let host_order = |range: &Range<usize>| {
+                    let mut res = data[range.clone()].to_vec();
+                    magic::swap_words(&mut res, word_size);
+                    res
+                };
+                Some((host_order(&value), mask.as_ref().map(host_order)))
+            } else {
+                None
+            };
+
+            rules.push(MappedRule {
+                indent,
+                start_offset,
+                range_length,
+                value,
+                mask,
+                host,
+            });
+        }
+
+        Ok(MappedEntry {
+            mime_type,
+            rules: start..rules.len(),
+        })
+    })?;
+
+    Ok(index)
+}
+
+/// A snapshot written by [`SharedMimeInfo::save_snapshot`][crate::SharedMimeInfo::save_snapshot],
+/// mapped read-only and queried in place, without loading it.
+///
+/// All the processes mapping the same snapshot share one copy of it in
+/// memory; each of them only builds a small index of the positions of
+/// the values in the file. The clones of a `MappedSnapshot` share the
+/// mapping, e.g. between the threads of a worker process.
+///
+/// The lookups follow the ones of the databases compiled into the
+/// program, like [`StaticMimeDb`][crate::codegen::StaticMimeDb].
+#[derive(Clone, Debug)]
+pub struct MappedSnapshot {
+    mapping: Arc<Mapping>,
+}
+
+impl MappedSnapshot {
+    /// Maps the snapshot at @path, checking all of it.
+    ///
+    /// # Safety
+    ///
+    /// The file at @path must not be modified, nor truncated, for as long
+    /// as the returned snapshot, or one of its clones, is alive: the
+    /// lookups borrow its strings without checking them again. Replacing
+    /// it atomically, like
+    /// [`save_snapshot`][crate::SharedMimeInfo::save_snapshot] does, is
+    /// fine.
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> io::Result<MappedSnapshot> {
+        let f = File::open(path.as_ref())?;
+
+        // The caller guarantees that the file is not modified while it is
+        // mapped
+        let data = unsafe { Mmap::map(&f)? };
+        let index = read_index(&data)?;
+
+        Ok(MappedSnapshot {
+            mapping: Arc::new(Mapping { data, index }),
+        })
+    }
+
+    fn index(&self) -> &Index {
+        &self.mapping.index
+    }
+
+    fn str(&self, range: &Range<usize>) -> &str {
+        // SAFETY: the strings were checked when indexing the snapshot, and
+        // the caller of open() guarantees that the file is not modified
+        unsafe { std::str::from_utf8_unchecked(&self.mapping.data[range.clone()]) }
+    }
+
+    // Looks up @mime_type in the @pairs sorted by their first string
+    fn find_pair<'a>(&'a self, pairs: &'a [(Range<usize>, Range<usize>)], mime_type: &str) -> Option<&'a str> {
+        pairs
+            .binary_search_by(|(key, _)| self.str(key).cmp(mime_type))
+            .ok()
+            .map(|idx| self.str(&pairs[idx].1))
+    }
+
+    fn canonical<'a>(&'a self, mime_type: &'a str) -> &'a str {
+        self.find_pair(&self.index().aliases, mime_type).unwrap_or(mime_type)
+    }
+
+    /// Returns all the MIME types of the snapshot, sorted by name.
+    pub fn mime_types(&self) -> impl Iterator<Item = &str> + '_ {
+        self.index().types.iter().map(move |t| self.str(t))
+    }
+
+    /// Retrieves the MIME type aliased by @mime_type, if any, without
+    /// copying it.
+    pub fn unalias_mime_type_ref(&self, mime_type: &str) -> Option<&str> {
+        self.find_pair(&self.index().aliases, mime_type)
+    }
+
+    /// Retrieves the explicit parents of the canonical type of
+    /// @mime_type, in the order of the database.
+    pub fn parents(&self, mime_type: &str) -> Vec<&str> {
+        let mime_type = self.canonical(mime_type);
+        let parents = &self.index().parents;
+
+        match parents.binary_search_by(|(key, _)| self.str(key).cmp(mime_type)) {
+            Ok(idx) => parents[idx].1.iter().map(|p| self.str(p)).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Looks up the generic icon listed for @mime_type or, failing that,
+    /// for its closest ancestor, if any.
+    pub fn generic_icon_name(&self, mime_type: &str) -> Option<&str> {
+        query::ancestors(self.canonical(mime_type), Hierarchy::Explicit, |t| self.parents(t))
+            .into_iter()
+            .find_map(|t| self.find_pair(&self.index().generic_icons, t))
+    }
+
+    fn glob_matches(&self, glob: &MappedGlob, file_name: &str, lc_file_name: &str) -> bool {
+        let pattern = self.str(&glob.pattern);
+
+        match &glob.kind {
+            PatternKind::Literal if glob.case_sensitive => pattern == file_name,
+            PatternKind::Literal => UniCase::new(pattern) == UniCase::new(file_name),
+            PatternKind::Suffix => {
+                let suffix = &pattern[1..];
+                file_name.ends_with(suffix) || (!glob.case_sensitive && lc_file_name.ends_with(suffix))
+            }
+            PatternKind::Glob(p) => {
+                let options = MatchOptions {
+                    case_sensitive: glob.case_sensitive,
+                    ..MatchOptions::new()
+                };
+                p.matches_with(file_name, options)
+            }
+        }
+    }
+
+    /// Looks up the MIME types of the best globs matching @file_name,
+    /// sorted by name; more than one type means the file name is
+    /// ambiguous.
+    pub fn mime_types_from_file_name(&self, file_name: &str) -> Vec<&str> {
+        let lc_file_name = file_name.to_lowercase();
+
+        let mut matching: Vec<&MappedGlob> = self
+            .index()
+            .globs
+            .iter()
+            .filter(|g| self.glob_matches(g, file_name, &lc_file_name))
+            .collect();
+        matching.sort_by(|a, b| b.weight.cmp(&a.weight).then_with(|| b.pattern.len().cmp(&a.pattern.len())));
+
+        let best = match matching.first() {
+            Some(v) => *v,
+            None => return Vec::new(),
+        };
+
+        let mut res: Vec<&str> = Vec::new();
+        for glob in matching {
+            if glob.weight != best.weight || glob.pattern.len() != best.pattern.len() {
+                break;
+            }
+
+            let mime_type = self.canonical(self.str(&glob.mime_type));
+            if !res.contains(&mime_type) {
+                res.push(mime_type);
+            }
+        }
+
+        res.sort_unstable();
+        res
+    }
+
+    /// Retrieves the MIME type of the first magic entry matching @data,
+    /// without copying it.
+    pub fn mime_type_for_data(&self, data: &[u8]) -> Option<&str> {
+        let index = self.index();
+
+        let mut rules = Vec::new();
+        for entry in &index.magic {
+            rules.clear();
+            rules.extend(index.rules[entry.rules.clone()].iter().map(|rule| MappedMatchlet {
+                rule,
+                data: &self.mapping.data,
+            }));
+
+            if magic::rules_match(&rules, data) {
+                return Some(self.canonical(self.str(&entry.mime_type)));
+            }
+        }
+
+        None
+    }
+}
+
+impl MimeQuery for MappedSnapshot {
+    fn unalias_mime_type(&self, mime_type: &str) -> Option<String> {
+        self.unalias_mime_type_ref(mime_type).map(String::from)
+    }
+
+    fn is_subclass_of(&self, mime_type: &str, base: &str) -> bool {
+        let base = self.canonical(base);
+
+        query::ancestors(self.canonical(mime_type), Hierarchy::Full, |t| self.parents(t)).contains(&base)
+    }
+
+    fn lookup_generic_icon_name(&self, mime_type: &str) -> Option<String> {
+        let res = self
+            .generic_icon_name(mime_type)
+            .map(String::from)
+            .unwrap_or_else(|| fallback_generic_icon_name(mime_type));
+
+        Some(res)
+    }
+
+    fn lookup_file_name(&self, file_name: &str) -> GlobMatch {
+        let mut res: Vec<String> = self
+            .mime_types_from_file_name(file_name)
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        match res.len() {
+            0 => GlobMatch::NoMatch,
+            1 => GlobMatch::Unique(res.remove(0)),
+            _ => GlobMatch::Ambiguous(res),
+        }
+    }
+
+    fn get_mime_type_for_data(&self, data: &[u8]) -> Option<String> {
+        self.mime_type_for_data(data).map(String::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::SharedMimeInfo;
+    use std::env;
+
+    #[test]
+    fn mapped_queries() {
+        let mime_db = SharedMimeInfo::new_for_directory(env::current_dir().unwrap().join("test_files"));
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+
+        let dir = TempDir::new("mapped");
+        let path = dir.join("snapshot");
+        mime_db.save_snapshot(&path).unwrap();
+        let mapped = unsafe { MappedSnapshot::open(&path) }.unwrap();
+        // The mapping outlives the file
+        drop(dir);
+
+        assert_eq!(mapped.mime_types().count(), mime_db.types.len());
+        assert_eq!(mapped.unalias_mime_type_ref("application/x-gzip"), Some("application/gzip"));
+        assert_eq!(mapped.parents("text/x-csrc"), vec!["text/plain"]);
+        assert!(mapped.is_subclass_of("application/x-gzip", "application/octet-stream"));
+        assert!(!mapped.is_subclass_of("image/png", "text/plain"));
+        for mime_type in ["image/png", "application/x-compressed-tar", "text/x-csrc", "foo/bar"] {
+            assert_eq!(MimeQuery::lookup_generic_icon_name(&mapped, mime_type), mime_db.lookup_generic_icon_name(mime_type));
+        }
+
+        assert_eq!(mapped.mime_types_from_file_name("a.PNG"), vec!["image/png"]);
+        assert_eq!(mapped.mime_types_from_file_name("a.C"), vec!["text/x-c++src"]);
+        assert_eq!(mapped.lookup_file_name("a.tar.gz"), mime_db.lookup_file_name("a.tar.gz"));
+        assert_eq!(mapped.lookup_file_name("a.unknown"), GlobMatch::NoMatch);
+
+        assert_eq!(mapped.mime_type_for_data(png_data), Some("image/png"));
+        assert_eq!(mapped.mime_type_for_data(b"\x00\x01\x02"), None);
+        assert_eq!(mapped.mime_type_for_data(&0x0110u16.to_ne_bytes()), Some("application/x-executable"));
+        assert_eq!(mapped.guess_mime_type(Some("upload"), Some(png_data)), "image/png");
+
+        // The clones share the mapping, across threads too
+        let clone = mapped.clone();
+        assert!(Arc::ptr_eq(&clone.mapping, &mapped.mapping));
+        let res = std::thread::spawn(move || clone.mime_type_for_data(png_data).map(String::from));
+        assert_eq!(res.join().unwrap().as_deref(), Some("image/png"));
+
+        assert!(read_index(b"XDGMSNAP\x00\x01").is_err());
+    }
+}
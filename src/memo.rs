@@ -0,0 +1,211 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+/// The storage of the results memoized by the lookups of a
+/// [`SharedMimeInfo`][crate::SharedMimeInfo], like the extensions that
+/// matched no glob, set with
+/// [`SharedMimeInfo::set_cache_backend`][crate::SharedMimeInfo::set_cache_backend].
+///
+/// The keys and values are opaque bytes, so that a backend can keep them
+/// out of the process, e.g. in a store shared by several servers. The
+/// keys start with a fingerprint of the database files the entry depends
+/// on, and the values hold MIME types, so that the databases of any
+/// version, in any process, can share a backend.
+///
+/// The backend is shared by all the lookups on the database, which can
+/// happen on multiple threads, and should be cheap.
+pub trait CacheBackend: Send + Sync {
+    /// Appends the value stored for @key to @value, and returns whether
+    /// there was one.
+    fn get(&self, key: &[u8], value: &mut Vec<u8>) -> bool;
+
+    /// Stores @value for @key, replacing any previous value; the backend
+    /// is free to drop it, or to evict other entries.
+    fn insert(&self, key: &[u8], value: &[u8]);
+
+    /// Removes all the entries; the databases never do, as the entries of
+    /// the other versions of the files stay valid for them.
+    fn clear(&self);
+}
+
+// The hash of the database files the keys of the memoized results start
+// with: FNV-1a, which is stable across processes, of the version of the
+// crate, which defines the format of the entries, and of the files
+pub(crate) struct Fingerprint(u64);
+
+impl Fingerprint {
+    pub(crate) fn new() -> Fingerprint {
+        let mut res = Fingerprint(0xcbf2_9ce4_8422_2325);
+        res.write(env!("CARGO_PKG_VERSION").as_bytes());
+
+        res
+    }
+
+    pub(crate) fn write(&mut self, data: &[u8]) {
+        for b in data {
+            self.0 ^= u64::from(*b);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    // Writes @data followed by a separator, so that the strings written
+    // one after the other cannot be confused with other ones
+    pub(crate) fn write_str(&mut self, data: &str) {
+        self.write(data.as_bytes());
+        self.write(b"\0");
+    }
+
+    pub(crate) fn finish(&self) -> [u8; 8] {
+        self.0.to_le_bytes()
+    }
+}
+
+#[derive(Default)]
+struct LruEntries {
+    // The values, with the last time they were used
+    values: HashMap<Vec<u8>, (Vec<u8>, u64)>,
+    // The keys, by the last time they were used
+    order: BTreeMap<u64, Vec<u8>>,
+    tick: u64,
+}
+
+/// An in-memory [`CacheBackend`] keeping a bounded number of entries, and
+/// evicting the least recently used one first; the default backend.
+pub struct LruCache {
+    capacity: usize,
+    entries: Mutex<LruEntries>,
+}
+
+impl LruCache {
+    /// Creates an empty cache keeping at most @capacity entries.
+    pub fn new(capacity: usize) -> LruCache {
+        LruCache {
+            capacity,
+            entries: Mutex::new(LruEntries::default()),
+        }
+    }
+
+    /// Returns the number of entries in the cache.
+    pub fn len(&self) -> usize {
+        self.entries.lock().map_or(0, |e| e.values.len())
+    }
+
+    /// Checks whether the cache has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl CacheBackend for LruCache {
+    fn get(&self, key: &[u8], value: &mut Vec<u8>) -> bool {
+        let mut entries = match self.entries.lock() {
+            Ok(v) => v,
+            Err(_) => return false,
+        };
+
+        entries.tick += 1;
+        let tick = entries.tick;
+        let LruEntries { values, order, .. } = &mut *entries;
+        match values.get_mut(key) {
+            Some((v, used)) => {
+                if let Some(key) = order.remove(used) {
+                    order.insert(tick, key);
+                }
+                *used = tick;
+                value.extend_from_slice(v);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut entries = match self.entries.lock() {
+            Ok(v) => v,
+            Err(_) => return,
+        };
+
+        entries.tick += 1;
+        let tick = entries.tick;
+        let LruEntries { values, order, .. } = &mut *entries;
+        if let Some((_, used)) = values.remove(key) {
+            order.remove(&used);
+        } else if values.len() >= self.capacity {
+            if let Some((_, oldest)) = order.pop_first() {
+                values.remove(&oldest);
+            }
+        }
+
+        values.insert(key.to_vec(), (value.to_vec(), tick));
+        order.insert(tick, key.to_vec());
+    }
+
+    fn clear(&self) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.values.clear();
+            entries.order.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(cache: &LruCache, key: &[u8]) -> Option<Vec<u8>> {
+        let mut value = Vec::new();
+        if cache.get(key, &mut value) {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn lru_cache() {
+        let cache = LruCache::new(2);
+        assert!(cache.is_empty());
+
+        cache.insert(b"a", b"1");
+        cache.insert(b"b", b"2");
+        assert_eq!(get(&cache, b"a"), Some(b"1".to_vec()));
+
+        // The least recently used entry is evicted first
+        cache.insert(b"c", b"3");
+        assert_eq!(cache.len(), 2);
+        assert_eq!(get(&cache, b"b"), None);
+        assert_eq!(get(&cache, b"a"), Some(b"1".to_vec()));
+
+        cache.insert(b"a", b"4");
+        assert_eq!(cache.len(), 2);
+        assert_eq!(get(&cache, b"a"), Some(b"4".to_vec()));
+        assert_eq!(get(&cache, b"c"), Some(b"3".to_vec()));
+
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(get(&cache, b"a"), None);
+
+        let cache = LruCache::new(0);
+        cache.insert(b"a", b"1");
+        assert_eq!(get(&cache, b"a"), None);
+    }
+
+    #[test]
+    fn fingerprint() {
+        let hash = |strings: &[&str]| {
+            let mut res = Fingerprint::new();
+            for s in strings {
+                res.write_str(s);
+            }
+            res.finish()
+        };
+
+        assert_eq!(hash(&["ab", "c"]), hash(&["ab", "c"]));
+        assert_ne!(hash(&["ab", "c"]), hash(&["a", "bc"]));
+        assert_ne!(hash(&[]), hash(&[""]));
+    }
+}
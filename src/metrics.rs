@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// The kind of a lookup, as counted by [`Metrics::lookup`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LookupKind {
+    /// A lookup on a file name.
+    FileName,
+    /// A lookup on file contents.
+    Data,
+}
+
+/// Hooks receiving the counters and timings of a
+/// [`SharedMimeInfo`][crate::SharedMimeInfo], e.g. to export them to
+/// Prometheus or StatsD; all the methods do nothing by default.
+///
+/// The hooks are shared by all the lookups on the database, which can
+/// happen on multiple threads, and should be cheap.
+pub trait Metrics: Send + Sync {
+    /// Called for each lookup of @kind.
+    fn lookup(&self, _kind: LookupKind) {}
+
+    /// Called for each file name lookup, with whether its extension was
+    /// in the cache of the extensions that matched no glob.
+    fn miss_cache(&self, _hit: bool) {}
+
+    /// Called with the number of @bytes read from a file or a stream to
+    /// sniff its type.
+    fn sniffed_bytes(&self, _bytes: usize) {}
+
+    /// Called after the database was reloaded, with the @duration of the
+    /// reload.
+    fn reload(&self, _duration: Duration) {}
+}
+
+/// A rule of the database, with the number of lookups it decided, as
+/// reported by [`SharedMimeInfo::hot_rules`][crate::SharedMimeInfo::hot_rules].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuleHits {
+    /// The kind of the lookups the rule decided.
+    pub kind: LookupKind,
+    /// The MIME type of the rule.
+    pub mime_type: String,
+    /// The rule, in the format of the database file it comes from; for
+    /// magic entries, only the header is included.
+    pub rule: String,
+    /// Where the rule comes from, as `<file>:<offset>` for magic entries;
+    /// `None` for globs.
+    pub location: Option<String>,
+    /// The number of lookups the rule decided.
+    pub hits: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct RuleKey {
+    kind: LookupKind,
+    mime_type: String,
+    rule: String,
+    location: Option<String>,
+}
+
+// The number of lookups decided by each rule, kept by rule so that the
+// counters survive the reloads
+#[derive(Default)]
+pub(crate) struct RuleCounters {
+    hits: Mutex<HashMap<RuleKey, u64>>,
+}
+
+impl RuleCounters {
+    pub(crate) fn count(&self, kind: LookupKind, mime_type: &str, rule: String, location: Option<String>) {
+        let key = RuleKey {
+            kind,
+            mime_type: mime_type.to_string(),
+            rule,
+            location,
+        };
+
+        *self.hits.lock().unwrap().entry(key).or_default() += 1;
+    }
+
+    // Returns the @count rules with the most hits, then by MIME type
+    pub(crate) fn top(&self, count: usize) -> Vec<RuleHits> {
+        let mut res: Vec<RuleHits> = self
+            .hits
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, hits)| RuleHits {
+                kind: key.kind,
+                mime_type: key.mime_type.clone(),
+                rule: key.rule.clone(),
+                location: key.location.clone(),
+                hits: *hits,
+            })
+            .collect();
+        res.sort_by(|a, b| {
+            b.hits
+                .cmp(&a.hits)
+                .then_with(|| a.mime_type.cmp(&b.mime_type))
+                .then_with(|| a.rule.cmp(&b.rule))
+        });
+        res.truncate(count);
+
+        res
+    }
+}
@@ -0,0 +1,91 @@
+use std::fmt;
+
+/// A MIME type split into its media type and subtype, like `image` and
+/// `svg+xml`, with the structured syntax suffix of RFC 6839 of the
+/// subtype, like `xml`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MimeType<'a> {
+    media_type: &'a str,
+    subtype: &'a str,
+}
+
+impl<'a> MimeType<'a> {
+    /// Parses @mime_type, skipping its parameters, like the `charset` of a
+    /// `Content-Type` header.
+    pub fn parse(mime_type: &'a str) -> Option<MimeType<'a>> {
+        let mime_type = mime_type.split(';').next().unwrap_or_default().trim();
+        let (media_type, subtype) = mime_type.split_once('/')?;
+
+        if media_type.is_empty() || subtype.is_empty() || subtype.contains('/') {
+            return None;
+        }
+
+        Some(MimeType { media_type, subtype })
+    }
+
+    /// The media type, like `image`.
+    pub fn media_type(&self) -> &'a str {
+        self.media_type
+    }
+
+    /// The subtype, like `svg+xml`.
+    pub fn subtype(&self) -> &'a str {
+        self.subtype
+    }
+
+    /// The structured syntax suffix, like the `xml` of `image/svg+xml`.
+    pub fn suffix(&self) -> Option<&'a str> {
+        match self.subtype.rsplit_once('+') {
+            Some((name, suffix)) if !name.is_empty() && !suffix.is_empty() => Some(suffix),
+            _ => None,
+        }
+    }
+
+    /// Checks whether the structured syntax suffix is @suffix, given with
+    /// or without its `+`, ignoring the case; e.g. `application/ld+json`
+    /// has the `json` suffix.
+    pub fn has_suffix(&self, suffix: &str) -> bool {
+        let suffix = suffix.strip_prefix('+').unwrap_or(suffix);
+
+        self.suffix().is_some_and(|s| s.eq_ignore_ascii_case(suffix))
+    }
+}
+
+impl fmt::Display for MimeType<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.media_type, self.subtype)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_mime_type() {
+        let svg = MimeType::parse("image/svg+xml; charset=utf-8").unwrap();
+        assert_eq!(svg.media_type(), "image");
+        assert_eq!(svg.subtype(), "svg+xml");
+        assert_eq!(svg.to_string(), "image/svg+xml");
+
+        for mime_type in &["", "image", "image/", "/svg", "a/b/c"] {
+            assert_eq!(MimeType::parse(mime_type), None, "{}", mime_type);
+        }
+    }
+
+    #[test]
+    fn structured_suffixes() {
+        let suffix = |mime_type| MimeType::parse(mime_type).unwrap().suffix();
+        assert_eq!(suffix("image/svg+xml"), Some("xml"));
+        assert_eq!(suffix("application/vnd.api+json"), Some("json"));
+        assert_eq!(suffix("application/epub+zip"), Some("zip"));
+        assert_eq!(suffix("text/plain"), None);
+        assert_eq!(suffix("application/+xml"), None);
+        assert_eq!(suffix("application/foo+"), None);
+
+        let ld = MimeType::parse("application/ld+json").unwrap();
+        assert!(ld.has_suffix("json"));
+        assert!(ld.has_suffix("+JSON"));
+        assert!(!ld.has_suffix("xml"));
+    }
+}
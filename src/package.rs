@@ -0,0 +1,534 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use roxmltree::{Document, Node, ParsingOptions};
+
+/// The XML namespace of the shared-mime-info packages.
+pub const FREEDESKTOP_NAMESPACE: &str = "http://www.freedesktop.org/standards/shared-mime-info";
+
+const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchType {
+    String,
+    Host16,
+    Host32,
+    Big16,
+    Big32,
+    Little16,
+    Little32,
+    Byte,
+}
+
+impl MatchType {
+    fn from_string(s: &str) -> Option<MatchType> {
+        match s {
+            "string" => Some(MatchType::String),
+            "host16" => Some(MatchType::Host16),
+            "host32" => Some(MatchType::Host32),
+            "big16" => Some(MatchType::Big16),
+            "big32" => Some(MatchType::Big32),
+            "little16" => Some(MatchType::Little16),
+            "little32" => Some(MatchType::Little32),
+            "byte" => Some(MatchType::Byte),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            MatchType::String => "string",
+            MatchType::Host16 => "host16",
+            MatchType::Host32 => "host32",
+            MatchType::Big16 => "big16",
+            MatchType::Big32 => "big32",
+            MatchType::Little16 => "little16",
+            MatchType::Little32 => "little32",
+            MatchType::Byte => "byte",
+        }
+    }
+}
+
+/// A `<match>` element, with its attributes as written in the package.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MagicMatch {
+    pub match_type: MatchType,
+    pub offset: String,
+    pub value: String,
+    pub mask: Option<String>,
+    pub children: Vec<MagicMatch>,
+}
+
+/// A `<magic>` element.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MagicDefinition {
+    pub priority: u32,
+    pub matches: Vec<MagicMatch>,
+}
+
+/// A `<glob>` element.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GlobDefinition {
+    pub pattern: String,
+    pub weight: i32,
+    pub case_sensitive: bool,
+}
+
+/// A `<mime-type>` element of a package.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MimeTypeDefinition {
+    pub mime_type: String,
+    /// The comments, with their optional `xml:lang` locale.
+    pub comments: Vec<(Option<String>, String)>,
+    pub globs: Vec<GlobDefinition>,
+    pub glob_deleteall: bool,
+    pub magic: Vec<MagicDefinition>,
+    pub magic_deleteall: bool,
+    pub aliases: Vec<String>,
+    pub sub_class_of: Vec<String>,
+    pub icon: Option<String>,
+    pub generic_icon: Option<String>,
+    /// The `<root-XML>` elements, as (namespace URI, local name).
+    pub root_xml: Vec<(String, String)>,
+}
+
+fn invalid_data<S: Into<String>>(msg: S) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+fn is_valid_mime_type(s: &str) -> bool {
+    let mut chunks = s.split('/');
+
+    match (chunks.next(), chunks.next(), chunks.next()) {
+        (Some(media), Some(subtype), None) => {
+            !media.is_empty()
+                && !subtype.is_empty()
+                && !s.contains(|c: char| c.is_whitespace() || c == ':' || c == '[' || c == ']')
+        }
+        _ => false,
+    }
+}
+
+fn is_element(node: &Node, name: &str) -> bool {
+    node.is_element()
+        && node.tag_name().name() == name
+        && node.tag_name().namespace() == Some(FREEDESKTOP_NAMESPACE)
+}
+
+fn required_attribute<'a>(node: &Node<'a, '_>, name: &str, mime_type: &str) -> io::Result<&'a str> {
+    match node.attribute(name) {
+        Some(v) => Ok(v),
+        None => Err(invalid_data(format!(
+            "{}: <{}> without the \"{}\" attribute",
+            mime_type,
+            node.tag_name().name(),
+            name
+        ))),
+    }
+}
+
+fn parse_match(node: &Node, mime_type: &str) -> io::Result<MagicMatch> {
+    let type_name = required_attribute(node, "type", mime_type)?;
+    let match_type = match MatchType::from_string(type_name) {
+        Some(v) => v,
+        None => {
+            return Err(invalid_data(format!(
+                "{}: unknown match type \"{}\"",
+                mime_type, type_name
+            )))
+        }
+    };
+
+    let mut children = Vec::new();
+    for child in node.children().filter(|n| is_element(n, "match")) {
+        children.push(parse_match(&child, mime_type)?);
+    }
+
+    let res = MagicMatch {
+        match_type,
+        offset: required_attribute(node, "offset", mime_type)?.to_string(),
+        value: required_attribute(node, "value", mime_type)?.to_string(),
+        mask: node.attribute("mask").map(|v| v.to_string()),
+        children,
+    };
+
+    // Ensure the rule can be compiled
+    if let Err(e) = crate::compile::compile_match(&res, 0, &mut Vec::new()) {
+        return Err(invalid_data(format!("{}: {}", mime_type, e)));
+    }
+
+    Ok(res)
+}
+
+fn parse_mime_type(node: &Node) -> io::Result<MimeTypeDefinition> {
+    let mime_type = match node.attribute("type") {
+        Some(v) if is_valid_mime_type(v) => v,
+        Some(v) => return Err(invalid_data(format!("invalid MIME type \"{}\"", v))),
+        None => return Err(invalid_data("<mime-type> without the \"type\" attribute")),
+    };
+
+    let mut res = MimeTypeDefinition {
+        mime_type: mime_type.to_string(),
+        ..Default::default()
+    };
+
+    for child in node.children().filter(|n| n.is_element()) {
+        if child.tag_name().namespace() != Some(FREEDESKTOP_NAMESPACE) {
+            continue;
+        }
+
+        match child.tag_name().name() {
+            "comment" => {
+                let lang = child
+                    .attribute((XML_NAMESPACE, "lang"))
+                    .map(|v| v.to_string());
+                res.comments
+                    .push((lang, child.text().unwrap_or("").to_string()));
+            }
+            "glob" => {
+                let pattern = required_attribute(&child, "pattern", mime_type)?;
+                let weight = match child.attribute("weight") {
+                    Some(v) => match v.parse::<i32>() {
+                        Ok(w) if (0..=100).contains(&w) => w,
+                        _ => {
+                            return Err(invalid_data(format!(
+                                "{}: invalid glob weight \"{}\"",
+                                mime_type, v
+                            )))
+                        }
+                    },
+                    None => 50,
+                };
+
+                res.globs.push(GlobDefinition {
+                    pattern: pattern.to_string(),
+                    weight,
+                    case_sensitive: child.attribute("case-sensitive") == Some("true"),
+                });
+            }
+            "glob-deleteall" => {
+                res.glob_deleteall = true;
+                res.globs.clear();
+            }
+            "magic" => {
+                let priority = match child.attribute("priority") {
+                    Some(v) => match v.parse::<u32>() {
+                        Ok(p) if p <= 100 => p,
+                        _ => {
+                            return Err(invalid_data(format!(
+                                "{}: invalid magic priority \"{}\"",
+                                mime_type, v
+                            )))
+                        }
+                    },
+                    None => 50,
+                };
+
+                let mut matches = Vec::new();
+                for m in child.children().filter(|n| is_element(n, "match")) {
+                    matches.push(parse_match(&m, mime_type)?);
+                }
+
+                res.magic.push(MagicDefinition { priority, matches });
+            }
+            "magic-deleteall" => {
+                res.magic_deleteall = true;
+                res.magic.clear();
+            }
+            "alias" => {
+                let alias = required_attribute(&child, "type", mime_type)?;
+                if !is_valid_mime_type(alias) {
+                    return Err(invalid_data(format!(
+                        "{}: invalid alias \"{}\"",
+                        mime_type, alias
+                    )));
+                }
+                res.aliases.push(alias.to_string());
+            }
+            "sub-class-of" => {
+                let parent = required_attribute(&child, "type", mime_type)?;
+                if !is_valid_mime_type(parent) {
+                    return Err(invalid_data(format!(
+                        "{}: invalid parent \"{}\"",
+                        mime_type, parent
+                    )));
+                }
+                res.sub_class_of.push(parent.to_string());
+            }
+            "icon" => {
+                res.icon = Some(required_attribute(&child, "name", mime_type)?.to_string());
+            }
+            "generic-icon" => {
+                res.generic_icon = Some(required_attribute(&child, "name", mime_type)?.to_string());
+            }
+            "root-XML" => {
+                let namespace = required_attribute(&child, "namespaceURI", mime_type)?;
+                let local_name = required_attribute(&child, "localName", mime_type)?;
+                res.root_xml
+                    .push((namespace.to_string(), local_name.to_string()));
+            }
+            // Elements from newer versions of the format are ignored
+            _ => {}
+        }
+    }
+
+    Ok(res)
+}
+
+/// Parses the contents of a shared-mime-info XML package.
+pub fn read_package_from_string(text: &str) -> io::Result<Vec<MimeTypeDefinition>> {
+    let options = ParsingOptions {
+        allow_dtd: true,
+        ..ParsingOptions::default()
+    };
+
+    let doc = match Document::parse_with_options(text, options) {
+        Ok(v) => v,
+        Err(e) => return Err(invalid_data(e.to_string())),
+    };
+
+    let root = doc.root_element();
+    if !is_element(&root, "mime-info") {
+        return Err(invalid_data(
+            "the root element is not a shared-mime-info <mime-info>",
+        ));
+    }
+
+    let mut res = Vec::new();
+    for node in root.children().filter(|n| is_element(n, "mime-type")) {
+        res.push(parse_mime_type(&node)?);
+    }
+
+    Ok(res)
+}
+
+/// Parses the XML package at @file_name.
+pub fn read_package_from_file<P: AsRef<Path>>(file_name: P) -> io::Result<Vec<MimeTypeDefinition>> {
+    let text = fs::read_to_string(file_name)?;
+
+    read_package_from_string(&text)
+}
+
+/// Parses the per-type XML file at @file_name, written by
+/// update-mime-database in `mime/<media>/<subtype>.xml`, with a
+/// `<mime-type>` root element.
+pub fn read_type_from_file<P: AsRef<Path>>(file_name: P) -> io::Result<MimeTypeDefinition> {
+    let text = fs::read_to_string(file_name)?;
+
+    let doc = match Document::parse(&text) {
+        Ok(v) => v,
+        Err(e) => return Err(invalid_data(e.to_string())),
+    };
+
+    let root = doc.root_element();
+    if !is_element(&root, "mime-type") {
+        return Err(invalid_data("the root element is not a <mime-type>"));
+    }
+
+    parse_mime_type(&root)
+}
+
+// Escapes @s for the text or the attribute values of an XML document
+fn escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&apos;"),
+            _ => res.push(c),
+        }
+    }
+
+    res
+}
+
+fn write_match(m: &MagicMatch, depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    out.push_str(&format!(
+        "{}<match type=\"{}\" offset=\"{}\" value=\"{}\"",
+        indent,
+        m.match_type.as_str(),
+        escape(&m.offset),
+        escape(&m.value)
+    ));
+    if let Some(mask) = &m.mask {
+        out.push_str(&format!(" mask=\"{}\"", escape(mask)));
+    }
+
+    if m.children.is_empty() {
+        out.push_str("/>\n");
+        return;
+    }
+
+    out.push_str(">\n");
+    for child in &m.children {
+        write_match(child, depth + 1, out);
+    }
+    out.push_str(&format!("{}</match>\n", indent));
+}
+
+/// Writes @definitions as the source XML of a shared-mime-info package,
+/// which [`read_package_from_string`] parses back.
+pub fn package_to_string(definitions: &[MimeTypeDefinition]) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(&format!("<mime-info xmlns=\"{}\">\n", FREEDESKTOP_NAMESPACE));
+
+    for def in definitions {
+        out.push_str(&format!("  <mime-type type=\"{}\">\n", escape(&def.mime_type)));
+
+        for (lang, comment) in &def.comments {
+            match lang {
+                Some(lang) => out.push_str(&format!(
+                    "    <comment xml:lang=\"{}\">{}</comment>\n",
+                    escape(lang),
+                    escape(comment)
+                )),
+                None => out.push_str(&format!("    <comment>{}</comment>\n", escape(comment))),
+            }
+        }
+        // Before the globs and the magic, which it would delete otherwise
+        if def.glob_deleteall {
+            out.push_str("    <glob-deleteall/>\n");
+        }
+        for glob in &def.globs {
+            out.push_str(&format!("    <glob pattern=\"{}\"", escape(&glob.pattern)));
+            if glob.weight != 50 {
+                out.push_str(&format!(" weight=\"{}\"", glob.weight));
+            }
+            if glob.case_sensitive {
+                out.push_str(" case-sensitive=\"true\"");
+            }
+            out.push_str("/>\n");
+        }
+        if def.magic_deleteall {
+            out.push_str("    <magic-deleteall/>\n");
+        }
+        for magic in &def.magic {
+            out.push_str(&format!("    <magic priority=\"{}\">\n", magic.priority));
+            for m in &magic.matches {
+                write_match(m, 3, &mut out);
+            }
+            out.push_str("    </magic>\n");
+        }
+        for alias in &def.aliases {
+            out.push_str(&format!("    <alias type=\"{}\"/>\n", escape(alias)));
+        }
+        for parent in &def.sub_class_of {
+            out.push_str(&format!("    <sub-class-of type=\"{}\"/>\n", escape(parent)));
+        }
+        if let Some(icon) = &def.icon {
+            out.push_str(&format!("    <icon name=\"{}\"/>\n", escape(icon)));
+        }
+        if let Some(icon) = &def.generic_icon {
+            out.push_str(&format!("    <generic-icon name=\"{}\"/>\n", escape(icon)));
+        }
+        for (uri, local_name) in &def.root_xml {
+            out.push_str(&format!(
+                "    <root-XML namespaceURI=\"{}\" localName=\"{}\"/>\n",
+                escape(uri),
+                escape(local_name)
+            ));
+        }
+
+        out.push_str("  </mime-type>\n");
+    }
+
+    out.push_str("</mime-info>\n");
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_package() {
+        let types = read_package_from_file("test_files/packages/x-xdg-mime-test.xml").unwrap();
+        let mut def = types[0].clone();
+        def.comments.push((Some("en".to_string()), "<A & \"B\">".to_string()));
+        def.glob_deleteall = true;
+        def.globs.push(GlobDefinition {
+            pattern: "*.XDG".to_string(),
+            weight: 50,
+            case_sensitive: true,
+        });
+
+        let xml = package_to_string(&[def.clone()]);
+        assert_eq!(read_package_from_string(&xml).unwrap(), vec![def]);
+    }
+
+    #[test]
+    fn parse_package() {
+        let types = read_package_from_file("test_files/packages/x-xdg-mime-test.xml").unwrap();
+        assert_eq!(types.len(), 1);
+
+        let t = &types[0];
+        assert_eq!(t.mime_type, "application/x-xdg-mime-test");
+        assert_eq!(t.comments[0], (None, "xdg-mime test document".to_string()));
+        assert_eq!(
+            t.comments[1],
+            (
+                Some("fr".to_string()),
+                "document de test xdg-mime".to_string()
+            )
+        );
+        assert_eq!(
+            t.globs,
+            vec![GlobDefinition {
+                pattern: "*.xdgtest".to_string(),
+                weight: 60,
+                case_sensitive: false,
+            }]
+        );
+        assert_eq!(t.magic[0].priority, 60);
+        assert_eq!(t.magic[0].matches[0].value, "XDG\\x00TEST");
+        assert_eq!(t.magic[0].matches[0].children.len(), 1);
+        assert_eq!(t.aliases, vec!["application/x-xdg-test".to_string()]);
+        assert_eq!(t.sub_class_of, vec!["text/plain".to_string()]);
+        assert_eq!(t.generic_icon, Some("text-x-generic".to_string()));
+    }
+
+    #[test]
+    fn parse_type_file() {
+        let t = read_type_from_file("test_files/mime/image/png.xml").unwrap();
+        assert_eq!(t.mime_type, "image/png");
+        assert_eq!(t.comments[0], (None, "PNG image".to_string()));
+        assert!(t.comments.contains(&(Some("de".to_string()), "PNG-Bild".to_string())));
+
+        assert!(read_type_from_file("test_files/packages/x-xdg-mime-test.xml").is_err());
+    }
+
+    #[test]
+    fn invalid_packages() {
+        let header = "<mime-info xmlns=\"http://www.freedesktop.org/standards/shared-mime-info\">";
+
+        assert!(read_package_from_string("<mime-info>").is_err());
+        assert!(read_package_from_string("<mime-info/>").is_err());
+        assert!(read_package_from_string(&format!(
+            "{}<mime-type type=\"foo\"/></mime-info>",
+            header
+        ))
+        .is_err());
+        assert!(read_package_from_string(&format!(
+            "{}<mime-type type=\"a/b\"><glob pattern=\"*.b\" weight=\"500\"/></mime-type></mime-info>",
+            header
+        ))
+        .is_err());
+        assert!(read_package_from_string(&format!(
+            "{}<mime-type type=\"a/b\"><magic><match type=\"big16\" value=\"x\" offset=\"0\"/></magic></mime-type></mime-info>",
+            header
+        ))
+        .is_err());
+        assert!(read_package_from_string(&format!(
+            "{}<mime-type type=\"a/b\"/></mime-info>",
+            header
+        ))
+        .is_ok());
+    }
+}
@@ -1,10 +1,13 @@
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::fs::File;
+use std::io;
 use std::io::BufRead;
 use std::io::BufReader;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+use crate::lines;
 
 #[derive(Clone, Eq)]
 pub struct Subclass {
@@ -20,7 +23,15 @@ impl Subclass {
         }
     }
 
-    fn from_string(s: String) -> Option<Subclass> {
+    pub fn mime_type(&self) -> &str {
+        &self.mime_type
+    }
+
+    pub fn parent_type(&self) -> &str {
+        &self.parent_type
+    }
+
+    pub fn from_string(s: String) -> Option<Subclass> {
         let mut chunks = s.split_whitespace();
 
         let mime_type = match chunks.next() {
@@ -34,15 +45,22 @@ impl Subclass {
         };
 
         Some(Subclass {
-            mime_type: mime_type,
-            parent_type: parent_type,
+            mime_type,
+            parent_type,
         })
     }
 }
 
 impl fmt::Debug for Subclass {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Subclass {} {}\n", self.parent_type, self.mime_type)
+        writeln!(f, "Subclass {} {}", self.parent_type, self.mime_type)
+    }
+}
+
+// Formats the subclass as a line of a subclasses file
+impl fmt::Display for Subclass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.mime_type, self.parent_type)
     }
 }
 
@@ -60,18 +78,33 @@ impl Ord for Subclass {
 
 impl PartialOrd for Subclass {
     fn partial_cmp(&self, other: &Subclass) -> Option<Ordering> {
-        Some(self.parent_type.cmp(&other.parent_type))
+        Some(self.cmp(other))
     }
 }
 
+/// The relationships followed when walking the MIME type hierarchy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Hierarchy {
+    /// Only the relationships listed in the `subclasses` files.
+    Explicit,
+    /// Also the relationships implied by the specification: the `text/*`
+    /// types are sub-classes of `text/plain`, and the types outside of
+    /// `inode/*` are sub-classes of `application/octet-stream`.
+    #[default]
+    Full,
+}
+
 pub struct ParentsMap {
     parents: HashMap<String, Vec<String>>,
+    // The file each relationship was loaded from
+    sources: HashMap<(String, String), PathBuf>,
 }
 
 impl ParentsMap {
     pub fn new() -> ParentsMap {
         ParentsMap {
             parents: HashMap::new(),
+            sources: HashMap::new(),
         }
     }
 
@@ -79,7 +112,7 @@ impl ParentsMap {
         let v = self
             .parents
             .entry(subclass.mime_type.clone())
-            .or_insert(Vec::new());
+            .or_default();
         if !v.contains(&subclass.parent_type) {
             v.push(subclass.parent_type.clone());
         }
@@ -91,6 +124,147 @@ impl ParentsMap {
         }
     }
 
+    /// Adds @subclasses, loaded from the file @source.
+    pub fn add_subclasses_from(&mut self, subclasses: Vec<Subclass>, source: &Path) {
+        for s in subclasses {
+            self.sources
+                .entry((s.mime_type.clone(), s.parent_type.clone()))
+                .or_insert_with(|| source.to_path_buf());
+            self.add_subclass(s);
+        }
+    }
+
+    /// Adds the parents from @other for the MIME types that do not have
+    /// any parent in this map.
+    pub fn add_missing_parents(&mut self, other: ParentsMap) {
+        for (mime_type, parents) in other.parents {
+            if self.parents.contains_key(&mime_type) {
+                continue;
+            }
+
+            for parent in &parents {
+                let key = (mime_type.clone(), parent.clone());
+                if let Some(source) = other.sources.get(&key) {
+                    self.sources.insert(key, source.clone());
+                }
+            }
+            self.parents.insert(mime_type, parents);
+        }
+    }
+
+    /// Replaces the MIME types and parents with the result of @canonical,
+    /// if any.
+    pub fn canonicalize_types<F: Fn(&str) -> Option<String>>(&mut self, canonical: F) {
+        let canonical = |mime_type: &str| canonical(mime_type).unwrap_or_else(|| mime_type.to_string());
+
+        // Keep the first declaration for the types merged together
+        let mut types: Vec<&String> = self.parents.keys().collect();
+        types.sort_unstable();
+
+        let mut map = ParentsMap::new();
+        for mime_type in types {
+            let child = canonical(mime_type);
+            for parent in &self.parents[mime_type] {
+                let subclass = Subclass::new(child.clone(), canonical(parent));
+                if let Some(source) = self.sources.get(&(mime_type.clone(), parent.clone())) {
+                    map.sources
+                        .entry((subclass.mime_type.clone(), subclass.parent_type.clone()))
+                        .or_insert_with(|| source.clone());
+                }
+                map.add_subclass(subclass);
+            }
+        }
+
+        *self = map;
+    }
+
+    /// Removes the relationships making cycles in the hierarchy, and
+    /// returns them with the file they were loaded from, if known.
+    ///
+    /// The types are walked depth-first by name, and their parents in
+    /// declaration order; the relationship closing a cycle is removed.
+    pub fn break_cycles(&mut self) -> Vec<(Subclass, Option<PathBuf>)> {
+        let mut types: Vec<String> = self.parents.keys().cloned().collect();
+        types.sort_unstable();
+
+        let mut done: HashSet<String> = HashSet::new();
+        let mut removed = Vec::new();
+
+        for root in types {
+            let mut path: Vec<String> = Vec::new();
+            self.visit(&root, &mut path, &mut done, &mut removed);
+        }
+
+        let mut res = Vec::new();
+        for (child, parent) in removed {
+            if let Some(parents) = self.parents.get_mut(&child) {
+                parents.retain(|p| *p != parent);
+            }
+            let source = self.sources.remove(&(child.clone(), parent.clone()));
+            res.push((Subclass::new(child, parent), source));
+        }
+
+        res
+    }
+
+    fn visit(
+        &self,
+        mime_type: &str,
+        path: &mut Vec<String>,
+        done: &mut HashSet<String>,
+        removed: &mut Vec<(String, String)>,
+    ) {
+        if done.contains(mime_type) {
+            return;
+        }
+
+        path.push(mime_type.to_string());
+
+        if let Some(parents) = self.parents.get(mime_type) {
+            for parent in parents {
+                if removed.iter().any(|(c, p)| c == mime_type && p == parent) {
+                    continue;
+                }
+
+                if path.contains(parent) {
+                    removed.push((mime_type.to_string(), parent.clone()));
+                } else {
+                    self.visit(parent, path, done, removed);
+                }
+            }
+        }
+
+        path.pop();
+        done.insert(mime_type.to_string());
+    }
+
+    pub fn iter(&self) -> std::collections::hash_map::Iter<'_, String, Vec<String>> {
+        self.parents.iter()
+    }
+
+    /// Returns the MIME types that have @mime_type as a direct parent,
+    /// sorted by name.
+    pub fn children_of(&self, mime_type: &str) -> Vec<&str> {
+        let mut res: Vec<&str> = self
+            .parents
+            .iter()
+            .filter(|(_, parents)| parents.iter().any(|p| p == mime_type))
+            .map(|(child, _)| child.as_str())
+            .collect();
+
+        res.sort_unstable();
+
+        res
+    }
+
+    /// Returns the direct parents of @mime_type, without copying it.
+    pub fn parents_of(&self, mime_type: &str) -> &[String] {
+        match self.parents.get(mime_type) {
+            Some(v) => v,
+            None => &[],
+        }
+    }
+
     pub fn lookup<S: Into<String>>(&self, mime_type: S) -> Option<&Vec<String>> {
         let mime_type = mime_type.into();
 
@@ -98,28 +272,22 @@ impl ParentsMap {
     }
 }
 
+/// Reads the subclasses from @reader, in the format of a `subclasses`
+/// file.
+///
+/// Comments and empty lines are skipped; the lines that cannot be parsed
+/// are returned as errors of kind `InvalidData`.
+pub fn read_subclasses<R: BufRead>(reader: R) -> impl Iterator<Item = io::Result<Subclass>> {
+    lines::parse_lines(reader, Subclass::from_string).map(|(_, res)| res)
+}
+
 pub fn read_subclasses_from_file<P: AsRef<Path>>(file_name: P) -> Vec<Subclass> {
     let f = match File::open(file_name) {
         Ok(v) => v,
         Err(_) => return Vec::new(),
     };
 
-    let mut res = Vec::new();
-    let file = BufReader::new(&f);
-    for line in file.lines() {
-        let line = line.unwrap();
-
-        if line.is_empty() || line.starts_with('#') {
-            continue;
-        }
-
-        match Subclass::from_string(line) {
-            Some(v) => res.push(v),
-            None => continue,
-        }
-    }
-
-    res
+    lines::collect_valid(read_subclasses(BufReader::new(f)))
 }
 
 #[cfg(test)]
@@ -142,8 +310,35 @@ mod tests {
         pm.add_subclass(Subclass::new("text/rfc822-headers", "text/plain"));
 
         assert_eq!(
-            pm.lookup(&"message/partial".to_string()),
+            pm.lookup("message/partial".to_string()),
             Some(&vec!["text/plain".to_string(),])
         );
     }
+
+    #[test]
+    fn break_cycles() {
+        let mut pm = ParentsMap::new();
+
+        pm.add_subclasses_from(
+            vec![
+                Subclass::new("text/x-a", "text/x-b"),
+                Subclass::new("text/x-b", "text/x-a"),
+                Subclass::new("text/x-c", "text/x-c"),
+                Subclass::new("text/x-c", "text/plain"),
+            ],
+            Path::new("subclasses"),
+        );
+
+        let removed = pm.break_cycles();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(removed[0].0.mime_type(), "text/x-b");
+        assert_eq!(removed[0].0.parent_type(), "text/x-a");
+        assert_eq!(removed[0].1, Some(PathBuf::from("subclasses")));
+        assert_eq!(removed[1].0.mime_type(), "text/x-c");
+
+        assert_eq!(pm.lookup("text/x-a"), Some(&vec!["text/x-b".to_string()]));
+        assert_eq!(pm.lookup("text/x-b"), Some(&vec![]));
+        assert_eq!(pm.lookup("text/x-c"), Some(&vec!["text/plain".to_string()]));
+        assert!(pm.break_cycles().is_empty());
+    }
 }
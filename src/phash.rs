@@ -0,0 +1,142 @@
+// The number of displacements tried for a bucket before growing the table
+const MAX_DISPLACEMENTS: u32 = 1 << 16;
+
+// Hashes @key with @seed: FNV-1a, followed by a final mix so that the
+// seeds give independent positions
+fn hash(key: &str, seed: u32) -> u64 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325 ^ u64::from(seed).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    for b in key.bytes() {
+        h ^= u64::from(b);
+        h = h.wrapping_mul(0x0100_0000_01b3);
+    }
+
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+
+    h
+}
+
+/// A map from strings to values, built once with a perfect hash function
+/// so that each lookup is a single probe, following the "hash, displace"
+/// scheme: the keys are spread in buckets, and each bucket gets the seed
+/// placing all its keys in free slots.
+#[derive(Clone, Debug)]
+pub struct PerfectMap<V> {
+    seeds: Vec<u32>,
+    slots: Vec<Option<(String, V)>>,
+}
+
+impl<V> PerfectMap<V> {
+    /// Builds the map of @entries, whose keys must be unique.
+    pub fn new(entries: Vec<(String, V)>) -> PerfectMap<V> {
+        let mut len = entries.len() + entries.len() / 4 + 1;
+        loop {
+            if let Some(seeds) = PerfectMap::<V>::find_seeds(&entries, len) {
+                let mut slots: Vec<Option<(String, V)>> = (0..len).map(|_| None).collect();
+                let n_buckets = seeds.len();
+                for (key, value) in entries {
+                    let seed = seeds[(hash(&key, 0) % n_buckets as u64) as usize];
+                    let slot = (hash(&key, seed) % len as u64) as usize;
+                    slots[slot] = Some((key, value));
+                }
+
+                return PerfectMap { seeds, slots };
+            }
+
+            len *= 2;
+        }
+    }
+
+    // Finds the seed of each bucket, for a table of @len slots
+    fn find_seeds(entries: &[(String, V)], len: usize) -> Option<Vec<u32>> {
+        let n_buckets = entries.len() / 4 + 1;
+
+        let mut buckets: Vec<Vec<&str>> = vec![Vec::new(); n_buckets];
+        for (key, _) in entries {
+            buckets[(hash(key, 0) % n_buckets as u64) as usize].push(key);
+        }
+
+        // The largest buckets are the hardest to place
+        let mut order: Vec<usize> = (0..n_buckets).collect();
+        order.sort_by_key(|b| std::cmp::Reverse(buckets[*b].len()));
+
+        let mut used = vec![false; len];
+        let mut seeds = vec![0; n_buckets];
+        let mut positions = Vec::new();
+        for bucket in order {
+            let keys = &buckets[bucket];
+            if keys.is_empty() {
+                break;
+            }
+
+            let seed = (1..MAX_DISPLACEMENTS).find(|seed| {
+                positions.clear();
+                for key in keys {
+                    let slot = (hash(key, *seed) % len as u64) as usize;
+                    if used[slot] || positions.contains(&slot) {
+                        return false;
+                    }
+                    positions.push(slot);
+                }
+
+                true
+            })?;
+
+            for slot in &positions {
+                used[*slot] = true;
+            }
+            seeds[bucket] = seed;
+        }
+
+        Some(seeds)
+    }
+
+    /// Returns the value of @key, if any.
+    pub fn get(&self, key: &str) -> Option<&V> {
+        if self.slots.is_empty() {
+            return None;
+        }
+
+        let seed = self.seeds[(hash(key, 0) % self.seeds.len() as u64) as usize];
+        match &self.slots[(hash(key, seed) % self.slots.len() as u64) as usize] {
+            Some((k, v)) if k == key => Some(v),
+            _ => None,
+        }
+    }
+
+    /// The number of keys of the map.
+    pub fn len(&self) -> usize {
+        self.slots.iter().filter(|s| s.is_some()).count()
+    }
+}
+
+impl<V> Default for PerfectMap<V> {
+    fn default() -> PerfectMap<V> {
+        PerfectMap {
+            seeds: Vec::new(),
+            slots: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookups() {
+        let keys: Vec<String> = (0..2000).map(|i| format!("ext{}", i)).collect();
+        let map = PerfectMap::new(keys.iter().cloned().zip(0..).collect());
+
+        assert_eq!(map.len(), keys.len());
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(map.get(key), Some(&i));
+        }
+        assert_eq!(map.get("ext2000"), None);
+        assert_eq!(map.get(""), None);
+
+        let empty: PerfectMap<usize> = PerfectMap::new(Vec::new());
+        assert_eq!(empty.get("png"), None);
+    }
+}
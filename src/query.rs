@@ -0,0 +1,125 @@
+use crate::{GlobMatch, Hierarchy, SharedMimeInfo, EMPTY_TYPE, TEXT_PLAIN_TYPE, UNKNOWN_TYPE};
+
+/// The lookups shared by the databases loaded at run time, like
+/// [`SharedMimeInfo`], and the ones compiled into the program, like
+/// [`StaticMimeDb`][crate::codegen::StaticMimeDb].
+pub trait MimeQuery {
+    /// Retrieves the MIME type aliased by @mime_type, if any.
+    fn unalias_mime_type(&self, mime_type: &str) -> Option<String>;
+
+    /// Checks whether @mime_type is @base, or a sub-class of it, resolving
+    /// the aliases of both.
+    fn is_subclass_of(&self, mime_type: &str, base: &str) -> bool;
+
+    /// Looks up the generic icon associated to a MIME type.
+    fn lookup_generic_icon_name(&self, mime_type: &str) -> Option<String>;
+
+    /// Looks up the MIME type of the given file name, without looking at
+    /// the data inside the file.
+    fn lookup_file_name(&self, file_name: &str) -> GlobMatch;
+
+    /// Retrieves the MIME type for the given data.
+    fn get_mime_type_for_data(&self, data: &[u8]) -> Option<String>;
+
+    /// Guesses the MIME type of a file from its @file_name and the @data at
+    /// its beginning, like [`SharedMimeInfo::guess_mime_type`].
+    fn guess_mime_type(&self, file_name: Option<&str>, data: Option<&[u8]>) -> String {
+        let name_match = file_name.map(|name| self.lookup_file_name(name));
+
+        guess_from(name_match, data, |data| self.get_mime_type_for_data(data))
+    }
+}
+
+// Guesses the MIME type from the result of the file name lookup, if any,
+// and @data, sniffed with @sniff: the file name is used when its best
+// match is unambiguous; otherwise the data decides between the types
+// matching the file name, if any
+pub(crate) fn guess_from<F>(name_match: Option<GlobMatch>, data: Option<&[u8]>, sniff: F) -> String
+where
+    F: FnOnce(&[u8]) -> Option<String>,
+{
+    let name_types = match name_match {
+        Some(GlobMatch::Unique(mime_type)) => return mime_type,
+        Some(GlobMatch::Ambiguous(types)) => types,
+        Some(GlobMatch::NoMatch) | None => Vec::new(),
+    };
+
+    let data = match data {
+        Some(v) => v,
+        None => return name_types.into_iter().next().unwrap_or_else(|| UNKNOWN_TYPE.to_string()),
+    };
+
+    if data.is_empty() {
+        return EMPTY_TYPE.to_string();
+    }
+
+    if let Some(mime_type) = sniff(data) {
+        if name_types.is_empty() || name_types.contains(&mime_type) {
+            return mime_type;
+        }
+    }
+
+    name_types.into_iter().next().unwrap_or_else(|| UNKNOWN_TYPE.to_string())
+}
+
+// Returns the canonical @mime_type, then its ancestors breadth-first, from
+// the explicit parents returned by @parents_of, with the implicit parents
+// of the specification in the full @hierarchy
+pub(crate) fn ancestors<'a, F>(mime_type: &'a str, hierarchy: Hierarchy, parents_of: F) -> Vec<&'a str>
+where
+    F: Fn(&'a str) -> Vec<&'a str>,
+{
+    let mut res = vec![mime_type];
+    let mut next = 0;
+    while let Some(current) = res.get(next).copied() {
+        next += 1;
+
+        let parents = parents_of(current);
+        let implicit = if hierarchy == Hierarchy::Explicit {
+            None
+        } else if current.starts_with("text/")
+            && current != TEXT_PLAIN_TYPE
+            && !parents.contains(&TEXT_PLAIN_TYPE)
+        {
+            Some(TEXT_PLAIN_TYPE)
+        } else if parents.is_empty() && current != UNKNOWN_TYPE && !current.starts_with("inode/") {
+            Some(UNKNOWN_TYPE)
+        } else {
+            None
+        };
+
+        for parent in parents.into_iter().chain(implicit) {
+            if !res.contains(&parent) {
+                res.push(parent);
+            }
+        }
+    }
+
+    res
+}
+
+impl MimeQuery for SharedMimeInfo {
+    fn unalias_mime_type(&self, mime_type: &str) -> Option<String> {
+        SharedMimeInfo::unalias_mime_type(self, mime_type)
+    }
+
+    fn is_subclass_of(&self, mime_type: &str, base: &str) -> bool {
+        SharedMimeInfo::is_subclass_of(self, mime_type, base)
+    }
+
+    fn lookup_generic_icon_name(&self, mime_type: &str) -> Option<String> {
+        SharedMimeInfo::lookup_generic_icon_name(self, mime_type)
+    }
+
+    fn lookup_file_name(&self, file_name: &str) -> GlobMatch {
+        SharedMimeInfo::lookup_file_name(self, file_name)
+    }
+
+    fn get_mime_type_for_data(&self, data: &[u8]) -> Option<String> {
+        SharedMimeInfo::get_mime_type_for_data(self, data)
+    }
+
+    fn guess_mime_type(&self, file_name: Option<&str>, data: Option<&[u8]>) -> String {
+        SharedMimeInfo::guess_mime_type(self, file_name, data)
+    }
+}
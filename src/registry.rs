@@ -0,0 +1,122 @@
+use std::ptr;
+
+use windows_sys::Win32::Foundation::{ERROR_MORE_DATA, ERROR_SUCCESS};
+use windows_sys::Win32::System::Registry::{RegEnumKeyExW, RegGetValueW, HKEY_CLASSES_ROOT, RRF_RT_REG_SZ};
+
+use crate::MemoryDirectory;
+
+// The name standing for the registry in the load warnings
+const REGISTRY_NAME: &str = "registry";
+
+// The longest name of a registry key, in UTF-16 units
+const MAX_KEY_LEN: usize = 255;
+
+// The longest value read, in UTF-16 units
+const MAX_VALUE_LEN: usize = 256;
+
+// Returns @s as a NUL-terminated UTF-16 string
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(Some(0)).collect()
+}
+
+// Returns the `Content Type` value of the `HKEY_CLASSES_ROOT` sub-key
+// @key, a NUL-terminated UTF-16 string
+fn content_type(key: &[u16]) -> Option<String> {
+    let name = wide("Content Type");
+    let mut value = [0u16; MAX_VALUE_LEN];
+    let mut size = (value.len() * 2) as u32;
+
+    // SAFETY: the key and value names are NUL-terminated, and the size is
+    // the one of the buffer, in bytes
+    let res = unsafe {
+        RegGetValueW(
+            HKEY_CLASSES_ROOT,
+            key.as_ptr(),
+            name.as_ptr(),
+            RRF_RT_REG_SZ,
+            ptr::null_mut(),
+            value.as_mut_ptr().cast(),
+            &mut size,
+        )
+    };
+    if res != ERROR_SUCCESS {
+        return None;
+    }
+
+    // The size includes the NUL terminator
+    let len = (size as usize / 2).saturating_sub(1).min(value.len());
+    String::from_utf16(&value[..len]).ok()
+}
+
+// Returns the extensions of the sub-keys of `HKEY_CLASSES_ROOT`, like
+// `.png`, with their `Content Type` value
+fn registry_extensions() -> Vec<(String, String)> {
+    let mut res = Vec::new();
+    let mut key = [0u16; MAX_KEY_LEN + 1];
+
+    for index in 0.. {
+        let mut len = key.len() as u32;
+        // SAFETY: the length is the one of the buffer, in UTF-16 units
+        let status = unsafe {
+            RegEnumKeyExW(
+                HKEY_CLASSES_ROOT,
+                index,
+                key.as_mut_ptr(),
+                &mut len,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+            )
+        };
+        if status == ERROR_MORE_DATA {
+            continue;
+        }
+        // The end of the keys, or an error
+        if status != ERROR_SUCCESS {
+            break;
+        }
+
+        let len = (len as usize).min(MAX_KEY_LEN);
+        if key[0] != u16::from(b'.') {
+            continue;
+        }
+        key[len] = 0;
+        if let (Ok(extension), Some(mime_type)) = (String::from_utf16(&key[..len]), content_type(&key[..=len])) {
+            res.push((extension, mime_type));
+        }
+    }
+
+    res
+}
+
+/// Returns the extensions registered in `HKEY_CLASSES_ROOT` with their
+/// `Content Type`, like `.png` and `image/png`, as the globs of a
+/// directory to load with
+/// [`SharedMimeInfoBuilder::memory_directory`][crate::SharedMimeInfoBuilder::memory_directory].
+///
+/// The registry only maps extensions to types, so the magic, aliases and
+/// sub-classes come from the other directories, like the database bundled
+/// by the `embedded-db` feature.
+pub fn registry_directory() -> MemoryDirectory {
+    MemoryDirectory::new(REGISTRY_NAME).extensions(registry_extensions())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SharedMimeInfo;
+
+    #[test]
+    fn read_registry() {
+        // Windows registers the plain text files
+        let extensions = registry_extensions();
+        assert!(extensions.iter().any(|(e, t)| e.eq_ignore_ascii_case(".txt") && t == "text/plain"));
+
+        let mime_db = SharedMimeInfo::builder()
+            .system_directories(false)
+            .memory_directory(registry_directory())
+            .build();
+        assert!(mime_db.get_mime_types_from_file_name("notes.txt").contains(&"text/plain".to_string()));
+    }
+}
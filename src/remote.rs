@@ -0,0 +1,222 @@
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::{Component, Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+/// A MIME database hosted on an HTTPS server.
+///
+/// The remote database is a copy of a `mime` directory, as generated by
+/// `update-mime-database`; each file is fetched from `<url>/<file name>`
+/// and validated against its SHA-256 checksum before being stored under
+/// the `mime` sub-directory of the cache directory. Files already in the
+/// cache with a matching checksum are not fetched again.
+///
+/// Only `https://` URLs are fetched from.
+#[derive(Clone, Debug)]
+pub struct RemoteDatabase {
+    url: String,
+    cache_dir: PathBuf,
+    files: Vec<(PathBuf, String)>,
+}
+
+impl RemoteDatabase {
+    /// Creates a new remote database for the given base @url, cached
+    /// inside @cache_dir.
+    pub fn new<S: Into<String>, P: AsRef<Path>>(url: S, cache_dir: P) -> RemoteDatabase {
+        RemoteDatabase {
+            url: url.into(),
+            cache_dir: cache_dir.as_ref().to_path_buf(),
+            files: Vec::new(),
+        }
+    }
+
+    /// Adds a file to fetch, like `globs2` or `magic`, with the hex-encoded
+    /// SHA-256 checksum of its contents.
+    pub fn file(mut self, file_name: &Path, sha256: &str) -> RemoteDatabase {
+        self.files.push((file_name.to_path_buf(), sha256.to_lowercase()));
+        self
+    }
+
+    fn is_cached(path: &Path, sha256: &str) -> bool {
+        match fs::read(path) {
+            Ok(v) => checksum(&v) == sha256,
+            Err(_) => false,
+        }
+    }
+
+    fn download(url: &str) -> io::Result<Vec<u8>> {
+        let response = match ureq::get(url).call() {
+            Ok(v) => v,
+            Err(e) => return Err(io::Error::other(e.to_string())),
+        };
+
+        let mut res = Vec::new();
+        response.into_reader().read_to_end(&mut res)?;
+
+        Ok(res)
+    }
+
+    pub(crate) fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
+
+    /// Fetches all the files of the remote database that are missing from,
+    /// or outdated in, the cache, and returns the data directory to load.
+    pub(crate) fn fetch(&self) -> io::Result<PathBuf> {
+        self.fetch_with(RemoteDatabase::download)
+    }
+
+    // Like fetch(), downloading the URLs with @download
+    fn fetch_with<F: FnMut(&str) -> io::Result<Vec<u8>>>(&self, mut download: F) -> io::Result<PathBuf> {
+        // Plain HTTP could be tampered with, or redirected, on the way
+        if !self.url.starts_with("https://") {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "the URL is not https"));
+        }
+
+        let mut mime_path = self.cache_dir.clone();
+        mime_path.push("mime");
+        fs::create_dir_all(&mime_path)?;
+
+        for (file_name, sha256) in &self.files {
+            // Only plain file names, which stay inside the cache, and cannot
+            // be a drive or a parent directory on Windows either
+            let mut components = file_name.components();
+            let file_name = match (components.next(), components.next(), file_name.to_str()) {
+                (Some(Component::Normal(_)), None, Some(v)) if !v.contains('/') && !v.starts_with('.') => v,
+                _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid file name")),
+            };
+
+            let mut path = mime_path.clone();
+            path.push(file_name);
+
+            if RemoteDatabase::is_cached(&path, sha256) {
+                continue;
+            }
+
+            let url = format!("{}/{}", self.url.trim_end_matches('/'), percent_encode(file_name));
+            let data = download(&url)?;
+            if checksum(&data) != *sha256 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "checksum mismatch"));
+            }
+
+            // Write to a temporary file first, so that an interrupted
+            // download never leaves a truncated file in the cache
+            let mut tmp_path = mime_path.clone();
+            tmp_path.push(format!(".{}.tmp", file_name));
+
+            let mut f = File::create(&tmp_path)?;
+            f.write_all(&data)?;
+            f.sync_all()?;
+            fs::rename(&tmp_path, &path)?;
+        }
+
+        Ok(self.cache_dir.clone())
+    }
+}
+
+// Encodes all the bytes of @s but the unreserved characters of URLs
+fn percent_encode(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => res.push(char::from(b)),
+            _ => res.push_str(&format!("%{:02X}", b)),
+        }
+    }
+
+    res
+}
+
+fn checksum(data: &[u8]) -> String {
+    let digest = Sha256::digest(data);
+
+    let mut res = String::with_capacity(digest.len() * 2);
+    for b in digest.iter() {
+        res.push_str(&format!("{:02x}", b));
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn fetch_from_cache() {
+        let globs = include_bytes!("../test_files/mime/globs2");
+
+        let cache_dir = TempDir::new("remote");
+        cache_dir.write("mime/globs2", &globs[..]);
+
+        // The URL is never hit, as the cached copy is valid
+        let remote = RemoteDatabase::new("https://invalid.example", cache_dir.path())
+            .file(Path::new("globs2"), &checksum(globs));
+        assert_eq!(remote.fetch().unwrap(), cache_dir.path());
+
+        let mime_db = crate::SharedMimeInfo::builder()
+            .system_directories(false)
+            .remote(remote)
+            .build();
+        assert_eq!(
+            mime_db.get_mime_types_from_file_name("bar.gif"),
+            vec!["image/gif".to_string()]
+        );
+
+        // A bad checksum forces a download, whose data is checked
+        let remote = RemoteDatabase::new("https://invalid.example/mime/", cache_dir.path())
+            .file(Path::new("globs2"), "0000");
+        let mut urls = Vec::new();
+        let err = remote
+            .fetch_with(|url| {
+                urls.push(url.to_string());
+                Ok(globs.to_vec())
+            })
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(urls, vec!["https://invalid.example/mime/globs2".to_string()]);
+
+        // The downloaded files replace the outdated ones
+        let cache_dir = TempDir::new("remote-download");
+        cache_dir.write("mime/globs 2", "outdated");
+        let remote = RemoteDatabase::new("https://invalid.example", cache_dir.path())
+            .file(Path::new("globs 2"), &checksum(globs));
+        let mut urls = Vec::new();
+        remote
+            .fetch_with(|url| {
+                urls.push(url.to_string());
+                Ok(globs.to_vec())
+            })
+            .unwrap();
+        assert_eq!(urls, vec!["https://invalid.example/globs%202".to_string()]);
+        assert_eq!(fs::read(cache_dir.join("mime/globs 2")).unwrap(), &globs[..]);
+
+        // Only the plain file names are fetched
+        let mut names = vec!["", ".", "..", "../globs2", "mime/globs2", ".globs2"];
+        if cfg!(windows) {
+            names.extend(&["..\\globs2", "C:\\globs2", "C:globs2"]);
+        }
+        for name in names {
+            let remote = RemoteDatabase::new("https://invalid.example", cache_dir.path()).file(Path::new(name), "0000");
+            let err = remote.fetch_with(|_| panic!("fetched {}", name)).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+
+        // Even with a valid cached copy, plain HTTP is refused
+        let remote = RemoteDatabase::new("http://invalid.example", cache_dir.path())
+            .file(Path::new("globs2"), &checksum(globs));
+        assert_eq!(remote.fetch().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+
+        // The databases that cannot be fetched are reported
+        let mime_db = crate::SharedMimeInfo::builder()
+            .system_directories(false)
+            .remote(remote)
+            .build();
+        assert_eq!(mime_db.load_warnings().len(), 1);
+        assert_eq!(mime_db.load_warnings()[0].kind, crate::LoadWarningKind::Remote);
+    }
+}
@@ -0,0 +1,257 @@
+use std::fmt;
+use std::io::Write;
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use crate::magic::{self, Matchlet};
+use crate::snapshot;
+use crate::{Glob, Guess, MagicEntry, MimeType, SharedMimeInfo};
+
+// The header of the magic files, which the serialized entries lack
+const MAGIC_HEADER: &[u8] = b"MIME-Magic\0\n";
+
+// Accepts the bytes as bytes, or as a sequence for the formats without
+// them, like JSON
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bytes")
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+        Ok(v.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+        Ok(v)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+        let mut res = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            res.push(byte);
+        }
+
+        Ok(res)
+    }
+}
+
+struct StrVisitor;
+
+impl<'de> Visitor<'de> for StrVisitor {
+    type Value = String;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a string")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<String, E> {
+        Ok(v.to_string())
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<String, E> {
+        Ok(v)
+    }
+}
+
+// A glob is serialized as its line in a `globs2` file, like
+// `50:text/plain:*.txt`; the data directory it comes from is not kept
+impl Serialize for Glob {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Glob {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Glob, D::Error> {
+        let line = deserializer.deserialize_str(StrVisitor)?;
+
+        Glob::parse_v2(line.clone()).map_err(|_| de::Error::invalid_value(de::Unexpected::Str(&line), &"a glob"))
+    }
+}
+
+impl Serialize for MimeType<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+// The parts of a MIME type borrow the deserialized string, which must
+// not need unescaping
+impl<'de: 'a, 'a> Deserialize<'de> for MimeType<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<MimeType<'a>, D::Error> {
+        let mime_type = <&str>::deserialize(deserializer)?;
+
+        MimeType::parse(mime_type)
+            .ok_or_else(|| de::Error::invalid_value(de::Unexpected::Str(mime_type), &"a MIME type"))
+    }
+}
+
+// Writes @entry as in a magic file
+fn write_magic_entry(entry: &MagicEntry, out: &mut Vec<u8>) {
+    // Writing to a vector cannot fail
+    let _ = writeln!(out, "[{}:{}]", entry.priority(), entry.mime_type());
+    for rule in entry.rules() {
+        if rule.indent() > 0 {
+            let _ = write!(out, "{}", rule.indent());
+        }
+        let _ = write!(out, ">{}=", rule.start_offset());
+        out.extend_from_slice(&(rule.value().len() as u16).to_be_bytes());
+        // The files store the host-endian values big-endian
+        let start = out.len();
+        out.extend_from_slice(rule.value());
+        magic::swap_words(&mut out[start..], rule.word_size());
+        if let Some(mask) = rule.mask() {
+            out.push(b'&');
+            let start = out.len();
+            out.extend_from_slice(mask);
+            magic::swap_words(&mut out[start..], rule.word_size());
+        }
+        if rule.word_size() != 1 {
+            let _ = write!(out, "~{}", rule.word_size());
+        }
+        if rule.range_length() != 1 {
+            let _ = write!(out, "+{}", rule.range_length());
+        }
+        out.push(b'\n');
+    }
+}
+
+// A magic entry is serialized as in a magic file, without the header of
+// the file; its location is not kept
+impl Serialize for MagicEntry {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut data = Vec::new();
+        write_magic_entry(self, &mut data);
+
+        serializer.serialize_bytes(&data)
+    }
+}
+
+impl<'de> Deserialize<'de> for MagicEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<MagicEntry, D::Error> {
+        let mut data = MAGIC_HEADER.to_vec();
+        data.extend(deserializer.deserialize_bytes(BytesVisitor)?);
+
+        match magic::read_magic(&data) {
+            (mut entries, 0) if entries.len() == 1 => Ok(entries.remove(0)),
+            _ => Err(de::Error::invalid_value(de::Unexpected::Bytes(&data[MAGIC_HEADER.len()..]), &"a magic entry")),
+        }
+    }
+}
+
+const GUESS_FIELDS: &[&str] = &["mime_type", "uncertain"];
+
+impl Serialize for Guess {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Guess", GUESS_FIELDS.len())?;
+        state.serialize_field("mime_type", self.mime_type())?;
+        state.serialize_field("uncertain", &self.uncertain())?;
+
+        state.end()
+    }
+}
+
+struct GuessVisitor;
+
+impl<'de> Visitor<'de> for GuessVisitor {
+    type Value = Guess;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a guess")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Guess, A::Error> {
+        let mime_type: String = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let uncertain = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+
+        Ok(Guess::new(mime_type, uncertain))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Guess, A::Error> {
+        let mut mime_type: Option<String> = None;
+        let mut uncertain = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "mime_type" => mime_type = Some(map.next_value()?),
+                "uncertain" => uncertain = Some(map.next_value()?),
+                _ => return Err(de::Error::unknown_field(&key, GUESS_FIELDS)),
+            }
+        }
+
+        let mime_type = mime_type.ok_or_else(|| de::Error::missing_field("mime_type"))?;
+        let uncertain = uncertain.ok_or_else(|| de::Error::missing_field("uncertain"))?;
+
+        Ok(Guess::new(mime_type, uncertain))
+    }
+}
+
+impl<'de> Deserialize<'de> for Guess {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Guess, D::Error> {
+        deserializer.deserialize_struct("Guess", GUESS_FIELDS, GuessVisitor)
+    }
+}
+
+// A database is serialized as a snapshot, like the ones of
+// SharedMimeInfo::save_snapshot(), which can only be read by the same
+// version of the crate
+impl Serialize for SharedMimeInfo {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&snapshot::write_snapshot(self))
+    }
+}
+
+impl<'de> Deserialize<'de> for SharedMimeInfo {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<SharedMimeInfo, D::Error> {
+        let data = deserializer.deserialize_bytes(BytesVisitor)?;
+
+        snapshot::read_snapshot(&data).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::value::{BorrowedStrDeserializer, BytesDeserializer, Error, StringDeserializer};
+    use std::env;
+
+    #[test]
+    fn deserialize() {
+        let glob = Glob::deserialize(StringDeserializer::<Error>::new("50:text/x-foo:*.foo:cs".to_string()));
+        assert_eq!(glob.unwrap(), Glob::new("text/x-foo", "*.foo", 50, true));
+        assert!(Glob::deserialize(StringDeserializer::<Error>::new("foo".to_string())).is_err());
+
+        let mime_type = MimeType::deserialize(BorrowedStrDeserializer::<Error>::new("image/svg+xml")).unwrap();
+        assert_eq!(mime_type.suffix(), Some("xml"));
+        assert!(MimeType::deserialize(BorrowedStrDeserializer::<Error>::new("image")).is_err());
+    }
+
+    #[test]
+    fn magic_entry_round_trip() {
+        let mime_db = SharedMimeInfo::new_for_directory(env::current_dir().unwrap().join("test_files"));
+
+        for entry in mime_db.magic_entries() {
+            let mut data = Vec::new();
+            write_magic_entry(entry, &mut data);
+            let res = MagicEntry::deserialize(BytesDeserializer::<Error>::new(&data)).unwrap();
+            assert_eq!(&res, entry);
+        }
+
+        assert!(MagicEntry::deserialize(BytesDeserializer::<Error>::new(b"[50:text/x-foo]\n")).is_err());
+    }
+
+    #[test]
+    fn database_round_trip() {
+        let mime_db = SharedMimeInfo::new_for_directory(env::current_dir().unwrap().join("test_files"));
+
+        let data = snapshot::write_snapshot(&mime_db);
+        let res = SharedMimeInfo::deserialize(BytesDeserializer::<Error>::new(&data)).unwrap();
+        assert_eq!(res.get_mime_types_from_file_name("foo.txt"), vec!["text/plain".to_string()]);
+        assert!(SharedMimeInfo::deserialize(BytesDeserializer::<Error>::new(b"garbage")).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{MagicInput, SharedMimeInfo};
+
+// The longest interpreter line read, like the limit of the Linux kernel
+pub(crate) const MAX_LINE_LEN: usize = 256;
+
+// The types of the scripts of the common interpreters, for the scripts the
+// magic rules miss, e.g. run through `env` or with a versioned interpreter
+pub(crate) const INTERPRETERS: &[(&str, &str)] = &[
+    ("sh", "application/x-shellscript"),
+    ("bash", "application/x-shellscript"),
+    ("dash", "application/x-shellscript"),
+    ("ksh", "application/x-shellscript"),
+    ("zsh", "application/x-shellscript"),
+    ("python", "text/x-python"),
+    ("python2", "text/x-python"),
+    ("python3", "text/x-python3"),
+    ("pypy", "text/x-python"),
+    ("pypy3", "text/x-python3"),
+    ("perl", "application/x-perl"),
+    ("ruby", "application/x-ruby"),
+    ("node", "application/javascript"),
+    ("nodejs", "application/javascript"),
+    ("deno", "application/javascript"),
+    ("bun", "application/javascript"),
+    ("php", "application/x-php"),
+    ("lua", "text/x-lua"),
+    ("luajit", "text/x-lua"),
+    ("tclsh", "text/x-tcl"),
+    ("wish", "text/x-tcl"),
+    ("awk", "application/x-awk"),
+    ("gawk", "application/x-awk"),
+    ("nawk", "application/x-awk"),
+    ("guile", "text/x-scheme"),
+    ("ocaml", "text/x-ocaml"),
+];
+
+// Returns the name of the interpreter of the interpreter @line, without
+// the `#!`, like `python3` for `/usr/bin/env -S python3 -u`
+fn interpreter_name(line: &str) -> Option<&str> {
+    let mut words = line.split_whitespace();
+    let name = words.next()?.rsplit('/').next()?;
+    if name != "env" {
+        return Some(name);
+    }
+
+    // The options and variables of env come before the command
+    words
+        .find(|w| !w.starts_with('-') && !w.contains('='))
+        .and_then(|w| w.rsplit('/').next())
+}
+
+// Returns the names to look up for the interpreter @name, from the most to
+// the least specific, like `python3.11`, `python3` and `python`
+fn candidates(name: &str) -> impl Iterator<Item = &str> {
+    let minor = name.rfind('.').map(|idx| &name[..idx]);
+    let unversioned = Some(name.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.' || c == '-'))
+        .filter(|v| !v.is_empty());
+
+    let mut last = None;
+    std::iter::once(name)
+        .chain(minor)
+        .chain(unversioned)
+        .filter(move |c| last.replace(*c) != Some(*c))
+}
+
+// Returns the type of the script in @data from its interpreter line, with
+// the interpreters of @db first, if any
+pub(crate) fn script_type<'a, D: MagicInput + ?Sized>(db: &'a SharedMimeInfo, data: &D) -> Option<&'a str> {
+    if data.len() < 3 || data.byte_at(0) != b'#' || data.byte_at(1) != b'!' {
+        return None;
+    }
+
+    // The data in chunks is copied, at most MAX_LINE_LEN bytes
+    let end = data.len().min(MAX_LINE_LEN);
+    let mut buf = [0; MAX_LINE_LEN];
+    let head = match data.as_slice() {
+        Some(v) => &v[..end],
+        None => {
+            for (idx, b) in buf[..end].iter_mut().enumerate() {
+                *b = data.byte_at(idx);
+            }
+            &buf[..end]
+        }
+    };
+    let line = head[2..].split(|b| *b == b'\n').next()?;
+    let line = std::str::from_utf8(line).ok()?;
+    let name = interpreter_name(line)?;
+
+    let mime_type = candidates(name).find_map(|c| {
+        let custom = db.interpreters.iter().find(|v| v.0 == c).map(|v| v.1.as_str());
+        custom.or_else(|| INTERPRETERS.iter().find(|v| v.0 == c).map(|v| v.1))
+    })?;
+
+    Some(db.canonical_ref(mime_type))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn interpreter_lines() {
+        assert_eq!(interpreter_name("/bin/sh"), Some("sh"));
+        assert_eq!(interpreter_name(" /usr/bin/perl -w"), Some("perl"));
+        assert_eq!(interpreter_name("/usr/bin/env -S FOO=1 python3 -u"), Some("python3"));
+        assert_eq!(interpreter_name("/usr/bin/env"), None);
+        assert_eq!(interpreter_name(""), None);
+
+        assert_eq!(candidates("python3.11").collect::<Vec<_>>(), vec!["python3.11", "python3", "python"]);
+        assert_eq!(candidates("ruby").collect::<Vec<_>>(), vec!["ruby"]);
+    }
+
+    #[test]
+    fn script_types() {
+        let mime_db = SharedMimeInfo::new_for_directory(env::current_dir().unwrap().join("test_files"));
+
+        assert_eq!(script_type(&mime_db, &b"#!/usr/bin/env python3\nprint()\n"[..]), Some("text/x-python3"));
+        assert_eq!(script_type(&mime_db, &b"#!/opt/bin/ruby2.7 -w\n"[..]), Some("application/x-ruby"));
+        assert_eq!(script_type(&mime_db, &b"#! /usr/bin/env -S deno run\n"[..]), Some("application/javascript"));
+        assert_eq!(script_type(&mime_db, &b"#!/usr/bin/env unknown\n"[..]), None);
+        assert_eq!(script_type(&mime_db, &b"# /bin/sh\n"[..]), None);
+
+        let chunks = crate::magic::ChunkedData::new(vec![&b"#!/usr/bin/p"[..], &b"erl -w\n"[..]]);
+        assert_eq!(script_type(&mime_db, &chunks), Some("application/x-perl"));
+    }
+}
@@ -0,0 +1,477 @@
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::alias::Alias;
+use crate::glob::Glob;
+use crate::icon::Icon;
+use crate::magic::{self, MagicEntry, MagicRule, Matchlet};
+use crate::parent::Subclass;
+use crate::treemagic::{TreeFileType, TreeMagicMatch, TreeMagicRule};
+use crate::{SharedMimeInfo, SharedMimeInfoBuilder};
+
+// The signature of the snapshot files
+const SIGNATURE: &[u8; 8] = b"XDGMSNAP";
+
+// The version of the format; readers reject the other major versions
+const MAJOR_VERSION: u16 = 1;
+const MINOR_VERSION: u16 = 0;
+
+// Returns the path written as @bytes, in the encoding of the paths of the
+// platform: their bytes on unix
+#[cfg(unix)]
+fn bytes_to_path(bytes: &[u8]) -> Option<PathBuf> {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    Some(PathBuf::from(OsStr::from_bytes(bytes)))
+}
+
+// Only the paths that are valid Unicode are read back
+#[cfg(not(unix))]
+fn bytes_to_path(bytes: &[u8]) -> Option<PathBuf> {
+    std::str::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+pub(crate) fn invalid_data<S: Into<String>>(reason: S) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason.into())
+}
+
+// Writes the values of a snapshot, in big-endian order; the strings and
+// lists are prefixed by their length
+struct Writer {
+    data: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, v: u8) {
+        self.data.push(v);
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.data.extend_from_slice(&v.to_be_bytes());
+    }
+
+    fn len(&mut self, len: usize) {
+        self.u32(len as u32);
+    }
+
+    fn bytes(&mut self, v: &[u8]) {
+        self.len(v.len());
+        self.data.extend_from_slice(v);
+    }
+
+    fn str(&mut self, v: &str) {
+        self.bytes(v.as_bytes());
+    }
+
+    fn path(&mut self, v: &Path) {
+        self.bytes(v.as_os_str().as_encoded_bytes());
+    }
+
+    fn opt_bytes(&mut self, v: Option<&[u8]>) {
+        match v {
+            Some(v) => {
+                self.u8(1);
+                self.bytes(v);
+            }
+            None => self.u8(0),
+        }
+    }
+}
+
+// Reads the values written by a Writer, checking the bounds
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let res = self
+            .data
+            .get(self.pos..self.pos.saturating_add(len))
+            .ok_or_else(|| invalid_data(format!("truncated snapshot at {}", self.pos)))?;
+        self.pos += len;
+
+        Ok(res)
+    }
+
+    pub(crate) fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        let b = self.take(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    pub(crate) fn u32(&mut self) -> io::Result<u32> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub(crate) fn len(&mut self) -> io::Result<usize> {
+        self.u32().map(|v| v as usize)
+    }
+
+    pub(crate) fn bytes(&mut self) -> io::Result<&'a [u8]> {
+        let len = self.len()?;
+        self.take(len)
+    }
+
+    pub(crate) fn str(&mut self) -> io::Result<&'a str> {
+        std::str::from_utf8(self.bytes()?).map_err(|_| invalid_data(format!("invalid string at {}", self.pos)))
+    }
+
+    fn path(&mut self) -> io::Result<PathBuf> {
+        let pos = self.pos;
+        bytes_to_path(self.bytes()?).ok_or_else(|| invalid_data(format!("invalid path at {}", pos)))
+    }
+
+    pub(crate) fn bool(&mut self) -> io::Result<bool> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub(crate) fn opt_bytes(&mut self) -> io::Result<Option<&'a [u8]>> {
+        if self.bool()? {
+            self.bytes().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    // Like bytes(), returning the position of the value in the data
+    pub(crate) fn bytes_range(&mut self) -> io::Result<Range<usize>> {
+        let len = self.len()?;
+        let start = self.pos;
+        self.take(len)?;
+
+        Ok(start..self.pos)
+    }
+
+    // Like str(), returning the position of the string in the data
+    pub(crate) fn str_range(&mut self) -> io::Result<Range<usize>> {
+        let start = self.pos + 4;
+        self.str()?;
+
+        Ok(start..self.pos)
+    }
+
+    pub(crate) fn opt_bytes_range(&mut self) -> io::Result<Option<Range<usize>>> {
+        if self.bool()? {
+            self.bytes_range().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    // Reads a list of @f items, without trusting its length for the
+    // allocation
+    pub(crate) fn list<T, F: FnMut(&mut Reader<'a>) -> io::Result<T>>(&mut self, mut f: F) -> io::Result<Vec<T>> {
+        let len = self.len()?;
+        let mut res = Vec::with_capacity(len.min(self.data.len() - self.pos));
+        for _ in 0..len {
+            res.push(f(self)?);
+        }
+
+        Ok(res)
+    }
+}
+
+fn file_type_code(file_type: Option<TreeFileType>) -> u8 {
+    match file_type {
+        None => 0,
+        Some(TreeFileType::File) => 1,
+        Some(TreeFileType::Directory) => 2,
+        Some(TreeFileType::Link) => 3,
+    }
+}
+
+fn file_type_from_code(code: u8) -> io::Result<Option<TreeFileType>> {
+    match code {
+        0 => Ok(None),
+        1 => Ok(Some(TreeFileType::File)),
+        2 => Ok(Some(TreeFileType::Directory)),
+        3 => Ok(Some(TreeFileType::Link)),
+        _ => Err(invalid_data(format!("invalid file type {}", code))),
+    }
+}
+
+// Writes @rule with its value and mask in the order of the magic files,
+// so that the snapshots can be read on hosts of any endianness
+fn write_rule(w: &mut Writer, rule: &MagicRule) {
+    let mut value = rule.value().to_vec();
+    magic::swap_words(&mut value, rule.word_size());
+    let mask = rule.mask().map(|m| {
+        let mut mask = m.to_vec();
+        magic::swap_words(&mut mask, rule.word_size());
+        mask
+    });
+
+    w.u32(rule.indent());
+    w.u32(rule.start_offset());
+    w.bytes(&value);
+    w.opt_bytes(mask.as_deref());
+    w.u32(rule.word_size());
+    w.u32(rule.range_length());
+}
+
+// Serializes the merged contents of @db; the settings of the lookups,
+// like the locales, are not part of the snapshot
+pub(crate) fn write_snapshot(db: &SharedMimeInfo) -> Vec<u8> {
+    let mut w = Writer { data: Vec::new() };
+    w.data.extend_from_slice(SIGNATURE);
+    w.data.extend_from_slice(&MAJOR_VERSION.to_be_bytes());
+    w.data.extend_from_slice(&MINOR_VERSION.to_be_bytes());
+
+    for paths in [&db.data_dirs, &db.loaded_files] {
+        w.len(paths.len());
+        paths.iter().for_each(|p| w.path(p));
+    }
+
+    for types in [&db.types, &db.thumbnailer_types] {
+        let mut types: Vec<&String> = types.iter().collect();
+        types.sort_unstable();
+        w.len(types.len());
+        types.iter().for_each(|t| w.str(t));
+    }
+
+    w.len(db.aliases.iter().len());
+    for alias in db.aliases.iter() {
+        w.str(&alias.alias);
+        w.str(&alias.mime_type);
+    }
+
+    // The order of the parents of each type is kept
+    let mut parents: Vec<(&String, &Vec<String>)> = db.parents.iter().collect();
+    parents.sort_unstable_by_key(|v| v.0);
+    w.len(parents.len());
+    for (mime_type, parents) in parents {
+        w.str(mime_type);
+        w.len(parents.len());
+        parents.iter().for_each(|p| w.str(p));
+    }
+
+    for icons in [&db.icons, &db.generic_icons] {
+        w.len(icons.len());
+        for icon in icons.iter() {
+            w.str(icon.mime_type());
+            w.str(icon.icon_name());
+        }
+    }
+
+    w.len(db.globs.iter().len());
+    for glob in db.globs.iter() {
+        w.str(glob.mime_type());
+        w.str(&glob.pattern());
+        w.u32(glob.weight() as u32);
+        w.u8(glob.case_sensitive() as u8);
+        w.opt_bytes(glob.source_directory().map(|p| p.as_os_str().as_encoded_bytes()));
+    }
+
+    w.len(db.magic.len());
+    for entry in &db.magic {
+        w.str(entry.mime_type());
+        w.u32(entry.priority());
+        w.len(entry.offset());
+        w.opt_bytes(entry.source().map(|p| p.as_os_str().as_encoded_bytes()));
+        w.len(entry.rules().len());
+        for rule in entry.rules() {
+            write_rule(&mut w, rule);
+        }
+    }
+
+    w.len(db.treemagic.len());
+    for rule in &db.treemagic {
+        w.str(&rule.mime_type);
+        w.u32(rule.priority);
+        w.len(rule.matches.len());
+        for m in &rule.matches {
+            w.u32(m.indent);
+            w.str(&m.path);
+            w.u8(file_type_code(m.file_type));
+            w.u8(m.executable as u8 | (m.match_case as u8) << 1 | (m.non_empty as u8) << 2);
+            w.opt_bytes(m.mime_type.as_ref().map(|t| t.as_bytes()));
+        }
+    }
+
+    w.data
+}
+
+// Checks the signature and the version at the beginning of a snapshot
+pub(crate) fn read_header(r: &mut Reader) -> io::Result<()> {
+    if r.take(SIGNATURE.len()).ok() != Some(&SIGNATURE[..]) {
+        return Err(invalid_data("not a snapshot of a MIME database"));
+    }
+    let (major, minor) = (r.u16()?, r.u16()?);
+    if major != MAJOR_VERSION {
+        return Err(invalid_data(format!("unsupported snapshot version {}.{}", major, minor)));
+    }
+
+    Ok(())
+}
+
+// Rebuilds a database from the snapshot in @data
+pub(crate) fn read_snapshot(data: &[u8]) -> io::Result<SharedMimeInfo> {
+    let mut r = Reader::new(data);
+    read_header(&mut r)?;
+
+    let mut db = SharedMimeInfo::create();
+    db.data_dirs = r.list(|r| r.path())?;
+    db.loaded_files = r.list(|r| r.path())?;
+    db.types = r.list(|r| r.str().map(String::from))?.into_iter().collect();
+    db.thumbnailer_types = r.list(|r| r.str().map(String::from))?.into_iter().collect();
+
+    db.aliases.add_aliases(r.list(|r| Ok(Alias::new(r.str()?, r.str()?)))?);
+
+    let mut subclasses = Vec::new();
+    for _ in 0..r.len()? {
+        let mime_type = r.str()?;
+        for parent in r.list(|r| r.str())? {
+            subclasses.push(Subclass::new(mime_type, parent));
+        }
+    }
+    db.parents.add_subclasses(subclasses);
+
+    let icon = |r: &mut Reader| -> io::Result<Icon> {
+        let mime_type = r.str()?;
+        Ok(Icon::new(r.str()?, mime_type))
+    };
+    db.icons = r.list(icon)?;
+    db.generic_icons = r.list(icon)?;
+
+    db.globs.add_globs(r.list(|r| {
+        let mut glob = Glob::new(r.str()?, r.str()?, r.u32()? as i32, r.bool()?);
+        if let Some(source) = r.opt_bytes()? {
+            glob.set_source_directory(&bytes_to_path(source).ok_or_else(|| invalid_data("invalid path"))?);
+        }
+        Ok(glob)
+    })?);
+
+    db.magic = r.list(|r| {
+        let mime_type = r.str()?;
+        let priority = r.u32()?;
+        let offset = r.len()?;
+        let source = r.opt_bytes()?;
+        let rules = r.list(|r| {
+            Ok(magic::host_rule(MagicRule::new(
+                r.u32()?,
+                r.u32()?,
+                r.bytes()?.to_vec(),
+                r.opt_bytes()?.map(|m| m.to_vec()),
+                r.u32()?,
+                r.u32()?,
+            )))
+        })?;
+
+        let mut entry = MagicEntry::new(mime_type, priority, rules, offset);
+        if let Some(source) = source {
+            entry.set_source(&bytes_to_path(source).ok_or_else(|| invalid_data("invalid path"))?);
+        }
+        Ok(entry)
+    })?;
+
+    db.treemagic = r.list(|r| {
+        Ok(TreeMagicRule {
+            mime_type: r.str()?.to_string(),
+            priority: r.u32()?,
+            matches: r.list(|r| {
+                let indent = r.u32()?;
+                let path = r.str()?.to_string();
+                let file_type = file_type_from_code(r.u8()?)?;
+                let flags = r.u8()?;
+                Ok(TreeMagicMatch {
+                    indent,
+                    path,
+                    file_type,
+                    executable: flags & 1 != 0,
+                    match_case: flags & 2 != 0,
+                    non_empty: flags & 4 != 0,
+                    mime_type: r.opt_bytes()?.map(|t| String::from_utf8_lossy(t).into_owned()),
+                })
+            })?,
+        })
+    })?;
+
+    // A reload loads the same data directories again
+    let mut source = SharedMimeInfoBuilder::new().system_directories(false);
+    for dir in &db.data_dirs {
+        source = source.directory(dir);
+    }
+    db.source = source;
+
+    Ok(db)
+}
+
+// Writes the snapshot of @db to @path, replacing it atomically
+pub(crate) fn save(db: &SharedMimeInfo, path: &Path) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".new");
+
+    fs::write(&tmp, write_snapshot(db))?;
+    fs::rename(&tmp, path)
+}
+
+// Reads the snapshot at @path
+pub(crate) fn load(path: &Path) -> io::Result<SharedMimeInfo> {
+    read_snapshot(&fs::read(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use std::env;
+
+    #[test]
+    fn snapshot_round_trip() {
+        let mime_db = SharedMimeInfo::new_for_directory(env::current_dir().unwrap().join("test_files"));
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+
+        let dir = TempDir::new("snapshot");
+        let path = dir.join("snapshot");
+        save(&mime_db, &path).unwrap();
+        let loaded = load(&path).unwrap();
+
+        assert_eq!(write_snapshot(&loaded), write_snapshot(&mime_db));
+        assert_eq!(loaded.get_mime_type_for_data(png_data), Some("image/png".to_string()));
+        assert_eq!(loaded.get_mime_types_from_file_name("a.tar.gz"), mime_db.get_mime_types_from_file_name("a.tar.gz"));
+        assert_eq!(loaded.unalias_mime_type("application/x-gzip"), Some("application/gzip".to_string()));
+        assert_eq!(loaded.get_parents("text/x-csrc"), mime_db.get_parents("text/x-csrc"));
+        assert_eq!(loaded.lookup_generic_icon_name("application/x-compressed-tar"), mime_db.lookup_generic_icon_name("application/x-compressed-tar"));
+        assert_eq!(loaded.treemagic, mime_db.treemagic);
+        assert_eq!(loaded.data_dirs, mime_db.data_dirs);
+
+        let mut data = write_snapshot(&mime_db);
+        data[9] = 2;
+        assert_eq!(read_snapshot(&data).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        let data = write_snapshot(&mime_db);
+        assert!(read_snapshot(&data[..data.len() / 2]).is_err());
+        assert!(read_snapshot(b"MIME-Magic\0\n").is_err());
+    }
+
+    #[test]
+    fn rule_byte_order() {
+        // A host16 rule, and its mask, are written big-endian
+        let rule = MagicRule::new(0, 4, 0x0102u16.to_ne_bytes().to_vec(), Some(0xff00u16.to_ne_bytes().to_vec()), 2, 1);
+
+        let mut w = Writer { data: Vec::new() };
+        write_rule(&mut w, &rule);
+        assert_eq!(
+            w.data,
+            b"\0\0\0\0\0\0\0\x04\0\0\0\x02\x01\x02\x01\0\0\0\x02\xff\0\0\0\0\x02\0\0\0\x01"
+        );
+
+        let exe_data = 0x0110u16.to_ne_bytes();
+        let mime_db = SharedMimeInfo::new_for_directory(env::current_dir().unwrap().join("test_files"));
+        let loaded = read_snapshot(&write_snapshot(&mime_db)).unwrap();
+        assert_eq!(loaded.magic, mime_db.magic);
+        assert_eq!(loaded.get_mime_type_for_data(&exe_data), Some("application/x-executable".to_string()));
+    }
+}
@@ -0,0 +1,137 @@
+use std::str;
+
+use crate::{SharedMimeInfo, TEXT_PLAIN_TYPE, UNKNOWN_TYPE};
+
+/// What some data looks like, as returned by [`sniff`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sniff {
+    /// The MIME type of the data.
+    pub mime: String,
+    /// The character set of the text data, like `utf-8`, if it can be
+    /// told.
+    pub charset: Option<String>,
+    /// Whether the data is text, i.e. its type is a sub-class of
+    /// `text/plain`.
+    pub is_text: bool,
+    /// Whether the type was guessed without a magic rule confirming it,
+    /// e.g. from the absence of binary bytes.
+    pub uncertain: bool,
+}
+
+// Returns the character set of @data from its byte order mark, if any
+fn bom_charset(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\xef\xbb\xbf") {
+        Some("utf-8")
+    } else if data.starts_with(b"\xff\xfe\x00\x00") {
+        Some("utf-32le")
+    } else if data.starts_with(b"\x00\x00\xfe\xff") {
+        Some("utf-32be")
+    } else if data.starts_with(b"\xff\xfe") {
+        Some("utf-16le")
+    } else if data.starts_with(b"\xfe\xff") {
+        Some("utf-16be")
+    } else {
+        None
+    }
+}
+
+// Returns the character set of @data if it looks like text: valid UTF-8,
+// allowing a sequence cut at the end, without control characters other
+// than whitespace
+fn text_charset(data: &[u8]) -> Option<&'static str> {
+    if let Some(charset) = bom_charset(data) {
+        return Some(charset);
+    }
+
+    let text = match str::from_utf8(data) {
+        Ok(v) => v,
+        Err(e) if e.error_len().is_none() => str::from_utf8(&data[..e.valid_up_to()]).ok()?,
+        Err(_) => return None,
+    };
+
+    let is_control = |c: char| c.is_control() && !matches!(c, '\t' | '\n' | '\r' | '\x0c');
+    if text.chars().any(is_control) {
+        return None;
+    }
+
+    if text.is_ascii() {
+        Some("us-ascii")
+    } else {
+        Some("utf-8")
+    }
+}
+
+// Sniffs @data with the MIME information of @db
+pub(crate) fn sniff_with(db: &SharedMimeInfo, data: &[u8]) -> Sniff {
+    let guess = db.guess().data(data).guess();
+    let mut mime = guess.mime_type().to_string();
+    let mut uncertain = guess.uncertain() || mime == UNKNOWN_TYPE;
+
+    let charset = if mime == UNKNOWN_TYPE || db.is_subclass_of(&mime, TEXT_PLAIN_TYPE) {
+        text_charset(data)
+    } else {
+        None
+    };
+
+    // The data that no magic rule recognizes can still be plain text
+    if mime == UNKNOWN_TYPE && charset.is_some() {
+        mime = TEXT_PLAIN_TYPE.to_string();
+        uncertain = true;
+    }
+
+    Sniff {
+        is_text: db.is_subclass_of(&mime, TEXT_PLAIN_TYPE),
+        charset: charset.map(|c| c.to_string()),
+        mime,
+        uncertain,
+    }
+}
+
+/// Sniffs the MIME type of @data, the beginning of a file, along with its
+/// character set and whether it is text, in one call, with the database
+/// of [`SharedMimeInfo::shared`].
+///
+/// Use [`SharedMimeInfo`] directly to control how the database is loaded,
+/// or to also use the file names.
+pub fn sniff(data: &[u8]) -> Sniff {
+    sniff_with(SharedMimeInfo::shared(), data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn sniff_data() {
+        let mime_db = SharedMimeInfo::new_for_directory(env::current_dir().unwrap().join("test_files"));
+
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+        let res = sniff_with(&mime_db, png_data);
+        assert_eq!(res.mime, "image/png");
+        assert_eq!(res.charset, None);
+        assert!(!res.is_text);
+        assert!(!res.uncertain);
+
+        let res = sniff_with(&mime_db, "déjà vu\n".as_bytes());
+        assert_eq!(res.mime, "text/plain");
+        assert_eq!(res.charset.as_deref(), Some("utf-8"));
+        assert!(res.is_text);
+        assert!(res.uncertain);
+
+        let res = sniff_with(&mime_db, b"<?xml version=\"1.0\"?>\n<svg xmlns=\"http://www.w3.org/2000/svg\"/>");
+        assert_eq!(res.mime, "image/svg+xml");
+        assert_eq!(res.charset.as_deref(), Some("us-ascii"));
+        assert!(res.is_text);
+
+        // Cut in the middle of a character
+        assert_eq!(text_charset(&"é".as_bytes()[..1]), Some("us-ascii"));
+        assert_eq!(text_charset(b"\xff\xfeh\x00i\x00"), Some("utf-16le"));
+        assert_eq!(text_charset(b"a\x00b"), None);
+
+        let res = sniff_with(&mime_db, b"\x00\x01\x02");
+        assert_eq!(res.mime, "application/octet-stream");
+        assert_eq!(res.charset, None);
+        assert!(res.uncertain);
+    }
+}
@@ -0,0 +1,124 @@
+use std::iter::Rev;
+use std::slice;
+use std::str::Chars;
+
+#[derive(Clone, Debug, Default)]
+struct Node {
+    // The children of the node, sorted by character
+    children: Vec<(char, usize)>,
+    // The values of the suffixes ending at the node
+    values: Vec<usize>,
+}
+
+/// A trie of suffixes, read from their last character, so that finding all
+/// the suffixes of a string is a single walk from its end, whatever the
+/// number of suffixes, like the `XdgGlobHash` of xdgmime.
+#[derive(Clone, Debug)]
+pub struct SuffixTrie {
+    nodes: Vec<Node>,
+}
+
+impl SuffixTrie {
+    pub fn new() -> SuffixTrie {
+        SuffixTrie {
+            nodes: vec![Node::default()],
+        }
+    }
+
+    /// Adds @value for the strings ending with @suffix.
+    pub fn insert<I: DoubleEndedIterator<Item = char>>(&mut self, suffix: I, value: usize) {
+        let mut node = 0;
+
+        for c in suffix.rev() {
+            node = match self.nodes[node].children.binary_search_by_key(&c, |v| v.0) {
+                Ok(idx) => self.nodes[node].children[idx].1,
+                Err(idx) => {
+                    let child = self.nodes.len();
+                    self.nodes.push(Node::default());
+                    self.nodes[node].children.insert(idx, (c, child));
+                    child
+                }
+            };
+        }
+
+        self.nodes[node].values.push(value);
+    }
+
+    /// Returns the values of all the suffixes of @s, from the shortest
+    /// suffix to the longest one.
+    pub fn matches<'a>(&'a self, s: &'a str) -> SuffixMatches<'a> {
+        SuffixMatches {
+            trie: self,
+            chars: s.chars().rev(),
+            node: Some(0),
+            values: self.nodes[0].values.iter(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.len() == 1 && self.nodes[0].values.is_empty()
+    }
+}
+
+impl Default for SuffixTrie {
+    fn default() -> SuffixTrie {
+        SuffixTrie::new()
+    }
+}
+
+/// The iterator returned by [`SuffixTrie::matches`].
+pub struct SuffixMatches<'a> {
+    trie: &'a SuffixTrie,
+    chars: Rev<Chars<'a>>,
+    // The node of the characters read so far, if any
+    node: Option<usize>,
+    values: slice::Iter<'a, usize>,
+}
+
+impl Iterator for SuffixMatches<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        loop {
+            if let Some(v) = self.values.next() {
+                return Some(*v);
+            }
+
+            let node = &self.trie.nodes[self.node?];
+            let c = self.chars.next()?;
+            self.node = node
+                .children
+                .binary_search_by_key(&c, |v| v.0)
+                .ok()
+                .map(|idx| node.children[idx].1);
+            if let Some(child) = self.node {
+                self.values = self.trie.nodes[child].values.iter();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suffixes() {
+        let mut trie = SuffixTrie::new();
+        assert!(trie.is_empty());
+
+        trie.insert(".tar.gz".chars(), 0);
+        trie.insert(".gz".chars(), 1);
+        trie.insert("~".chars(), 2);
+        trie.insert(".gz".chars(), 3);
+        trie.insert("é.txt".chars(), 4);
+        assert!(!trie.is_empty());
+
+        assert_eq!(trie.matches("a.tar.gz").collect::<Vec<_>>(), vec![1, 3, 0]);
+        assert_eq!(trie.matches("tar.gz").collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(trie.matches("a.gz~").collect::<Vec<_>>(), vec![2]);
+        assert_eq!(trie.matches("déjà.txt").collect::<Vec<_>>(), Vec::<usize>::new());
+        assert_eq!(trie.matches("café.txt").collect::<Vec<_>>(), vec![4]);
+        assert_eq!(trie.matches("").count(), 0);
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::guess::inode_type;
+use crate::{LookupContext, SharedMimeInfo};
+
+/// The files of a MIME type in a directory tree, as counted by
+/// [`SharedMimeInfo::summarize_tree`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypeUsage {
+    /// The MIME type of the files.
+    pub mime_type: String,
+    /// The number of files.
+    pub files: u64,
+    /// The total size of the files, in bytes.
+    pub size: u64,
+}
+
+// Counts the files below @root by MIME type, guessing their types with a
+// single lookup context; the directories are opened one at a time, and
+// the symbolic links are not followed
+pub(crate) fn summarize_tree(db: &SharedMimeInfo, root: &Path) -> io::Result<Vec<TypeUsage>> {
+    let mut usage: HashMap<String, (u64, u64)> = HashMap::new();
+    let mut ctx = LookupContext::new();
+    let max_read = db.max_magic_extent();
+
+    // Only the root must be readable
+    let mut pending = Vec::new();
+    let mut entries = Some(fs::read_dir(root)?);
+    while let Some(dir) = entries.take().or_else(|| pending.pop().and_then(|p| fs::read_dir(p).ok())) {
+        for entry in dir.flatten() {
+            let metadata = match entry.metadata() {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+            if metadata.is_dir() {
+                pending.push(entry.path());
+                continue;
+            }
+
+            let path = entry.path();
+            let mime_type = match inode_type(&metadata) {
+                Some(v) => v,
+                None => db.guess_mime_type_for_path_with(&path, max_read, &mut ctx),
+            };
+            match usage.get_mut(mime_type) {
+                Some((files, size)) => {
+                    *files += 1;
+                    *size += metadata.len();
+                }
+                None => {
+                    usage.insert(mime_type.to_string(), (1, metadata.len()));
+                }
+            }
+        }
+    }
+
+    let mut res: Vec<TypeUsage> = usage
+        .into_iter()
+        .map(|(mime_type, (files, size))| TypeUsage { mime_type, files, size })
+        .collect();
+    res.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.mime_type.cmp(&b.mime_type)));
+
+    Ok(res)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use std::env;
+
+    #[test]
+    fn summarize() {
+        let mime_db = SharedMimeInfo::new_for_directory(env::current_dir().unwrap().join("test_files"));
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+
+        let dir = TempDir::new("summary");
+        fs::create_dir_all(dir.join("src/nested")).unwrap();
+        fs::write(dir.join("logo.png"), &png_data[..]).unwrap();
+        fs::write(dir.join("src/nested/logo"), &png_data[..]).unwrap();
+        fs::write(dir.join("src/main.c"), "int main() {}\n").unwrap();
+        fs::write(dir.join("empty"), "").unwrap();
+        std::os::unix::fs::symlink("logo.png", dir.join("link")).unwrap();
+
+        let summary = summarize_tree(&mime_db, dir.path()).unwrap();
+        let usage = |mime_type: &str, files, size| TypeUsage {
+            mime_type: mime_type.to_string(),
+            files,
+            size,
+        };
+
+        assert_eq!(summary[0], usage("image/png", 2, 2 * png_data.len() as u64));
+        assert!(summary.contains(&usage("text/x-csrc", 1, 14)));
+        assert!(summary.contains(&usage("application/x-zerosize", 1, 0)));
+        assert!(summary.contains(&usage("inode/symlink", 1, 8)));
+        assert_eq!(summary.len(), 4);
+
+        assert!(summarize_tree(&mime_db, &dir.join("missing")).is_err());
+    }
+}
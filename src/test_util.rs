@@ -0,0 +1,50 @@
+// The helpers shared by the tests of the modules
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// A directory for the files a test writes, removed with its contents when
+// dropped, even if the test fails; the files a test only reads belong in
+// test_files instead
+pub(crate) struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    // Creates the empty directory `xdg-mime-<name>-<pid>` in the temporary
+    // directory, @name being unique to the test
+    pub(crate) fn new(name: &str) -> TempDir {
+        let path = env::temp_dir().join(format!("xdg-mime-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+
+        TempDir { path }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(crate) fn join<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.path.join(path)
+    }
+
+    // Writes @contents to the file at @path, relative to the directory,
+    // creating its parent directories, and returns its full path
+    pub(crate) fn write<P: AsRef<Path>, C: AsRef<[u8]>>(&self, path: P, contents: C) -> PathBuf {
+        let path = self.path.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(&path, contents).unwrap();
+
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
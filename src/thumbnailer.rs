@@ -0,0 +1,64 @@
+use std::fs;
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::path::Path;
+
+// Reads the MIME types handled by the `.thumbnailer` file @file_name
+fn read_thumbnailer<P: AsRef<Path>>(file_name: P) -> Vec<String> {
+    let f = match File::open(file_name) {
+        Ok(v) => v,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut res = Vec::new();
+    let mut in_entry = false;
+    for line in BufReader::new(f).lines().map_while(Result::ok) {
+        let line = line.trim();
+
+        if line.starts_with('[') {
+            in_entry = line == "[Thumbnailer Entry]";
+            continue;
+        }
+
+        if let Some(types) = line.strip_prefix("MimeType=") {
+            if in_entry {
+                res.extend(types.split(';').filter(|t| !t.is_empty()).map(|t| t.to_string()));
+            }
+        }
+    }
+
+    res
+}
+
+/// Reads the MIME types handled by the thumbnailers installed in the
+/// `thumbnailers` sub-directory of the data directory @directory.
+pub fn read_thumbnailer_types<P: AsRef<Path>>(directory: P) -> Vec<String> {
+    let entries = match fs::read_dir(directory.as_ref().join("thumbnailers")) {
+        Ok(v) => v,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut files: Vec<_> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().map(|e| e == "thumbnailer").unwrap_or(false))
+        .collect();
+    files.sort();
+
+    files.iter().flat_map(read_thumbnailer).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_types() {
+        assert_eq!(
+            read_thumbnailer_types("test_files"),
+            vec!["application/pdf".to_string(), "application/x-gzip".to_string()]
+        );
+        assert!(read_thumbnailer_types("test_files/nonexistent").is_empty());
+    }
+}
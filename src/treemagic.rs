@@ -0,0 +1,517 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str;
+
+/// The type of a file in a [`DirSnapshot`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TreeFileType {
+    File,
+    Directory,
+    Link,
+}
+
+/// A file of a [`DirSnapshot`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotEntry {
+    /// The type of the file.
+    pub file_type: TreeFileType,
+    /// Whether the file is executable.
+    pub executable: bool,
+    /// Whether the directory has any entry, even if they are not part of
+    /// the snapshot.
+    pub non_empty: bool,
+    /// The MIME type of the file, if known.
+    pub mime_type: Option<String>,
+}
+
+impl SnapshotEntry {
+    /// Creates an entry of type @file_type, with no other information.
+    pub fn new(file_type: TreeFileType) -> SnapshotEntry {
+        SnapshotEntry {
+            file_type,
+            executable: false,
+            non_empty: false,
+            mime_type: None,
+        }
+    }
+}
+
+/// A listing of a directory tree, provided by the caller, that the
+/// treemagic rules are matched against.
+///
+/// The paths are relative to the root of the tree, with `/` separators.
+#[derive(Clone, Debug, Default)]
+pub struct DirSnapshot {
+    entries: HashMap<String, SnapshotEntry>,
+}
+
+impl DirSnapshot {
+    /// Creates an empty snapshot.
+    pub fn new() -> DirSnapshot {
+        DirSnapshot::default()
+    }
+
+    /// Adds the file at @path to the snapshot.
+    pub fn insert<S: Into<String>>(&mut self, path: S, entry: SnapshotEntry) {
+        let path = path.into();
+        self.entries.insert(path.trim_matches('/').to_string(), entry);
+    }
+
+    fn lookup(&self, path: &str, match_case: bool) -> Option<(&str, &SnapshotEntry)> {
+        if let Some((k, v)) = self.entries.get_key_value(path) {
+            return Some((k, v));
+        }
+
+        if match_case {
+            return None;
+        }
+
+        // Several entries can only differ by case on case-sensitive
+        // file systems; pick the first one by name
+        self.entries
+            .iter()
+            .filter(|(k, _)| k.to_lowercase() == path.to_lowercase())
+            .min_by(|a, b| a.0.cmp(b.0))
+            .map(|(k, v)| (k.as_str(), v))
+    }
+
+    fn has_children(&self, path: &str) -> bool {
+        self.entries
+            .keys()
+            .any(|k| k.len() > path.len() + 1 && k.starts_with(path) && k.as_bytes()[path.len()] == b'/')
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct TreeMagicMatch {
+    pub(crate) indent: u32,
+    pub(crate) path: String,
+    // None matches any type of file
+    pub(crate) file_type: Option<TreeFileType>,
+    pub(crate) executable: bool,
+    pub(crate) match_case: bool,
+    pub(crate) non_empty: bool,
+    pub(crate) mime_type: Option<String>,
+}
+
+impl TreeMagicMatch {
+    // [ <indent> ] '>' '"' <path> '"' '=' <type> [ ',' <option> ]*
+    fn from_string(s: &str) -> Option<TreeMagicMatch> {
+        let (indent, rest) = s.split_at(s.find('>')?);
+        let indent = if indent.is_empty() { 0 } else { indent.parse().ok()? };
+
+        let rest = rest.strip_prefix(">\"")?;
+        let (path, rest) = rest.split_at(rest.find('"')?);
+        let rest = rest.strip_prefix("\"=")?;
+
+        let mut options = rest.split(',');
+        let file_type = match options.next()? {
+            "file" => Some(TreeFileType::File),
+            "directory" => Some(TreeFileType::Directory),
+            "link" => Some(TreeFileType::Link),
+            "any" => None,
+            _ => return None,
+        };
+
+        let mut res = TreeMagicMatch {
+            indent,
+            path: path.to_string(),
+            file_type,
+            executable: false,
+            match_case: false,
+            non_empty: false,
+            mime_type: None,
+        };
+
+        for option in options {
+            match option {
+                "executable" => res.executable = true,
+                "match-case" => res.match_case = true,
+                "non-empty" => res.non_empty = true,
+                v if v.contains('/') => res.mime_type = Some(v.to_string()),
+                _ => return None,
+            }
+        }
+
+        Some(res)
+    }
+
+    fn matches(&self, snapshot: &DirSnapshot) -> bool {
+        let (path, entry) = match snapshot.lookup(self.path.trim_matches('/'), self.match_case) {
+            Some(v) => v,
+            None => return false,
+        };
+
+        if let Some(file_type) = self.file_type {
+            if entry.file_type != file_type {
+                return false;
+            }
+        }
+
+        if self.executable && !entry.executable {
+            return false;
+        }
+
+        if self.non_empty && !entry.non_empty && !snapshot.has_children(path) {
+            return false;
+        }
+
+        match &self.mime_type {
+            Some(mime_type) => entry.mime_type.as_ref() == Some(mime_type),
+            None => true,
+        }
+    }
+}
+
+/// An entry of a `treemagic` file, matching a directory tree, e.g. the
+/// root of a removable medium, to a `x-content/*` MIME type.
+#[derive(Clone, PartialEq, Eq)]
+pub struct TreeMagicRule {
+    pub(crate) mime_type: String,
+    pub(crate) priority: u32,
+    pub(crate) matches: Vec<TreeMagicMatch>,
+}
+
+impl fmt::Debug for TreeMagicRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "MIME type: {:?} (priority: {:?}):\nmatches:\n{:?}",
+            self.mime_type, self.priority, self.matches
+        )
+    }
+}
+
+// Formats a summary of the rule, with its header and number of matches
+impl fmt::Display for TreeMagicRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}:{}] {} matches", self.priority, self.mime_type, self.matches.len())
+    }
+}
+
+impl TreeMagicRule {
+    pub fn mime_type(&self) -> &str {
+        &self.mime_type
+    }
+
+    pub fn priority(&self) -> u32 {
+        self.priority
+    }
+
+    pub(crate) fn set_mime_type(&mut self, mime_type: String) {
+        self.mime_type = mime_type;
+    }
+
+    // '[' <priority> ':' <mime_type> ']'
+    fn from_header(s: &str) -> Option<TreeMagicRule> {
+        let s = s.strip_prefix('[')?.strip_suffix(']')?;
+        let (priority, mime_type) = s.split_at(s.find(':')?);
+        let mime_type = &mime_type[1..];
+
+        if mime_type.is_empty() {
+            return None;
+        }
+
+        Some(TreeMagicRule {
+            mime_type: mime_type.to_string(),
+            priority: priority.parse().ok()?,
+            matches: Vec::new(),
+        })
+    }
+
+    /// Checks whether the directory tree of @snapshot matches the rule.
+    ///
+    /// Like for magic, the matches are a flat list representing a tree:
+    /// a match succeeds if its path is found in the snapshot with the
+    /// requested properties, and any of its nested matches succeeds.
+    pub fn matches(&self, snapshot: &DirSnapshot) -> bool {
+        self.matches_level(0, 0, snapshot)
+    }
+
+    // Checks the matches at @level, starting from the index @start
+    // and up to the end of the enclosing match
+    fn matches_level(&self, start: usize, level: u32, snapshot: &DirSnapshot) -> bool {
+        let mut i = start;
+        while i < self.matches.len() && self.matches[i].indent >= level {
+            let m = &self.matches[i];
+            i += 1;
+
+            if m.indent != level || !m.matches(snapshot) {
+                continue;
+            }
+
+            let has_children = i < self.matches.len() && self.matches[i].indent > level;
+            if !has_children || self.matches_level(i, level + 1, snapshot) {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+/// Adds the rules from @other for the MIME types that do not have any
+/// rule in @rules, keeping them sorted by priority.
+pub fn add_missing_rules(rules: &mut Vec<TreeMagicRule>, other: Vec<TreeMagicRule>) {
+    let known: HashSet<String> = rules.iter().map(|r| r.mime_type.clone()).collect();
+
+    for rule in other {
+        if !known.contains(&rule.mime_type) {
+            rules.push(rule);
+        }
+    }
+
+    rules.sort_by_key(|r| Reverse(r.priority));
+}
+
+// Returns the paths of the files under @root matching @path, relative to
+// @root; without @match_case, each component can match several files
+fn resolve_path(root: &Path, path: &str, match_case: bool) -> Vec<String> {
+    let mut res = vec![String::new()];
+
+    for component in path.split('/').filter(|c| !c.is_empty()) {
+        let mut next = Vec::new();
+
+        for parent in &res {
+            let join = |name: &str| {
+                if parent.is_empty() {
+                    name.to_string()
+                } else {
+                    format!("{}/{}", parent, name)
+                }
+            };
+
+            if match_case {
+                let candidate = join(component);
+                if fs::symlink_metadata(root.join(&candidate)).is_ok() {
+                    next.push(candidate);
+                }
+                continue;
+            }
+
+            let dir = match fs::read_dir(root.join(parent)) {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+            for entry in dir.flatten() {
+                if let Some(name) = entry.file_name().to_str() {
+                    if name.to_lowercase() == component.to_lowercase() {
+                        next.push(join(name));
+                    }
+                }
+            }
+        }
+
+        next.sort_unstable();
+        res = next;
+    }
+
+    res
+}
+
+// Describes the file at @path, following symbolic links like GIO does
+fn snapshot_entry(path: &Path) -> Option<SnapshotEntry> {
+    let metadata = match fs::metadata(path) {
+        Ok(v) => v,
+        Err(_) => {
+            // A dangling link
+            fs::symlink_metadata(path).ok()?;
+            return Some(SnapshotEntry::new(TreeFileType::Link));
+        }
+    };
+
+    let mut res = if metadata.is_dir() {
+        let mut res = SnapshotEntry::new(TreeFileType::Directory);
+        res.non_empty = fs::read_dir(path).map(|mut d| d.next().is_some()).unwrap_or(false);
+        res
+    } else {
+        SnapshotEntry::new(TreeFileType::File)
+    };
+    res.executable = is_executable(path, &metadata);
+
+    Some(res)
+}
+
+// Whether the file at @path, with @metadata, can be run
+#[cfg(unix)]
+fn is_executable(_path: &Path, metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+// The files have no executable bit, and the programs are told by their
+// extension
+#[cfg(not(unix))]
+fn is_executable(path: &Path, _metadata: &fs::Metadata) -> bool {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+
+    ["bat", "cmd", "com", "exe"].iter().any(|e| extension.eq_ignore_ascii_case(e))
+}
+
+/// Creates a snapshot of the files under @root that @rules look at; the
+/// MIME type of the files is only determined, with @mime_type_of, for the
+/// rules that need them.
+pub(crate) fn snapshot_for_rules<F: Fn(&Path) -> String>(
+    root: &Path,
+    rules: &[TreeMagicRule],
+    mime_type_of: F,
+) -> DirSnapshot {
+    let mut res = DirSnapshot::new();
+
+    for m in rules.iter().flat_map(|r| r.matches.iter()) {
+        for path in resolve_path(root, &m.path, m.match_case) {
+            let entry = match res.entries.get_mut(&path) {
+                Some(v) => v,
+                None => match snapshot_entry(&root.join(&path)) {
+                    Some(v) => res.entries.entry(path.clone()).or_insert(v),
+                    None => continue,
+                },
+            };
+
+            if m.mime_type.is_some() && entry.mime_type.is_none() && entry.file_type == TreeFileType::File {
+                entry.mime_type = Some(mime_type_of(&root.join(&path)));
+            }
+        }
+    }
+
+    res
+}
+
+/// Parses the contents of a treemagic file, returning the rules and the
+/// number of bytes left unparsed, if any.
+pub fn read_treemagic(data: &[u8]) -> (Vec<TreeMagicRule>, usize) {
+    let header = b"MIME-TreeMagic\0\n";
+    if !data.starts_with(header) {
+        return (Vec::new(), data.len());
+    }
+
+    let mut res: Vec<TreeMagicRule> = Vec::new();
+    let mut offset = header.len();
+    while offset < data.len() {
+        let end = match data[offset..].iter().position(|b| *b == b'\n') {
+            Some(v) => offset + v,
+            None => break,
+        };
+
+        let line = match str::from_utf8(&data[offset..end]) {
+            Ok(v) => v,
+            Err(_) => break,
+        };
+
+        if line.starts_with('[') {
+            match TreeMagicRule::from_header(line) {
+                Some(v) => res.push(v),
+                None => break,
+            }
+        } else {
+            // Matches before any header are invalid
+            match (res.last_mut(), TreeMagicMatch::from_string(line)) {
+                (Some(rule), Some(m)) => rule.matches.push(m),
+                _ => break,
+            }
+        }
+
+        offset = end + 1;
+    }
+
+    (res, data.len() - offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_treemagic_file() {
+        let data = include_bytes!("../test_files/mime/treemagic");
+        let (rules, unparsed) = read_treemagic(data);
+
+        assert_eq!(unparsed, 0);
+        assert!(rules.iter().all(|r| r.mime_type().starts_with("x-content/")));
+
+        let software = rules.iter().find(|r| r.mime_type() == "x-content/win32-software").unwrap();
+        assert_eq!(software.priority(), 50);
+        assert!(software.matches[0].executable);
+
+        let (rules, unparsed) = read_treemagic(b"MIME-TreeMagic\0\n[50:x-content/foo]\n>\"a\"=socket\n");
+        assert_eq!(rules.len(), 1);
+        assert_eq!(unparsed, 12);
+    }
+
+    #[test]
+    fn match_snapshot() {
+        let data = include_bytes!("../test_files/mime/treemagic");
+        let (rules, _) = read_treemagic(data);
+        let rule = |mime_type| rules.iter().find(|r| r.mime_type() == mime_type).unwrap();
+
+        let mut snapshot = DirSnapshot::new();
+        snapshot.insert("video_ts", SnapshotEntry::new(TreeFileType::Directory));
+        snapshot.insert("video_ts/video_ts.ifo", SnapshotEntry::new(TreeFileType::File));
+        snapshot.insert("DCIM", SnapshotEntry::new(TreeFileType::Directory));
+        snapshot.insert("PICTURES", SnapshotEntry::new(TreeFileType::Directory));
+
+        assert!(rule("x-content/video-dvd").matches(&snapshot));
+        assert!(!rule("x-content/video-bluray").matches(&snapshot));
+        // Non-empty directories
+        assert!(!rule("x-content/image-dcf").matches(&snapshot));
+        snapshot.insert("DCIM/100CANON", SnapshotEntry::new(TreeFileType::Directory));
+        assert!(rule("x-content/image-dcf").matches(&snapshot));
+
+        let mut entry = SnapshotEntry::new(TreeFileType::Directory);
+        entry.non_empty = true;
+        snapshot.insert("pictures", entry);
+        assert!(!rule("x-content/image-picturecd").matches(&snapshot));
+        let mut entry = SnapshotEntry::new(TreeFileType::Directory);
+        entry.non_empty = true;
+        snapshot.insert("PICTURES", entry);
+        assert!(rule("x-content/image-picturecd").matches(&snapshot));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn snapshot_from_disk() {
+        use crate::test_util::TempDir;
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = TempDir::new("treemagic");
+        fs::create_dir_all(root.join("Video_TS")).unwrap();
+        fs::write(root.join("Video_TS/VIDEO_TS.IFO"), "").unwrap();
+        fs::write(root.join("autorun.exe"), "").unwrap();
+
+        let data = include_bytes!("../test_files/mime/treemagic");
+        let (rules, _) = read_treemagic(data);
+        let rule = |mime_type| rules.iter().find(|r| r.mime_type() == mime_type).unwrap();
+
+        let snapshot = snapshot_for_rules(root.path(), &rules, |_| "application/octet-stream".to_string());
+        assert!(rule("x-content/video-dvd").matches(&snapshot));
+        assert!(!rule("x-content/win32-software").matches(&snapshot));
+
+        fs::set_permissions(root.join("autorun.exe"), fs::Permissions::from_mode(0o755)).unwrap();
+        let snapshot = snapshot_for_rules(root.path(), &rules, |_| "application/octet-stream".to_string());
+        assert!(rule("x-content/win32-software").matches(&snapshot));
+    }
+
+    #[test]
+    fn nested_matches() {
+        let data = b"MIME-TreeMagic\0\n[50:x-content/foo]\n>\"a\"=directory\n1>\"a/b\"=file,executable\n>\"c\"=file\n";
+        let (rules, _) = read_treemagic(&data[..]);
+
+        let mut snapshot = DirSnapshot::new();
+        snapshot.insert("a", SnapshotEntry::new(TreeFileType::Directory));
+        snapshot.insert("a/b", SnapshotEntry::new(TreeFileType::File));
+        assert!(!rules[0].matches(&snapshot));
+
+        let mut entry = SnapshotEntry::new(TreeFileType::File);
+        entry.executable = true;
+        snapshot.insert("a/b", entry);
+        assert!(rules[0].matches(&snapshot));
+
+        let mut snapshot = DirSnapshot::new();
+        snapshot.insert("c", SnapshotEntry::new(TreeFileType::File));
+        assert!(rules[0].matches(&snapshot));
+    }
+}
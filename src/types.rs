@@ -0,0 +1,38 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use crate::lines;
+
+/// Parses a line of a `types` file.
+pub fn parse_type(line: String) -> Option<String> {
+    let mime_type = line.trim();
+    if mime_type.is_empty() || mime_type.contains(char::is_whitespace) {
+        return None;
+    }
+
+    Some(mime_type.to_string())
+}
+
+pub fn read_types_from_file<P: AsRef<Path>>(file_name: P) -> Vec<String> {
+    let f = match File::open(file_name) {
+        Ok(v) => v,
+        Err(_) => return Vec::new(),
+    };
+
+    lines::collect_valid(lines::parse_lines(BufReader::new(f), parse_type).map(|(_, res)| res))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_types() {
+        let types = read_types_from_file("test_files/mime/types");
+        assert_eq!(types.len(), 790);
+        assert_eq!(types[0], "application/andrew-inset");
+
+        assert!(read_types_from_file("test_files/mime/nonexistent").is_empty());
+    }
+}
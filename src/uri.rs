@@ -0,0 +1,103 @@
+// Returns the value of the hexadecimal digit @c
+fn hex_value(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Decodes the percent-encoded bytes of @s; the invalid sequences are kept
+/// as they are, and the invalid UTF-8 replaced.
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+
+    let mut res = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let (Some(hi), Some(lo)) = (hex_value(bytes[i + 1]), hex_value(bytes[i + 2])) {
+                res.push(hi << 4 | lo);
+                i += 3;
+                continue;
+            }
+        }
+
+        res.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&res).into_owned()
+}
+
+/// Extracts the file name from @uri, like `file:///tmp/a%20b.txt` or
+/// `https://example.com/photo.jpg?size=large`, as used by
+/// [`SharedMimeInfo::guess_for_uri`][crate::SharedMimeInfo::guess_for_uri].
+///
+/// The scheme and authority, the query and the fragment are stripped, and
+/// the last segment of the path is percent-decoded. `None` is returned
+/// when the path has no last segment, e.g. for `https://example.com/`.
+pub fn file_name_from_uri(uri: &str) -> Option<String> {
+    let mut path = uri;
+
+    if let Some(idx) = path.find('#') {
+        path = &path[..idx];
+    }
+    if let Some(idx) = path.find('?') {
+        path = &path[..idx];
+    }
+
+    // <scheme> ':' [ '//' <authority> ] <path>
+    let scheme_end = path.find(':').filter(|idx| {
+        *idx > 0 && path[..*idx].chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+    });
+    if let Some(idx) = scheme_end {
+        path = &path[idx + 1..];
+        if let Some(rest) = path.strip_prefix("//") {
+            path = match rest.find('/') {
+                Some(idx) => &rest[idx..],
+                None => "",
+            };
+        }
+    }
+
+    let segment = match path.rfind('/') {
+        Some(idx) => &path[idx + 1..],
+        None => path,
+    };
+
+    if segment.is_empty() {
+        return None;
+    }
+
+    Some(percent_decode(segment))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        assert_eq!(percent_decode("a%20b"), "a b");
+        assert_eq!(percent_decode("%C3%A9t%c3%a9"), "été");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%4"), "%zz%4");
+        assert_eq!(percent_decode("%ff"), "\u{fffd}");
+    }
+
+    #[test]
+    fn file_names() {
+        assert_eq!(file_name_from_uri("file:///tmp/a%20b.txt"), Some("a b.txt".to_string()));
+        assert_eq!(
+            file_name_from_uri("https://example.com/photos/photo.jpg?size=large#top"),
+            Some("photo.jpg".to_string())
+        );
+        assert_eq!(file_name_from_uri("https://example.com"), None);
+        assert_eq!(file_name_from_uri("https://example.com/dir/"), None);
+        assert_eq!(file_name_from_uri("mailto:someone@example.com"), Some("someone@example.com".to_string()));
+        assert_eq!(file_name_from_uri("relative/name.png"), Some("name.png".to_string()));
+        assert_eq!(file_name_from_uri("/abs/x.tar.gz"), Some("x.tar.gz".to_string()));
+    }
+}
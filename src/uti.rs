@@ -0,0 +1,73 @@
+use std::ptr;
+
+use core_foundation::base::TCFType;
+use core_foundation::string::{CFString, CFStringRef};
+use core_services::{kUTTagClassFilenameExtension, kUTTagClassMIMEType, UTTypeCreatePreferredIdentifierForTag};
+
+// The conversions between the Uniform Type Identifiers of macOS and their
+// tags, through Core Services; only the file names are looked up, as Core
+// Services has no identifier lookup for raw data, which is left to the XDG
+// magic
+
+#[link(name = "CoreServices", kind = "framework")]
+extern "C" {
+    // Missing from the core-services crate
+    fn UTTypeCopyPreferredTagWithClass(uti: CFStringRef, tag_class: CFStringRef) -> CFStringRef;
+}
+
+// Takes the ownership of @string, returned by a Create or Copy function
+fn wrap_created(string: CFStringRef) -> Option<CFString> {
+    if string.is_null() {
+        None
+    } else {
+        // SAFETY: the string is owned by the caller of the function that
+        // returned it
+        Some(unsafe { CFString::wrap_under_create_rule(string) })
+    }
+}
+
+// Returns the preferred MIME type of @uti
+fn uti_tag(uti: &CFString) -> Option<String> {
+    // SAFETY: the tag class is a constant of Core Services
+    let mime_type = unsafe { UTTypeCopyPreferredTagWithClass(uti.as_concrete_TypeRef(), kUTTagClassMIMEType) };
+
+    wrap_created(mime_type).map(|s| s.to_string())
+}
+
+/// Converts the Uniform Type Identifier @uti of macOS, like `public.png`,
+/// into its preferred MIME type, if it has one.
+pub fn uti_mime_type(uti: &str) -> Option<String> {
+    uti_tag(&CFString::new(uti))
+}
+
+// Returns the MIME type of the Uniform Type Identifier of the extension of
+// @file_name; the dynamic identifiers made up for unknown extensions have
+// none
+pub(crate) fn file_name_type(file_name: &str) -> Option<String> {
+    let (_, extension) = file_name.rsplit_once('.').filter(|(stem, ext)| !stem.is_empty() && !ext.is_empty())?;
+    let extension = CFString::new(extension);
+
+    // SAFETY: the tag class is a constant of Core Services, and the
+    // identifier may conform to any other
+    let uti = unsafe {
+        UTTypeCreatePreferredIdentifierForTag(kUTTagClassFilenameExtension, extension.as_concrete_TypeRef(), ptr::null())
+    };
+
+    uti_tag(&wrap_created(uti)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uti_types() {
+        assert_eq!(uti_mime_type("public.png").as_deref(), Some("image/png"));
+        assert_eq!(uti_mime_type("org.xdg-mime.nonexistent"), None);
+
+        assert_eq!(file_name_type("logo.png").as_deref(), Some("image/png"));
+        assert_eq!(file_name_type("notes.xdg-mime-unknown"), None);
+        assert_eq!(file_name_type("png"), None);
+        assert_eq!(file_name_type(".png"), None);
+    }
+}
@@ -0,0 +1,227 @@
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, Weak};
+use std::thread;
+use std::time::Duration;
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::SharedMimeInfo;
+
+// How long the directories must stay unchanged before reloading, so that
+// all the files written by update-mime-database are loaded at once
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// Keeps a [`SharedMimeInfo`] up to date, reloading it in a background
+/// thread when the `mime` sub-directories of its data directories change,
+/// e.g. after update-mime-database runs.
+///
+/// The data directories without a `mime` sub-directory yet, like a new
+/// `~/.local/share`, are watched too, and loaded once it is created.
+///
+/// The watching stops when the watcher is dropped.
+pub struct DatabaseWatcher {
+    db: Arc<RwLock<SharedMimeInfo>>,
+    // The only strong reference to the watcher: dropping it stops the
+    // reloading thread
+    watcher: Arc<Mutex<RecommendedWatcher>>,
+}
+
+fn read(db: &RwLock<SharedMimeInfo>) -> RwLockReadGuard<'_, SharedMimeInfo> {
+    match db.read() {
+        Ok(v) => v,
+        Err(e) => e.into_inner(),
+    }
+}
+
+// Returns the directories to watch for the data directories @data_dirs:
+// the `mime` sub-directory of each one or, if it does not exist yet, the
+// closest existing directory on the way to it
+fn watched_dirs(data_dirs: &[PathBuf]) -> Vec<PathBuf> {
+    data_dirs
+        .iter()
+        .filter_map(|dir| {
+            let mime_dir = dir.join("mime");
+            if mime_dir.is_dir() {
+                Some(mime_dir)
+            } else {
+                dir.ancestors().find(|v| v.is_dir()).map(Path::to_path_buf)
+            }
+        })
+        .collect()
+}
+
+// Whether the change of @path may change the database loaded from
+// @data_dirs: it is in one of their `mime` sub-directories, or on the way
+// to one, like the creation of a missing data directory
+fn is_relevant(path: &Path, data_dirs: &[PathBuf]) -> bool {
+    data_dirs.iter().any(|dir| {
+        let mime_dir = dir.join("mime");
+        path.starts_with(&mime_dir) || mime_dir.starts_with(path)
+    })
+}
+
+// Watches the directories for @data_dirs that are not in @watched yet
+fn watch_new_dirs(watcher: &Mutex<RecommendedWatcher>, data_dirs: &[PathBuf], watched: &mut HashSet<PathBuf>) {
+    let mut watcher = match watcher.lock() {
+        Ok(v) => v,
+        Err(e) => e.into_inner(),
+    };
+
+    for dir in watched_dirs(data_dirs) {
+        if !watched.contains(&dir) && watcher.watch(&dir, RecursiveMode::NonRecursive).is_ok() {
+            watched.insert(dir);
+        }
+    }
+}
+
+impl DatabaseWatcher {
+    /// Starts watching the data directories of @db.
+    pub fn new(db: SharedMimeInfo) -> io::Result<DatabaseWatcher> {
+        DatabaseWatcher::with_callback(db, |_| {})
+    }
+
+    /// Starts watching the data directories of @db, calling @callback with
+    /// the new database after each reload.
+    pub fn with_callback<F>(db: SharedMimeInfo, mut callback: F) -> io::Result<DatabaseWatcher>
+    where
+        F: FnMut(&SharedMimeInfo) + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel::<notify::Result<Event>>();
+        let watcher = notify::recommended_watcher(sender).map_err(io::Error::other)?;
+        let watcher = Arc::new(Mutex::new(watcher));
+
+        let data_dirs = db.source.candidate_data_dirs();
+        let mut watched = HashSet::new();
+        watch_new_dirs(&watcher, &data_dirs, &mut watched);
+
+        let db = Arc::new(RwLock::new(db));
+
+        // The thread must not keep the watcher alive, so that dropping it
+        // closes the channel and ends the thread
+        let thread_db = db.clone();
+        let thread_watcher: Weak<Mutex<RecommendedWatcher>> = Arc::downgrade(&watcher);
+        thread::spawn(move || {
+            while let Ok(event) = receiver.recv() {
+                // The events of the parents of the missing directories are
+                // mostly about other files
+                match event {
+                    Ok(Event { kind: EventKind::Access(_), .. }) => continue,
+                    Ok(Event { paths, .. })
+                        if !paths.is_empty() && !paths.iter().any(|p| is_relevant(p, &data_dirs)) =>
+                    {
+                        continue
+                    }
+                    _ => {}
+                }
+
+                loop {
+                    match receiver.recv_timeout(SETTLE_TIME) {
+                        Ok(_) => continue,
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+
+                // The new directories are watched before loading them, so
+                // that no change is missed
+                if let Some(watcher) = thread_watcher.upgrade() {
+                    watch_new_dirs(&watcher, &data_dirs, &mut watched);
+                }
+
+                // The lookups keep using the old database while the new
+                // one is loaded
+                #[cfg(feature = "metrics")]
+                let start = std::time::Instant::now();
+                let new_db = read(&thread_db).source.clone().build();
+                {
+                    let mut db = match thread_db.write() {
+                        Ok(v) => v,
+                        Err(e) => e.into_inner(),
+                    };
+                    db.replace_with(new_db);
+                    #[cfg(feature = "metrics")]
+                    db.count_reload(start.elapsed());
+                }
+
+                callback(&read(&thread_db));
+            }
+        });
+
+        Ok(DatabaseWatcher { db, watcher })
+    }
+
+    /// Returns the current version of the database, which is not reloaded
+    /// while the guard is held.
+    pub fn database(&self) -> RwLockReadGuard<'_, SharedMimeInfo> {
+        read(&self.db)
+    }
+
+    /// Returns the shared current version of the database, e.g. to use it
+    /// from other threads.
+    pub fn shared(&self) -> Arc<RwLock<SharedMimeInfo>> {
+        self.db.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::GlobMatch;
+    use std::fs;
+
+    #[test]
+    fn reload_on_change() {
+        let dir = TempDir::new("watch");
+        fs::create_dir_all(dir.join("mime")).unwrap();
+        fs::write(dir.join("mime/globs2"), "50:text/x-foo:*.foo\n").unwrap();
+
+        let db = SharedMimeInfo::builder().system_directories(false).directory(dir.path()).build();
+        let (sender, receiver) = mpsc::channel();
+        let watcher = DatabaseWatcher::with_callback(db, move |db| {
+            let _ = sender.send(db.lookup_file_name("a.bar"));
+        })
+        .unwrap();
+        assert_eq!(watcher.database().lookup_file_name("a.bar"), GlobMatch::NoMatch);
+
+        fs::write(dir.join("mime/globs2"), "50:text/x-bar:*.bar\n").unwrap();
+        let res = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(res, GlobMatch::Unique("text/x-bar".to_string()));
+        assert_eq!(watcher.database().lookup_file_name("a.foo"), GlobMatch::NoMatch);
+    }
+
+    #[test]
+    fn load_new_directory() {
+        let dir = TempDir::new("watch-new");
+        let data_dir = dir.join("share");
+
+        let db = SharedMimeInfo::builder().system_directories(false).directory(&data_dir).build();
+        let (sender, receiver) = mpsc::channel();
+        let watcher = DatabaseWatcher::with_callback(db, move |db| {
+            let _ = sender.send(db.lookup_file_name("a.bar"));
+        })
+        .unwrap();
+        assert_eq!(watcher.database().lookup_file_name("a.bar"), GlobMatch::NoMatch);
+
+        // Neither the data directory nor its mime sub-directory exist yet
+        fs::create_dir_all(data_dir.join("mime")).unwrap();
+        fs::write(data_dir.join("mime/globs2"), "50:text/x-bar:*.bar\n").unwrap();
+        let expected = GlobMatch::Unique("text/x-bar".to_string());
+        while receiver.recv_timeout(Duration::from_secs(10)).unwrap() != expected {}
+        assert_eq!(watcher.database().lookup_file_name("a.bar"), expected);
+    }
+
+    #[test]
+    fn relevant_paths() {
+        let data_dirs = [PathBuf::from("/home/user/.local/share")];
+
+        assert!(is_relevant(Path::new("/home/user/.local/share/mime/globs2"), &data_dirs));
+        assert!(is_relevant(Path::new("/home/user/.local/share/mime"), &data_dirs));
+        assert!(is_relevant(Path::new("/home/user/.local"), &data_dirs));
+        assert!(!is_relevant(Path::new("/home/user/.local/share/applications"), &data_dirs));
+        assert!(!is_relevant(Path::new("/home/user/.bashrc"), &data_dirs));
+    }
+}
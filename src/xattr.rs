@@ -0,0 +1,126 @@
+use std::io;
+use std::path::Path;
+
+/// The extended attribute holding the MIME type of a file, as used by
+/// the indexers and by `setfattr -n user.mime_type`.
+pub const MIME_XATTR: &str = "user.mime_type";
+
+// The longest value of the attribute read
+#[cfg(target_os = "linux")]
+const MAX_XATTR_LEN: usize = 256;
+
+/// How the `user.mime_type` extended attribute of a file is used when
+/// guessing its MIME type from its path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum XattrPolicy {
+    /// The attribute decides, without reading the file.
+    Trust,
+    /// The attribute decides unless it contradicts the contents of the
+    /// file.
+    Verify,
+    /// The attribute is not read.
+    #[default]
+    Ignore,
+}
+
+#[cfg(target_os = "linux")]
+fn c_path(path: &Path) -> io::Result<std::ffi::CString> {
+    use std::os::unix::ffi::OsStrExt;
+
+    std::ffi::CString::new(path.as_os_str().as_bytes()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+// Returns the value of the `user.mime_type` attribute of the file at
+// @path, if it is set and looks like a MIME type
+#[cfg(target_os = "linux")]
+pub(crate) fn read_mime_xattr(path: &Path) -> Option<String> {
+    let path = c_path(path).ok()?;
+    let name = std::ffi::CString::new(MIME_XATTR).ok()?;
+    let mut buf = vec![0u8; MAX_XATTR_LEN];
+
+    // SAFETY: the path and name are NUL-terminated, and the size is the
+    // one of the buffer
+    let len = unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), buf.as_mut_ptr().cast(), buf.len()) };
+    if len < 0 {
+        return None;
+    }
+    buf.truncate(len as usize);
+
+    // Some tools store the terminating NUL
+    let value = std::str::from_utf8(&buf).ok()?.trim_end_matches('\0').trim();
+    match value.find('/') {
+        Some(idx) if idx > 0 && idx < value.len() - 1 => Some(value.to_ascii_lowercase()),
+        _ => None,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn read_mime_xattr(_path: &Path) -> Option<String> {
+    None
+}
+
+/// Stores @mime_type in the `user.mime_type` extended attribute of the
+/// file at @path, e.g. to cache a verified guess on the file itself.
+///
+/// An error of kind `Unsupported` is returned on the platforms or file
+/// systems without user extended attributes.
+#[cfg(target_os = "linux")]
+pub fn write_mime_xattr<P: AsRef<Path>>(path: P, mime_type: &str) -> io::Result<()> {
+    let path = c_path(path.as_ref())?;
+    let name = std::ffi::CString::new(MIME_XATTR)?;
+
+    // SAFETY: the path and name are NUL-terminated, and the size is the
+    // one of the value
+    let res = unsafe { libc::setxattr(path.as_ptr(), name.as_ptr(), mime_type.as_ptr().cast(), mime_type.len(), 0) };
+    if res < 0 {
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() == Some(libc::EOPNOTSUPP) {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, err));
+        }
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn write_mime_xattr<P: AsRef<Path>>(_path: P, _mime_type: &str) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "extended attributes are not supported"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::{Guess, SharedMimeInfo, UNKNOWN_TYPE};
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn mime_xattr() {
+        let mime_db = SharedMimeInfo::new_for_directory(env::current_dir().unwrap().join("test_files"));
+        let png_data = include_bytes!("../test_files/files/rust-logo.png");
+
+        let dir = TempDir::new("xattr");
+        let png_path = dir.join("upload");
+        fs::write(&png_path, &png_data[..]).unwrap();
+        let text_path = dir.join("notes");
+        fs::write(&text_path, "int main() {}\n").unwrap();
+
+        assert_eq!(read_mime_xattr(&png_path), None);
+        match write_mime_xattr(&png_path, "application/x-gzip") {
+            Ok(()) => {}
+            // The temporary directory may not support user attributes
+            Err(_) => return,
+        }
+        write_mime_xattr(&text_path, "text/x-csrc").unwrap();
+        assert_eq!(read_mime_xattr(&png_path).as_deref(), Some("application/x-gzip"));
+
+        let guess = |path, policy| mime_db.guess().path(path).xattr(policy).guess();
+        assert_eq!(guess(&png_path, XattrPolicy::Trust), Guess::new("application/gzip", false));
+        assert_eq!(guess(&png_path, XattrPolicy::Verify), Guess::new("image/png", false));
+        assert_eq!(guess(&png_path, XattrPolicy::Ignore), Guess::new("image/png", false));
+        assert_eq!(guess(&text_path, XattrPolicy::Verify), Guess::new("text/x-csrc", false));
+        assert_eq!(guess(&text_path, XattrPolicy::Ignore).mime_type(), UNKNOWN_TYPE);
+    }
+}
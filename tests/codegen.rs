@@ -0,0 +1,109 @@
+// The code generated by xdg_mime::codegen for the database of test_files,
+// compared with the database it was generated from
+
+use std::env;
+use std::fs;
+
+use xdg_mime::{MimeQuery, SharedMimeInfo};
+
+include!("codegen/mime_db.rs");
+
+fn load_test_data() -> SharedMimeInfo {
+    SharedMimeInfo::new_for_directory(env::current_dir().unwrap().join("test_files"))
+}
+
+#[test]
+fn generated_code_is_up_to_date() {
+    let mut out = env::temp_dir();
+    out.push(format!("xdg-mime-codegen-test-{}.rs", std::process::id()));
+
+    xdg_mime::codegen::write_static_db(&out, &["test_files"]).unwrap();
+    let code = fs::read_to_string(&out).unwrap();
+    assert!(
+        code == include_str!("codegen/mime_db.rs"),
+        "tests/codegen/mime_db.rs is out of date, the new code is in {}",
+        out.display()
+    );
+
+    fs::remove_file(&out).unwrap();
+}
+
+#[test]
+fn same_types() {
+    let db = load_test_data();
+
+    for mime_type in db.mime_types() {
+        assert_eq!(
+            MIME_DB.unalias_mime_type(mime_type),
+            MimeQuery::unalias_mime_type(&db, mime_type),
+            "{}",
+            mime_type
+        );
+        assert_eq!(
+            MIME_DB.lookup_generic_icon_name(mime_type),
+            MimeQuery::lookup_generic_icon_name(&db, mime_type),
+            "{}",
+            mime_type
+        );
+        for base in &["text/plain", "application/xml", "application/zip", "image/*", "application/octet-stream"] {
+            assert_eq!(
+                MIME_DB.is_subclass_of(mime_type, base),
+                MimeQuery::is_subclass_of(&db, mime_type, base),
+                "{} {}",
+                mime_type,
+                base
+            );
+        }
+    }
+}
+
+#[test]
+fn same_file_names() {
+    let db = load_test_data();
+
+    for glob in db.globs() {
+        let pattern = glob.pattern();
+        if pattern.contains(['[', '?']) {
+            continue;
+        }
+
+        for file_name in &[pattern.replace('*', "foo"), pattern.replace('*', "foo").to_uppercase()] {
+            assert_eq!(
+                MIME_DB.lookup_file_name(file_name),
+                MimeQuery::lookup_file_name(&db, file_name),
+                "{}",
+                file_name
+            );
+        }
+    }
+}
+
+#[test]
+fn same_data() {
+    let db = load_test_data();
+
+    let mut samples: Vec<Vec<u8>> = vec![
+        include_bytes!("../test_files/files/rust-logo.png").to_vec(),
+        include_bytes!("../test_files/files/rust-logo.svg").to_vec(),
+        b"plain text\n".to_vec(),
+        Vec::new(),
+    ];
+    // The values of the rules matching at the start of the data
+    for entry in MIME_DB.magic {
+        for rule in entry.rules {
+            if rule.indent == 0 && rule.start_offset == 0 && rule.mask.is_none() {
+                samples.push(rule.value.to_vec());
+            }
+        }
+    }
+
+    for data in &samples {
+        assert_eq!(MIME_DB.get_mime_type_for_data(data), MimeQuery::get_mime_type_for_data(&db, data), "{:?}", data);
+        assert_eq!(
+            MIME_DB.guess_mime_type(Some("foo.txt"), Some(data)),
+            MimeQuery::guess_mime_type(&db, Some("foo.txt"), Some(data)),
+            "{:?}",
+            data
+        );
+    }
+}